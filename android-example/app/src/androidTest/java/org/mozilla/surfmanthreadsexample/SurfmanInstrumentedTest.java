@@ -26,6 +26,21 @@ public class SurfmanInstrumentedTest {
     private static native void testNewlyCreatedContextsAreNotCurrent();
     private static native void testSurfaceTextureBlitFramebuffer();
     private static native void testSurfaceTextureRightSideUp();
+    private static native void testGLESContextCreation();
+    private static native void testDebugContextCreation();
+    private static native void testRobustContextResetStatus();
+    private static native void testMultisampledSurfaceResolve();
+    private static native void testSurfaceFormats();
+    private static native void testGenericSurfacePresentMode();
+    private static native void testGenericSurfacePresentWithDamage();
+    private static native void testReadSurfacePixels();
+    private static native void testFences();
+    private static native void testAdapterInfoAndContextCapabilities();
+    private static native void testConnectionBackendPreference();
+    private static native void testContextDescriptorConfig();
+    private static native void testEnumerateContextDescriptors();
+    private static native void testContextVersionFallbacks();
+    private static native void testLiveResources();
 
     static {
         System.loadLibrary("surfman_android_threads");
@@ -88,4 +103,79 @@ public class SurfmanInstrumentedTest {
     public void surfaceTextureRightSideUp() {
         testSurfaceTextureRightSideUp();
     }
+
+    @Test
+    public void glesContextCreation() {
+        testGLESContextCreation();
+    }
+
+    @Test
+    public void debugContextCreation() {
+        testDebugContextCreation();
+    }
+
+    @Test
+    public void robustContextResetStatus() {
+        testRobustContextResetStatus();
+    }
+
+    @Test
+    public void multisampledSurfaceResolve() {
+        testMultisampledSurfaceResolve();
+    }
+
+    @Test
+    public void surfaceFormats() {
+        testSurfaceFormats();
+    }
+
+    @Test
+    public void genericSurfacePresentMode() {
+        testGenericSurfacePresentMode();
+    }
+
+    @Test
+    public void genericSurfacePresentWithDamage() {
+        testGenericSurfacePresentWithDamage();
+    }
+
+    @Test
+    public void readSurfacePixels() {
+        testReadSurfacePixels();
+    }
+
+    @Test
+    public void fences() {
+        testFences();
+    }
+
+    @Test
+    public void adapterInfoAndContextCapabilities() {
+        testAdapterInfoAndContextCapabilities();
+    }
+
+    @Test
+    public void connectionBackendPreference() {
+        testConnectionBackendPreference();
+    }
+
+    @Test
+    public void contextDescriptorConfig() {
+        testContextDescriptorConfig();
+    }
+
+    @Test
+    public void enumerateContextDescriptors() {
+        testEnumerateContextDescriptors();
+    }
+
+    @Test
+    public void contextVersionFallbacks() {
+        testContextVersionFallbacks();
+    }
+
+    @Test
+    public void liveResources() {
+        testLiveResources();
+    }
 }
@@ -151,6 +151,126 @@ pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInst
     tests::test_surface_texture_right_side_up();
 }
 
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testGLESContextCreation(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_gles_context_creation();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testDebugContextCreation(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_debug_context_creation();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testRobustContextResetStatus(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_robust_context_reset_status();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testMultisampledSurfaceResolve(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_multisampled_surface_resolve();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testSurfaceFormats(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_surface_formats();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testGenericSurfacePresentMode(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_generic_surface_present_mode();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testGenericSurfacePresentWithDamage(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_generic_surface_present_with_damage();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testReadSurfacePixels(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_read_surface_pixels();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testFences(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_fences();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testAdapterInfoAndContextCapabilities(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_adapter_info_and_context_capabilities();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testContextDescriptorConfig(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_context_descriptor_config();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testEnumerateContextDescriptors(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_enumerate_context_descriptors();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testContextVersionFallbacks(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_context_version_fallbacks();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testConnectionBackendPreference(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_connection_backend_preference();
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testLiveResources(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    tests::test_live_resources();
+}
+
 struct JavaResourceLoader {
     loader: GlobalRef,
     vm: JavaVM,
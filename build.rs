@@ -24,6 +24,7 @@ fn main() {
         // Native displays.
         x11_platform: { all(free_unix, feature = "sm-x11") },
         wayland_platform: { all(free_unix) },
+        osmesa_platform: { all(free_unix, feature = "sm-osmesa") },
 
         // Features:
         // Here we collect the features that are only valid on certain platforms and
@@ -33,6 +34,7 @@ fn main() {
         angle_default: { all(windows_platform, feature = "sm-angle-default") },
         no_wgl: { all(windows_platform, feature = "sm-no-wgl") },
         wayland_default: { all(wayland_platform, any(not(x11_platform), feature = "sm-wayland-default")) },
+        osmesa_default: { all(osmesa_platform, feature = "sm-osmesa-default") },
     }
 
     let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
@@ -54,11 +56,23 @@ fn main() {
     // Generate GL bindings.
     if target_os == "android" || target_env == "ohos" {
         let mut file = File::create(dest.join("gl_bindings.rs")).unwrap();
-        let registry = Registry::new(Api::Gles2, (3, 0), Profile::Core, Fallbacks::All, []);
+        let registry = Registry::new(
+            Api::Gles2,
+            (3, 0),
+            Profile::Core,
+            Fallbacks::All,
+            ["GL_EXT_robustness"],
+        );
         registry.write_bindings(StructGenerator, &mut file).unwrap();
     } else {
         let mut file = File::create(dest.join("gl_bindings.rs")).unwrap();
-        let registry = Registry::new(Api::Gl, (3, 3), Profile::Core, Fallbacks::All, []);
+        let registry = Registry::new(
+            Api::Gl,
+            (3, 3),
+            Profile::Core,
+            Fallbacks::All,
+            ["GL_KHR_debug", "GL_KHR_robustness", "GL_ARB_robustness"],
+        );
         registry.write_bindings(StructGenerator, &mut file).unwrap();
     }
 }
@@ -6,7 +6,7 @@
 use crate::common::{ck, Buffer, FilesystemResourceLoader, Program, Shader, ShaderKind};
 
 use clap::{App, Arg};
-use euclid::default::Size2D;
+use euclid::default::{Point2D, Rect, Size2D};
 use gl;
 use gl::types::{GLchar, GLenum, GLint, GLuint, GLvoid};
 use png::{BitDepth, ColorType, Encoder};
@@ -15,7 +15,7 @@ use std::mem;
 use std::path::Path;
 use std::slice;
 use surfman::{Connection, ContextAttributeFlags, ContextAttributes, GLApi, GLVersion};
-use surfman::{SurfaceAccess, SurfaceType};
+use surfman::{PixelFormat, SurfaceAccess, SurfaceFormat, SurfaceType};
 
 mod common;
 
@@ -114,6 +114,8 @@ fn main() {
             SurfaceAccess::GPUOnly,
             SurfaceType::Generic {
                 size: Size2D::new(FRAMEBUFFER_WIDTH, FRAMEBUFFER_HEIGHT),
+                samples: 0,
+                format: SurfaceFormat::RGBA8,
             },
         )
         .unwrap();
@@ -124,8 +126,10 @@ fn main() {
     device.make_context_current(&context).unwrap();
     gl::load_with(|symbol_name| device.get_proc_address(&context, symbol_name));
 
-    let mut pixels: Vec<u8> = vec![0; FRAMEBUFFER_WIDTH as usize * FRAMEBUFFER_HEIGHT as usize * 4];
-    let tri_vertex_array = TriVertexArray::new(device.gl_api(), device.surface_gl_texture_target());
+    let tri_vertex_array = TriVertexArray::new(
+        device.context_gl_api(&context),
+        device.surface_gl_texture_target(),
+    );
 
     unsafe {
         let surface_info = device.context_surface_info(&context).unwrap().unwrap();
@@ -144,19 +148,25 @@ fn main() {
         ck();
         gl::Flush();
         ck();
-
-        gl::ReadPixels(
-            0,
-            0,
-            FRAMEBUFFER_WIDTH,
-            FRAMEBUFFER_HEIGHT,
-            gl::RGBA,
-            gl::UNSIGNED_BYTE,
-            pixels.as_mut_ptr() as *mut GLvoid,
-        );
-        ck();
     }
 
+    let mut surface = device
+        .unbind_surface_from_context(&mut context)
+        .unwrap()
+        .unwrap();
+    let pixels = device
+        .read_surface_pixels(
+            &context,
+            &surface,
+            Rect::new(
+                Point2D::zero(),
+                Size2D::new(FRAMEBUFFER_WIDTH, FRAMEBUFFER_HEIGHT),
+            ),
+            PixelFormat::RGBA8,
+        )
+        .unwrap();
+
+    device.destroy_surface(&mut context, &mut surface).unwrap();
     device.destroy_context(&mut context).unwrap();
 
     let mut encoder = Encoder::new(
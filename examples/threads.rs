@@ -8,7 +8,7 @@ use euclid::default::{Point2D, Rect, Size2D, Vector2D};
 use gl::types::{GLchar, GLenum, GLint, GLuint, GLvoid};
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread;
-use surfman::{declare_surfman, SurfaceAccess, SurfaceTexture, SurfaceType};
+use surfman::{declare_surfman, SurfaceAccess, SurfaceFormat, SurfaceTexture, SurfaceType};
 use surfman::{Adapter, Connection, Context, ContextDescriptor, Device, GLApi, Surface};
 
 #[cfg(not(target_os = "android"))]
@@ -241,7 +241,7 @@ impl App {
         gl::load_with(|symbol_name| device.get_proc_address(&context, symbol_name));
 
         // Set up GL objects and state.
-        let gl_api = device.gl_api();
+        let gl_api = device.context_gl_api(&context);
         let surface_gl_texture_target = device.surface_gl_texture_target();
         let grid_vertex_array =
             GridVertexArray::new(gl_api, surface_gl_texture_target, &*resource_loader);
@@ -462,7 +462,11 @@ fn worker_thread(
 ) {
     // Open the device, create a context, and make it current.
     let size = Size2D::new(SUBSCREEN_WIDTH, SUBSCREEN_HEIGHT);
-    let surface_type = SurfaceType::Generic { size };
+    let surface_type = SurfaceType::Generic {
+        size,
+        samples: 0,
+        format: SurfaceFormat::RGBA8,
+    };
     let mut device = connection.create_device(&adapter).unwrap();
     let mut context = device.create_context(&context_descriptor, None).unwrap();
     let surface = device
@@ -475,7 +479,7 @@ fn worker_thread(
 
     // Set up GL objects and state.
     let vertex_array = CheckVertexArray::new(
-        device.gl_api(),
+        device.context_gl_api(&context),
         device.surface_gl_texture_target(),
         &*resource_loader,
     );
@@ -498,7 +502,11 @@ fn worker_thread(
     let mut theta_z = INITIAL_ROTATION_Z;
 
     // Send an initial surface back to the main thread.
-    let surface_type = SurfaceType::Generic { size };
+    let surface_type = SurfaceType::Generic {
+        size,
+        samples: 0,
+        format: SurfaceFormat::RGBA8,
+    };
     let surface = Some(
         device
             .create_surface(&context, SurfaceAccess::GPUOnly, surface_type)
@@ -0,0 +1,732 @@
+// surfman/surfman/src/bin/surfman-info.rs
+//
+//! Prints what `surfman` can do on this machine: every backend that can be connected to, the
+//! adapters it exposes, the configurations that match each combination of context attribute
+//! flags, which context versions can be created, which surface formats can be bound to textures,
+//! the EGL display extensions, and the OpenGL extensions that `surfman` cares about.
+//!
+//! Run with `--json` for machine-readable output, e.g. to attach to a bug report.
+
+use euclid::default::Size2D;
+use std::env;
+use std::fmt::Write;
+use std::process;
+use surfman::connection::Connection as ConnectionInterface;
+use surfman::device::Device as DeviceInterface;
+use surfman::{AdapterInfo, BackendKind, ContextAttributeFlags, ContextAttributes};
+use surfman::{ContextConfigInfo, Error, GLApi, GLVersion};
+use surfman::{SurfaceAccess, SurfaceFormat, SurfaceType};
+
+const USAGE: &str = "Usage: surfman-info [--json]
+
+Prints the backends, adapters, context configurations, context versions, surface texture formats,
+and EGL and OpenGL extensions that surfman supports on this machine.
+
+Options:
+    --json      Print the report as JSON
+    -h, --help  Print this message";
+
+// The extensions that `surfman` or its clients rely on, in the order they are reported.
+const RELEVANT_EXTENSIONS: [&str; 12] = [
+    "GL_ARB_robustness",
+    "GL_EXT_robustness",
+    "GL_KHR_robustness",
+    "GL_KHR_debug",
+    "GL_ARB_framebuffer_sRGB",
+    "GL_EXT_sRGB",
+    "GL_EXT_color_buffer_float",
+    "GL_EXT_color_buffer_half_float",
+    "GL_EXT_read_format_bgra",
+    "GL_ARB_texture_rectangle",
+    "GL_OES_EGL_image",
+    "GL_OES_EGL_image_external",
+];
+
+const SURFACE_FORMATS: [SurfaceFormat; 4] = [
+    SurfaceFormat::RGBA8,
+    SurfaceFormat::RGBA16F,
+    SurfaceFormat::RGB10_A2,
+    SurfaceFormat::SRGB8_ALPHA8,
+];
+
+const DESKTOP_GL_VERSIONS: [(u8, u8); 12] = [
+    (2, 1),
+    (3, 0),
+    (3, 1),
+    (3, 2),
+    (3, 3),
+    (4, 0),
+    (4, 1),
+    (4, 2),
+    (4, 3),
+    (4, 4),
+    (4, 5),
+    (4, 6),
+];
+
+const GLES_VERSIONS: [(u8, u8); 4] = [(2, 0), (3, 0), (3, 1), (3, 2)];
+
+const SURFACE_SIZE: i32 = 16;
+
+// The flags that select a configuration's buffers, and those that select what kind of context is
+// created from it. Every combination of the two is probed.
+const BUFFER_FLAGS: [ContextAttributeFlags; 3] = [
+    ContextAttributeFlags::ALPHA,
+    ContextAttributeFlags::DEPTH,
+    ContextAttributeFlags::STENCIL,
+];
+const CONTEXT_FLAGS: [ContextAttributeFlags; 3] = [
+    ContextAttributeFlags::GLES,
+    ContextAttributeFlags::DEBUG,
+    ContextAttributeFlags::ROBUST,
+];
+
+type EnumerateAdapters<C> = fn(&C) -> Result<Vec<<C as ConnectionInterface>::Adapter>, Error>;
+
+fn main() {
+    let mut json = false;
+    for arg in env::args().skip(1) {
+        match &*arg {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => {
+                eprintln!("surfman-info: unknown argument `{}`\n\n{}", arg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    let report = Value::Object(vec![("backends", Value::List(probe_backends()))]);
+    let mut output = String::new();
+    if json {
+        report.write_json(&mut output, 0);
+    } else {
+        report.write_text(&mut output, 0);
+    }
+    println!("{}", output.trim_end());
+}
+
+fn probe_backends() -> Vec<Value> {
+    BackendKind::ALL
+        .iter()
+        .filter_map(|&kind| probe_backend(kind))
+        .collect()
+}
+
+// The default connection on Linux only reaches the backends that it was built to choose between,
+// so probe each backend module directly instead. This also gives access to `enumerate_adapters()`,
+// which only the Linux backends have.
+#[cfg(free_unix)]
+fn probe_backend(kind: BackendKind) -> Option<Value> {
+    use surfman::platform::unix;
+    match kind {
+        BackendKind::Wayland => probe_connection::<unix::wayland::device::Device>(
+            kind,
+            unix::wayland::connection::Connection::enumerate_adapters,
+        ),
+        BackendKind::Surfaceless => probe_connection::<unix::generic::device::Device>(
+            kind,
+            unix::generic::connection::Connection::enumerate_adapters,
+        ),
+        #[cfg(x11_platform)]
+        BackendKind::X11 => probe_connection::<unix::x11::device::Device>(
+            kind,
+            unix::x11::connection::Connection::enumerate_adapters,
+        ),
+        #[cfg(osmesa_platform)]
+        BackendKind::OSMesa => probe_connection::<unix::osmesa::device::Device>(
+            kind,
+            unix::osmesa::connection::Connection::enumerate_adapters,
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(not(free_unix))]
+fn probe_backend(kind: BackendKind) -> Option<Value> {
+    probe_connection::<surfman::Device>(kind, |_| Err(Error::UnsupportedOnThisPlatform))
+}
+
+// Returns `None` if the backend isn't available on this platform at all.
+fn probe_connection<D>(
+    kind: BackendKind,
+    enumerate_adapters: EnumerateAdapters<D::Connection>,
+) -> Option<Value>
+where
+    D: DeviceInterface,
+    D::Connection: ConnectionInterface<Device = D>,
+{
+    let mut fields = vec![("name", Value::from(kind.name()))];
+    match D::Connection::new_with_preference(&[kind]) {
+        Err(Error::UnsupportedOnThisPlatform) => return None,
+        Err(err) => fields.push(error_field(err)),
+        Ok(connection) => {
+            let api = match connection.gl_api() {
+                GLApi::GL => "OpenGL",
+                GLApi::GLES => "OpenGL ES",
+            };
+            fields.push(("api", Value::from(api)));
+            let adapters = match enumerate_adapters(&connection) {
+                Ok(adapters) => adapters,
+                Err(Error::UnsupportedOnThisPlatform) => vec![],
+                Err(err) => {
+                    fields.push(("enumerate_adapters", Value::Object(vec![error_field(err)])));
+                    vec![]
+                }
+            };
+            fields.push((
+                "adapters",
+                Value::List(probe_adapters(&connection, adapters)),
+            ));
+        }
+    }
+    Some(Value::Object(fields))
+}
+
+// Probes each of the given enumerated adapters, keyed by their enumeration index, so that identical
+// GPUs are still listed separately. If there are none, because the connection can't enumerate
+// them, this opens each kind of adapter that can be asked for instead, and merges the kinds that
+// turn out to be the same adapter.
+fn probe_adapters<C>(connection: &C, enumerated_adapters: Vec<C::Adapter>) -> Vec<Value>
+where
+    C: ConnectionInterface,
+    C::Device: DeviceInterface<Connection = C>,
+{
+    let merge_identical = enumerated_adapters.is_empty();
+    let candidates: Vec<(String, Result<C::Adapter, Error>)> = if merge_identical {
+        vec![
+            ("hardware".to_owned(), connection.create_hardware_adapter()),
+            (
+                "low-power".to_owned(),
+                connection.create_low_power_adapter(),
+            ),
+            ("software".to_owned(), connection.create_software_adapter()),
+        ]
+    } else {
+        enumerated_adapters
+            .into_iter()
+            .enumerate()
+            .map(|(index, adapter)| (format!("#{}", index), Ok(adapter)))
+            .collect()
+    };
+
+    let mut adapters: Vec<ProbedAdapter> = vec![];
+    for (selection, adapter) in candidates {
+        let mut device = match adapter.and_then(|adapter| connection.create_device(&adapter)) {
+            Ok(device) => device,
+            Err(err) => {
+                adapters.push(ProbedAdapter {
+                    selections: vec![selection],
+                    info: None,
+                    fields: vec![error_field(err)],
+                });
+                continue;
+            }
+        };
+
+        let adapter_info = device.adapter_info().ok();
+        if let Some(existing) = adapters.iter_mut().find(|existing| {
+            merge_identical && adapter_info.is_some() && existing.info == adapter_info
+        }) {
+            existing.selections.push(selection);
+            continue;
+        }
+
+        let fields = probe_device(&mut device);
+        adapters.push(ProbedAdapter {
+            selections: vec![selection],
+            info: adapter_info,
+            fields,
+        });
+    }
+
+    adapters
+        .into_iter()
+        .map(|adapter| {
+            let selections = adapter.selections.into_iter().map(Value::from).collect();
+            let mut all_fields = vec![("adapter", Value::List(selections))];
+            all_fields.extend(adapter.fields);
+            Value::Object(all_fields)
+        })
+        .collect()
+}
+
+struct ProbedAdapter {
+    selections: Vec<String>,
+    info: Option<AdapterInfo>,
+    fields: Vec<(&'static str, Value)>,
+}
+
+fn probe_device<D>(device: &mut D) -> Vec<(&'static str, Value)>
+where
+    D: DeviceInterface,
+{
+    let mut fields = vec![];
+    match device.adapter_info() {
+        Ok(info) => {
+            fields.push(("vendor", Value::from(info.vendor)));
+            fields.push(("renderer", Value::from(info.renderer)));
+            fields.push(("version", Value::from(info.version)));
+            fields.push(("driver", Value::from(info.driver_name)));
+            fields.push(("software", Value::Bool(info.is_software)));
+            if !info.display_extensions.is_empty() {
+                let display_extensions = info
+                    .display_extensions
+                    .into_iter()
+                    .map(Value::from)
+                    .collect();
+                fields.push(("display_extensions", Value::List(display_extensions)));
+            }
+        }
+        Err(err) => fields.push(("adapter_info", Value::Object(vec![error_field(err)]))),
+    }
+    fields.push(("configs", Value::List(probe_configs(device))));
+    fields.push(("versions", Value::List(probe_versions(device))));
+
+    let attributes = ContextAttributes {
+        version: default_version(device.gl_api()),
+        flags: ContextAttributeFlags::ALPHA
+            | ContextAttributeFlags::DEPTH
+            | ContextAttributeFlags::STENCIL,
+    };
+    let mut context = match device
+        .create_context_descriptor(&attributes)
+        .and_then(|descriptor| device.create_context(&descriptor, None))
+    {
+        Ok(context) => context,
+        Err(err) => {
+            fields.push(("context", Value::Object(vec![error_field(err)])));
+            return fields;
+        }
+    };
+
+    let surface_textures = SURFACE_FORMATS
+        .iter()
+        .map(|&format| {
+            let result = probe_surface_texture(device, &mut context, format);
+            Value::Object(vec![
+                ("format", Value::from(format!("{:?}", format))),
+                result_field(result),
+            ])
+        })
+        .collect();
+    fields.push(("surface_textures", Value::List(surface_textures)));
+
+    match device.context_capabilities(&context) {
+        Ok(capabilities) => {
+            let shading_language_version = capabilities
+                .shading_language_version
+                .map(|version| format!("{}.{:02}", version.major, version.minor));
+            let extensions = RELEVANT_EXTENSIONS
+                .iter()
+                .map(|&name| {
+                    Value::Object(vec![
+                        ("name", Value::from(name)),
+                        ("supported", Value::Bool(capabilities.has_extension(name))),
+                    ])
+                })
+                .collect();
+            fields.push((
+                "capabilities",
+                Value::Object(vec![
+                    ("version", Value::from(format_version(capabilities.version))),
+                    ("shading_language", Value::from(shading_language_version)),
+                    (
+                        "max_texture_size",
+                        Value::Int(capabilities.max_texture_size.into()),
+                    ),
+                    (
+                        "max_renderbuffer_size",
+                        Value::Int(capabilities.max_renderbuffer_size.into()),
+                    ),
+                    ("max_samples", Value::Int(capabilities.max_samples.into())),
+                    (
+                        "extension_count",
+                        Value::Int(capabilities.extensions.len() as i64),
+                    ),
+                    ("extensions", Value::List(extensions)),
+                ]),
+            ));
+        }
+        Err(err) => fields.push(("capabilities", Value::Object(vec![error_field(err)]))),
+    }
+
+    if let Err(err) = device.destroy_context(&mut context) {
+        eprintln!("surfman-info: failed to destroy a context: {:?}", err);
+    }
+    fields
+}
+
+// For each combination of flags, lists the IDs of every matching configuration, describes the one
+// that `create_context_descriptor()` would pick (the first), and checks that a context can be
+// created from it.
+fn probe_configs<D>(device: &mut D) -> Vec<Value>
+where
+    D: DeviceInterface,
+{
+    let mut configs = vec![];
+    for context_flags in flag_combinations(&CONTEXT_FLAGS) {
+        let version = if context_flags.contains(ContextAttributeFlags::GLES) {
+            default_version(GLApi::GLES)
+        } else {
+            default_version(device.gl_api())
+        };
+        for buffer_flags in flag_combinations(&BUFFER_FLAGS) {
+            let flags = context_flags | buffer_flags;
+            let mut fields = vec![("flags", Value::from(format_flags(flags)))];
+            let candidates =
+                match device.enumerate_context_descriptors(&ContextAttributes { version, flags }) {
+                    Ok(candidates) => candidates,
+                    Err(err) => {
+                        fields.push(error_field(err));
+                        configs.push(Value::Object(fields));
+                        continue;
+                    }
+                };
+            let candidate_ids = candidates
+                .iter()
+                .map(
+                    |descriptor| match device.context_descriptor_config(descriptor) {
+                        Ok(config) => Value::from(config.native_id.map(i64::from)),
+                        Err(err) => Value::from(format!("{:?}", err)),
+                    },
+                )
+                .collect();
+            fields.push(("candidates", Value::List(candidate_ids)));
+            match candidates.first() {
+                None => fields.push(error_field(Error::NoPixelFormatFound)),
+                Some(descriptor) => {
+                    match device.context_descriptor_config(descriptor) {
+                        Ok(config) => fields.extend(config_fields(&config)),
+                        Err(err) => fields.push(error_field(err)),
+                    }
+                    let context = device
+                        .create_context(descriptor, None)
+                        .and_then(|mut context| device.destroy_context(&mut context));
+                    fields.push(("context", Value::Object(vec![result_field(context)])));
+                }
+            }
+            configs.push(Value::Object(fields));
+        }
+    }
+    configs
+}
+
+// Returns every combination of the given flags, starting with none of them.
+fn flag_combinations(flags: &[ContextAttributeFlags]) -> Vec<ContextAttributeFlags> {
+    (0..1 << flags.len())
+        .map(|bits: u32| {
+            flags
+                .iter()
+                .enumerate()
+                .filter(|&(index, _)| bits & (1 << index) != 0)
+                .fold(ContextAttributeFlags::empty(), |all, (_, &flag)| all | flag)
+        })
+        .collect()
+}
+
+fn probe_versions<D>(device: &mut D) -> Vec<Value>
+where
+    D: DeviceInterface,
+{
+    let mut requests = vec![];
+    for &(major, minor) in DESKTOP_GL_VERSIONS.iter() {
+        let version = GLVersion::new(major, minor);
+        if (major, minor) >= (3, 2) {
+            requests.push((version, ContextAttributeFlags::empty()));
+        }
+        requests.push((version, ContextAttributeFlags::COMPATIBILITY_PROFILE));
+    }
+    for &(major, minor) in GLES_VERSIONS.iter() {
+        requests.push((GLVersion::new(major, minor), ContextAttributeFlags::GLES));
+    }
+
+    let mut versions = vec![];
+    for (version, flags) in requests {
+        let profile = if flags.contains(ContextAttributeFlags::GLES) {
+            "es"
+        } else if flags.contains(ContextAttributeFlags::COMPATIBILITY_PROFILE) {
+            "compatibility"
+        } else {
+            "core"
+        };
+        let mut fields = vec![
+            ("requested", Value::from(format_version(version))),
+            ("profile", Value::from(profile)),
+        ];
+        let context = device
+            .create_context_descriptor(&ContextAttributes { version, flags })
+            .and_then(|descriptor| device.create_context(&descriptor, None));
+        match context {
+            Ok(mut context) => {
+                let capabilities = device.context_capabilities(&context);
+                if let Err(err) = device.destroy_context(&mut context) {
+                    eprintln!("surfman-info: failed to destroy a context: {:?}", err);
+                }
+                match capabilities {
+                    Ok(capabilities) => {
+                        fields.push(("created", Value::from(format_version(capabilities.version))))
+                    }
+                    Err(err) => fields.push(error_field(err)),
+                }
+            }
+            Err(err) => fields.push(error_field(err)),
+        }
+        versions.push(Value::Object(fields));
+    }
+    versions
+}
+
+fn probe_surface_texture<D>(
+    device: &mut D,
+    context: &mut D::Context,
+    format: SurfaceFormat,
+) -> Result<(), Error>
+where
+    D: DeviceInterface,
+{
+    let surface_type = SurfaceType::Generic {
+        size: Size2D::new(SURFACE_SIZE, SURFACE_SIZE),
+        samples: 0,
+        format,
+    };
+    let mut surface = device.create_surface(context, SurfaceAccess::GPUOnly, surface_type)?;
+    let result = match device.create_surface_texture(context, surface) {
+        Ok(surface_texture) => match device.destroy_surface_texture(context, surface_texture) {
+            Ok(returned_surface) => {
+                surface = returned_surface;
+                Ok(())
+            }
+            Err((err, surface_texture)) => {
+                // Leaking is the only option left; dropping the surface would panic.
+                std::mem::forget(surface_texture);
+                return Err(err);
+            }
+        },
+        Err((err, returned_surface)) => {
+            surface = returned_surface;
+            Err(err)
+        }
+    };
+    device.destroy_surface(context, &mut surface)?;
+    result
+}
+
+fn default_version(api: GLApi) -> GLVersion {
+    match api {
+        GLApi::GL => GLVersion::new(3, 0),
+        GLApi::GLES => GLVersion::new(2, 0),
+    }
+}
+
+fn config_fields(config: &ContextConfigInfo) -> Vec<(&'static str, Value)> {
+    vec![
+        ("id", Value::from(config.native_id.map(i64::from))),
+        ("red_bits", Value::Int(config.red_bits.into())),
+        ("green_bits", Value::Int(config.green_bits.into())),
+        ("blue_bits", Value::Int(config.blue_bits.into())),
+        ("alpha_bits", Value::Int(config.alpha_bits.into())),
+        ("depth_bits", Value::Int(config.depth_bits.into())),
+        ("stencil_bits", Value::Int(config.stencil_bits.into())),
+        ("samples", Value::Int(config.samples.into())),
+        ("caveat", Value::from(format!("{:?}", config.caveat))),
+        (
+            "surface_types",
+            Value::from(format!("{:?}", config.surface_types)),
+        ),
+    ]
+}
+
+fn format_flags(flags: ContextAttributeFlags) -> String {
+    if flags.is_empty() {
+        "(none)".to_owned()
+    } else {
+        format!("{:?}", flags)
+    }
+}
+
+fn format_version(version: GLVersion) -> String {
+    format!("{}.{}", version.major, version.minor)
+}
+
+fn error_field(err: Error) -> (&'static str, Value) {
+    ("error", Value::from(format!("{:?}", err)))
+}
+
+fn result_field(result: Result<(), Error>) -> (&'static str, Value) {
+    match result {
+        Ok(()) => ("supported", Value::Bool(true)),
+        Err(err) => error_field(err),
+    }
+}
+
+// A minimal JSON-like document, so that the report can be printed either as text or as JSON
+// without pulling in a serialization library.
+enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    List(Vec<Value>),
+    Object(Vec<(&'static str, Value)>),
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Value {
+        Value::Str(string.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Value {
+        Value::Str(string)
+    }
+}
+
+impl<T> From<Option<T>> for Value
+where
+    T: Into<Value>,
+{
+    fn from(option: Option<T>) -> Value {
+        option.map_or(Value::Null, Into::into)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(int: i64) -> Value {
+        Value::Int(int)
+    }
+}
+
+impl Value {
+    fn write_json(&self, output: &mut String, indent: usize) {
+        match self {
+            Value::Null => output.push_str("null"),
+            Value::Bool(boolean) => write!(output, "{}", boolean).unwrap(),
+            Value::Int(int) => write!(output, "{}", int).unwrap(),
+            Value::Str(string) => write_json_string(output, string),
+            Value::List(items) if items.is_empty() => output.push_str("[]"),
+            Value::List(items) => {
+                output.push_str("[\n");
+                for (index, item) in items.iter().enumerate() {
+                    push_indent(output, indent + 1);
+                    item.write_json(output, indent + 1);
+                    output.push_str(if index + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                push_indent(output, indent);
+                output.push(']');
+            }
+            Value::Object(fields) => {
+                output.push_str("{\n");
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    push_indent(output, indent + 1);
+                    write_json_string(output, key);
+                    output.push_str(": ");
+                    value.write_json(output, indent + 1);
+                    output.push_str(if index + 1 < fields.len() {
+                        ",\n"
+                    } else {
+                        "\n"
+                    });
+                }
+                push_indent(output, indent);
+                output.push('}');
+            }
+        }
+    }
+
+    // Objects print one `key: value` line per field, and lists print their items as `-` bullets.
+    fn write_text(&self, output: &mut String, indent: usize) {
+        match self {
+            Value::List(items) => {
+                for item in items {
+                    push_indent(output, indent);
+                    output.push_str("- ");
+                    match item {
+                        Value::Object(_) | Value::List(_) => {
+                            // Put the first field on the bullet line itself.
+                            let mut nested = String::new();
+                            item.write_text(&mut nested, indent + 1);
+                            output.push_str(nested.trim_start());
+                        }
+                        _ => {
+                            output.push_str(&item.to_text());
+                            output.push('\n');
+                        }
+                    }
+                }
+            }
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    push_indent(output, indent);
+                    match value {
+                        // Lists of plain values, like configuration IDs, fit on one line.
+                        Value::List(items)
+                            if !items.is_empty() && !items.iter().any(Value::is_nested) =>
+                        {
+                            let items: Vec<String> = items.iter().map(Value::to_text).collect();
+                            writeln!(output, "{}: {}", key, items.join(", ")).unwrap();
+                        }
+                        Value::List(items) if !items.is_empty() => {
+                            writeln!(output, "{}:", key).unwrap();
+                            value.write_text(output, indent + 1);
+                        }
+                        Value::Object(fields) if !fields.is_empty() => {
+                            writeln!(output, "{}:", key).unwrap();
+                            value.write_text(output, indent + 1);
+                        }
+                        _ => writeln!(output, "{}: {}", key, value.to_text()).unwrap(),
+                    }
+                }
+            }
+            _ => {
+                push_indent(output, indent);
+                output.push_str(&self.to_text());
+                output.push('\n');
+            }
+        }
+    }
+
+    fn is_nested(&self) -> bool {
+        matches!(self, Value::List(_) | Value::Object(_))
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Value::Null => "-".to_owned(),
+            Value::Bool(true) => "yes".to_owned(),
+            Value::Bool(false) => "no".to_owned(),
+            Value::Int(int) => int.to_string(),
+            Value::Str(string) => string.clone(),
+            Value::List(_) => "[]".to_owned(),
+            Value::Object(_) => "{}".to_owned(),
+        }
+    }
+}
+
+fn push_indent(output: &mut String, indent: usize) {
+    for _ in 0..indent {
+        output.push_str("  ");
+    }
+}
+
+fn write_json_string(output: &mut String, string: &str) {
+    output.push('"');
+    for character in string.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            character if (character as u32) < 0x20 => {
+                write!(output, "\\u{:04x}", character as u32).unwrap()
+            }
+            character => output.push(character),
+        }
+    }
+    output.push('"');
+}
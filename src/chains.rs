@@ -23,7 +23,7 @@
 #![allow(missing_docs)]
 
 use crate::device::Device as DeviceAPI;
-use crate::{ContextID, Error, SurfaceAccess, SurfaceInfo, SurfaceType};
+use crate::{ContextID, Error, SurfaceAccess, SurfaceFormat, SurfaceInfo, SurfaceType};
 use euclid::default::Size2D;
 use fnv::{FnvHashMap, FnvHashSet};
 use log::debug;
@@ -173,7 +173,11 @@ impl<Device: DeviceAPI> SwapChainData<Device> {
                     "Creating a new surface ({:?}) for context {:?}",
                     self.size, self.context_id
                 );
-                let surface_type = SurfaceType::Generic { size: self.size };
+                let surface_type = SurfaceType::Generic {
+                    size: self.size,
+                    samples: 0,
+                    format: SurfaceFormat::RGBA8,
+                };
                 device.create_surface(context, self.surface_access, surface_type)
             })?;
 
@@ -189,6 +193,14 @@ impl<Device: DeviceAPI> SwapChainData<Device> {
         self.back_buffer
             .replace_surface(device, context, new_back_buffer)?;
 
+        #[cfg(feature = "tracing")]
+        tracing::Span::current()
+            .record("back_surface_id", back_info.id.0)
+            .record(
+                "front_surface_id",
+                device.surface_info(&new_front_buffer).id.0,
+            );
+
         if let PreserveBuffer::Yes(gl) = preserve_buffer {
             let front_info = device.surface_info(&new_front_buffer);
             gl.bind_framebuffer(gl::READ_FRAMEBUFFER, front_info.framebuffer_object);
@@ -269,7 +281,11 @@ impl<Device: DeviceAPI> SwapChainData<Device> {
         if (size.width < 1) || (size.height < 1) {
             return Err(Error::Failed);
         }
-        let surface_type = SurfaceType::Generic { size };
+        let surface_type = SurfaceType::Generic {
+            size,
+            samples: 0,
+            format: SurfaceFormat::RGBA8,
+        };
         let new_back_buffer = device.create_surface(context, self.surface_access, surface_type)?;
         let mut old_back_buffer = self.back_buffer.take_surface(device, context)?;
         self.back_buffer
@@ -312,6 +328,10 @@ impl<Device: DeviceAPI> SwapChainData<Device> {
     // Take the current front buffer.
     // Returns the most recent recycled surface if there is no current front buffer.
     // Called by a consumer.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(context_id = self.context_id.0))
+    )]
     fn take_surface(&mut self) -> Option<Device::Surface> {
         self.pending_surface
             .take()
@@ -321,12 +341,20 @@ impl<Device: DeviceAPI> SwapChainData<Device> {
     // Take the current front buffer.
     // Returns `None` if there is no current front buffer.
     // Called by a consumer.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(context_id = self.context_id.0))
+    )]
     fn take_pending_surface(&mut self) -> Option<Device::Surface> {
         self.pending_surface.take()
     }
 
     // Recycle the current front buffer.
     // Called by a consumer.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(context_id = self.context_id.0))
+    )]
     fn recycle_surface(&mut self, surface: Device::Surface) {
         self.recycled_surfaces.push(surface)
     }
@@ -485,6 +513,19 @@ impl<Device: DeviceAPI> SwapChain<Device> {
     /// Swap the back and front buffers.
     /// Called by the producer.
     /// Returns an error if `context` is not the producer context for this swap chain.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                context_id = device.context_id(context).0,
+                back_surface_id = tracing::field::Empty,
+                front_surface_id = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn swap_buffers(
         &self,
         device: &mut Device,
@@ -606,7 +647,11 @@ impl<Device: DeviceAPI> SwapChain<Device> {
         surface_access: SurfaceAccess,
         size: Size2D<i32>,
     ) -> Result<SwapChain<Device>, Error> {
-        let surface_type = SurfaceType::Generic { size };
+        let surface_type = SurfaceType::Generic {
+            size,
+            samples: 0,
+            format: SurfaceFormat::RGBA8,
+        };
         let surface = device.create_surface(context, surface_access, surface_type)?;
         Ok(SwapChain(Arc::new(Mutex::new(SwapChainData {
             size,
@@ -7,8 +7,121 @@ use crate::GLApi;
 
 use euclid::default::Size2D;
 
+use std::env;
 use std::os::raw::c_void;
 
+/// The environment variable that overrides which backend `Connection::new()` picks on platforms
+/// with more than one.
+///
+/// The value is a comma-separated list of backend names, as returned by `BackendKind::name()`,
+/// in order of preference (e.g. `x11,surfaceless`).
+pub const BACKEND_ENV_VAR: &str = "SURFMAN_BACKEND";
+
+/// Identifies the backend behind a connection.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BackendKind {
+    /// A Wayland display server, via EGL.
+    Wayland,
+    /// An X11 display server, via EGL.
+    X11,
+    /// EGL without a display server, for headless rendering. This is the fallback on Linux when
+    /// there is no Wayland or X11 display.
+    Surfaceless,
+    /// Mesa's software renderer, OSMesa, without EGL or a display server.
+    OSMesa,
+    /// EGL on Android.
+    Android,
+    /// EGL on OpenHarmony.
+    OHOS,
+    /// The native WGL interface on Windows.
+    WGL,
+    /// Google's ANGLE library on Windows.
+    ANGLE,
+    /// The native CGL interface on macOS.
+    CGL,
+}
+
+impl BackendKind {
+    /// Every backend kind, including those that aren't available on this platform.
+    pub const ALL: [BackendKind; 9] = [
+        BackendKind::Wayland,
+        BackendKind::X11,
+        BackendKind::Surfaceless,
+        BackendKind::OSMesa,
+        BackendKind::Android,
+        BackendKind::OHOS,
+        BackendKind::WGL,
+        BackendKind::ANGLE,
+        BackendKind::CGL,
+    ];
+
+    /// Returns the lowercase name of this backend, as accepted by the `SURFMAN_BACKEND`
+    /// environment variable.
+    pub fn name(self) -> &'static str {
+        match self {
+            BackendKind::Wayland => "wayland",
+            BackendKind::X11 => "x11",
+            BackendKind::Surfaceless => "surfaceless",
+            BackendKind::OSMesa => "osmesa",
+            BackendKind::Android => "android",
+            BackendKind::OHOS => "ohos",
+            BackendKind::WGL => "wgl",
+            BackendKind::ANGLE => "angle",
+            BackendKind::CGL => "cgl",
+        }
+    }
+
+    /// Returns the backend with the given name, ignoring case, or `None` if there is no such
+    /// backend.
+    pub fn from_name(name: &str) -> Option<BackendKind> {
+        BackendKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    // Returns the backends listed in the `SURFMAN_BACKEND` environment variable, or `None` if it
+    // isn't set or doesn't name any backend.
+    pub(crate) fn preference_from_env() -> Option<Vec<BackendKind>> {
+        let value = env::var(BACKEND_ENV_VAR).ok()?;
+        let preference: Vec<BackendKind> = value
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .filter_map(|name| {
+                let kind = BackendKind::from_name(name);
+                if kind.is_none() {
+                    warn!(
+                        "surfman: Ignoring unknown backend {:?} in {}",
+                        name, BACKEND_ENV_VAR
+                    );
+                }
+                kind
+            })
+            .collect();
+        if preference.is_empty() {
+            None
+        } else {
+            Some(preference)
+        }
+    }
+
+    // Returns `ConnectionFailed` if the `SURFMAN_BACKEND` environment variable lists backends but
+    // not this one. Connections that only have one backend check this in `new()`.
+    pub(crate) fn check_preference_from_env(self) -> Result<(), Error> {
+        match BackendKind::preference_from_env() {
+            Some(preference) if !preference.contains(&self) => {
+                warn!(
+                    "surfman: Not connecting with the {} backend, since {} doesn't list it",
+                    self.name(),
+                    BACKEND_ENV_VAR
+                );
+                Err(Error::ConnectionFailed)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Methods relating to display server connections.
 pub trait Connection: Sized {
     /// The adapter type associated with this connection.
@@ -23,8 +136,20 @@ pub trait Connection: Sized {
     type NativeWidget;
 
     /// Connects to the default display.
+    ///
+    /// If the `SURFMAN_BACKEND` environment variable is set, only the backends it lists are
+    /// used, and this fails if none of them can connect.
     fn new() -> Result<Self, Error>;
 
+    /// Connects to the display with the first of the given backends that is available, in order.
+    ///
+    /// Returns `UnsupportedOnThisPlatform` if none of the backends are supported by this
+    /// connection type.
+    fn new_with_preference(preference: &[BackendKind]) -> Result<Self, Error>;
+
+    /// Returns which backend this connection uses.
+    fn backend_kind(&self) -> BackendKind;
+
     /// Returns the native connection corresponding to this connection.
     fn native_connection(&self) -> Self::NativeConnection;
 
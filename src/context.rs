@@ -4,13 +4,15 @@
 
 #![allow(unused_imports)]
 
+use crate::device::Device as DeviceInterface;
 use crate::gl;
-use crate::gl::types::GLuint;
-use crate::info::GLVersion;
-use crate::Gl;
+use crate::gl::types::{GLchar, GLenum, GLsizei, GLuint};
+use crate::info::{ContextConfigInfo, GLApi, GLVersion};
+use crate::{Error, Gl};
 
 use std::ffi::CStr;
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_void};
+use std::slice;
 use std::sync::Mutex;
 
 /// A unique ID among all currently-allocated contexts.
@@ -45,9 +47,45 @@ bitflags! {
         /// The OpenGL compatibility profile will be used. If this is not present, the core profile
         /// is used.
         const COMPATIBILITY_PROFILE = 0x08;
+        /// An OpenGL ES context will be created instead of a desktop OpenGL context.
+        ///
+        /// Only backends that can create both kinds of context (currently the Linux EGL backends)
+        /// look at this flag. Backends that only support desktop OpenGL return an
+        /// `UnsupportedGLType` error if it is set, and backends that only support OpenGL ES always
+        /// create OpenGL ES contexts.
+        const GLES                  = 0x10;
+        /// A debug context will be created, and messages that the driver reports through
+        /// `KHR_debug` will be forwarded to the `log` crate, tagged with the ID of the context.
+        ///
+        /// This is a hint: backends that can't create debug contexts ignore it, and
+        /// `Device::context_descriptor_attributes()` only reports it for contexts that really are
+        /// debug contexts.
+        const DEBUG                 = 0x20;
+        /// A robust context will be created: out-of-bounds buffer accesses are guaranteed not to
+        /// crash the process, and the context is lost when the GPU is reset. Use
+        /// `Device::context_reset_status()` to find out whether that has happened.
+        ///
+        /// If the system can't create robust contexts, a `RequiredExtensionUnavailable` error is
+        /// returned when creating the context descriptor.
+        const ROBUST                = 0x40;
     }
 }
 
+/// Whether a context has been lost because of a GPU reset.
+///
+/// See `Device::context_reset_status()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContextResetStatus {
+    /// The context hasn't been reset.
+    NoReset,
+    /// The context was reset, and it caused the reset.
+    GuiltyReset,
+    /// The context was reset by something that it didn't do, such as a hang in another context.
+    InnocentReset,
+    /// The context was reset, but the cause is unknown.
+    UnknownReset,
+}
+
 /// Attributes that control aspects of a context and/or surfaces created from that context.
 ///
 /// Similar to: <https://www.khronos.org/registry/webgl/specs/latest/1.0/#WEBGLCONTEXTATTRIBUTES>
@@ -56,7 +94,8 @@ pub struct ContextAttributes {
     /// The OpenGL or OpenGL ES version that this context supports.
     ///
     /// Keep in mind that OpenGL and OpenGL ES have different version numbering schemes. Before
-    /// filling in this field, check the result of `Device::gl_api()`.
+    /// filling in this field, check the result of `Device::gl_api()`. To fall back to other
+    /// versions if this one isn't supported, use `Device::create_context_with_fallbacks()`.
     pub version: GLVersion,
     /// Various flags.
     pub flags: ContextAttributeFlags,
@@ -72,6 +111,157 @@ impl ContextAttributes {
     }
 }
 
+// The versions that are tried within a `ContextVersionRange`, besides its endpoints.
+static GL_VERSIONS: [GLVersion; 13] = [
+    GLVersion { major: 4, minor: 6 },
+    GLVersion { major: 4, minor: 5 },
+    GLVersion { major: 4, minor: 4 },
+    GLVersion { major: 4, minor: 3 },
+    GLVersion { major: 4, minor: 2 },
+    GLVersion { major: 4, minor: 1 },
+    GLVersion { major: 4, minor: 0 },
+    GLVersion { major: 3, minor: 3 },
+    GLVersion { major: 3, minor: 2 },
+    GLVersion { major: 3, minor: 1 },
+    GLVersion { major: 3, minor: 0 },
+    GLVersion { major: 2, minor: 1 },
+    GLVersion { major: 2, minor: 0 },
+];
+static GL_ES_VERSIONS: [GLVersion; 4] = [
+    GLVersion { major: 3, minor: 2 },
+    GLVersion { major: 3, minor: 1 },
+    GLVersion { major: 3, minor: 0 },
+    GLVersion { major: 2, minor: 0 },
+];
+
+/// A range of OpenGL or OpenGL ES versions to try, from the highest to the lowest, when creating
+/// a context with `Device::create_context_with_fallbacks()`.
+///
+/// Besides the endpoints of the range, every released version of the API in between is tried.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ContextVersionRange {
+    /// The highest version to try.
+    pub max_version: GLVersion,
+    /// The lowest version to try.
+    pub min_version: GLVersion,
+    /// The flags to create the context with, whichever version it ends up with.
+    pub flags: ContextAttributeFlags,
+}
+
+impl ContextVersionRange {
+    /// Creates a range of versions to try, from `max_version` down to `min_version`.
+    #[inline]
+    pub fn new(
+        max_version: GLVersion,
+        min_version: GLVersion,
+        flags: ContextAttributeFlags,
+    ) -> ContextVersionRange {
+        ContextVersionRange {
+            max_version,
+            min_version,
+            flags,
+        }
+    }
+
+    /// Creates a range that only contains the version of the given attributes.
+    #[inline]
+    pub fn exact(attributes: &ContextAttributes) -> ContextVersionRange {
+        ContextVersionRange::new(attributes.version, attributes.version, attributes.flags)
+    }
+
+    // Returns the attributes to try for this range, in order. `gl_api` is the API of the device,
+    // which the `GLES` flag overrides.
+    pub(crate) fn candidates(&self, gl_api: GLApi) -> Vec<ContextAttributes> {
+        let known_versions = if self.flags.contains(ContextAttributeFlags::GLES) {
+            &GL_ES_VERSIONS[..]
+        } else {
+            match gl_api {
+                GLApi::GL => &GL_VERSIONS[..],
+                GLApi::GLES => &GL_ES_VERSIONS[..],
+            }
+        };
+
+        let mut versions: Vec<GLVersion> = known_versions
+            .iter()
+            .copied()
+            .filter(|&version| version >= self.min_version && version <= self.max_version)
+            .collect();
+        if self.min_version <= self.max_version {
+            versions.push(self.max_version);
+            versions.push(self.min_version);
+        }
+        versions.sort_unstable_by(|a, b| b.cmp(a));
+        versions.dedup();
+
+        versions
+            .into_iter()
+            .map(|version| ContextAttributes {
+                version,
+                flags: self.flags,
+            })
+            .collect()
+    }
+}
+
+// Implements `Device::create_context_with_fallbacks()` in terms of the other methods of the
+// device.
+//
+// Drivers disagree about which versions and profiles they support (Mesa, for instance, only
+// supports the compatibility profile up to OpenGL 3.0 on some drivers), so every failure just
+// moves on to the next candidate.
+pub(crate) fn create_context_with_fallbacks<D>(
+    device: &mut D,
+    ranges: &[ContextVersionRange],
+    share_with: Option<&D::Context>,
+) -> Result<(D::Context, ContextAttributes), Error>
+where
+    D: DeviceInterface,
+{
+    let gl_api = device.gl_api();
+    let mut result = Err(Error::UnsupportedGLVersion);
+    for range in ranges {
+        for attributes in range.candidates(gl_api) {
+            let context =
+                device
+                    .create_context_descriptor(&attributes)
+                    .and_then(|context_descriptor| {
+                        device.create_context(&context_descriptor, share_with)
+                    });
+            match context {
+                Ok(context) => return Ok((context, attributes)),
+                Err(err) => result = Err(err),
+            }
+        }
+    }
+    result
+}
+
+// Implements `Device::choose_context_descriptor()` in terms of the other methods of the device.
+pub(crate) fn choose_context_descriptor<D, F>(
+    device: &D,
+    attributes: &ContextAttributes,
+    mut score: F,
+) -> Result<D::ContextDescriptor, Error>
+where
+    D: DeviceInterface,
+    F: FnMut(&ContextConfigInfo) -> Option<u32>,
+{
+    let mut best: Option<(u32, D::ContextDescriptor)> = None;
+    for context_descriptor in device.enumerate_context_descriptors(attributes)? {
+        let config = device.context_descriptor_config(&context_descriptor)?;
+        let candidate_score = match score(&config) {
+            None => continue,
+            Some(candidate_score) => candidate_score,
+        };
+        match best {
+            Some((best_score, _)) if best_score >= candidate_score => {}
+            _ => best = Some((candidate_score, context_descriptor)),
+        }
+    }
+    best.map(|(_, context_descriptor)| context_descriptor)
+        .ok_or(Error::NoPixelFormatFound)
+}
+
 #[cfg(any(target_os = "android", target_env = "ohos"))]
 pub(crate) fn current_context_uses_compatibility_profile(_gl: &Gl) -> bool {
     false
@@ -106,3 +296,147 @@ pub(crate) fn current_context_uses_compatibility_profile(gl: &Gl) -> bool {
         false
     }
 }
+
+#[cfg(any(target_os = "android", target_env = "ohos"))]
+pub(crate) fn current_context_is_debug(_gl: &Gl) -> bool {
+    false
+}
+
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+#[allow(dead_code)]
+pub(crate) fn current_context_is_debug(gl: &Gl) -> bool {
+    unsafe {
+        let mut context_flags = 0;
+        gl.GetIntegerv(gl::CONTEXT_FLAGS, &mut context_flags);
+        gl.GetError() == gl::NO_ERROR && (context_flags & gl::CONTEXT_FLAG_DEBUG_BIT as i32) != 0
+    }
+}
+
+// The OpenGL ES bindings don't include `KHR_debug`, so messages aren't forwarded there.
+#[cfg(any(target_os = "android", target_env = "ohos"))]
+pub(crate) unsafe fn install_debug_message_callback(_gl: &Gl, _context_id: ContextID) {}
+
+/// Routes `KHR_debug` messages from the current context into the `log` crate.
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+#[allow(dead_code)]
+pub(crate) unsafe fn install_debug_message_callback(gl: &Gl, context_id: ContextID) {
+    if !gl.DebugMessageCallback.is_loaded() {
+        warn!(
+            "Debug context {:?} was requested, but `glDebugMessageCallback` is unavailable",
+            context_id
+        );
+        return;
+    }
+
+    // Synchronous output makes the messages show up next to the call that caused them.
+    gl.Enable(gl::DEBUG_OUTPUT);
+    gl.Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+    gl.DebugMessageCallback(
+        Some(debug_message_callback),
+        context_id.0 as usize as *const c_void,
+    );
+}
+
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+extern "system" fn debug_message_callback(
+    source: GLenum,
+    gltype: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    user_param: *mut c_void,
+) {
+    let context_id = ContextID(user_param as usize as u64);
+    let message = unsafe {
+        if length < 0 {
+            CStr::from_ptr(message).to_string_lossy()
+        } else {
+            String::from_utf8_lossy(slice::from_raw_parts(message as *const u8, length as usize))
+        }
+    };
+
+    let level = match severity {
+        gl::DEBUG_SEVERITY_HIGH => log::Level::Error,
+        gl::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
+        gl::DEBUG_SEVERITY_LOW => log::Level::Info,
+        _ => log::Level::Debug,
+    };
+    log!(
+        level,
+        "GL debug message from context {:?} (source {:#x}, type {:#x}, ID {}): {}",
+        context_id,
+        source,
+        gltype,
+        id,
+        message.trim_end()
+    );
+}
+
+#[cfg(any(target_os = "android", target_env = "ohos"))]
+#[allow(dead_code)]
+pub(crate) fn current_context_is_robust(gl: &Gl) -> bool {
+    unsafe {
+        let mut reset_notification_strategy = 0;
+        gl.GetIntegerv(
+            gl::RESET_NOTIFICATION_STRATEGY_EXT,
+            &mut reset_notification_strategy,
+        );
+        gl.GetError() == gl::NO_ERROR
+            && reset_notification_strategy == gl::LOSE_CONTEXT_ON_RESET_EXT as i32
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+#[allow(dead_code)]
+pub(crate) fn current_context_is_robust(gl: &Gl) -> bool {
+    unsafe {
+        let mut reset_notification_strategy = 0;
+        gl.GetIntegerv(
+            gl::RESET_NOTIFICATION_STRATEGY,
+            &mut reset_notification_strategy,
+        );
+        gl.GetError() == gl::NO_ERROR
+            && reset_notification_strategy == gl::LOSE_CONTEXT_ON_RESET as i32
+    }
+}
+
+#[cfg(any(target_os = "android", target_env = "ohos"))]
+#[allow(dead_code)]
+pub(crate) fn current_context_reset_status(gl: &Gl) -> ContextResetStatus {
+    unsafe {
+        if !gl.GetGraphicsResetStatusEXT.is_loaded() {
+            return ContextResetStatus::NoReset;
+        }
+        match gl.GetGraphicsResetStatusEXT() {
+            gl::GUILTY_CONTEXT_RESET_EXT => ContextResetStatus::GuiltyReset,
+            gl::INNOCENT_CONTEXT_RESET_EXT => ContextResetStatus::InnocentReset,
+            gl::UNKNOWN_CONTEXT_RESET_EXT => ContextResetStatus::UnknownReset,
+            _ => ContextResetStatus::NoReset,
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+#[allow(dead_code)]
+pub(crate) fn current_context_reset_status(gl: &Gl) -> ContextResetStatus {
+    unsafe {
+        // Drivers that only support `GL_ARB_robustness`, or OpenGL ES before 3.2, don't export
+        // the core entry point.
+        let status = if gl.GetGraphicsResetStatus.is_loaded() {
+            gl.GetGraphicsResetStatus()
+        } else if gl.GetGraphicsResetStatusARB.is_loaded() {
+            gl.GetGraphicsResetStatusARB()
+        } else if gl.GetGraphicsResetStatusKHR.is_loaded() {
+            gl.GetGraphicsResetStatusKHR()
+        } else {
+            return ContextResetStatus::NoReset;
+        };
+        match status {
+            gl::GUILTY_CONTEXT_RESET => ContextResetStatus::GuiltyReset,
+            gl::INNOCENT_CONTEXT_RESET => ContextResetStatus::InnocentReset,
+            gl::UNKNOWN_CONTEXT_RESET => ContextResetStatus::UnknownReset,
+            _ => ContextResetStatus::NoReset,
+        }
+    }
+}
@@ -4,10 +4,13 @@
 
 use super::connection::Connection as ConnectionInterface;
 use crate::gl::types::{GLenum, GLuint};
-use crate::{ContextAttributes, ContextID, Error, GLApi, SurfaceAccess, SurfaceInfo, SurfaceType};
-use euclid::default::Size2D;
+use crate::{AdapterInfo, ContextAttributes, ContextCapabilities, ContextConfigInfo, ContextID};
+use crate::{ContextResetStatus, ContextVersionRange, Error, GLApi, LiveResource};
+use crate::{PixelFormat, PresentMode, SurfaceAccess, SurfaceInfo, SurfaceType};
+use euclid::default::{Rect, Size2D};
 
 use std::os::raw::c_void;
+use std::time::Duration;
 
 /// A thread-local handle to a device.
 ///
@@ -28,6 +31,8 @@ where
     type Surface;
     /// The surface texture type associated with this device.
     type SurfaceTexture;
+    /// The fence type associated with this device.
+    type Fence;
 
     // device.rs
 
@@ -43,6 +48,18 @@ where
     /// Returns the OpenGL API flavor that this device supports (OpenGL or OpenGL ES).
     fn gl_api(&self) -> GLApi;
 
+    /// Returns information about the adapter that this device renders with.
+    ///
+    /// This creates and destroys a temporary context in order to query the OpenGL strings.
+    fn adapter_info(&mut self) -> Result<AdapterInfo, Error>;
+
+    /// Returns the contexts, surfaces, and surface textures that this device has created and
+    /// that have not yet been destroyed.
+    ///
+    /// Resources are only tracked in debug builds; in release builds this is always empty. Any
+    /// resources still alive when the device is dropped are logged as leaks.
+    fn live_resources(&self) -> Vec<LiveResource>;
+
     // context.rs
 
     /// Creates a context descriptor with the given attributes.
@@ -53,6 +70,30 @@ where
         attributes: &ContextAttributes,
     ) -> Result<Self::ContextDescriptor, Error>;
 
+    /// Returns a context descriptor for every framebuffer configuration that satisfies the given
+    /// attributes, in the order that the driver prefers them.
+    ///
+    /// The first candidate is the one that `create_context_descriptor()` picks. Use
+    /// `context_descriptor_config()` to inspect each candidate; any of them can be passed to
+    /// `create_context()`.
+    fn enumerate_context_descriptors(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<Vec<Self::ContextDescriptor>, Error>;
+
+    /// Creates a context descriptor with the given attributes, using the framebuffer
+    /// configuration that the given function scores highest.
+    ///
+    /// The function returns `None` to reject a configuration. Ties go to the candidate that the
+    /// driver prefers. If every candidate is rejected, this returns `NoPixelFormatFound`.
+    fn choose_context_descriptor<F>(
+        &self,
+        attributes: &ContextAttributes,
+        score: F,
+    ) -> Result<Self::ContextDescriptor, Error>
+    where
+        F: FnMut(&ContextConfigInfo) -> Option<u32>;
+
     /// Creates a new OpenGL context.
     ///
     /// The context initially has no surface attached. Until a surface is bound to it, rendering
@@ -63,6 +104,17 @@ where
         share_with: Option<&Self::Context>,
     ) -> Result<Self::Context, Error>;
 
+    /// Creates a new OpenGL context with the first version that the driver supports, trying each
+    /// range in turn from its highest version to its lowest.
+    ///
+    /// Returns the context along with the attributes that it was created with. If no version
+    /// works, this returns the error from the last attempt.
+    fn create_context_with_fallbacks(
+        &mut self,
+        ranges: &[ContextVersionRange],
+        share_with: Option<&Self::Context>,
+    ) -> Result<(Self::Context, ContextAttributes), Error>;
+
     /// Wraps a native context object in an OpenGL context.
     unsafe fn create_context_from_native_context(
         &self,
@@ -77,6 +129,12 @@ where
     /// Returns the descriptor that this context was created with.
     fn context_descriptor(&self, context: &Self::Context) -> Self::ContextDescriptor;
 
+    /// Returns the OpenGL API flavor (OpenGL or OpenGL ES) that the given context uses.
+    ///
+    /// On backends that support both, this depends on whether the context was created with
+    /// `ContextAttributeFlags::GLES`; otherwise it is the same as `gl_api()`.
+    fn context_gl_api(&self, context: &Self::Context) -> GLApi;
+
     /// Makes the context the current OpenGL context for this thread.
     ///
     /// After calling this function, it is valid to use OpenGL rendering commands.
@@ -94,6 +152,14 @@ where
         context_descriptor: &Self::ContextDescriptor,
     ) -> ContextAttributes;
 
+    /// Returns the framebuffer configuration that the context descriptor selected.
+    ///
+    /// Backends that can't query this return `UnsupportedOnThisPlatform`.
+    fn context_descriptor_config(
+        &self,
+        context_descriptor: &Self::ContextDescriptor,
+    ) -> Result<ContextConfigInfo, Error>;
+
     /// Fetches the address of an OpenGL function associated with this context.
     ///
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -139,6 +205,43 @@ where
     /// This includes, most notably, the OpenGL framebuffer object needed to render to the surface.
     fn context_surface_info(&self, context: &Self::Context) -> Result<Option<SurfaceInfo>, Error>;
 
+    /// Returns whether the context has been lost because of a GPU reset, and if so, whether it
+    /// was responsible for the reset.
+    ///
+    /// Only contexts created with the `ROBUST` flag are guaranteed to be told about resets. Once a
+    /// reset has been reported, the context is unusable and must be destroyed and recreated.
+    fn context_reset_status(&self, context: &Self::Context) -> Result<ContextResetStatus, Error>;
+
+    /// Returns the version, extensions, and limits of the given context.
+    fn context_capabilities(&self, context: &Self::Context) -> Result<ContextCapabilities, Error>;
+
+    /// Inserts a fence into the command stream of the given context, and flushes the context.
+    ///
+    /// The fence is signaled once the GPU has finished all commands issued to the context before
+    /// it. Fences can be sent to other threads and waited on by other contexts of devices opened
+    /// from the same connection, which makes hand-offs of surfaces between contexts safe.
+    ///
+    /// Backends without fence objects wait for the context's commands to finish instead, and
+    /// return a fence that is already signaled. Fences must be destroyed with `destroy_fence()`.
+    fn create_fence(&self, context: &Self::Context) -> Result<Self::Fence, Error>;
+
+    /// Makes the given context wait for a fence to be signaled before executing any further
+    /// commands.
+    ///
+    /// Where possible, the wait happens on the GPU and this returns immediately. Otherwise, this
+    /// blocks until the fence is signaled.
+    fn wait_fence(&self, context: &Self::Context, fence: &Self::Fence) -> Result<(), Error>;
+
+    /// Blocks the calling thread until a fence is signaled or the timeout elapses.
+    ///
+    /// Returns true if the fence was signaled and false if the wait timed out.
+    fn client_wait_fence(&self, fence: &Self::Fence, timeout: Duration) -> Result<bool, Error>;
+
+    /// Destroys a fence.
+    ///
+    /// Fences must be explicitly destroyed with this function, or a panic may occur.
+    fn destroy_fence(&self, fence: &mut Self::Fence) -> Result<(), Error>;
+
     /// Returns the native context associated with the given context.
     fn native_context(&self, context: &Self::Context) -> Self::NativeContext;
 
@@ -209,12 +312,51 @@ where
     ///
     /// The supplied context must match the context the surface was created with, or an
     /// `IncompatibleSurface` error is returned.
+    ///
+    /// Generic surfaces always return `NoWidgetAttached`, but multisampled ones are resolved into
+    /// their texture first.
     fn present_surface(
         &self,
         context: &Self::Context,
         surface: &mut Self::Surface,
     ) -> Result<(), Error>;
 
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// changed since the last present.
+    ///
+    /// The rectangles are in framebuffer coordinates, with the origin at the bottom left. An empty
+    /// list means that the whole surface changed. Backends that can't present partial updates
+    /// present the whole surface, exactly like `present_surface()`.
+    fn present_surface_with_damage(
+        &self,
+        context: &Self::Context,
+        surface: &mut Self::Surface,
+        damage: &[Rect<i32>],
+    ) -> Result<(), Error>;
+
+    /// Returns the number of presents since the current back buffer of a widget surface was last
+    /// the back buffer, so that only the regions damaged since then need to be redrawn.
+    ///
+    /// A value of 0 means the contents of the back buffer are undefined, and everything must be
+    /// redrawn. This is always the case on backends that can't query the buffer age.
+    fn surface_buffer_age(
+        &self,
+        context: &Self::Context,
+        surface: &Self::Surface,
+    ) -> Result<u32, Error>;
+
+    /// Sets how `present_surface()` synchronizes with the display for the given widget surface.
+    ///
+    /// The supplied context must match the context the surface was created with, or an
+    /// `IncompatibleSurface` error is returned. If the platform can't honor the requested mode,
+    /// this returns `UnsupportedOnThisPlatform`, and the previous mode stays in effect.
+    fn set_present_mode(
+        &self,
+        context: &Self::Context,
+        surface: &Self::Surface,
+        mode: PresentMode,
+    ) -> Result<(), Error>;
+
     /// Resizes a widget surface.
     fn resize_surface(
         &self,
@@ -231,6 +373,24 @@ where
     /// 0, the default framebuffer, depending on platform.
     fn surface_info(&self, surface: &Self::Surface) -> SurfaceInfo;
 
+    /// Reads back the pixels in the given rectangle of a surface.
+    ///
+    /// The rectangle has its origin at the top left of the surface, and the returned rows are
+    /// ordered from top to bottom, so row 0 is the top row, as in most image formats. Rows are
+    /// tightly packed. The surface must not be bound to a context; unbind it first in order to
+    /// read back what was rendered to it.
+    ///
+    /// The supplied context must match the context the surface was created with, or an
+    /// `IncompatibleSurface` error is returned. A rectangle that doesn't lie within the surface,
+    /// or a pixel format that the driver can't read the surface in, results in a `Failed` error.
+    fn read_surface_pixels(
+        &self,
+        context: &Self::Context,
+        surface: &Self::Surface,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error>;
+
     /// Returns the OpenGL texture object containing the contents of this surface.
     ///
     /// It is only legal to read from, not write to, this texture object.
@@ -2,6 +2,9 @@
 //
 //! Various errors that methods can produce.
 
+use std::error::Error as StdError;
+use std::fmt::{self, Debug, Display, Formatter};
+
 /// Various errors that methods can produce.
 #[derive(Debug)]
 pub enum Error {
@@ -22,15 +25,15 @@ pub enum Error {
     /// The system doesn't support the requested OpenGL API version.
     UnsupportedGLVersion,
     /// Choosing an OpenGL pixel format failed.
-    PixelFormatSelectionFailed(WindowingApiError),
+    PixelFormatSelectionFailed(NativeError),
     /// The system couldn't choose an OpenGL pixel format.
     NoPixelFormatFound,
     /// The system couldn't create an OpenGL context.
-    ContextCreationFailed(WindowingApiError),
+    ContextCreationFailed(NativeError),
     /// The system couldn't destroy the OpenGL context.
-    ContextDestructionFailed(WindowingApiError),
+    ContextDestructionFailed(NativeError),
     /// The system couldn't make the OpenGL context current or not current.
-    MakeCurrentFailed(WindowingApiError),
+    MakeCurrentFailed(NativeError),
     /// The system OpenGL library couldn't be located.
     NoGLLibraryFound,
     /// An extension necessary for this library to function isn't supported.
@@ -46,13 +49,15 @@ pub enum Error {
     /// The device couldn't be opened.
     DeviceOpenFailed,
     /// The system couldn't create a surface.
-    SurfaceCreationFailed(WindowingApiError),
+    SurfaceCreationFailed(NativeError),
     /// The system couldn't import a surface from another thread.
-    SurfaceImportFailed(WindowingApiError),
+    SurfaceImportFailed(NativeError),
+    /// The system couldn't export a surface for use by other APIs or processes.
+    SurfaceExportFailed(NativeError),
     /// The system couldn't create a surface texture from a surface.
-    SurfaceTextureCreationFailed(WindowingApiError),
+    SurfaceTextureCreationFailed(NativeError),
     /// The system couldn't present a widget surface.
-    PresentFailed(WindowingApiError),
+    PresentFailed(NativeError),
     /// A context couldn't be created because there is no current context.
     NoCurrentContext,
     /// The current connection couldn't be fetched because there is no current connection.
@@ -69,6 +74,8 @@ pub enum Error {
     /// The surface texture is from a hardware device, but this is a software device, or vice
     /// versa.
     IncompatibleSurfaceTexture,
+    /// The fence was created on a device with a different display connection.
+    IncompatibleFence,
     /// The surface has no window attachment.
     NoWidgetAttached,
     /// The surface has a window attachment.
@@ -94,12 +101,78 @@ pub enum Error {
     IncompatibleNativeContext,
     /// The native device does not match the supplied connection.
     IncompatibleNativeDevice,
+    /// Sending data to or receiving data from another process failed.
+    IpcFailed(std::io::Error),
+}
+
+/// A failed call into the native windowing API.
+///
+/// This records which function failed, the raw error code it reported (if any), and the
+/// platform-independent category of the error.
+///
+/// The details are boxed so that `Error` stays small; surfaces are returned alongside errors in
+/// several places.
+#[derive(Clone, PartialEq, Eq)]
+pub struct NativeError(Box<NativeErrorInfo>);
+
+#[derive(Clone, PartialEq, Eq)]
+struct NativeErrorInfo {
+    kind: WindowingApiError,
+    call: &'static str,
+    code: Option<i32>,
+}
+
+impl NativeError {
+    /// Creates a new error for a failed call to the native function `call`.
+    #[inline]
+    pub fn new(kind: WindowingApiError, call: &'static str) -> NativeError {
+        NativeError(Box::new(NativeErrorInfo {
+            kind,
+            call,
+            code: None,
+        }))
+    }
+
+    /// Attaches the raw error code reported by the native API.
+    #[inline]
+    pub fn with_code(mut self, code: i32) -> NativeError {
+        self.0.code = Some(code);
+        self
+    }
+
+    /// Returns the category of the error.
+    #[inline]
+    pub fn kind(&self) -> WindowingApiError {
+        self.0.kind
+    }
+
+    /// Returns the name of the native function that failed; e.g. `eglCreateContext`.
+    #[inline]
+    pub fn call(&self) -> &'static str {
+        self.0.call
+    }
+
+    /// Returns the raw error code reported by the native API, if it reports one.
+    #[inline]
+    pub fn code(&self) -> Option<i32> {
+        self.0.code
+    }
+}
+
+impl Debug for NativeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("NativeError")
+            .field("kind", &self.0.kind)
+            .field("call", &self.0.call)
+            .field("code", &self.0.code)
+            .finish()
+    }
 }
 
 /// Abstraction of the errors that EGL, CGL, GLX, CGL, etc. return.
 ///
 /// They all tend to follow similar patterns.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum WindowingApiError {
     /// Miscellaneous error.
     Failed,
@@ -189,3 +262,141 @@ pub enum WindowingApiError {
     /// EGL: The EGL configuration is unsupported.
     BadConfig,
 }
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let message = match *self {
+            Error::Failed => "the operation failed",
+            Error::UnsupportedOnThisPlatform => "the platform doesn't support this operation",
+            Error::Unimplemented => "this operation isn't implemented yet on this platform",
+            Error::UnsupportedGLType => "the requested OpenGL API type isn't supported",
+            Error::UnsupportedGLProfile => {
+                "the requested OpenGL profile isn't supported for this version"
+            }
+            Error::UnsupportedGLVersion => "the requested OpenGL version isn't supported",
+            Error::PixelFormatSelectionFailed(_) => "couldn't choose an OpenGL pixel format",
+            Error::NoPixelFormatFound => "no suitable OpenGL pixel format was found",
+            Error::ContextCreationFailed(_) => "couldn't create the OpenGL context",
+            Error::ContextDestructionFailed(_) => "couldn't destroy the OpenGL context",
+            Error::MakeCurrentFailed(_) => {
+                "couldn't make the OpenGL context current or not current"
+            }
+            Error::NoGLLibraryFound => "the system OpenGL library couldn't be located",
+            Error::RequiredExtensionUnavailable => "a required extension isn't supported",
+            Error::GLFunctionNotFound => "an OpenGL function couldn't be found",
+            Error::ExternalRenderTarget => "the context renders to an external render target",
+            Error::SurfaceAlreadyBound => "a surface is already bound to the context",
+            Error::NoAdapterFound => "no suitable adapter was found",
+            Error::DeviceOpenFailed => "the device couldn't be opened",
+            Error::SurfaceCreationFailed(_) => "couldn't create the surface",
+            Error::SurfaceImportFailed(_) => "couldn't import the surface",
+            Error::SurfaceExportFailed(_) => "couldn't export the surface",
+            Error::SurfaceTextureCreationFailed(_) => "couldn't create the surface texture",
+            Error::PresentFailed(_) => "couldn't present the surface",
+            Error::NoCurrentContext => "there is no current context",
+            Error::NoCurrentConnection => "there is no current connection",
+            Error::IncompatibleSurface => "the surface wasn't created from this context",
+            Error::IncompatibleContextDescriptor => {
+                "the context descriptor is incompatible with this device"
+            }
+            Error::IncompatibleContext => "the context is incompatible with this device",
+            Error::IncompatibleSharedContext => "the shared context is incompatible for sharing",
+            Error::IncompatibleSurfaceTexture => {
+                "the surface texture is incompatible with this device"
+            }
+            Error::IncompatibleFence => "the fence was created on a different display connection",
+            Error::NoWidgetAttached => "the surface has no window attached",
+            Error::WidgetAttached => "the surface has a window attached",
+            Error::InvalidNativeWidget => "the native widget is invalid",
+            Error::SurfaceDataInaccessible => "the surface data isn't accessible from the CPU",
+            Error::SurfaceLockFailed => "couldn't lock the surface for CPU access",
+            Error::ConnectionFailed => "couldn't open a connection to the display server",
+            Error::ConnectionRequired => "a display server connection is required",
+            Error::IncompatibleAdapter => "the adapter is incompatible with this connection",
+            Error::IncompatibleNativeWidget => "the native widget is incompatible with this device",
+            Error::IncompatibleRawDisplayHandle => {
+                "the raw display handle is incompatible with this backend"
+            }
+            Error::IncompatibleNativeContext => {
+                "the native context is incompatible with this device"
+            }
+            Error::IncompatibleNativeDevice => {
+                "the native device is incompatible with this connection"
+            }
+            Error::IpcFailed(_) => "interprocess communication failed",
+        };
+        f.write_str(message)
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            Error::PixelFormatSelectionFailed(ref err)
+            | Error::ContextCreationFailed(ref err)
+            | Error::ContextDestructionFailed(ref err)
+            | Error::MakeCurrentFailed(ref err)
+            | Error::SurfaceCreationFailed(ref err)
+            | Error::SurfaceImportFailed(ref err)
+            | Error::SurfaceExportFailed(ref err)
+            | Error::SurfaceTextureCreationFailed(ref err)
+            | Error::PresentFailed(ref err) => Some(err),
+            Error::IpcFailed(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Display for NativeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} failed: {}", self.0.call, self.0.kind)?;
+        if let Some(code) = self.0.code {
+            write!(f, " (error code {:#x})", code)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for NativeError {}
+
+impl Display for WindowingApiError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let message = match *self {
+            WindowingApiError::Failed => "failed",
+            WindowingApiError::BadAttribute => "bad attribute",
+            WindowingApiError::BadProperty => "bad renderer property",
+            WindowingApiError::BadPixelFormat => "bad pixel format",
+            WindowingApiError::BadRendererInfo => "bad renderer info",
+            WindowingApiError::BadContext => "bad context",
+            WindowingApiError::BadDrawable => "bad drawable",
+            WindowingApiError::BadDisplay => "bad display",
+            WindowingApiError::BadState => "bad context state",
+            WindowingApiError::BadValue => "bad value",
+            WindowingApiError::BadMatch => "inconsistent arguments",
+            WindowingApiError::BadEnumeration => "bad enumerant",
+            WindowingApiError::BadOffScreen => "bad off-screen drawable",
+            WindowingApiError::BadFullScreen => "bad full-screen drawable",
+            WindowingApiError::BadWindow => "bad window",
+            WindowingApiError::BadAddress => "bad address",
+            WindowingApiError::BadCodeModule => "bad code module",
+            WindowingApiError::BadAlloc => "allocation failed",
+            WindowingApiError::BadConnection => "bad connection",
+            WindowingApiError::NotInitialized => "not initialized",
+            WindowingApiError::BadAccess => "resource unavailable",
+            WindowingApiError::BadCurrentSurface => "current surface is no longer valid",
+            WindowingApiError::BadSurface => "bad surface",
+            WindowingApiError::BadParameter => "bad parameter",
+            WindowingApiError::BadNativePixmap => "bad native pixmap",
+            WindowingApiError::BadNativeWindow => "bad native window",
+            WindowingApiError::ContextLost => "context lost",
+            WindowingApiError::BadScreen => "bad screen",
+            WindowingApiError::NoExtension => "extension unavailable",
+            WindowingApiError::BadVisual => "bad visual",
+            WindowingApiError::BadOperation => "bad operation",
+            WindowingApiError::BadConfig => "bad config",
+        };
+        f.write_str(message)
+    }
+}
+
+impl StdError for WindowingApiError {}
@@ -4,7 +4,10 @@
 
 use crate::gl;
 use crate::gl::types::{GLenum, GLint, GLuint};
-use crate::Gl;
+use crate::{Error, GLVersion, Gl, PixelFormat};
+
+use euclid::default::{Rect, Size2D};
+use std::os::raw::c_void;
 
 #[allow(dead_code)]
 pub(crate) fn create_and_bind_framebuffer(
@@ -49,3 +52,93 @@ pub(crate) fn destroy_framebuffer(gl: &Gl, framebuffer_object: GLuint) {
         gl.DeleteFramebuffers(1, &framebuffer_object);
     }
 }
+
+// Reads back the pixels in `rect` from a framebuffer of the given size, which must be bound to a
+// current context.
+//
+// The rectangle has its origin at the top left of the framebuffer, and the returned rows are
+// ordered from top to bottom, unlike `glReadPixels()`.
+#[allow(dead_code)]
+pub(crate) fn read_framebuffer_pixels(
+    gl: &Gl,
+    framebuffer_object: GLuint,
+    framebuffer_size: Size2D<i32>,
+    rect: Rect<i32>,
+    format: PixelFormat,
+) -> Result<Vec<u8>, Error> {
+    if rect.size.width < 0
+        || rect.size.height < 0
+        || !Rect::from_size(framebuffer_size).contains_rect(&rect)
+    {
+        return Err(Error::Failed);
+    }
+
+    let row_length = rect.size.width as usize * format.bytes_per_pixel();
+    let mut pixels = vec![0; row_length * rect.size.height as usize];
+    if pixels.is_empty() {
+        return Ok(pixels);
+    }
+
+    unsafe {
+        // OpenGL ES 2.0 has neither separate read framebuffers nor pixel pack buffers.
+        let (framebuffer_binding, framebuffer_target) = if GLVersion::current(gl)?.major >= 3 {
+            (gl::READ_FRAMEBUFFER_BINDING, gl::READ_FRAMEBUFFER)
+        } else {
+            (gl::FRAMEBUFFER_BINDING, gl::FRAMEBUFFER)
+        };
+        let has_pack_state = framebuffer_target == gl::READ_FRAMEBUFFER;
+
+        let mut old_framebuffer = 0;
+        gl.GetIntegerv(framebuffer_binding, &mut old_framebuffer);
+        let mut old_pack_alignment = 0;
+        gl.GetIntegerv(gl::PACK_ALIGNMENT, &mut old_pack_alignment);
+        let mut old_pack_state = [0; 4];
+        if has_pack_state {
+            gl.GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut old_pack_state[0]);
+            gl.GetIntegerv(gl::PACK_ROW_LENGTH, &mut old_pack_state[1]);
+            gl.GetIntegerv(gl::PACK_SKIP_PIXELS, &mut old_pack_state[2]);
+            gl.GetIntegerv(gl::PACK_SKIP_ROWS, &mut old_pack_state[3]);
+            gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl.PixelStorei(gl::PACK_ROW_LENGTH, 0);
+            gl.PixelStorei(gl::PACK_SKIP_PIXELS, 0);
+            gl.PixelStorei(gl::PACK_SKIP_ROWS, 0);
+        }
+        gl.BindFramebuffer(framebuffer_target, framebuffer_object);
+        gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+
+        let (gl_format, gl_type) = format.gl_read_formats();
+        gl.ReadPixels(
+            rect.origin.x,
+            framebuffer_size.height - rect.max_y(),
+            rect.size.width,
+            rect.size.height,
+            gl_format,
+            gl_type,
+            pixels.as_mut_ptr() as *mut c_void,
+        );
+        let result = gl.GetError();
+
+        gl.PixelStorei(gl::PACK_ALIGNMENT, old_pack_alignment);
+        if has_pack_state {
+            gl.BindBuffer(gl::PIXEL_PACK_BUFFER, old_pack_state[0] as GLuint);
+            gl.PixelStorei(gl::PACK_ROW_LENGTH, old_pack_state[1]);
+            gl.PixelStorei(gl::PACK_SKIP_PIXELS, old_pack_state[2]);
+            gl.PixelStorei(gl::PACK_SKIP_ROWS, old_pack_state[3]);
+        }
+        gl.BindFramebuffer(framebuffer_target, old_framebuffer as GLuint);
+
+        if result != gl::NO_ERROR {
+            return Err(Error::Failed);
+        }
+    }
+
+    // OpenGL returns the bottom row first, so flip the rows.
+    let row_count = rect.size.height as usize;
+    for row in 0..(row_count / 2) {
+        let (top, bottom) = pixels.split_at_mut((row_count - row - 1) * row_length);
+        top[(row * row_length)..((row + 1) * row_length)]
+            .swap_with_slice(&mut bottom[..row_length]);
+    }
+
+    Ok(pixels)
+}
@@ -6,7 +6,7 @@
 use super::super::connection::{Connection, NativeConnection};
 use super::super::device::{Adapter, Device, NativeDevice};
 use super::super::surface::NativeWidget;
-use crate::connection::Connection as ConnectionInterface;
+use crate::connection::{BackendKind, Connection as ConnectionInterface};
 use crate::info::GLApi;
 use crate::Error;
 
@@ -27,6 +27,16 @@ impl ConnectionInterface for Connection {
         Connection::new()
     }
 
+    #[inline]
+    fn new_with_preference(preference: &[BackendKind]) -> Result<Connection, Error> {
+        Connection::new_with_preference(preference)
+    }
+
+    #[inline]
+    fn backend_kind(&self) -> BackendKind {
+        Connection::backend_kind(self)
+    }
+
     #[inline]
     fn native_connection(&self) -> Self::NativeConnection {
         Connection::native_connection(self)
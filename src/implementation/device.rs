@@ -4,16 +4,21 @@
 //! `Device` trait for a backend.
 
 use super::super::connection::Connection;
-use super::super::context::{Context, ContextDescriptor, NativeContext};
+use super::super::context::{Context, ContextDescriptor, Fence, NativeContext};
 use super::super::device::{Adapter, Device};
 use super::super::surface::{NativeWidget, Surface, SurfaceTexture};
 use crate::connection::Connection as ConnectionInterface;
 use crate::device::Device as DeviceInterface;
 use crate::gl::types::{GLenum, GLuint};
-use crate::{ContextAttributes, ContextID, Error, GLApi, SurfaceAccess, SurfaceInfo, SurfaceType};
-use euclid::default::Size2D;
+#[cfg(free_unix)]
+use crate::platform::unix::dmabuf::{DmaBufDescriptor, DmaBufDevice};
+use crate::{AdapterInfo, ContextAttributes, ContextCapabilities, ContextConfigInfo, ContextID};
+use crate::{ContextResetStatus, ContextVersionRange, Error, GLApi, LiveResource};
+use crate::{PixelFormat, PresentMode, SurfaceAccess, SurfaceInfo, SurfaceType};
+use euclid::default::{Rect, Size2D};
 
 use std::os::raw::c_void;
+use std::time::Duration;
 
 #[deny(unconditional_recursion)]
 impl DeviceInterface for Device {
@@ -23,6 +28,7 @@ impl DeviceInterface for Device {
     type NativeContext = NativeContext;
     type Surface = Surface;
     type SurfaceTexture = SurfaceTexture;
+    type Fence = Fence;
 
     // device.rs
 
@@ -47,6 +53,16 @@ impl DeviceInterface for Device {
         Device::gl_api(self)
     }
 
+    #[inline]
+    fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        Device::adapter_info(self)
+    }
+
+    #[inline]
+    fn live_resources(&self) -> Vec<LiveResource> {
+        Device::live_resources(self)
+    }
+
     // context.rs
 
     #[inline]
@@ -57,6 +73,26 @@ impl DeviceInterface for Device {
         Device::create_context_descriptor(self, attributes)
     }
 
+    #[inline]
+    fn enumerate_context_descriptors(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<Vec<Self::ContextDescriptor>, Error> {
+        Device::enumerate_context_descriptors(self, attributes)
+    }
+
+    #[inline]
+    fn choose_context_descriptor<F>(
+        &self,
+        attributes: &ContextAttributes,
+        score: F,
+    ) -> Result<Self::ContextDescriptor, Error>
+    where
+        F: FnMut(&ContextConfigInfo) -> Option<u32>,
+    {
+        Device::choose_context_descriptor(self, attributes, score)
+    }
+
     #[inline]
     fn create_context(
         &mut self,
@@ -66,6 +102,15 @@ impl DeviceInterface for Device {
         Device::create_context(self, descriptor, share_with)
     }
 
+    #[inline]
+    fn create_context_with_fallbacks(
+        &mut self,
+        ranges: &[ContextVersionRange],
+        share_with: Option<&Self::Context>,
+    ) -> Result<(Self::Context, ContextAttributes), Error> {
+        Device::create_context_with_fallbacks(self, ranges, share_with)
+    }
+
     #[inline]
     unsafe fn create_context_from_native_context(
         &self,
@@ -84,6 +129,11 @@ impl DeviceInterface for Device {
         Device::context_descriptor(self, context)
     }
 
+    #[inline]
+    fn context_gl_api(&self, context: &Self::Context) -> GLApi {
+        Device::context_gl_api(self, context)
+    }
+
     #[inline]
     fn make_context_current(&self, context: &Self::Context) -> Result<(), Error> {
         Device::make_context_current(self, context)
@@ -102,6 +152,14 @@ impl DeviceInterface for Device {
         Device::context_descriptor_attributes(self, context_descriptor)
     }
 
+    #[inline]
+    fn context_descriptor_config(
+        &self,
+        context_descriptor: &Self::ContextDescriptor,
+    ) -> Result<ContextConfigInfo, Error> {
+        Device::context_descriptor_config(self, context_descriptor)
+    }
+
     #[inline]
     fn get_proc_address(&self, context: &Self::Context, symbol_name: &str) -> *const c_void {
         Device::get_proc_address(self, context, symbol_name)
@@ -134,6 +192,36 @@ impl DeviceInterface for Device {
         Device::context_surface_info(self, context)
     }
 
+    #[inline]
+    fn context_reset_status(&self, context: &Self::Context) -> Result<ContextResetStatus, Error> {
+        Device::context_reset_status(self, context)
+    }
+
+    #[inline]
+    fn context_capabilities(&self, context: &Self::Context) -> Result<ContextCapabilities, Error> {
+        Device::context_capabilities(self, context)
+    }
+
+    #[inline]
+    fn create_fence(&self, context: &Self::Context) -> Result<Self::Fence, Error> {
+        Device::create_fence(self, context)
+    }
+
+    #[inline]
+    fn wait_fence(&self, context: &Self::Context, fence: &Self::Fence) -> Result<(), Error> {
+        Device::wait_fence(self, context, fence)
+    }
+
+    #[inline]
+    fn client_wait_fence(&self, fence: &Self::Fence, timeout: Duration) -> Result<bool, Error> {
+        Device::client_wait_fence(self, fence, timeout)
+    }
+
+    #[inline]
+    fn destroy_fence(&self, fence: &mut Self::Fence) -> Result<(), Error> {
+        Device::destroy_fence(self, fence)
+    }
+
     #[inline]
     fn native_context(&self, context: &Self::Context) -> Self::NativeContext {
         Device::native_context(self, context)
@@ -192,6 +280,35 @@ impl DeviceInterface for Device {
         Device::present_surface(self, context, surface)
     }
 
+    #[inline]
+    fn present_surface_with_damage(
+        &self,
+        context: &Self::Context,
+        surface: &mut Self::Surface,
+        damage: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        Device::present_surface_with_damage(self, context, surface, damage)
+    }
+
+    #[inline]
+    fn surface_buffer_age(
+        &self,
+        context: &Self::Context,
+        surface: &Self::Surface,
+    ) -> Result<u32, Error> {
+        Device::surface_buffer_age(self, context, surface)
+    }
+
+    #[inline]
+    fn set_present_mode(
+        &self,
+        context: &Self::Context,
+        surface: &Self::Surface,
+        mode: PresentMode,
+    ) -> Result<(), Error> {
+        Device::set_present_mode(self, context, surface, mode)
+    }
+
     #[inline]
     fn resize_surface(
         &self,
@@ -207,8 +324,47 @@ impl DeviceInterface for Device {
         Device::surface_info(self, surface)
     }
 
+    #[inline]
+    fn read_surface_pixels(
+        &self,
+        context: &Self::Context,
+        surface: &Self::Surface,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        Device::read_surface_pixels(self, context, surface, rect, format)
+    }
+
     #[inline]
     fn surface_texture_object(&self, surface_texture: &Self::SurfaceTexture) -> GLuint {
         Device::surface_texture_object(self, surface_texture)
     }
 }
+
+#[cfg(free_unix)]
+#[deny(unconditional_recursion)]
+impl DmaBufDevice for Device {
+    #[inline]
+    fn export_surface_dmabuf(
+        &self,
+        context: &Context,
+        surface: &Surface,
+    ) -> Result<DmaBufDescriptor, Error> {
+        Device::export_surface_dmabuf(self, context, surface)
+    }
+
+    #[inline]
+    fn create_surface_from_dmabuf(
+        &mut self,
+        context: &Context,
+        descriptor: DmaBufDescriptor,
+        access: SurfaceAccess,
+    ) -> Result<Surface, Error> {
+        Device::create_surface_from_dmabuf(self, context, descriptor, access)
+    }
+
+    #[inline]
+    fn adapter_identity(&self) -> String {
+        Device::adapter_identity(self)
+    }
+}
@@ -3,8 +3,10 @@
 //! OpenGL information.
 
 use crate::gl;
-use crate::Gl;
+use crate::gl::types::{GLenum, GLint, GLuint};
+use crate::{Error, Gl};
 
+use std::collections::BTreeSet;
 use std::ffi::CStr;
 use std::os::raw::c_char;
 
@@ -21,7 +23,7 @@ pub enum GLApi {
 ///
 /// Since OpenGL and OpenGL ES have different version numbering schemes, the valid values here
 /// depend on the value of `Device::gl_api()`.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct GLVersion {
     /// The major OpenGL version (e.g. 4 in 4.2).
     pub major: u8,
@@ -36,30 +38,278 @@ impl GLVersion {
         GLVersion { major, minor }
     }
 
+    /// Returns the version of the current context.
+    ///
+    /// Returns `UnsupportedGLVersion` if the `GL_VERSION` string can't be parsed.
     #[allow(dead_code)]
-    pub(crate) fn current(gl: &Gl) -> GLVersion {
+    pub(crate) fn current(gl: &Gl) -> Result<GLVersion, Error> {
+        unsafe { get_string(gl, gl::VERSION) }
+            .as_deref()
+            .and_then(GLVersion::parse)
+            .ok_or(Error::UnsupportedGLVersion)
+    }
+
+    /// Parses the leading `major.minor` version number out of a `GL_VERSION` or
+    /// `GL_SHADING_LANGUAGE_VERSION` string.
+    ///
+    /// Vendor prefixes such as `OpenGL ES` or `OpenGL ES GLSL ES` are skipped, as is anything
+    /// after the minor version.
+    pub(crate) fn parse(version_string: &str) -> Option<GLVersion> {
+        let version = version_string.trim_start_matches(|c: char| !c.is_ascii_digit());
+        let (major, rest) = version.split_at(version.find(|c: char| !c.is_ascii_digit())?);
+        let rest = rest.strip_prefix('.')?;
+        let minor = &rest[..rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len())];
+        Some(GLVersion::new(major.parse().ok()?, minor.parse().ok()?))
+    }
+}
+
+/// Information about the adapter that a device renders with.
+///
+/// This is returned by `Device::adapter_info()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdapterInfo {
+    /// The `GL_VENDOR` string (e.g. `Intel` or `Mesa`).
+    pub vendor: String,
+    /// The `GL_RENDERER` string, which usually names the GPU.
+    pub renderer: String,
+    /// The `GL_VERSION` string, which usually includes the driver version.
+    pub version: String,
+    /// The name of the driver that the EGL display loaded (e.g. `iris` or `swrast`), via
+    /// `EGL_MESA_query_driver`.
+    ///
+    /// This is `None` on backends other than EGL, or if the extension is unsupported.
+    pub driver_name: Option<String>,
+    /// Whether the adapter renders on the CPU.
+    pub is_software: bool,
+    /// The names of all extensions that the EGL display supports.
+    ///
+    /// These decide which backends and adapters can be used. This is empty on backends other
+    /// than EGL.
+    pub display_extensions: BTreeSet<String>,
+}
+
+/// The framebuffer configuration that a context descriptor selected, called an "EGL config" or
+/// a "pixel format" by the native APIs.
+///
+/// This is returned by `Device::context_descriptor_config()`, and can be used to pick among the
+/// candidates returned by `Device::enumerate_context_descriptors()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContextConfigInfo {
+    /// The native ID of the configuration (e.g. `EGL_CONFIG_ID`), or `None` if the backend has
+    /// no such ID.
+    pub native_id: Option<i32>,
+    /// The number of bits in the red channel of the color buffer.
+    pub red_bits: i32,
+    /// The number of bits in the green channel of the color buffer.
+    pub green_bits: i32,
+    /// The number of bits in the blue channel of the color buffer.
+    pub blue_bits: i32,
+    /// The number of bits in the alpha channel of the color buffer.
+    pub alpha_bits: i32,
+    /// The number of bits in the depth buffer.
+    pub depth_bits: i32,
+    /// The number of bits in the stencil buffer.
+    pub stencil_bits: i32,
+    /// The number of samples per pixel, or 0 if the configuration isn't multisampled.
+    pub samples: i32,
+    /// Whether the driver warns against using the configuration.
+    pub caveat: ConfigCaveat,
+    /// The kinds of native surface that the configuration can render to.
+    ///
+    /// `surfman` surfaces other than widget surfaces render to framebuffer objects, so this only
+    /// matters for widget surfaces and for native surfaces wrapped by the caller.
+    pub surface_types: ConfigSurfaceTypes,
+}
+
+/// A warning that the driver attaches to a framebuffer configuration, such as
+/// `EGL_CONFIG_CAVEAT`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigCaveat {
+    /// The configuration has no caveat.
+    None,
+    /// Rendering with the configuration may be slow, usually because it isn't accelerated by
+    /// the GPU.
+    Slow,
+    /// The configuration doesn't pass the conformance tests of the API.
+    NonConformant,
+}
+
+bitflags! {
+    /// The kinds of native surface that a framebuffer configuration can render to, such as
+    /// `EGL_SURFACE_TYPE`.
+    pub struct ConfigSurfaceTypes: u8 {
+        /// Windows.
+        const WINDOW  = 0x01;
+        /// Off-screen pbuffers.
+        const PBUFFER = 0x02;
+        /// Pixmaps or bitmaps.
+        const PIXMAP  = 0x04;
+    }
+}
+
+/// The version, extensions, and limits of an OpenGL context.
+///
+/// This is returned by `Device::context_capabilities()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContextCapabilities {
+    /// The API of the context (OpenGL or OpenGL ES).
+    pub api: GLApi,
+    /// The OpenGL or OpenGL ES version of the context.
+    pub version: GLVersion,
+    /// The GLSL or GLSL ES version of the context, if the driver reports one that can be parsed.
+    ///
+    /// The minor version has two digits, so GLSL 4.50 is reported as major 4, minor 50.
+    pub shading_language_version: Option<GLVersion>,
+    /// The names of all extensions that the context supports.
+    pub extensions: BTreeSet<String>,
+    /// The value of `GL_MAX_TEXTURE_SIZE`.
+    pub max_texture_size: i32,
+    /// The value of `GL_MAX_RENDERBUFFER_SIZE`.
+    pub max_renderbuffer_size: i32,
+    /// The value of `GL_MAX_SAMPLES`, or 0 if the context doesn't support multisampled
+    /// renderbuffers.
+    pub max_samples: i32,
+}
+
+impl AdapterInfo {
+    /// Queries the adapter information from the current context.
+    ///
+    /// EGL backends fill in `display_extensions` afterward. `software` should be true if the backend already knows that the adapter is a software
+    /// one; otherwise, this is guessed from the renderer string.
+    pub(crate) fn current(gl: &Gl, driver_name: Option<String>, software: bool) -> AdapterInfo {
         unsafe {
-            let version_string = gl.GetString(gl::VERSION) as *const c_char;
-            let version_string = CStr::from_ptr(version_string)
-                .to_string_lossy()
-                .trim_start_matches("OpenGL ES")
-                .trim_start()
-                .to_owned();
-            let mut version_string_iter = version_string.split(|c| c == '.' || c == ' ');
-            let major_version: u8 = version_string_iter
-                .next()
-                .expect("Where's the major GL version?")
-                .parse()
-                .expect("Couldn't parse the major GL version!");
-            let minor_version: u8 = version_string_iter
-                .next()
-                .expect("Where's the minor GL version?")
-                .parse()
-                .expect("Couldn't parse the minor GL version!");
-            GLVersion {
-                major: major_version,
-                minor: minor_version,
+            let renderer = get_string(gl, gl::RENDERER).unwrap_or_default();
+            let is_software = software || renderer_is_software(&renderer);
+            AdapterInfo {
+                vendor: get_string(gl, gl::VENDOR).unwrap_or_default(),
+                renderer,
+                version: get_string(gl, gl::VERSION).unwrap_or_default(),
+                driver_name,
+                is_software,
+                display_extensions: BTreeSet::new(),
             }
         }
     }
 }
+
+impl ContextCapabilities {
+    /// Queries the capabilities of the current context.
+    pub(crate) fn current(gl: &Gl) -> Result<ContextCapabilities, Error> {
+        unsafe {
+            let version_string = get_string(gl, gl::VERSION).ok_or(Error::UnsupportedGLVersion)?;
+            let version = GLVersion::parse(&version_string).ok_or(Error::UnsupportedGLVersion)?;
+            let api = if version_string.starts_with("OpenGL ES") {
+                GLApi::GLES
+            } else {
+                GLApi::GL
+            };
+            let shading_language_version = get_string(gl, gl::SHADING_LANGUAGE_VERSION)
+                .and_then(|string| GLVersion::parse(&string));
+
+            // `glGetString(GL_EXTENSIONS)` is unavailable in core profiles, so use the indexed
+            // query where possible.
+            let extensions: BTreeSet<String> = if version.major >= 3 {
+                let mut extension_count = 0;
+                gl.GetIntegerv(gl::NUM_EXTENSIONS, &mut extension_count);
+                (0..extension_count as GLuint)
+                    .filter_map(|index| {
+                        let extension = gl.GetStringi(gl::EXTENSIONS, index) as *const c_char;
+                        if extension.is_null() {
+                            None
+                        } else {
+                            Some(CStr::from_ptr(extension).to_string_lossy().into_owned())
+                        }
+                    })
+                    .collect()
+            } else {
+                get_string(gl, gl::EXTENSIONS)
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(|extension| extension.to_owned())
+                    .collect()
+            };
+
+            let get_integer = |name| {
+                let mut value: GLint = 0;
+                gl.GetIntegerv(name, &mut value);
+                value
+            };
+            let max_samples = if version.major >= 3
+                || extensions.contains("GL_ARB_framebuffer_object")
+                || extensions.contains("GL_EXT_framebuffer_multisample")
+            {
+                get_integer(gl::MAX_SAMPLES)
+            } else {
+                0
+            };
+
+            Ok(ContextCapabilities {
+                api,
+                version,
+                shading_language_version,
+                max_texture_size: get_integer(gl::MAX_TEXTURE_SIZE),
+                max_renderbuffer_size: get_integer(gl::MAX_RENDERBUFFER_SIZE),
+                max_samples,
+                extensions,
+            })
+        }
+    }
+
+    /// Returns true if the context supports the named extension.
+    #[inline]
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+}
+
+unsafe fn get_string(gl: &Gl, name: GLenum) -> Option<String> {
+    let string = gl.GetString(name) as *const c_char;
+    if string.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(string).to_string_lossy().into_owned())
+    }
+}
+
+pub(crate) fn renderer_is_software(renderer: &str) -> bool {
+    [
+        "llvmpipe",
+        "softpipe",
+        "Software Rasterizer",
+        "SwiftShader",
+        "Microsoft Basic Render Driver",
+        "Apple Software Renderer",
+        "GDI Generic",
+    ]
+    .iter()
+    .any(|name| renderer.contains(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GLVersion;
+
+    #[test]
+    fn test_gl_version_parse() {
+        assert_eq!(
+            GLVersion::parse("4.6.0 NVIDIA 535.54.03"),
+            Some(GLVersion::new(4, 6))
+        );
+        assert_eq!(
+            GLVersion::parse("OpenGL ES 3.2 Mesa 23.2.1"),
+            Some(GLVersion::new(3, 2))
+        );
+        assert_eq!(
+            GLVersion::parse("OpenGL ES GLSL ES 3.20"),
+            Some(GLVersion::new(3, 20))
+        );
+        assert_eq!(
+            GLVersion::parse("OpenGL ES-CM 1.1"),
+            Some(GLVersion::new(1, 1))
+        );
+        assert_eq!(GLVersion::parse("4"), None);
+        assert_eq!(GLVersion::parse("unknown"), None);
+    }
+}
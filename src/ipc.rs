@@ -0,0 +1,331 @@
+// surfman/surfman/src/ipc.rs
+//
+//! Sharing surfaces between processes over Unix domain sockets.
+//!
+//! Generic surfaces are exported as DMA-BUFs, whose file descriptors are sent over a `UnixStream`
+//! with `SCM_RIGHTS`, along with the size, format and memory layout of the buffer. The receiving
+//! process imports them into a surface of its own, which shares its pixels with the original
+//! surface, so no pixels are copied.
+//!
+//! Both processes should call `handshake()` once after connecting, to check that they render with
+//! the same driver and GPU. After that, surfaces can be sent with `send_surface()` and received
+//! with `receive_surface()` or `receive_surface_texture()`.
+
+use crate::platform::unix::dmabuf::{DmaBufDescriptor, DmaBufDevice, DmaBufPlane};
+use crate::{Error, SurfaceAccess};
+
+use euclid::default::Size2D;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::raw::c_void;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::ptr;
+use std::time::Duration;
+
+/// The version of the protocol spoken over the socket.
+///
+/// The handshake fails if the two processes speak different versions.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+const MESSAGE_HANDSHAKE: u32 = 1;
+const MESSAGE_SURFACE: u32 = 2;
+
+const HANDSHAKE_HEADER_SIZE: usize = 12;
+const MAX_IDENTITY_LENGTH: usize = 4096;
+
+// Surface messages have a fixed size: the message type, width, height, fourcc, modifier and plane
+// count, followed by the offset and stride of each possible plane. The file descriptors of the
+// planes are attached to the message.
+const MAX_PLANES: usize = 4;
+const SURFACE_MESSAGE_SIZE: usize = 28 + MAX_PLANES * 8;
+
+/// Checks that the process on the other end of the stream renders with the same driver and GPU as
+/// the given device, so that surfaces can be shared with it.
+///
+/// Both processes must call this once, before sending or receiving any surfaces. If the adapters
+/// differ, this returns `IncompatibleAdapter`.
+pub fn handshake<D>(stream: &UnixStream, device: &D) -> Result<(), Error>
+where
+    D: DmaBufDevice,
+{
+    let identity = device.adapter_identity();
+    if identity.len() > MAX_IDENTITY_LENGTH {
+        return Err(protocol_error("adapter identity is too long"));
+    }
+
+    let mut message = Vec::with_capacity(HANDSHAKE_HEADER_SIZE + identity.len());
+    message.extend_from_slice(&MESSAGE_HANDSHAKE.to_le_bytes());
+    message.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    message.extend_from_slice(&(identity.len() as u32).to_le_bytes());
+    message.extend_from_slice(identity.as_bytes());
+    let mut writer = stream;
+    writer.write_all(&message).map_err(Error::IpcFailed)?;
+
+    let mut reader = stream;
+    let mut header = [0; HANDSHAKE_HEADER_SIZE];
+    reader.read_exact(&mut header).map_err(Error::IpcFailed)?;
+    if read_u32(&header, 0) != MESSAGE_HANDSHAKE {
+        return Err(protocol_error("expected a handshake"));
+    }
+    if read_u32(&header, 4) != PROTOCOL_VERSION {
+        return Err(protocol_error("mismatched protocol versions"));
+    }
+    let peer_identity_length = read_u32(&header, 8) as usize;
+    if peer_identity_length > MAX_IDENTITY_LENGTH {
+        return Err(protocol_error("adapter identity is too long"));
+    }
+    let mut peer_identity = vec![0; peer_identity_length];
+    reader
+        .read_exact(&mut peer_identity)
+        .map_err(Error::IpcFailed)?;
+
+    if peer_identity != identity.as_bytes() {
+        return Err(Error::IncompatibleAdapter);
+    }
+    Ok(())
+}
+
+/// Sends a generic surface to the process on the other end of the stream.
+///
+/// This waits for all rendering to the surface so far to finish, so that the receiver sees it.
+/// The surface must not be bound to its context, and remains owned by the sender; afterward, both
+/// processes' surfaces refer to the same pixels.
+pub fn send_surface<D>(
+    stream: &UnixStream,
+    device: &D,
+    context: &D::Context,
+    surface: &D::Surface,
+) -> Result<(), Error>
+where
+    D: DmaBufDevice,
+{
+    let mut fence = device.create_fence(context)?;
+    let result = device.client_wait_fence(&fence, Duration::MAX);
+    device.destroy_fence(&mut fence)?;
+    result?;
+
+    let descriptor = device.export_surface_dmabuf(context, surface)?;
+    send_dmabuf(stream, &descriptor)
+}
+
+/// Receives a surface sent with `send_surface()`, and imports it into the given context.
+///
+/// The new surface belongs to the receiving process, and must be destroyed with
+/// `destroy_surface()` as usual.
+pub fn receive_surface<D>(
+    stream: &UnixStream,
+    device: &mut D,
+    context: &D::Context,
+    access: SurfaceAccess,
+) -> Result<D::Surface, Error>
+where
+    D: DmaBufDevice,
+{
+    let descriptor = receive_dmabuf(stream)?;
+    device.create_surface_from_dmabuf(context, descriptor, access)
+}
+
+/// Receives a surface sent with `send_surface()`, and wraps it in a surface texture for sampling
+/// in the given context.
+///
+/// Destroying the surface texture with `destroy_surface_texture()` returns the imported surface,
+/// which must then be destroyed with `destroy_surface()`.
+pub fn receive_surface_texture<D>(
+    stream: &UnixStream,
+    device: &mut D,
+    context: &mut D::Context,
+) -> Result<D::SurfaceTexture, Error>
+where
+    D: DmaBufDevice,
+{
+    let surface = receive_surface(stream, device, context, SurfaceAccess::GPUOnly)?;
+    match device.create_surface_texture(context, surface) {
+        Ok(surface_texture) => Ok(surface_texture),
+        Err((err, mut surface)) => {
+            device.destroy_surface(context, &mut surface)?;
+            Err(err)
+        }
+    }
+}
+
+/// Sends DMA-BUFs to the process on the other end of the stream.
+///
+/// This is the lower-level counterpart of `send_surface()`, for buffers that come from elsewhere.
+/// The file descriptors remain open in the sending process.
+pub fn send_dmabuf(stream: &UnixStream, descriptor: &DmaBufDescriptor) -> Result<(), Error> {
+    if descriptor.planes.is_empty() || descriptor.planes.len() > MAX_PLANES {
+        return Err(Error::IpcFailed(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unsupported number of planes",
+        )));
+    }
+
+    let mut message = [0; SURFACE_MESSAGE_SIZE];
+    write_u32(&mut message, 0, MESSAGE_SURFACE);
+    write_u32(&mut message, 4, descriptor.size.width as u32);
+    write_u32(&mut message, 8, descriptor.size.height as u32);
+    write_u32(&mut message, 12, descriptor.fourcc);
+    message[16..24].copy_from_slice(&descriptor.modifier.to_le_bytes());
+    write_u32(&mut message, 24, descriptor.planes.len() as u32);
+    for (plane_index, plane) in descriptor.planes.iter().enumerate() {
+        write_u32(&mut message, 28 + plane_index * 8, plane.offset);
+        write_u32(&mut message, 32 + plane_index * 8, plane.stride);
+    }
+
+    let fds: Vec<RawFd> = descriptor
+        .planes
+        .iter()
+        .map(|plane| plane.fd.as_raw_fd())
+        .collect();
+    send_with_fds(stream, &message, &fds).map_err(Error::IpcFailed)
+}
+
+/// Receives DMA-BUFs sent with `send_dmabuf()` or `send_surface()`.
+///
+/// This is the lower-level counterpart of `receive_surface()`.
+pub fn receive_dmabuf(stream: &UnixStream) -> Result<DmaBufDescriptor, Error> {
+    let mut message = [0; SURFACE_MESSAGE_SIZE];
+    let fds = receive_with_fds(stream, &mut message, MAX_PLANES).map_err(Error::IpcFailed)?;
+
+    if read_u32(&message, 0) != MESSAGE_SURFACE {
+        return Err(protocol_error("expected a surface"));
+    }
+    let plane_count = read_u32(&message, 24) as usize;
+    if plane_count == 0 || plane_count > MAX_PLANES || fds.len() != plane_count {
+        return Err(protocol_error("mismatched number of planes"));
+    }
+
+    let planes = fds
+        .into_iter()
+        .enumerate()
+        .map(|(plane_index, fd)| DmaBufPlane {
+            fd,
+            offset: read_u32(&message, 28 + plane_index * 8),
+            stride: read_u32(&message, 32 + plane_index * 8),
+        })
+        .collect();
+    let mut modifier = [0; 8];
+    modifier.copy_from_slice(&message[16..24]);
+    Ok(DmaBufDescriptor {
+        size: Size2D::new(read_u32(&message, 4) as i32, read_u32(&message, 8) as i32),
+        fourcc: read_u32(&message, 12),
+        modifier: u64::from_le_bytes(modifier),
+        planes,
+    })
+}
+
+fn send_with_fds(stream: &UnixStream, data: &[u8], fds: &[RawFd]) -> io::Result<()> {
+    unsafe {
+        let fds_size = mem::size_of_val(fds) as u32;
+        let control_size = libc::CMSG_SPACE(fds_size) as usize;
+        // `u64`s keep the control buffer aligned for `cmsghdr`.
+        let mut control = vec![0u64; (control_size + 7) / 8];
+
+        let mut iov = libc::iovec {
+            iov_base: data.as_ptr() as *mut c_void,
+            iov_len: data.len(),
+        };
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = control_size as _;
+
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(fds_size) as _;
+        ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut RawFd, fds.len());
+
+        let sent = loop {
+            let sent = libc::sendmsg(stream.as_raw_fd(), &msg, libc::MSG_NOSIGNAL);
+            if sent >= 0 {
+                break sent as usize;
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        };
+
+        // The file descriptors went along with the first byte, so just send the rest.
+        let mut writer = stream;
+        writer.write_all(&data[sent..])
+    }
+}
+
+fn receive_with_fds(
+    stream: &UnixStream,
+    data: &mut [u8],
+    max_fds: usize,
+) -> io::Result<Vec<OwnedFd>> {
+    unsafe {
+        let control_size = libc::CMSG_SPACE((max_fds * mem::size_of::<RawFd>()) as u32) as usize;
+        let mut control = vec![0u64; (control_size + 7) / 8];
+
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr() as *mut c_void,
+            iov_len: data.len(),
+        };
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = control_size as _;
+
+        let received = loop {
+            let received = libc::recvmsg(stream.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC);
+            if received >= 0 {
+                break received as usize;
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        };
+
+        // Take ownership of the file descriptors first, so that they get closed on failure.
+        let mut fds = vec![];
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let fds_size = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                let fd_data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                for fd_index in 0..(fds_size / mem::size_of::<RawFd>()) {
+                    let fd = ptr::read_unaligned(fd_data.add(fd_index));
+                    fds.push(OwnedFd::from_raw_fd(fd));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+
+        if received == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "too many file descriptors",
+            ));
+        }
+
+        let mut reader = stream;
+        reader.read_exact(&mut data[received..])?;
+        Ok(fds)
+    }
+}
+
+fn protocol_error(message: &str) -> Error {
+    Error::IpcFailed(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[offset..(offset + 4)]);
+    u32::from_le_bytes(word)
+}
+
+fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..(offset + 4)].copy_from_slice(&value.to_le_bytes());
+}
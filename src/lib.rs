@@ -24,7 +24,7 @@ extern crate objc;
 
 pub mod platform;
 pub use platform::default::connection::{Connection, NativeConnection};
-pub use platform::default::context::{Context, ContextDescriptor, NativeContext};
+pub use platform::default::context::{Context, ContextDescriptor, Fence, NativeContext};
 pub use platform::default::device::{Adapter, Device, NativeDevice};
 pub use platform::default::surface::{NativeWidget, Surface, SurfaceTexture};
 
@@ -36,22 +36,36 @@ pub use platform::system::device::{Adapter as SystemAdapter, Device as SystemDev
 #[cfg(target_os = "macos")]
 pub use platform::system::surface::Surface as SystemSurface;
 
+#[cfg(free_unix)]
+pub use platform::unix::dmabuf::{DmaBufDescriptor, DmaBufDevice, DmaBufPlane};
+
 #[cfg(feature = "chains")]
 pub mod chains;
 pub mod connection;
+pub use crate::connection::BackendKind;
 pub mod device;
+#[cfg(free_unix)]
+pub mod ipc;
 
 pub mod error;
-pub use crate::error::{Error, WindowingApiError};
+pub use crate::error::{Error, NativeError, WindowingApiError};
 
 mod context;
-pub use crate::context::{ContextAttributeFlags, ContextAttributes, ContextID};
+pub use crate::context::ContextVersionRange;
+pub use crate::context::{ContextAttributeFlags, ContextAttributes, ContextID, ContextResetStatus};
 
 mod info;
-pub use crate::info::{GLApi, GLVersion};
+pub use crate::info::{AdapterInfo, ContextCapabilities, ContextConfigInfo, GLApi, GLVersion};
+pub use crate::info::{ConfigCaveat, ConfigSurfaceTypes};
+
+mod resources;
+pub use crate::resources::{LiveResource, ResourceKind};
 
 mod surface;
-pub use crate::surface::{SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType, SystemSurfaceInfo};
+pub use crate::surface::{
+    PixelFormat, PresentMode, SurfaceAccess, SurfaceFormat, SurfaceID, SurfaceInfo, SurfaceType,
+    SystemSurfaceInfo,
+};
 
 pub mod macros;
 
@@ -62,6 +76,7 @@ pub(crate) use crate::gl::Gles2 as Gl;
 
 mod gl_utils;
 mod renderbuffers;
+mod trace;
 
 #[allow(clippy::all)]
 mod gl {
@@ -6,6 +6,8 @@
 
 use super::device::{Adapter, Device, NativeDevice};
 use super::surface::NativeWidget;
+use crate::connection::BackendKind;
+use crate::resources::ResourceTracker;
 use crate::Error;
 use crate::GLApi;
 
@@ -18,6 +20,11 @@ use euclid::default::Size2D;
 
 use std::os::raw::c_void;
 
+#[cfg(android_platform)]
+const BACKEND_KIND: BackendKind = BackendKind::Android;
+#[cfg(ohos_platform)]
+const BACKEND_KIND: BackendKind = BackendKind::OHOS;
+
 /// A connection to the display server.
 #[derive(Clone)]
 pub struct Connection;
@@ -28,11 +35,36 @@ pub struct NativeConnection;
 
 impl Connection {
     /// Connects to the default display.
+    ///
+    /// Returns `ConnectionFailed` if the `SURFMAN_BACKEND` environment variable names other
+    /// backends but not this one.
     #[inline]
     pub fn new() -> Result<Connection, Error> {
+        BACKEND_KIND.check_preference_from_env()?;
+        Connection::connect()
+    }
+
+    fn connect() -> Result<Connection, Error> {
         Ok(Connection)
     }
 
+    /// Connects to the default display if `BackendKind::Android` (or `BackendKind::OHOS` on
+    /// OpenHarmony) is among the given backends, and returns `UnsupportedOnThisPlatform`
+    /// otherwise.
+    #[inline]
+    pub fn new_with_preference(preference: &[BackendKind]) -> Result<Connection, Error> {
+        if !preference.contains(&BACKEND_KIND) {
+            return Err(Error::UnsupportedOnThisPlatform);
+        }
+        Connection::connect()
+    }
+
+    /// Returns which backend this connection uses.
+    #[inline]
+    pub fn backend_kind(&self) -> BackendKind {
+        BACKEND_KIND
+    }
+
     /// An alias for `Connection::new()`, present for consistency with other backends.
     #[inline]
     pub unsafe fn from_native_connection(_: NativeConnection) -> Result<Connection, Error> {
@@ -98,6 +130,7 @@ impl Connection {
         Ok(Device {
             egl_display: native_device.0,
             display_is_owned: false,
+            resources: ResourceTracker::new(),
         })
     }
 
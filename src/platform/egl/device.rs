@@ -3,10 +3,14 @@
 //! A thread-local handle to the device.
 
 use super::connection::Connection;
+use super::context::GL_FUNCTIONS;
 use crate::egl;
 use crate::egl::types::EGLDisplay;
-use crate::platform::generic::egl::device::EGL_FUNCTIONS;
-use crate::{Error, GLApi};
+use crate::platform::generic::egl::context::CurrentContextGuard;
+use crate::platform::generic::egl::device::{self, EGL_FUNCTIONS};
+use crate::resources::ResourceTracker;
+use crate::LiveResource;
+use crate::{AdapterInfo, ContextAttributeFlags, ContextAttributes, Error, GLApi, GLVersion};
 
 /// Represents a hardware display adapter that can be used for rendering (including the CPU).
 ///
@@ -20,6 +24,7 @@ pub struct Adapter;
 pub struct Device {
     pub(crate) egl_display: EGLDisplay,
     pub(crate) display_is_owned: bool,
+    pub(crate) resources: ResourceTracker,
 }
 
 /// Wrapper for an `EGLDisplay`.
@@ -64,6 +69,7 @@ impl Device {
                 Ok(Device {
                     egl_display,
                     display_is_owned: true,
+                    resources: ResourceTracker::new(),
                 })
             }
         })
@@ -92,4 +98,38 @@ impl Device {
     pub fn gl_api(&self) -> GLApi {
         GLApi::GLES
     }
+
+    /// Returns information about the adapter that this device renders with.
+    ///
+    /// This creates and destroys a temporary context in order to query the OpenGL strings.
+    pub fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        let context_descriptor = self.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(2, 0),
+            flags: ContextAttributeFlags::empty(),
+        })?;
+
+        // Destroying the temporary context leaves no context current, so restore the caller's
+        // context afterward.
+        let _guard = CurrentContextGuard::new();
+        let mut context = self.create_context(&context_descriptor, None)?;
+        let adapter_info = self.make_context_current(&context).map(|()| {
+            let driver_name = unsafe { device::display_driver_name(self.egl_display) };
+            let display_extensions = unsafe { device::display_extensions(self.egl_display) };
+            GL_FUNCTIONS.with(|gl| AdapterInfo {
+                display_extensions,
+                ..AdapterInfo::current(gl, driver_name, false)
+            })
+        });
+        self.destroy_context(&mut context)?;
+        adapter_info
+    }
+
+    /// Returns the contexts, surfaces, and surface textures that this device has created and
+    /// that have not yet been destroyed.
+    ///
+    /// Resources are only tracked in debug builds; in release builds this is always empty.
+    #[inline]
+    pub fn live_resources(&self) -> Vec<LiveResource> {
+        self.resources.live_resources()
+    }
 }
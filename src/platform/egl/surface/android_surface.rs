@@ -29,9 +29,13 @@ use crate::platform::generic::egl::ffi::EGL_IMAGE_PRESERVED_KHR;
 use crate::platform::generic::egl::ffi::EGL_NATIVE_BUFFER_ANDROID;
 use crate::platform::generic::egl::ffi::EGL_NO_IMAGE_KHR;
 use crate::renderbuffers::Renderbuffers;
-use crate::{Error, SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType, WindowingApiError};
+use crate::trace;
+use crate::{
+    Error, NativeError, PixelFormat, PresentMode, SurfaceAccess, SurfaceFormat, SurfaceID,
+    SurfaceInfo, SurfaceType, WindowingApiError,
+};
 
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
 use std::marker::PhantomData;
 use std::os::raw::c_void;
 use std::ptr;
@@ -61,18 +65,43 @@ impl Device {
     ///
     /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
     /// up in a `SurfaceTexture` for reading by other contexts.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = tracing::field::Empty,
+                width = tracing::field::Empty,
+                height = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_surface(
         &mut self,
         context: &Context,
         _: SurfaceAccess,
         surface_type: SurfaceType<NativeWidget>,
     ) -> Result<Surface, Error> {
-        match surface_type {
-            SurfaceType::Generic { size } => self.create_generic_surface(context, &size),
+        let surface = match surface_type {
+            // Multisampled and non-RGBA8 generic surfaces are only supported on the Linux EGL
+            // backends.
+            SurfaceType::Generic {
+                samples, format, ..
+            } if samples > 1 || format != SurfaceFormat::RGBA8 => {
+                Err(Error::UnsupportedOnThisPlatform)
+            }
+            SurfaceType::Generic { size, .. } => self.create_generic_surface(context, &size),
             SurfaceType::Widget { native_widget } => unsafe {
                 self.create_window_surface(context, native_widget.native_window)
             },
-        }
+        }?;
+        let surface_info = self.surface_info(&surface);
+        trace::record_surface(&surface_info);
+        self.resources.track_surface(&surface_info);
+        Ok(surface)
     }
 
     fn create_generic_surface(
@@ -101,7 +130,9 @@ impl Device {
                 let mut hardware_buffer = ptr::null_mut();
                 let result = AHardwareBuffer_allocate(&hardware_buffer_desc, &mut hardware_buffer);
                 if result != 0 {
-                    return Err(Error::SurfaceCreationFailed(WindowingApiError::Failed));
+                    let err =
+                        NativeError::new(WindowingApiError::Failed, "AHardwareBuffer_allocate");
+                    return Err(Error::SurfaceCreationFailed(err.with_code(result)));
                 }
 
                 // Create an EGL image, and bind it to a texture.
@@ -181,11 +212,23 @@ impl Device {
     /// in another context.
     ///
     /// Calling this method on a widget surface returns a `WidgetAttached` error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
     pub fn create_surface_texture(
         &self,
         context: &mut Context,
         surface: Surface,
     ) -> Result<SurfaceTexture, (Error, Surface)> {
+        let surface_info = self.surface_info(&surface);
         unsafe {
             match surface.objects {
                 SurfaceObjects::Window { .. } => return Err((Error::WidgetAttached, surface)),
@@ -200,6 +243,8 @@ impl Device {
                     let local_egl_image = self.create_egl_image(context, hardware_buffer);
                     let texture_object =
                         generic::egl::surface::bind_egl_image_to_gl_texture(gl, local_egl_image);
+                    self.resources
+                        .track_surface_texture(context.id, &surface_info);
                     Ok(SurfaceTexture {
                         surface,
                         local_egl_image,
@@ -218,22 +263,130 @@ impl Device {
     ///
     /// The supplied context must match the context the surface was created with, or an
     /// `IncompatibleSurface` error is returned.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn present_surface(&self, context: &Context, surface: &mut Surface) -> Result<(), Error> {
         if context.id != surface.context_id {
             return Err(Error::IncompatibleSurface);
         }
 
-        EGL_FUNCTIONS.with(|egl| unsafe {
+        self.present_surface_with_damage(context, surface, &[])
+    }
+
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// changed since the last present.
+    ///
+    /// The rectangles are in framebuffer coordinates, with the origin at the bottom left. This
+    /// uses `EGL_KHR_swap_buffers_with_damage` or `EGL_EXT_swap_buffers_with_damage` if available,
+    /// and presents the whole surface otherwise.
+    pub fn present_surface_with_damage(
+        &self,
+        context: &Context,
+        surface: &mut Surface,
+        damage: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        unsafe {
             match surface.objects {
                 SurfaceObjects::Window { egl_surface } => {
-                    egl.SwapBuffers(self.egl_display, egl_surface);
+                    generic::egl::surface::swap_buffers_with_damage(
+                        self.egl_display,
+                        egl_surface,
+                        damage,
+                    );
                     Ok(())
                 }
                 SurfaceObjects::HardwareBuffer { .. } => Err(Error::NoWidgetAttached),
             }
+        }
+    }
+
+    /// Reads back the pixels in the given rectangle of a surface.
+    ///
+    /// The rectangle has its origin at the top left of the surface, and the returned rows are
+    /// ordered from top to bottom. The surface must not be bound to a context.
+    pub fn read_surface_pixels(
+        &self,
+        context: &Context,
+        surface: &Surface,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        GL_FUNCTIONS.with(|gl| unsafe {
+            match surface.objects {
+                SurfaceObjects::HardwareBuffer {
+                    framebuffer_object, ..
+                } => {
+                    let _guard = self.temporarily_make_context_current(context)?;
+                    gl_utils::read_framebuffer_pixels(
+                        gl,
+                        framebuffer_object,
+                        surface.size,
+                        rect,
+                        format,
+                    )
+                }
+                SurfaceObjects::Window { egl_surface } => {
+                    generic::egl::context::with_context_and_surface_current(
+                        self.egl_display,
+                        context.egl_context,
+                        egl_surface,
+                        || gl_utils::read_framebuffer_pixels(gl, 0, surface.size, rect, format),
+                    )?
+                }
+            }
         })
     }
 
+    /// Returns the age of the back buffer of a widget surface, via `EGL_EXT_buffer_age`.
+    ///
+    /// A value of 0 means the contents of the back buffer are undefined. This is always the case
+    /// if `EGL_EXT_buffer_age` isn't supported.
+    pub fn surface_buffer_age(&self, context: &Context, surface: &Surface) -> Result<u32, Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        unsafe {
+            match surface.objects {
+                SurfaceObjects::Window { egl_surface } => {
+                    generic::egl::context::with_context_and_surface_current(
+                        self.egl_display,
+                        context.egl_context,
+                        egl_surface,
+                        || generic::egl::surface::query_buffer_age(self.egl_display, egl_surface),
+                    )?
+                }
+                SurfaceObjects::HardwareBuffer { .. } => Err(Error::NoWidgetAttached),
+            }
+        }
+    }
+
+    /// Sets how `present_surface()` synchronizes with the display for the given widget surface.
+    ///
+    /// This is currently unsupported on this backend, so it always returns
+    /// `UnsupportedOnThisPlatform`.
+    pub fn set_present_mode(&self, _: &Context, _: &Surface, _: PresentMode) -> Result<(), Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
     /// Resizes a widget surface.
     pub fn resize_surface(
         &self,
@@ -241,7 +394,10 @@ impl Device {
         surface: &mut Surface,
         size: Size2D<i32>,
     ) -> Result<(), Error> {
+        let surface_id = self.surface_info(surface).id;
         surface.size = size;
+        self.resources
+            .update_surface(surface_id, &self.surface_info(surface));
         Ok(())
     }
 
@@ -286,6 +442,18 @@ impl Device {
     ///
     /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
     /// the `drop` method.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_surface(
         &self,
         context: &mut Context,
@@ -294,6 +462,7 @@ impl Device {
         if context.id != surface.context_id {
             return Err(Error::IncompatibleSurface);
         }
+        let surface_id = self.surface_info(surface).id;
 
         unsafe {
             match surface.objects {
@@ -334,6 +503,7 @@ impl Device {
         }
 
         surface.destroyed = true;
+        self.resources.untrack_surface(surface_id);
         Ok(())
     }
 
@@ -364,6 +534,8 @@ impl Device {
                 surface_texture.local_egl_image = EGL_NO_IMAGE_KHR;
             }
 
+            self.resources
+                .untrack_surface_texture(self.surface_info(&surface_texture.surface).id);
             Ok(surface_texture.surface)
         })
     }
@@ -400,6 +572,7 @@ impl Device {
                 } => framebuffer_object,
                 SurfaceObjects::Window { .. } => 0,
             },
+            format: SurfaceFormat::RGBA8,
         }
     }
 
@@ -5,6 +5,7 @@
 use crate::context::ContextID;
 use crate::gl::types::GLuint;
 use crate::platform::generic::egl::ffi::EGLImageKHR;
+use crate::resources;
 
 use euclid::default::Size2D;
 use std::fmt::{self, Debug, Formatter};
@@ -75,7 +76,13 @@ impl Debug for Surface {
 impl Drop for Surface {
     fn drop(&mut self) {
         if !self.destroyed && !thread::panicking() {
-            panic!("Should have destroyed the surface first with `destroy_surface()`!")
+            panic!(
+                "{}",
+                resources::leaked_surface_message(
+                    "Should have destroyed the surface first with `destroy_surface()`!",
+                    self.id(),
+                )
+            )
         }
     }
 }
@@ -6,17 +6,23 @@ use std::marker::PhantomData;
 use std::os::raw::c_void;
 use std::ptr;
 
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
 use log::info;
 
 use crate::egl;
 use crate::egl::types::EGLSurface;
 use crate::gl;
 use crate::gl::types::{GLenum, GLuint};
+use crate::gl_utils;
+use crate::platform::generic;
 use crate::platform::generic::egl::device::EGL_FUNCTIONS;
 use crate::platform::generic::egl::ffi::EGL_EXTENSION_FUNCTIONS;
 use crate::platform::generic::egl::ffi::EGL_NO_IMAGE_KHR;
-use crate::{Error, SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType};
+use crate::trace;
+use crate::{
+    Error, PixelFormat, PresentMode, SurfaceAccess, SurfaceFormat, SurfaceID, SurfaceInfo,
+    SurfaceType,
+};
 
 use super::super::context::{Context, GL_FUNCTIONS};
 use super::super::device::Device;
@@ -41,6 +47,20 @@ impl Device {
     ///
     /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
     /// up in a `SurfaceTexture` for reading by other contexts.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = tracing::field::Empty,
+                width = tracing::field::Empty,
+                height = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_surface(
         &mut self,
         context: &Context,
@@ -48,12 +68,23 @@ impl Device {
         surface_type: SurfaceType<NativeWidget>,
     ) -> Result<Surface, Error> {
         info!("Device create_surface with Context");
-        match surface_type {
-            SurfaceType::Generic { size } => self.create_generic_surface(context, &size),
+        let surface = match surface_type {
+            // Multisampled and non-RGBA8 generic surfaces are only supported on the Linux EGL
+            // backends.
+            SurfaceType::Generic {
+                samples, format, ..
+            } if samples > 1 || format != SurfaceFormat::RGBA8 => {
+                Err(Error::UnsupportedOnThisPlatform)
+            }
+            SurfaceType::Generic { size, .. } => self.create_generic_surface(context, &size),
             SurfaceType::Widget { native_widget } => unsafe {
                 self.create_window_surface(context, native_widget)
             },
-        }
+        }?;
+        let surface_info = self.surface_info(&surface);
+        trace::record_surface(&surface_info);
+        self.resources.track_surface(&surface_info);
+        Ok(surface)
     }
 
     fn create_generic_surface(
@@ -112,6 +143,17 @@ impl Device {
     /// Calling this method on a widget surface returns a `WidgetAttached` error.
     /// On OpenHarmony, currently only widget surfaces are implemented in surfman, so
     /// this method unconditionally returns the `WidgetAttached` error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(_context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
     pub fn create_surface_texture(
         &self,
         _context: &mut Context,
@@ -127,21 +169,116 @@ impl Device {
     ///
     /// The supplied context must match the context the surface was created with, or an
     /// `IncompatibleSurface` error is returned.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn present_surface(&self, context: &Context, surface: &mut Surface) -> Result<(), Error> {
         if context.id != surface.context_id {
             return Err(Error::IncompatibleSurface);
         }
 
-        EGL_FUNCTIONS.with(|egl| unsafe {
+        self.present_surface_with_damage(context, surface, &[])
+    }
+
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// changed since the last present.
+    ///
+    /// The rectangles are in framebuffer coordinates, with the origin at the bottom left. This
+    /// uses `EGL_KHR_swap_buffers_with_damage` or `EGL_EXT_swap_buffers_with_damage` if available,
+    /// and presents the whole surface otherwise.
+    pub fn present_surface_with_damage(
+        &self,
+        context: &Context,
+        surface: &mut Surface,
+        damage: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        unsafe {
             match surface.objects {
                 SurfaceObjects::Window { egl_surface } => {
-                    egl.SwapBuffers(self.egl_display, egl_surface);
+                    generic::egl::surface::swap_buffers_with_damage(
+                        self.egl_display,
+                        egl_surface,
+                        damage,
+                    );
                     Ok(())
                 }
             }
+        }
+    }
+
+    /// Reads back the pixels in the given rectangle of a surface.
+    ///
+    /// The rectangle has its origin at the top left of the surface, and the returned rows are
+    /// ordered from top to bottom. The surface must not be bound to a context.
+    pub fn read_surface_pixels(
+        &self,
+        context: &Context,
+        surface: &Surface,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        GL_FUNCTIONS.with(|gl| unsafe {
+            match surface.objects {
+                SurfaceObjects::Window { egl_surface } => {
+                    generic::egl::context::with_context_and_surface_current(
+                        self.egl_display,
+                        context.egl_context,
+                        egl_surface,
+                        || gl_utils::read_framebuffer_pixels(gl, 0, surface.size, rect, format),
+                    )?
+                }
+            }
         })
     }
 
+    /// Returns the age of the back buffer of a widget surface, via `EGL_EXT_buffer_age`.
+    ///
+    /// A value of 0 means the contents of the back buffer are undefined. This is always the case
+    /// if `EGL_EXT_buffer_age` isn't supported.
+    pub fn surface_buffer_age(&self, context: &Context, surface: &Surface) -> Result<u32, Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        unsafe {
+            match surface.objects {
+                SurfaceObjects::Window { egl_surface } => {
+                    generic::egl::context::with_context_and_surface_current(
+                        self.egl_display,
+                        context.egl_context,
+                        egl_surface,
+                        || generic::egl::surface::query_buffer_age(self.egl_display, egl_surface),
+                    )?
+                }
+            }
+        }
+    }
+
+    /// Sets how `present_surface()` synchronizes with the display for the given widget surface.
+    ///
+    /// This is currently unsupported on this backend, so it always returns
+    /// `UnsupportedOnThisPlatform`.
+    pub fn set_present_mode(&self, _: &Context, _: &Surface, _: PresentMode) -> Result<(), Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
     /// Resizes a widget surface.
     pub fn resize_surface(
         &self,
@@ -149,7 +286,10 @@ impl Device {
         surface: &mut Surface,
         size: Size2D<i32>,
     ) -> Result<(), Error> {
+        let surface_id = self.surface_info(surface).id;
         surface.size = size;
+        self.resources
+            .update_surface(surface_id, &self.surface_info(surface));
         Ok(())
     }
 
@@ -160,6 +300,18 @@ impl Device {
     ///
     /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
     /// the `drop` method.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_surface(
         &self,
         context: &mut Context,
@@ -168,6 +320,7 @@ impl Device {
         if context.id != surface.context_id {
             return Err(Error::IncompatibleSurface);
         }
+        let surface_id = self.surface_info(surface).id;
 
         unsafe {
             match surface.objects {
@@ -181,6 +334,7 @@ impl Device {
         }
 
         surface.destroyed = true;
+        self.resources.untrack_surface(surface_id);
         Ok(())
     }
 
@@ -244,6 +398,7 @@ impl Device {
             framebuffer_object: match surface.objects {
                 SurfaceObjects::Window { .. } => 0,
             },
+            format: SurfaceFormat::RGBA8,
         }
     }
 
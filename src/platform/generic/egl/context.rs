@@ -2,22 +2,27 @@
 //
 //! Functionality common to backends using EGL contexts.
 
-use super::device::EGL_FUNCTIONS;
+use super::device::{self, EGL_FUNCTIONS};
 use super::error::ToWindowingApiError;
 use super::ffi::EGL_CONTEXT_OPENGL_PROFILE_MASK;
+use super::ffi::EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT;
+use super::ffi::EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT;
 use super::ffi::{EGL_CONTEXT_MINOR_VERSION_KHR, EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT};
 use super::surface::{EGLBackedSurface, ExternalEGLSurfaces};
 use crate::context::{self, CREATE_CONTEXT_MUTEX};
 use crate::egl;
-use crate::egl::types::{EGLConfig, EGLContext, EGLDisplay, EGLSurface, EGLint};
+use crate::egl::types::{EGLConfig, EGLContext, EGLDisplay, EGLSurface, EGLenum, EGLint};
+use crate::resources;
 use crate::surface::Framebuffer;
+use crate::{ConfigCaveat, ConfigSurfaceTypes, ContextConfigInfo, Gl, SurfaceInfo};
 use crate::{ContextAttributeFlags, ContextAttributes, ContextID, Error, GLApi, GLVersion};
-use crate::{Gl, SurfaceInfo};
 
+use std::cell::Cell;
 use std::ffi::CString;
 use std::mem;
 use std::os::raw::{c_char, c_void};
 use std::ptr;
+use std::rc::Rc;
 use std::thread;
 
 #[allow(dead_code)]
@@ -27,6 +32,9 @@ const RGB_CHANNEL_BIT_DEPTH: EGLint = 8;
 pub(crate) struct EGLBackedContext {
     pub(crate) egl_context: EGLContext,
     pub(crate) id: ContextID,
+    // Cleared when the context is destroyed, so that objects created in it, such as readbacks,
+    // know not to touch it afterward.
+    pub(crate) alive: Rc<Cell<bool>>,
     framebuffer: Framebuffer<EGLBackedSurface, ExternalEGLSurfaces>,
     context_is_owned: bool,
 }
@@ -49,7 +57,10 @@ pub struct NativeContext {
 pub struct ContextDescriptor {
     pub(crate) egl_config_id: EGLint,
     pub(crate) gl_version: GLVersion,
+    pub(crate) gl_api: GLApi,
     pub(crate) compatibility_profile: bool,
+    pub(crate) debug: bool,
+    pub(crate) robust: bool,
 }
 
 #[must_use]
@@ -64,7 +75,13 @@ impl Drop for EGLBackedContext {
     #[inline]
     fn drop(&mut self) {
         if self.egl_context != egl::NO_CONTEXT && !thread::panicking() {
-            panic!("Contexts must be destroyed explicitly with `destroy_context`!")
+            panic!(
+                "{}",
+                resources::leaked_context_message(
+                    "Contexts must be destroyed explicitly with `destroy_context`!",
+                    self.id,
+                )
+            )
         }
     }
 }
@@ -86,10 +103,10 @@ impl Drop for CurrentContextGuard {
 
 impl EGLBackedContext {
     pub(crate) unsafe fn new(
+        gl: &Gl,
         egl_display: EGLDisplay,
         descriptor: &ContextDescriptor,
         share_with: Option<&EGLBackedContext>,
-        gl_api: GLApi,
     ) -> Result<EGLBackedContext, Error> {
         let mut next_context_id = CREATE_CONTEXT_MUTEX.lock().unwrap();
 
@@ -98,13 +115,45 @@ impl EGLBackedContext {
             egl_display,
             descriptor,
             share_with.map_or(egl::NO_CONTEXT, |ctx| ctx.egl_context),
-            gl_api,
         )?;
 
+        // Some drivers, such as Mesa for OpenGL 3.1, quietly leave out the compatibility profile
+        // if they don't support it, so make sure we got what was asked for. Contexts older than
+        // that always have the whole API.
+        if descriptor.compatibility_profile
+            && descriptor.gl_api == GLApi::GL
+            && descriptor.gl_version >= GLVersion::new(3, 1)
+        {
+            let uses_compatibility_profile = with_context_current(egl_display, egl_context, || {
+                context::current_context_uses_compatibility_profile(gl)
+            });
+            match uses_compatibility_profile {
+                Ok(true) => {}
+                Ok(false) => {
+                    EGL_FUNCTIONS.with(|egl| egl.DestroyContext(egl_display, egl_context));
+                    return Err(Error::UnsupportedGLProfile);
+                }
+                Err(err) => {
+                    EGL_FUNCTIONS.with(|egl| egl.DestroyContext(egl_display, egl_context));
+                    return Err(err);
+                }
+            }
+        }
+
+        if descriptor.debug {
+            if let Err(err) =
+                install_debug_message_callback(gl, egl_display, egl_context, *next_context_id)
+            {
+                EGL_FUNCTIONS.with(|egl| egl.DestroyContext(egl_display, egl_context));
+                return Err(err);
+            }
+        }
+
         // Wrap and return it.
         let context = EGLBackedContext {
             egl_context,
             id: *next_context_id,
+            alive: Rc::new(Cell::new(true)),
             framebuffer: Framebuffer::None,
             context_is_owned: true,
         };
@@ -117,6 +166,7 @@ impl EGLBackedContext {
         let context = EGLBackedContext {
             egl_context: native_context.egl_context,
             id: *next_context_id,
+            alive: Rc::new(Cell::new(true)),
             framebuffer: Framebuffer::External(ExternalEGLSurfaces {
                 draw: native_context.egl_draw_surface,
                 read: native_context.egl_read_surface,
@@ -142,6 +192,7 @@ impl EGLBackedContext {
             }
 
             self.egl_context = egl::NO_CONTEXT;
+            self.alive.set(false);
         });
     }
 
@@ -174,7 +225,7 @@ impl EGLBackedContext {
                 self.egl_context,
             );
             if result == egl::FALSE {
-                let err = egl.GetError().to_windowing_api_error();
+                let err = egl.GetError().to_native_error("eglMakeCurrent");
                 return Err(Error::MakeCurrentFailed(err));
             }
             Ok(())
@@ -261,11 +312,14 @@ impl NativeContext {
 }
 
 impl ContextDescriptor {
-    pub(crate) unsafe fn new(
+    // Returns a descriptor for each config that satisfies the attributes, in the order that
+    // `eglChooseConfig` sorted them.
+    pub(crate) unsafe fn enumerate(
         egl_display: EGLDisplay,
         attributes: &ContextAttributes,
         extra_config_attributes: &[EGLint],
-    ) -> Result<ContextDescriptor, Error> {
+        gl_api: GLApi,
+    ) -> Result<Vec<ContextDescriptor>, Error> {
         let flags = attributes.flags;
 
         let alpha_size = if flags.contains(ContextAttributeFlags::ALPHA) {
@@ -285,16 +339,22 @@ impl ContextDescriptor {
         };
 
         let compatibility_profile = flags.contains(ContextAttributeFlags::COMPATIBILITY_PROFILE);
+        let debug = flags.contains(ContextAttributeFlags::DEBUG);
+        let robust = flags.contains(ContextAttributeFlags::ROBUST);
 
-        // Mesa doesn't support the OpenGL compatibility profile post version 3.0. Take that into
-        // account.
-        if compatibility_profile
-            && (attributes.version.major > 3
-                || attributes.version.major == 3 && attributes.version.minor > 0)
-        {
+        // OpenGL ES has no notion of profiles.
+        if compatibility_profile && flags.contains(ContextAttributeFlags::GLES) {
             return Err(Error::UnsupportedGLProfile);
         }
 
+        // Mesa rejects the robustness attributes without this extension, even though EGL 1.5
+        // has them in core for desktop OpenGL.
+        if robust
+            && !device::display_has_extension(egl_display, "EGL_EXT_create_context_robustness")
+        {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
         // Create required config attributes.
         //
         // We check these separately because `eglChooseConfig` on its own might give us 32-bit
@@ -332,11 +392,11 @@ impl ContextDescriptor {
                 &mut config_count,
             );
             if result == egl::FALSE {
-                let err = egl.GetError().to_windowing_api_error();
+                let err = egl.GetError().to_native_error("eglChooseConfig");
                 return Err(Error::PixelFormatSelectionFailed(err));
             }
             if config_count == 0 {
-                return Err(Error::NoPixelFormatFound);
+                return Ok(vec![]);
             }
 
             // Enumerate all those configs.
@@ -350,33 +410,34 @@ impl ContextDescriptor {
                 &mut real_config_count,
             );
             if result == egl::FALSE {
-                let err = egl.GetError().to_windowing_api_error();
+                let err = egl.GetError().to_native_error("eglChooseConfig");
                 return Err(Error::PixelFormatSelectionFailed(err));
             }
 
+            configs.truncate(real_config_count as usize);
+
             // Sanitize configs.
-            let egl_config = configs
+            let gl_version = attributes.version;
+            Ok(configs
                 .into_iter()
                 .filter(|&egl_config| {
                     required_config_attributes
                         .chunks(2)
                         .all(|pair| get_config_attr(egl_display, egl_config, pair[0]) == pair[1])
                 })
-                .next();
-            let egl_config = match egl_config {
-                None => return Err(Error::NoPixelFormatFound),
-                Some(egl_config) => egl_config,
-            };
-
-            // Get the config ID and version.
-            let egl_config_id = get_config_attr(egl_display, egl_config, egl::CONFIG_ID as EGLint);
-            let gl_version = attributes.version;
-
-            Ok(ContextDescriptor {
-                egl_config_id,
-                gl_version,
-                compatibility_profile,
-            })
+                .map(|egl_config| ContextDescriptor {
+                    egl_config_id: get_config_attr(
+                        egl_display,
+                        egl_config,
+                        egl::CONFIG_ID as EGLint,
+                    ),
+                    gl_version,
+                    gl_api,
+                    compatibility_profile,
+                    debug,
+                    robust,
+                })
+                .collect())
         })
     }
 
@@ -386,17 +447,25 @@ impl ContextDescriptor {
         egl_context: EGLContext,
     ) -> ContextDescriptor {
         let egl_config_id = get_context_attr(egl_display, egl_context, egl::CONFIG_ID as EGLint);
+        let gl_api = context_gl_api(egl_display, egl_context);
 
         EGL_FUNCTIONS.with(|egl| {
             let _guard = CurrentContextGuard::new();
             egl.MakeCurrent(egl_display, egl::NO_SURFACE, egl::NO_SURFACE, egl_context);
-            let gl_version = GLVersion::current(gl);
-            let compatibility_profile = context::current_context_uses_compatibility_profile(gl);
+            // Every context supports at least OpenGL (ES) 2.0.
+            let gl_version = GLVersion::current(gl).unwrap_or(GLVersion::new(2, 0));
+            let compatibility_profile =
+                gl_api == GLApi::GL && context::current_context_uses_compatibility_profile(gl);
+            let debug = context::current_context_is_debug(gl);
+            let robust = context::current_context_is_robust(gl);
 
             ContextDescriptor {
                 egl_config_id,
                 gl_version,
+                gl_api,
                 compatibility_profile,
+                debug,
+                robust,
             }
         })
     }
@@ -444,6 +513,9 @@ impl ContextDescriptor {
             ContextAttributeFlags::COMPATIBILITY_PROFILE,
             self.compatibility_profile,
         );
+        attribute_flags.set(ContextAttributeFlags::GLES, self.gl_api == GLApi::GLES);
+        attribute_flags.set(ContextAttributeFlags::DEBUG, self.debug);
+        attribute_flags.set(ContextAttributeFlags::ROBUST, self.robust);
 
         // Create appropriate context attributes.
         ContextAttributes {
@@ -451,6 +523,43 @@ impl ContextDescriptor {
             version: self.gl_version,
         }
     }
+
+    pub(crate) unsafe fn config_info(&self, egl_display: EGLDisplay) -> ContextConfigInfo {
+        let egl_config = egl_config_from_id(egl_display, self.egl_config_id);
+        let attr = |attr| get_config_attr(egl_display, egl_config, attr as EGLint);
+
+        let surface_type = attr(egl::SURFACE_TYPE);
+        let mut surface_types = ConfigSurfaceTypes::empty();
+        surface_types.set(
+            ConfigSurfaceTypes::WINDOW,
+            surface_type & egl::WINDOW_BIT as EGLint != 0,
+        );
+        surface_types.set(
+            ConfigSurfaceTypes::PBUFFER,
+            surface_type & egl::PBUFFER_BIT as EGLint != 0,
+        );
+        surface_types.set(
+            ConfigSurfaceTypes::PIXMAP,
+            surface_type & egl::PIXMAP_BIT as EGLint != 0,
+        );
+
+        ContextConfigInfo {
+            native_id: Some(self.egl_config_id),
+            red_bits: attr(egl::RED_SIZE),
+            green_bits: attr(egl::GREEN_SIZE),
+            blue_bits: attr(egl::BLUE_SIZE),
+            alpha_bits: attr(egl::ALPHA_SIZE),
+            depth_bits: attr(egl::DEPTH_SIZE),
+            stencil_bits: attr(egl::STENCIL_SIZE),
+            samples: attr(egl::SAMPLES),
+            caveat: match attr(egl::CONFIG_CAVEAT) as EGLenum {
+                egl::SLOW_CONFIG => ConfigCaveat::Slow,
+                egl::NON_CONFORMANT_CONFIG => ConfigCaveat::NonConformant,
+                _ => ConfigCaveat::None,
+            },
+            surface_types,
+        }
+    }
 }
 
 impl CurrentContextGuard {
@@ -470,10 +579,9 @@ pub(crate) unsafe fn create_context(
     egl_display: EGLDisplay,
     descriptor: &ContextDescriptor,
     share_with: EGLContext,
-    gl_api: GLApi,
 ) -> Result<EGLContext, Error> {
     EGL_FUNCTIONS.with(|egl| {
-        let ok = egl.BindAPI(match gl_api {
+        let ok = egl.BindAPI(match descriptor.gl_api {
             GLApi::GL => egl::OPENGL_API,
             GLApi::GLES => egl::OPENGL_ES_API,
         });
@@ -498,6 +606,30 @@ pub(crate) unsafe fn create_context(
         ]);
     }
 
+    if descriptor.debug {
+        egl_context_attributes.extend(&[egl::CONTEXT_OPENGL_DEBUG as EGLint, egl::TRUE as EGLint]);
+    }
+
+    // The core EGL 1.5 robustness attributes only apply to desktop OpenGL.
+    if descriptor.robust {
+        let (robust_access, reset_notification_strategy) = match descriptor.gl_api {
+            GLApi::GL => (
+                egl::CONTEXT_OPENGL_ROBUST_ACCESS,
+                egl::CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY,
+            ),
+            GLApi::GLES => (
+                EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT,
+                EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT,
+            ),
+        };
+        egl_context_attributes.extend(&[
+            robust_access as EGLint,
+            egl::TRUE as EGLint,
+            reset_notification_strategy as EGLint,
+            egl::LOSE_CONTEXT_ON_RESET as EGLint,
+        ]);
+    }
+
     // Include some extra zeroes to work around broken implementations.
     //
     // FIXME(pcwalton): Which implementations are those? (This is copied from Gecko.)
@@ -511,8 +643,7 @@ pub(crate) unsafe fn create_context(
             egl_context_attributes.as_ptr(),
         );
         if egl_context == egl::NO_CONTEXT {
-            let err = egl.GetError();
-            let err = err.to_windowing_api_error();
+            let err = egl.GetError().to_native_error("eglCreateContext");
             return Err(Error::ContextCreationFailed(err));
         }
 
@@ -520,6 +651,97 @@ pub(crate) unsafe fn create_context(
     })
 }
 
+/// Routes `KHR_debug` messages from the given context into the `log` crate. The context is made
+/// current temporarily in order to install the callback.
+pub(crate) unsafe fn install_debug_message_callback(
+    gl: &Gl,
+    egl_display: EGLDisplay,
+    egl_context: EGLContext,
+    context_id: ContextID,
+) -> Result<(), Error> {
+    with_context_current(egl_display, egl_context, || {
+        context::install_debug_message_callback(gl, context_id)
+    })
+}
+
+/// Runs the given closure with the given context current and no surface attached, then restores
+/// whichever context was current before.
+///
+/// If the context can't be made current, for example because it has been destroyed, the closure
+/// isn't run and `MakeCurrentFailed` is returned.
+pub(crate) unsafe fn with_context_current<F, R>(
+    egl_display: EGLDisplay,
+    egl_context: EGLContext,
+    f: F,
+) -> Result<R, Error>
+where
+    F: FnOnce() -> R,
+{
+    with_context_and_surface_current(egl_display, egl_context, egl::NO_SURFACE, f)
+}
+
+/// Like `with_context_current()`, but attaches the given surface for drawing and reading.
+pub(crate) unsafe fn with_context_and_surface_current<F, R>(
+    egl_display: EGLDisplay,
+    egl_context: EGLContext,
+    egl_surface: EGLSurface,
+    f: F,
+) -> Result<R, Error>
+where
+    F: FnOnce() -> R,
+{
+    let guard = CurrentContextGuard::new();
+    EGL_FUNCTIONS.with(|egl| {
+        let ok = egl.MakeCurrent(egl_display, egl_surface, egl_surface, egl_context);
+        if ok == egl::FALSE {
+            let err = egl.GetError().to_native_error("eglMakeCurrent");
+            return Err(Error::MakeCurrentFailed(err));
+        }
+
+        let result = f();
+
+        // The guard can't restore "no context", so do that ourselves.
+        if guard.egl_display == egl::NO_DISPLAY {
+            egl.MakeCurrent(
+                egl_display,
+                egl::NO_SURFACE,
+                egl::NO_SURFACE,
+                egl::NO_CONTEXT,
+            );
+        }
+        Ok(result)
+    })
+}
+
+/// Returns the API that the given attributes request on backends that can create both desktop
+/// OpenGL and OpenGL ES contexts.
+pub(crate) fn requested_gl_api(attributes: &ContextAttributes) -> GLApi {
+    if attributes.flags.contains(ContextAttributeFlags::GLES) {
+        GLApi::GLES
+    } else {
+        GLApi::GL
+    }
+}
+
+/// Returns the API that the given context was created with.
+pub(crate) unsafe fn context_gl_api(egl_display: EGLDisplay, egl_context: EGLContext) -> GLApi {
+    match get_context_attr(egl_display, egl_context, egl::CONTEXT_CLIENT_TYPE as EGLint) as EGLenum
+    {
+        egl::OPENGL_ES_API => GLApi::GLES,
+        _ => GLApi::GL,
+    }
+}
+
+/// Returns the `EGL_RENDERABLE_TYPE` bit that a config must have in order to create a context of
+/// the given API and version.
+pub(crate) fn renderable_type_bit(gl_api: GLApi, version: GLVersion) -> EGLint {
+    match gl_api {
+        GLApi::GL => egl::OPENGL_BIT as EGLint,
+        GLApi::GLES if version.major >= 3 => egl::OPENGL_ES3_BIT as EGLint,
+        GLApi::GLES => egl::OPENGL_ES2_BIT as EGLint,
+    }
+}
+
 pub(crate) unsafe fn make_no_context_current(egl_display: EGLDisplay) -> Result<(), Error> {
     EGL_FUNCTIONS.with(|egl| {
         let result = egl.MakeCurrent(
@@ -529,7 +751,7 @@ pub(crate) unsafe fn make_no_context_current(egl_display: EGLDisplay) -> Result<
             egl::NO_CONTEXT,
         );
         if result == egl::FALSE {
-            let err = egl.GetError().to_windowing_api_error();
+            let err = egl.GetError().to_native_error("eglMakeCurrent");
             return Err(Error::MakeCurrentFailed(err));
         }
         Ok(())
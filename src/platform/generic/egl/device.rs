@@ -2,9 +2,14 @@
 //
 //! Functionality common to backends using EGL displays.
 
+use crate::egl;
+use crate::egl::types::{EGLAttrib, EGLDeviceEXT, EGLDisplay, EGLint};
 use crate::egl::Egl;
+use crate::platform::generic::egl::ffi::{EGL_DEVICE_EXT, EGL_DRM_DEVICE_FILE_EXT};
+use crate::platform::generic::egl::ffi::{EGL_EXTENSION_FUNCTIONS, EGL_NO_DEVICE_EXT};
 
-use std::ffi::CString;
+use std::collections::BTreeSet;
+use std::ffi::{CStr, CString};
 use std::mem;
 use std::os::raw::{c_char, c_void};
 
@@ -76,3 +81,85 @@ pub(crate) unsafe fn lookup_egl_extension(name: &'static [u8]) -> *mut c_void {
     EGL_FUNCTIONS
         .with(|egl| mem::transmute(egl.GetProcAddress(&name[0] as *const u8 as *const c_char)))
 }
+
+/// Returns a string identifying the driver and GPU behind the given display.
+///
+/// This consists of the EGL vendor and version strings, followed by the path of the DRM device
+/// node if the display exposes it via `EGL_EXT_device_drm`. Two displays with the same identity
+/// can share buffers with one another.
+pub(crate) unsafe fn display_identity(egl_display: EGLDisplay) -> String {
+    let mut identity = EGL_FUNCTIONS.with(|egl| {
+        let query_string = |name| {
+            let string = egl.QueryString(egl_display, name as EGLint);
+            if string.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(string).to_string_lossy().into_owned()
+            }
+        };
+        format!(
+            "{}\n{}",
+            query_string(egl::VENDOR),
+            query_string(egl::VERSION)
+        )
+    });
+
+    if let (Some(query_device_string), Some(egl_device)) = (
+        EGL_EXTENSION_FUNCTIONS.QueryDeviceStringEXT,
+        display_device(egl_display),
+    ) {
+        let device_file = query_device_string(egl_device, EGL_DRM_DEVICE_FILE_EXT as EGLint);
+        if !device_file.is_null() {
+            identity.push('\n');
+            identity.push_str(&CStr::from_ptr(device_file).to_string_lossy());
+        }
+    }
+
+    identity
+}
+
+/// Returns the EGL device behind the given display, via `EGL_EXT_device_query`.
+pub(crate) unsafe fn display_device(egl_display: EGLDisplay) -> Option<EGLDeviceEXT> {
+    let query_display_attrib = EGL_EXTENSION_FUNCTIONS.QueryDisplayAttribEXT?;
+    let mut egl_device = EGL_NO_DEVICE_EXT as EGLAttrib;
+    if query_display_attrib(egl_display, EGL_DEVICE_EXT as EGLint, &mut egl_device) == egl::FALSE
+        || egl_device == EGL_NO_DEVICE_EXT as EGLAttrib
+    {
+        return None;
+    }
+    Some(egl_device as EGLDeviceEXT)
+}
+
+/// Returns the name of the driver that the given display loaded, via `EGL_MESA_query_driver`.
+pub(crate) unsafe fn display_driver_name(egl_display: EGLDisplay) -> Option<String> {
+    let get_display_driver_name = EGL_EXTENSION_FUNCTIONS.GetDisplayDriverName?;
+    if !display_has_extension(egl_display, "EGL_MESA_query_driver") {
+        return None;
+    }
+    let driver_name = get_display_driver_name(egl_display);
+    if driver_name.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(driver_name).to_string_lossy().into_owned())
+    }
+}
+
+/// Returns true if the given display advertises the named EGL extension.
+pub(crate) unsafe fn display_has_extension(egl_display: EGLDisplay, name: &str) -> bool {
+    display_extensions(egl_display).contains(name)
+}
+
+/// Returns the names of all EGL extensions that the given display advertises.
+pub(crate) unsafe fn display_extensions(egl_display: EGLDisplay) -> BTreeSet<String> {
+    EGL_FUNCTIONS.with(|egl| {
+        let extensions = egl.QueryString(egl_display, egl::EXTENSIONS as EGLint);
+        if extensions.is_null() {
+            return BTreeSet::new();
+        }
+        CStr::from_ptr(extensions)
+            .to_string_lossy()
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect()
+    })
+}
@@ -4,10 +4,11 @@
 
 use crate::egl;
 use crate::egl::types::{EGLenum, EGLint};
-use crate::WindowingApiError;
+use crate::{NativeError, WindowingApiError};
 
 pub(crate) trait ToWindowingApiError {
     fn to_windowing_api_error(self) -> WindowingApiError;
+    fn to_native_error(self, call: &'static str) -> NativeError;
 }
 
 impl ToWindowingApiError for EGLint {
@@ -30,4 +31,8 @@ impl ToWindowingApiError for EGLint {
             _ => WindowingApiError::Failed,
         }
     }
+
+    fn to_native_error(self, call: &'static str) -> NativeError {
+        NativeError::new(self.to_windowing_api_error(), call).with_code(self)
+    }
 }
@@ -9,3 +9,4 @@ pub(crate) mod device;
 pub(crate) mod error;
 pub(crate) mod ffi;
 pub(crate) mod surface;
+pub(crate) mod sync;
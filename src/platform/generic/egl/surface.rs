@@ -2,30 +2,44 @@
 //
 //! Functionality common to backends using EGL surfaces.
 
-use super::context::CurrentContextGuard;
-use super::device::EGL_FUNCTIONS;
+use super::context::{self, CurrentContextGuard, EGLBackedContext};
+use super::device::{self, EGL_FUNCTIONS};
 use crate::egl;
-use crate::egl::types::{EGLAttrib, EGLConfig, EGLContext, EGLDisplay, EGLSurface, EGLint};
+use crate::egl::types::EGLint;
+use crate::egl::types::{EGLAttrib, EGLBoolean, EGLConfig, EGLContext, EGLDisplay, EGLSurface};
 use crate::gl;
-use crate::gl::types::{GLint, GLuint};
+use crate::gl::types::{GLint, GLsizei, GLsizeiptr, GLsync, GLuint, GLuint64};
 use crate::gl_utils;
 use crate::platform::generic::egl::error::ToWindowingApiError;
 use crate::platform::generic::egl::ffi::EGLClientBuffer;
 use crate::platform::generic::egl::ffi::EGLImageKHR;
+use crate::platform::generic::egl::ffi::EGL_BUFFER_AGE_EXT;
 use crate::platform::generic::egl::ffi::EGL_EXTENSION_FUNCTIONS;
 use crate::platform::generic::egl::ffi::EGL_GL_TEXTURE_2D_KHR;
 use crate::platform::generic::egl::ffi::EGL_IMAGE_PRESERVED_KHR;
 use crate::platform::generic::egl::ffi::EGL_NO_IMAGE_KHR;
-use crate::renderbuffers::Renderbuffers;
+#[cfg(free_unix)]
+use crate::platform::unix::dmabuf::{DmaBufDescriptor, DmaBufPlane, DRM_FORMAT_MOD_INVALID};
+use crate::renderbuffers::{MultisampleFramebuffer, Renderbuffers};
+use crate::resources;
 use crate::Gl;
-use crate::{ContextAttributes, ContextID, Error, SurfaceID, SurfaceInfo};
+use crate::SurfaceInfo;
+use crate::{ContextAttributes, ContextID, Error, PixelFormat, SurfaceAccess, SurfaceFormat};
+use crate::{GLVersion, SurfaceID};
+use crate::{NativeError, WindowingApiError};
 
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
+use std::cell::Cell;
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
 use std::mem;
 use std::os::raw::c_void;
+#[cfg(free_unix)]
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
 use std::ptr;
+use std::rc::Rc;
+use std::slice;
+use std::thread;
 
 #[allow(dead_code)]
 #[derive(Clone)]
@@ -37,6 +51,7 @@ pub(crate) struct ExternalEGLSurfaces {
 pub struct EGLBackedSurface {
     pub(crate) context_id: ContextID,
     pub(crate) size: Size2D<i32>,
+    pub(crate) format: SurfaceFormat,
     pub(crate) objects: EGLSurfaceObjects,
     pub(crate) destroyed: bool,
 }
@@ -49,6 +64,20 @@ impl Debug for EGLBackedSurface {
 
 unsafe impl Send for EGLBackedSurface {}
 
+impl Drop for EGLBackedSurface {
+    fn drop(&mut self) {
+        if !self.destroyed && !thread::panicking() {
+            panic!(
+                "{}",
+                resources::leaked_surface_message(
+                    "Should have destroyed the surface first with `destroy_surface()`!",
+                    self.id(),
+                )
+            )
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub(crate) enum EGLSurfaceObjects {
     TextureImage {
@@ -56,6 +85,8 @@ pub(crate) enum EGLSurfaceObjects {
         framebuffer_object: GLuint,
         texture_object: GLuint,
         renderbuffers: Renderbuffers,
+        multisample_framebuffer: Option<MultisampleFramebuffer>,
+        pixel_buffer: Option<PixelBuffer>,
     },
     Window {
         native_window: *const c_void,
@@ -63,6 +94,29 @@ pub(crate) enum EGLSurfaceObjects {
     },
 }
 
+// A pixel buffer object that CPU-accessible surfaces are read back into and uploaded from when
+// their data is locked. It belongs to the context that created the surface.
+pub(crate) struct PixelBuffer {
+    egl_context: EGLContext,
+    pixel_buffer_object: GLuint,
+    stride: usize,
+    len: usize,
+    mapped: bool,
+}
+
+// An asynchronous readback of a surface into a pixel buffer object, guarded by a fence. It belongs
+// to the context that started it, and becomes unusable once that context is destroyed.
+pub(crate) struct EGLReadback {
+    egl_context: EGLContext,
+    context_alive: Rc<Cell<bool>>,
+    pixel_buffer_object: GLuint,
+    sync: GLsync,
+    size: Size2D<i32>,
+    stride: usize,
+    len: usize,
+    data: *const u8,
+}
+
 pub(crate) struct EGLSurfaceTexture {
     pub(crate) surface: EGLBackedSurface,
     pub(crate) texture_object: GLuint,
@@ -76,6 +130,7 @@ impl Debug for EGLSurfaceTexture {
 }
 
 impl EGLBackedSurface {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new_generic(
         gl: &Gl,
         egl_display: EGLDisplay,
@@ -83,7 +138,10 @@ impl EGLBackedSurface {
         context_id: ContextID,
         context_attributes: &ContextAttributes,
         size: &Size2D<i32>,
-    ) -> EGLBackedSurface {
+        access: SurfaceAccess,
+        samples: u8,
+        format: SurfaceFormat,
+    ) -> Result<EGLBackedSurface, Error> {
         let egl_image_attribs = [
             EGL_IMAGE_PRESERVED_KHR as EGLint,
             egl::FALSE as EGLint,
@@ -107,15 +165,16 @@ impl EGLBackedSurface {
             if unpack_buffer != 0 {
                 gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
             }
+            let (internal_format, gl_format, gl_type) = format.gl_texture_formats();
             gl.TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGBA as GLint,
+                internal_format,
                 size.width,
                 size.height,
                 0,
-                gl::RGBA,
-                gl::UNSIGNED_BYTE,
+                gl_format,
+                gl_type,
                 ptr::null(),
             );
             // Restore the old bindings
@@ -134,31 +193,244 @@ impl EGLBackedSurface {
                 egl_image_attribs.as_ptr(),
             );
 
-            // Create the framebuffer, and bind the texture to it.
-            let framebuffer_object =
-                gl_utils::create_and_bind_framebuffer(gl, gl::TEXTURE_2D, texture_object);
+            // This fails if the driver doesn't support the requested format.
+            if egl_image == EGL_NO_IMAGE_KHR {
+                let err =
+                    EGL_FUNCTIONS.with(|egl| egl.GetError().to_native_error("eglCreateImageKHR"));
+                gl.DeleteTextures(1, &texture_object);
+                return Err(Error::SurfaceCreationFailed(err));
+            }
+
+            EGLBackedSurface::new_texture_image(
+                gl,
+                egl_display,
+                egl_context,
+                context_id,
+                context_attributes,
+                size,
+                access,
+                samples,
+                format,
+                egl_image,
+                texture_object,
+            )
+        }
+    }
+
+    // Wraps DMA-BUFs in an EGL image, via `EGL_EXT_image_dma_buf_import`, and creates a surface
+    // that renders into it. The context must be current.
+    #[cfg(free_unix)]
+    pub(crate) fn new_from_dmabuf(
+        gl: &Gl,
+        egl_display: EGLDisplay,
+        egl_context: EGLContext,
+        context_id: ContextID,
+        context_attributes: &ContextAttributes,
+        descriptor: DmaBufDescriptor,
+        access: SurfaceAccess,
+    ) -> Result<EGLBackedSurface, Error> {
+        use super::ffi;
+        use crate::egl::types::EGLenum;
+
+        const PLANE_ATTRIBUTES: [[EGLenum; 5]; 4] = [
+            [
+                ffi::EGL_DMA_BUF_PLANE0_FD_EXT,
+                ffi::EGL_DMA_BUF_PLANE0_OFFSET_EXT,
+                ffi::EGL_DMA_BUF_PLANE0_PITCH_EXT,
+                ffi::EGL_DMA_BUF_PLANE0_MODIFIER_LO_EXT,
+                ffi::EGL_DMA_BUF_PLANE0_MODIFIER_HI_EXT,
+            ],
+            [
+                ffi::EGL_DMA_BUF_PLANE1_FD_EXT,
+                ffi::EGL_DMA_BUF_PLANE1_OFFSET_EXT,
+                ffi::EGL_DMA_BUF_PLANE1_PITCH_EXT,
+                ffi::EGL_DMA_BUF_PLANE1_MODIFIER_LO_EXT,
+                ffi::EGL_DMA_BUF_PLANE1_MODIFIER_HI_EXT,
+            ],
+            [
+                ffi::EGL_DMA_BUF_PLANE2_FD_EXT,
+                ffi::EGL_DMA_BUF_PLANE2_OFFSET_EXT,
+                ffi::EGL_DMA_BUF_PLANE2_PITCH_EXT,
+                ffi::EGL_DMA_BUF_PLANE2_MODIFIER_LO_EXT,
+                ffi::EGL_DMA_BUF_PLANE2_MODIFIER_HI_EXT,
+            ],
+            [
+                ffi::EGL_DMA_BUF_PLANE3_FD_EXT,
+                ffi::EGL_DMA_BUF_PLANE3_OFFSET_EXT,
+                ffi::EGL_DMA_BUF_PLANE3_PITCH_EXT,
+                ffi::EGL_DMA_BUF_PLANE3_MODIFIER_LO_EXT,
+                ffi::EGL_DMA_BUF_PLANE3_MODIFIER_HI_EXT,
+            ],
+        ];
 
-            // Bind renderbuffers as appropriate.
-            let renderbuffers = Renderbuffers::new(gl, size, context_attributes);
-            renderbuffers.bind_to_current_framebuffer(gl);
+        let format = match descriptor.surface_format() {
+            Some(format) => format,
+            None => {
+                return Err(Error::SurfaceImportFailed(NativeError::new(
+                    WindowingApiError::BadPixelFormat,
+                    "eglCreateImageKHR",
+                )))
+            }
+        };
+        if descriptor.planes.is_empty() || descriptor.planes.len() > PLANE_ATTRIBUTES.len() {
+            return Err(Error::SurfaceImportFailed(NativeError::new(
+                WindowingApiError::BadParameter,
+                "eglCreateImageKHR",
+            )));
+        }
+
+        unsafe {
+            if !device::display_has_extension(egl_display, "EGL_EXT_image_dma_buf_import") {
+                return Err(Error::RequiredExtensionUnavailable);
+            }
+            let has_modifier = descriptor.modifier != DRM_FORMAT_MOD_INVALID;
+            if has_modifier
+                && !device::display_has_extension(
+                    egl_display,
+                    "EGL_EXT_image_dma_buf_import_modifiers",
+                )
+            {
+                return Err(Error::RequiredExtensionUnavailable);
+            }
+
+            let mut egl_image_attribs = vec![
+                egl::WIDTH as EGLint,
+                descriptor.size.width,
+                egl::HEIGHT as EGLint,
+                descriptor.size.height,
+                ffi::EGL_LINUX_DRM_FOURCC_EXT as EGLint,
+                descriptor.fourcc as EGLint,
+            ];
+            for (plane, attributes) in descriptor.planes.iter().zip(PLANE_ATTRIBUTES.iter()) {
+                egl_image_attribs.extend_from_slice(&[
+                    attributes[0] as EGLint,
+                    plane.fd.as_raw_fd(),
+                    attributes[1] as EGLint,
+                    plane.offset as EGLint,
+                    attributes[2] as EGLint,
+                    plane.stride as EGLint,
+                ]);
+                if has_modifier {
+                    egl_image_attribs.extend_from_slice(&[
+                        attributes[3] as EGLint,
+                        descriptor.modifier as u32 as EGLint,
+                        attributes[4] as EGLint,
+                        (descriptor.modifier >> 32) as u32 as EGLint,
+                    ]);
+                }
+            }
+            egl_image_attribs.push(egl::NONE as EGLint);
 
-            debug_assert_eq!(
-                gl.CheckFramebufferStatus(gl::FRAMEBUFFER),
-                gl::FRAMEBUFFER_COMPLETE
+            // EGL doesn't take ownership of the file descriptors, so they get closed when the
+            // descriptor is dropped at the end of this function. The image keeps the underlying
+            // buffers alive.
+            let egl_image = (EGL_EXTENSION_FUNCTIONS.CreateImageKHR)(
+                egl_display,
+                egl::NO_CONTEXT,
+                ffi::EGL_LINUX_DMA_BUF_EXT,
+                ptr::null_mut(),
+                egl_image_attribs.as_ptr(),
             );
+            if egl_image == EGL_NO_IMAGE_KHR {
+                let err =
+                    EGL_FUNCTIONS.with(|egl| egl.GetError().to_native_error("eglCreateImageKHR"));
+                return Err(Error::SurfaceImportFailed(err));
+            }
 
-            EGLBackedSurface {
+            let texture_object = bind_egl_image_to_gl_texture(gl, egl_image);
+            EGLBackedSurface::new_texture_image(
+                gl,
+                egl_display,
+                egl_context,
                 context_id,
-                size: *size,
-                objects: EGLSurfaceObjects::TextureImage {
-                    egl_image,
-                    framebuffer_object,
-                    texture_object,
-                    renderbuffers,
-                },
-                destroyed: false,
-            }
+                context_attributes,
+                &descriptor.size,
+                access,
+                0,
+                format,
+                egl_image,
+                texture_object,
+            )
+        }
+    }
+
+    // Creates a surface that renders into an EGL image through a framebuffer object, taking
+    // ownership of the image and of the texture that it is bound to. The context must be current.
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn new_texture_image(
+        gl: &Gl,
+        egl_display: EGLDisplay,
+        egl_context: EGLContext,
+        context_id: ContextID,
+        context_attributes: &ContextAttributes,
+        size: &Size2D<i32>,
+        access: SurfaceAccess,
+        samples: u8,
+        format: SurfaceFormat,
+        egl_image: EGLImageKHR,
+        texture_object: GLuint,
+    ) -> Result<EGLBackedSurface, Error> {
+        // Create the framebuffer, and bind the texture to it.
+        let framebuffer_object =
+            gl_utils::create_and_bind_framebuffer(gl, gl::TEXTURE_2D, texture_object);
+        let mut complete = gl.CheckFramebufferStatus(gl::FRAMEBUFFER) == gl::FRAMEBUFFER_COMPLETE;
+
+        // If multisampling was requested, render to a separate multisampled framebuffer
+        // instead. It gets resolved into the texture later.
+        let samples = if samples > 1 {
+            MultisampleFramebuffer::clamp_samples(gl, samples as GLsizei)
+        } else {
+            0
+        };
+        let multisample_framebuffer = if samples > 0 {
+            Some(MultisampleFramebuffer::new(
+                gl,
+                size,
+                samples,
+                format.gl_renderbuffer_format(),
+            ))
+        } else {
+            None
+        };
+
+        // Bind renderbuffers as appropriate, with the same number of samples as the color buffer.
+        let renderbuffers = Renderbuffers::with_samples(gl, size, context_attributes, samples);
+        renderbuffers.bind_to_current_framebuffer(gl);
+
+        complete &= gl.CheckFramebufferStatus(gl::FRAMEBUFFER) == gl::FRAMEBUFFER_COMPLETE;
+
+        // Surfaces that the CPU can access get a pixel buffer to stage their data in.
+        let pixel_buffer = if access.cpu_access_allowed() {
+            Some(PixelBuffer::new(gl, egl_context, size, access, format))
+        } else {
+            None
+        };
+
+        let mut surface = EGLBackedSurface {
+            context_id,
+            size: *size,
+            format,
+            objects: EGLSurfaceObjects::TextureImage {
+                egl_image,
+                framebuffer_object,
+                texture_object,
+                renderbuffers,
+                multisample_framebuffer,
+                pixel_buffer,
+            },
+            destroyed: false,
+        };
+
+        // Some formats can be sampled from but not rendered to.
+        if !complete {
+            surface.destroy(gl, egl_display, context_id)?;
+            return Err(Error::SurfaceCreationFailed(NativeError::new(
+                WindowingApiError::BadPixelFormat,
+                "glCheckFramebufferStatus",
+            )));
         }
+
+        Ok(surface)
     }
 
     pub(crate) fn new_window(
@@ -181,6 +453,7 @@ impl EGLBackedSurface {
             EGLBackedSurface {
                 context_id,
                 size: *size,
+                format: SurfaceFormat::RGBA8,
                 objects: EGLSurfaceObjects::Window {
                     native_window,
                     egl_surface,
@@ -225,11 +498,19 @@ impl EGLBackedSurface {
                     ref mut framebuffer_object,
                     ref mut texture_object,
                     ref mut renderbuffers,
+                    ref mut multisample_framebuffer,
+                    ref mut pixel_buffer,
                 } => {
                     gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
                     gl.DeleteFramebuffers(1, framebuffer_object);
                     *framebuffer_object = 0;
                     renderbuffers.destroy(gl);
+                    if let Some(ref mut multisample_framebuffer) = *multisample_framebuffer {
+                        multisample_framebuffer.destroy(gl);
+                    }
+                    if let Some(pixel_buffer) = pixel_buffer.take() {
+                        pixel_buffer.destroy(gl);
+                    }
 
                     let result = (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, *egl_image);
                     assert_ne!(result, egl::FALSE);
@@ -257,11 +538,23 @@ impl EGLBackedSurface {
         }
     }
 
-    // TODO(pcwalton): Damage regions.
     pub(crate) fn present(
         &self,
+        gl: &Gl,
         egl_display: EGLDisplay,
         egl_context: EGLContext,
+    ) -> Result<(), Error> {
+        self.present_with_damage(gl, egl_display, egl_context, &[])
+    }
+
+    // Like `present()`, but only the given regions need to be updated on screen. An empty list of
+    // regions presents the whole surface.
+    pub(crate) fn present_with_damage(
+        &self,
+        gl: &Gl,
+        egl_display: EGLDisplay,
+        egl_context: EGLContext,
+        damage: &[Rect<i32>],
     ) -> Result<(), Error> {
         unsafe {
             match self.objects {
@@ -273,18 +566,475 @@ impl EGLBackedSurface {
                     EGL_FUNCTIONS.with(|egl| {
                         egl.MakeCurrent(egl_display, egl_surface, egl_surface, egl_context);
 
-                        let ok = egl.SwapBuffers(egl_display, egl_surface);
+                        let ok = swap_buffers_with_damage(egl_display, egl_surface, damage);
                         if ok != egl::FALSE {
                             Ok(())
                         } else {
                             Err(Error::PresentFailed(
-                                egl.GetError().to_windowing_api_error(),
+                                egl.GetError().to_native_error("eglSwapBuffers"),
                             ))
                         }
                     })
                 }
-                EGLSurfaceObjects::TextureImage { .. } => Err(Error::NoWidgetAttached),
+                // Generic surfaces have no widget to present to, so they always return
+                // `NoWidgetAttached`. Multisampled ones are resolved into their texture first.
+                EGLSurfaceObjects::TextureImage {
+                    multisample_framebuffer: Some(_),
+                    ..
+                } => {
+                    context::with_context_current(egl_display, egl_context, || self.resolve(gl))?;
+                    Err(Error::NoWidgetAttached)
+                }
+                EGLSurfaceObjects::TextureImage {
+                    multisample_framebuffer: None,
+                    ..
+                } => Err(Error::NoWidgetAttached),
+            }
+        }
+    }
+
+    // Reads the contents of a CPU-accessible surface into its pixel buffer and maps that buffer.
+    // Returns a pointer to the data, its length, and its stride. The data must be unlocked with
+    // `unlock_data()` in order for changes to show up in the surface.
+    pub(crate) fn lock_data(
+        &mut self,
+        gl: &Gl,
+        egl_display: EGLDisplay,
+    ) -> Result<(*mut u8, usize, usize), Error> {
+        let (size, format) = (self.size, self.format);
+        let (framebuffer_object, pixel_buffer) = match self.objects {
+            EGLSurfaceObjects::TextureImage {
+                framebuffer_object,
+                pixel_buffer: Some(ref mut pixel_buffer),
+                ..
+            } => (framebuffer_object, pixel_buffer),
+            EGLSurfaceObjects::TextureImage { .. } | EGLSurfaceObjects::Window { .. } => {
+                return Err(Error::SurfaceDataInaccessible)
+            }
+        };
+        if pixel_buffer.mapped {
+            return Err(Error::SurfaceLockFailed);
+        }
+
+        unsafe {
+            let egl_context = pixel_buffer.egl_context;
+            let ptr = context::with_context_current(egl_display, egl_context, || {
+                // Pixel buffer objects and `glMapBufferRange()` need OpenGL 3.0 or OpenGL ES 3.0.
+                if GLVersion::current(gl)?.major < 3 {
+                    return Err(Error::UnsupportedGLVersion);
+                }
+
+                let (_, gl_format, gl_type) = format.gl_texture_formats();
+                let (mut old_read_framebuffer, mut old_pack_buffer) = (0, 0);
+                gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut old_read_framebuffer);
+                gl.GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut old_pack_buffer);
+
+                gl.BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer_object);
+                gl.BindBuffer(gl::PIXEL_PACK_BUFFER, pixel_buffer.pixel_buffer_object);
+                gl.ReadPixels(
+                    0,
+                    0,
+                    size.width,
+                    size.height,
+                    gl_format,
+                    gl_type,
+                    ptr::null_mut(),
+                );
+                let ptr = gl.MapBufferRange(
+                    gl::PIXEL_PACK_BUFFER,
+                    0,
+                    pixel_buffer.len as GLsizeiptr,
+                    gl::MAP_READ_BIT | gl::MAP_WRITE_BIT,
+                );
+
+                gl.BindBuffer(gl::PIXEL_PACK_BUFFER, old_pack_buffer as GLuint);
+                gl.BindFramebuffer(gl::READ_FRAMEBUFFER, old_read_framebuffer as GLuint);
+                Ok(ptr as *mut u8)
+            })??;
+
+            if ptr.is_null() {
+                return Err(Error::SurfaceLockFailed);
+            }
+            pixel_buffer.mapped = true;
+            Ok((ptr, pixel_buffer.len, pixel_buffer.stride))
+        }
+    }
+
+    // Unmaps the pixel buffer of a surface locked with `lock_data()` and uploads its contents to
+    // the surface.
+    pub(crate) fn unlock_data(&mut self, gl: &Gl, egl_display: EGLDisplay) {
+        let (size, format) = (self.size, self.format);
+        let (texture_object, pixel_buffer) = match self.objects {
+            EGLSurfaceObjects::TextureImage {
+                texture_object,
+                pixel_buffer: Some(ref mut pixel_buffer),
+                ..
+            } if pixel_buffer.mapped => (texture_object, pixel_buffer),
+            _ => return,
+        };
+
+        unsafe {
+            // If the context is gone, the pixel buffer went with it, so there's nothing to upload.
+            let _ = context::with_context_current(egl_display, pixel_buffer.egl_context, || {
+                let (_, gl_format, gl_type) = format.gl_texture_formats();
+                let (mut old_texture_object, mut old_unpack_buffer) = (0, 0);
+                gl.GetIntegerv(gl::TEXTURE_BINDING_2D, &mut old_texture_object);
+                gl.GetIntegerv(gl::PIXEL_UNPACK_BUFFER_BINDING, &mut old_unpack_buffer);
+
+                gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, pixel_buffer.pixel_buffer_object);
+                gl.UnmapBuffer(gl::PIXEL_UNPACK_BUFFER);
+                gl.BindTexture(gl::TEXTURE_2D, texture_object);
+                gl.TexSubImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    0,
+                    0,
+                    size.width,
+                    size.height,
+                    gl_format,
+                    gl_type,
+                    ptr::null(),
+                );
+
+                gl.BindTexture(gl::TEXTURE_2D, old_texture_object as GLuint);
+                gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, old_unpack_buffer as GLuint);
+            });
+        }
+        pixel_buffer.mapped = false;
+    }
+
+    // Returns the age of the back buffer of a widget surface, or 0 if its contents are undefined.
+    pub(crate) fn buffer_age(
+        &self,
+        egl_display: EGLDisplay,
+        egl_context: EGLContext,
+    ) -> Result<u32, Error> {
+        let egl_surface = match self.objects {
+            EGLSurfaceObjects::Window { egl_surface, .. } => egl_surface,
+            EGLSurfaceObjects::TextureImage { .. } => return Err(Error::NoWidgetAttached),
+        };
+
+        // The surface must be current in order to query its buffer age.
+        unsafe {
+            context::with_context_and_surface_current(
+                egl_display,
+                egl_context,
+                egl_surface,
+                || query_buffer_age(egl_display, egl_surface),
+            )?
+        }
+    }
+
+    // Reads back the pixels in `rect`, which has its origin at the top left, with the rows ordered
+    // from top to bottom. The surface must not be bound to its context.
+    pub(crate) fn read_pixels(
+        &self,
+        gl: &Gl,
+        egl_display: EGLDisplay,
+        egl_context: EGLContext,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        unsafe {
+            match self.objects {
+                // Unbound multisampled surfaces have already been resolved into the texture.
+                EGLSurfaceObjects::TextureImage {
+                    framebuffer_object, ..
+                } => context::with_context_current(egl_display, egl_context, || {
+                    gl_utils::read_framebuffer_pixels(
+                        gl,
+                        framebuffer_object,
+                        self.size,
+                        rect,
+                        format,
+                    )
+                })?,
+                EGLSurfaceObjects::Window { egl_surface, .. } => {
+                    context::with_context_and_surface_current(
+                        egl_display,
+                        egl_context,
+                        egl_surface,
+                        || gl_utils::read_framebuffer_pixels(gl, 0, self.size, rect, format),
+                    )?
+                }
+            }
+        }
+    }
+
+    // Exports the image backing a generic surface as DMA-BUF file descriptors, via
+    // `EGL_MESA_image_dma_buf_export`. The surface must not be bound to its context.
+    #[cfg(free_unix)]
+    pub(crate) fn export_dmabuf(
+        &self,
+        gl: &Gl,
+        egl_display: EGLDisplay,
+        egl_context: EGLContext,
+    ) -> Result<DmaBufDescriptor, Error> {
+        let egl_image = match self.objects {
+            EGLSurfaceObjects::TextureImage { egl_image, .. } => egl_image,
+            EGLSurfaceObjects::Window { .. } => return Err(Error::WidgetAttached),
+        };
+
+        unsafe {
+            let (export_query, export) = match (
+                EGL_EXTENSION_FUNCTIONS.ExportDMABUFImageQueryMESA,
+                EGL_EXTENSION_FUNCTIONS.ExportDMABUFImageMESA,
+            ) {
+                (Some(export_query), Some(export))
+                    if device::display_has_extension(
+                        egl_display,
+                        "EGL_MESA_image_dma_buf_export",
+                    ) =>
+                {
+                    (export_query, export)
+                }
+                _ => return Err(Error::RequiredExtensionUnavailable),
+            };
+
+            // Submit any rendering to the surface, so that the buffer is up to date once the GPU
+            // catches up.
+            context::with_context_current(egl_display, egl_context, || gl.Flush())?;
+
+            let (mut fourcc, mut plane_count) = (0, 0);
+            if export_query(
+                egl_display,
+                egl_image,
+                &mut fourcc,
+                &mut plane_count,
+                ptr::null_mut(),
+            ) == egl::FALSE
+            {
+                let err = EGL_FUNCTIONS.with(|egl| {
+                    egl.GetError()
+                        .to_native_error("eglExportDMABUFImageQueryMESA")
+                });
+                return Err(Error::SurfaceExportFailed(err));
+            }
+
+            let plane_count = plane_count as usize;
+            let mut modifiers = vec![DRM_FORMAT_MOD_INVALID; plane_count];
+            if export_query(
+                egl_display,
+                egl_image,
+                &mut fourcc,
+                ptr::null_mut(),
+                modifiers.as_mut_ptr(),
+            ) == egl::FALSE
+            {
+                let err = EGL_FUNCTIONS.with(|egl| {
+                    egl.GetError()
+                        .to_native_error("eglExportDMABUFImageQueryMESA")
+                });
+                return Err(Error::SurfaceExportFailed(err));
+            }
+
+            let mut fds = vec![-1; plane_count];
+            let mut strides = vec![0; plane_count];
+            let mut offsets = vec![0; plane_count];
+            if export(
+                egl_display,
+                egl_image,
+                fds.as_mut_ptr(),
+                strides.as_mut_ptr(),
+                offsets.as_mut_ptr(),
+            ) == egl::FALSE
+            {
+                let err = EGL_FUNCTIONS
+                    .with(|egl| egl.GetError().to_native_error("eglExportDMABUFImageMESA"));
+                return Err(Error::SurfaceExportFailed(err));
+            }
+
+            // Take ownership of the file descriptors right away, so that they get closed if
+            // anything below fails. Planes that live in the same buffer as the previous plane may
+            // not get a file descriptor of their own.
+            let mut planes: Vec<DmaBufPlane> = Vec::with_capacity(plane_count);
+            for plane_index in 0..plane_count {
+                let fd = match (fds[plane_index], planes.last()) {
+                    (-1, Some(previous_plane)) => {
+                        previous_plane.fd.try_clone().map_err(|io_error| {
+                            let err = NativeError::new(WindowingApiError::BadAlloc, "dup");
+                            Error::SurfaceExportFailed(match io_error.raw_os_error() {
+                                Some(code) => err.with_code(code),
+                                None => err,
+                            })
+                        })?
+                    }
+                    (-1, None) => {
+                        return Err(Error::SurfaceExportFailed(NativeError::new(
+                            WindowingApiError::Failed,
+                            "eglExportDMABUFImageMESA",
+                        )))
+                    }
+                    (fd, _) => OwnedFd::from_raw_fd(fd),
+                };
+                planes.push(DmaBufPlane {
+                    fd,
+                    offset: offsets[plane_index] as u32,
+                    stride: strides[plane_index] as u32,
+                });
             }
+
+            Ok(DmaBufDescriptor {
+                size: self.size,
+                fourcc: fourcc as u32,
+                modifier: modifiers.first().cloned().unwrap_or(DRM_FORMAT_MOD_INVALID),
+                planes,
+            })
+        }
+    }
+
+    // Starts reading back the whole surface, in its format, into a new pixel buffer object. The
+    // surface must not be bound to its context.
+    pub(crate) fn begin_readback(
+        &self,
+        gl: &Gl,
+        egl_display: EGLDisplay,
+        context: &EGLBackedContext,
+    ) -> Result<EGLReadback, Error> {
+        let egl_context = context.egl_context;
+        let (framebuffer_object, egl_surface) = match self.objects {
+            // Unbound multisampled surfaces have already been resolved into the texture.
+            EGLSurfaceObjects::TextureImage {
+                framebuffer_object, ..
+            } => (framebuffer_object, egl::NO_SURFACE),
+            EGLSurfaceObjects::Window { egl_surface, .. } => (0, egl_surface),
+        };
+
+        let (size, format) = (self.size, self.format);
+        let stride = size.width as usize * format.bytes_per_pixel();
+        let len = stride * size.height as usize;
+
+        unsafe {
+            context::with_context_and_surface_current(
+                egl_display,
+                egl_context,
+                egl_surface,
+                || {
+                    // Pixel buffer objects and fences need OpenGL 3.0 or OpenGL ES 3.0.
+                    if GLVersion::current(gl)?.major < 3 {
+                        return Err(Error::UnsupportedGLVersion);
+                    }
+
+                    let (_, gl_format, gl_type) = format.gl_texture_formats();
+                    let (mut old_read_framebuffer, mut old_pack_buffer) = (0, 0);
+                    gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut old_read_framebuffer);
+                    gl.GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut old_pack_buffer);
+
+                    let mut pixel_buffer_object = 0;
+                    gl.GenBuffers(1, &mut pixel_buffer_object);
+                    gl.BindBuffer(gl::PIXEL_PACK_BUFFER, pixel_buffer_object);
+                    gl.BufferData(
+                        gl::PIXEL_PACK_BUFFER,
+                        len as GLsizeiptr,
+                        ptr::null(),
+                        gl::STREAM_READ,
+                    );
+                    gl.BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer_object);
+                    gl.ReadPixels(
+                        0,
+                        0,
+                        size.width,
+                        size.height,
+                        gl_format,
+                        gl_type,
+                        ptr::null_mut(),
+                    );
+                    let sync = gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+
+                    // Submit the readback now, so that it runs while the caller does other work.
+                    gl.Flush();
+
+                    gl.BindBuffer(gl::PIXEL_PACK_BUFFER, old_pack_buffer as GLuint);
+                    gl.BindFramebuffer(gl::READ_FRAMEBUFFER, old_read_framebuffer as GLuint);
+
+                    if sync.is_null() {
+                        gl.DeleteBuffers(1, &pixel_buffer_object);
+                        return Err(Error::Failed);
+                    }
+
+                    Ok(EGLReadback {
+                        egl_context,
+                        context_alive: context.alive.clone(),
+                        pixel_buffer_object,
+                        sync,
+                        size,
+                        stride,
+                        len,
+                        data: ptr::null(),
+                    })
+                },
+            )?
+        }
+    }
+
+    // Sets the swap interval of a widget surface, failing if its config doesn't support that
+    // interval.
+    pub(crate) fn set_swap_interval(
+        &self,
+        egl_display: EGLDisplay,
+        egl_context: EGLContext,
+        interval: EGLint,
+    ) -> Result<(), Error> {
+        let egl_surface = match self.objects {
+            EGLSurfaceObjects::Window { egl_surface, .. } => egl_surface,
+            EGLSurfaceObjects::TextureImage { .. } => return Err(Error::NoWidgetAttached),
+        };
+
+        // The swap interval applies to the draw surface of the current context.
+        unsafe {
+            context::with_context_and_surface_current(
+                egl_display,
+                egl_context,
+                egl_surface,
+                || {
+                    EGL_FUNCTIONS.with(|egl| {
+                        // EGL silently clamps intervals that the config doesn't support, so check
+                        // ourselves.
+                        let mut egl_config_id = 0;
+                        let result = egl.QuerySurface(
+                            egl_display,
+                            egl_surface,
+                            egl::CONFIG_ID as EGLint,
+                            &mut egl_config_id,
+                        );
+                        assert_ne!(result, egl::FALSE);
+                        let egl_config = context::egl_config_from_id(egl_display, egl_config_id);
+                        let min_interval = context::get_config_attr(
+                            egl_display,
+                            egl_config,
+                            egl::MIN_SWAP_INTERVAL as EGLint,
+                        );
+                        let max_interval = context::get_config_attr(
+                            egl_display,
+                            egl_config,
+                            egl::MAX_SWAP_INTERVAL as EGLint,
+                        );
+                        if interval < min_interval || interval > max_interval {
+                            return Err(Error::UnsupportedOnThisPlatform);
+                        }
+
+                        if egl.SwapInterval(egl_display, interval) == egl::FALSE {
+                            return Err(Error::PresentFailed(
+                                egl.GetError().to_native_error("eglSwapInterval"),
+                            ));
+                        }
+                        Ok(())
+                    })
+                },
+            )?
+        }
+    }
+
+    // Resolves a multisampled surface into its texture. The context that owns the surface must be
+    // current.
+    pub(crate) fn resolve(&self, gl: &Gl) {
+        if let EGLSurfaceObjects::TextureImage {
+            framebuffer_object,
+            multisample_framebuffer: Some(ref multisample_framebuffer),
+            ..
+        } = self.objects
+        {
+            multisample_framebuffer.resolve(gl, &self.size, framebuffer_object);
         }
     }
 
@@ -293,12 +1043,22 @@ impl EGLBackedSurface {
             size: self.size,
             id: self.id(),
             context_id: self.context_id,
-            framebuffer_object: match self.objects {
-                EGLSurfaceObjects::TextureImage {
-                    framebuffer_object, ..
-                } => framebuffer_object,
-                EGLSurfaceObjects::Window { .. } => 0,
-            },
+            framebuffer_object: self.framebuffer_object(),
+            format: self.format,
+        }
+    }
+
+    // Returns the framebuffer object that is rendered to, which is the multisampled one if present.
+    fn framebuffer_object(&self) -> GLuint {
+        match self.objects {
+            EGLSurfaceObjects::TextureImage {
+                multisample_framebuffer: Some(ref multisample_framebuffer),
+                ..
+            } => multisample_framebuffer.framebuffer_object,
+            EGLSurfaceObjects::TextureImage {
+                framebuffer_object, ..
+            } => framebuffer_object,
+            EGLSurfaceObjects::Window { .. } => 0,
         }
     }
 
@@ -321,16 +1081,37 @@ impl EGLBackedSurface {
         unsafe {
             EGL_FUNCTIONS.with(|egl| {
                 if egl.GetCurrentContext() != egl_context {
+                    // Multisampled surfaces still have to be resolved, so that surface textures
+                    // created from them see what was rendered.
+                    if let EGLSurfaceObjects::TextureImage {
+                        multisample_framebuffer: Some(_),
+                        ..
+                    } = self.objects
+                    {
+                        // The surface has already been detached from the context, so a failure
+                        // here can't be returned without leaking it.
+                        if let Err(err) =
+                            context::with_context_current(egl_display, egl_context, || {
+                                self.resolve(gl)
+                            })
+                        {
+                            warn!(
+                                "surfman: Failed to resolve multisampled surface {:?} while \
+                                 unbinding it: {:?}",
+                                self.id(),
+                                err
+                            );
+                        }
+                    }
                     return;
                 }
 
+                self.resolve(gl);
                 egl.MakeCurrent(egl_display, egl::NO_SURFACE, egl::NO_SURFACE, egl_context);
 
                 match self.objects {
-                    EGLSurfaceObjects::TextureImage {
-                        framebuffer_object, ..
-                    } => {
-                        gl_utils::unbind_framebuffer_if_necessary(gl, framebuffer_object);
+                    EGLSurfaceObjects::TextureImage { .. } => {
+                        gl_utils::unbind_framebuffer_if_necessary(gl, self.framebuffer_object());
                     }
                     EGLSurfaceObjects::Window { .. } => {}
                 }
@@ -349,6 +1130,152 @@ impl EGLBackedSurface {
     }
 }
 
+impl PixelBuffer {
+    fn new(
+        gl: &Gl,
+        egl_context: EGLContext,
+        size: &Size2D<i32>,
+        access: SurfaceAccess,
+        format: SurfaceFormat,
+    ) -> PixelBuffer {
+        let stride = size.width as usize * format.bytes_per_pixel();
+        let len = stride * size.height as usize;
+        let usage = match access {
+            SurfaceAccess::GPUCPUWriteCombined => gl::STREAM_DRAW,
+            SurfaceAccess::GPUOnly | SurfaceAccess::GPUCPU => gl::STREAM_READ,
+        };
+
+        unsafe {
+            // Pixel pack buffers need OpenGL 3.0 or OpenGL ES 3.0. Without them, no buffer is
+            // created, and `lock_data()` reports `UnsupportedGLVersion`.
+            let mut pixel_buffer_object = 0;
+            if GLVersion::current(gl).map_or(false, |version| version.major >= 3) {
+                gl.GenBuffers(1, &mut pixel_buffer_object);
+                let mut old_pack_buffer = 0;
+                gl.GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut old_pack_buffer);
+                gl.BindBuffer(gl::PIXEL_PACK_BUFFER, pixel_buffer_object);
+                gl.BufferData(gl::PIXEL_PACK_BUFFER, len as GLsizeiptr, ptr::null(), usage);
+                gl.BindBuffer(gl::PIXEL_PACK_BUFFER, old_pack_buffer as GLuint);
+            }
+
+            PixelBuffer {
+                egl_context,
+                pixel_buffer_object,
+                stride,
+                len,
+                mapped: false,
+            }
+        }
+    }
+
+    fn destroy(self, gl: &Gl) {
+        unsafe {
+            if self.mapped {
+                gl.BindBuffer(gl::PIXEL_PACK_BUFFER, self.pixel_buffer_object);
+                gl.UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+                gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            }
+            gl.DeleteBuffers(1, &self.pixel_buffer_object);
+        }
+    }
+}
+
+impl EGLReadback {
+    #[inline]
+    pub(crate) fn size(&self) -> Size2D<i32> {
+        self.size
+    }
+
+    #[inline]
+    pub(crate) fn stride(&self) -> usize {
+        self.stride
+    }
+
+    // Maps the pixel buffer if the readback has finished, waiting for it for at most `timeout`
+    // nanoseconds. Returns false if it's still in progress, and `Failed` if the context that
+    // started it has been destroyed.
+    pub(crate) fn map(
+        &mut self,
+        gl: &Gl,
+        egl_display: EGLDisplay,
+        timeout: GLuint64,
+    ) -> Result<bool, Error> {
+        if self.data.is_null() {
+            if !self.context_alive.get() {
+                return Err(Error::Failed);
+            }
+            unsafe {
+                let data = context::with_context_current(egl_display, self.egl_context, || {
+                    match gl.ClientWaitSync(self.sync, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) {
+                        gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => {}
+                        gl::TIMEOUT_EXPIRED => return Ok(None),
+                        _ => return Err(Error::Failed),
+                    }
+
+                    let mut old_pack_buffer = 0;
+                    gl.GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut old_pack_buffer);
+                    gl.BindBuffer(gl::PIXEL_PACK_BUFFER, self.pixel_buffer_object);
+                    let data = gl.MapBufferRange(
+                        gl::PIXEL_PACK_BUFFER,
+                        0,
+                        self.len as GLsizeiptr,
+                        gl::MAP_READ_BIT,
+                    );
+                    gl.BindBuffer(gl::PIXEL_PACK_BUFFER, old_pack_buffer as GLuint);
+
+                    if data.is_null() {
+                        return Err(Error::Failed);
+                    }
+                    Ok(Some(data as *const u8))
+                })??;
+
+                match data {
+                    None => return Ok(false),
+                    Some(data) => self.data = data,
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    // Returns the pixel data. The pixel buffer must have been mapped with `map()`.
+    pub(crate) fn data(&self) -> &[u8] {
+        assert!(!self.data.is_null());
+        unsafe { slice::from_raw_parts(self.data, self.len) }
+    }
+
+    pub(crate) fn destroy(&mut self, gl: &Gl, egl_display: EGLDisplay) {
+        if self.pixel_buffer_object == 0 {
+            return;
+        }
+
+        // If the context has been destroyed, the buffer and sync went with it. Deleting them
+        // anyway would delete whatever has the same names in the current context, and the EGL
+        // handle may even have been reused for another context.
+        if !self.context_alive.get() {
+            self.data = ptr::null();
+            self.pixel_buffer_object = 0;
+            return;
+        }
+
+        unsafe {
+            let _ = context::with_context_current(egl_display, self.egl_context, || {
+                if !self.data.is_null() {
+                    let mut old_pack_buffer = 0;
+                    gl.GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut old_pack_buffer);
+                    gl.BindBuffer(gl::PIXEL_PACK_BUFFER, self.pixel_buffer_object);
+                    gl.UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+                    gl.BindBuffer(gl::PIXEL_PACK_BUFFER, old_pack_buffer as GLuint);
+                }
+                gl.DeleteSync(self.sync);
+                gl.DeleteBuffers(1, &self.pixel_buffer_object);
+            });
+        }
+        self.data = ptr::null();
+        self.pixel_buffer_object = 0;
+    }
+}
+
 impl EGLSurfaceTexture {
     pub(crate) fn destroy(mut self, gl: &Gl) -> EGLBackedSurface {
         unsafe {
@@ -397,6 +1324,73 @@ pub(crate) unsafe fn create_pbuffer_surface(
     })
 }
 
+// Presents the given surface, using `EGL_KHR_swap_buffers_with_damage` or
+// `EGL_EXT_swap_buffers_with_damage` to only update the damaged regions if available. The surface
+// must be the draw surface of the current context.
+pub(crate) unsafe fn swap_buffers_with_damage(
+    egl_display: EGLDisplay,
+    egl_surface: EGLSurface,
+    damage: &[Rect<i32>],
+) -> EGLBoolean {
+    let swap_buffers_with_damage = if damage.is_empty() {
+        None
+    } else if device::display_has_extension(egl_display, "EGL_KHR_swap_buffers_with_damage") {
+        EGL_EXTENSION_FUNCTIONS.SwapBuffersWithDamageKHR
+    } else if device::display_has_extension(egl_display, "EGL_EXT_swap_buffers_with_damage") {
+        EGL_EXTENSION_FUNCTIONS.SwapBuffersWithDamageEXT
+    } else {
+        None
+    };
+
+    match swap_buffers_with_damage {
+        Some(swap_buffers_with_damage) => {
+            let rects: Vec<EGLint> = damage
+                .iter()
+                .flat_map(|rect| {
+                    [
+                        rect.origin.x,
+                        rect.origin.y,
+                        rect.size.width,
+                        rect.size.height,
+                    ]
+                })
+                .collect();
+            swap_buffers_with_damage(
+                egl_display,
+                egl_surface,
+                rects.as_ptr(),
+                damage.len() as EGLint,
+            )
+        }
+        None => EGL_FUNCTIONS.with(|egl| egl.SwapBuffers(egl_display, egl_surface)),
+    }
+}
+
+// Returns the age of the back buffer of the given surface via `EGL_EXT_buffer_age`, or 0 if the
+// extension isn't available. The surface must be the draw surface of the current context.
+pub(crate) unsafe fn query_buffer_age(
+    egl_display: EGLDisplay,
+    egl_surface: EGLSurface,
+) -> Result<u32, Error> {
+    if !device::display_has_extension(egl_display, "EGL_EXT_buffer_age") {
+        return Ok(0);
+    }
+
+    EGL_FUNCTIONS.with(|egl| {
+        let mut buffer_age = 0;
+        let result = egl.QuerySurface(
+            egl_display,
+            egl_surface,
+            EGL_BUFFER_AGE_EXT as EGLint,
+            &mut buffer_age,
+        );
+        if result == egl::FALSE {
+            return Err(Error::Failed);
+        }
+        Ok(buffer_age as u32)
+    })
+}
+
 #[allow(dead_code)]
 pub(crate) unsafe fn bind_egl_image_to_gl_texture(gl: &Gl, egl_image: EGLImageKHR) -> GLuint {
     let mut texture = 0;
@@ -0,0 +1,195 @@
+// surfman/surfman/src/platform/generic/egl/sync.rs
+//
+//! Fences common to the various EGL backends.
+
+use super::device;
+use super::ffi::{EGLSyncKHR, EGLTimeKHR, EGL_CONDITION_SATISFIED_KHR, EGL_EXTENSION_FUNCTIONS};
+use super::ffi::{EGL_FOREVER_KHR, EGL_NO_SYNC_KHR, EGL_SYNC_FENCE_KHR};
+use super::ffi::{EGL_SYNC_NATIVE_FENCE_ANDROID, EGL_TIMEOUT_EXPIRED_KHR};
+use crate::egl;
+use crate::egl::types::{EGLDisplay, EGLenum, EGLint};
+use crate::{Error, Gl};
+
+#[cfg(unix)]
+use super::ffi::{EGL_NO_NATIVE_FENCE_FD_ANDROID, EGL_SYNC_NATIVE_FENCE_FD_ANDROID};
+#[cfg(unix)]
+use std::os::unix::io::{FromRawFd, IntoRawFd, OwnedFd};
+
+use std::convert::TryFrom;
+use std::thread;
+use std::time::Duration;
+
+// A fence in the command stream of a context, via `EGL_KHR_fence_sync`.
+//
+// Without `EGL_KHR_fence_sync`, creating a fence waits for the context's commands to finish, and
+// the fence has no EGL sync object.
+pub(crate) struct EGLFence {
+    pub(crate) egl_display: EGLDisplay,
+    egl_sync: EGLSyncKHR,
+    native: bool,
+    destroyed: bool,
+}
+
+unsafe impl Send for EGLFence {}
+
+impl Drop for EGLFence {
+    fn drop(&mut self) {
+        if !self.destroyed && !thread::panicking() {
+            panic!("Should have destroyed the fence first with `destroy_fence()`!")
+        }
+    }
+}
+
+impl EGLFence {
+    // Inserts a fence into the command stream of the current context and flushes it.
+    //
+    // If `EGL_ANDROID_native_fence_sync` is supported, the fence is backed by a sync file, so that
+    // it can be exported.
+    pub(crate) unsafe fn new(gl: &Gl, egl_display: EGLDisplay) -> Result<EGLFence, Error> {
+        let create_sync = match EGL_EXTENSION_FUNCTIONS.CreateSyncKHR {
+            Some(create_sync)
+                if device::display_has_extension(egl_display, "EGL_KHR_fence_sync") =>
+            {
+                create_sync
+            }
+            _ => {
+                gl.Finish();
+                return Ok(EGLFence {
+                    egl_display,
+                    egl_sync: EGL_NO_SYNC_KHR,
+                    native: false,
+                    destroyed: false,
+                });
+            }
+        };
+
+        let native = device::display_has_extension(egl_display, "EGL_ANDROID_native_fence_sync");
+        let sync_type = if native {
+            EGL_SYNC_NATIVE_FENCE_ANDROID
+        } else {
+            EGL_SYNC_FENCE_KHR
+        };
+        let attributes = [egl::NONE as EGLint];
+        let egl_sync = create_sync(egl_display, sync_type, attributes.as_ptr());
+        if egl_sync == EGL_NO_SYNC_KHR {
+            return Err(Error::Failed);
+        }
+
+        // Other contexts may wait on the fence, so make sure that it actually gets signaled.
+        // This also creates the sync file of native fences.
+        gl.Flush();
+
+        Ok(EGLFence {
+            egl_display,
+            egl_sync,
+            native,
+            destroyed: false,
+        })
+    }
+
+    // Imports a sync file as a fence in the command stream of the current context, via
+    // `EGL_ANDROID_native_fence_sync`. EGL takes ownership of the file descriptor on success.
+    #[cfg(unix)]
+    pub(crate) unsafe fn from_fd(egl_display: EGLDisplay, fd: OwnedFd) -> Result<EGLFence, Error> {
+        let create_sync = match EGL_EXTENSION_FUNCTIONS.CreateSyncKHR {
+            Some(create_sync)
+                if device::display_has_extension(egl_display, "EGL_ANDROID_native_fence_sync") =>
+            {
+                create_sync
+            }
+            _ => return Err(Error::RequiredExtensionUnavailable),
+        };
+
+        let attributes = [
+            EGL_SYNC_NATIVE_FENCE_FD_ANDROID as EGLint,
+            fd.into_raw_fd(),
+            egl::NONE as EGLint,
+        ];
+        let egl_sync = create_sync(
+            egl_display,
+            EGL_SYNC_NATIVE_FENCE_ANDROID,
+            attributes.as_ptr(),
+        );
+        if egl_sync == EGL_NO_SYNC_KHR {
+            // The file descriptor is still ours, so close it.
+            drop(OwnedFd::from_raw_fd(attributes[1]));
+            return Err(Error::Failed);
+        }
+
+        Ok(EGLFence {
+            egl_display,
+            egl_sync,
+            native: true,
+            destroyed: false,
+        })
+    }
+
+    // Returns a new sync file descriptor for a native fence.
+    #[cfg(unix)]
+    pub(crate) unsafe fn export_fd(&self) -> Result<OwnedFd, Error> {
+        let dup_native_fence_fd = match EGL_EXTENSION_FUNCTIONS.DupNativeFenceFDANDROID {
+            Some(dup_native_fence_fd) if self.native => dup_native_fence_fd,
+            _ => return Err(Error::RequiredExtensionUnavailable),
+        };
+
+        let fd = dup_native_fence_fd(self.egl_display, self.egl_sync);
+        if fd == EGL_NO_NATIVE_FENCE_FD_ANDROID {
+            return Err(Error::Failed);
+        }
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+
+    // Makes the current context wait on the GPU for the fence to be signaled, via
+    // `EGL_KHR_wait_sync`. Without that extension, this waits on the CPU instead.
+    pub(crate) unsafe fn wait(&self) -> Result<(), Error> {
+        if self.egl_sync == EGL_NO_SYNC_KHR {
+            return Ok(());
+        }
+
+        let wait_sync = match EGL_EXTENSION_FUNCTIONS.WaitSyncKHR {
+            Some(wait_sync)
+                if device::display_has_extension(self.egl_display, "EGL_KHR_wait_sync") =>
+            {
+                wait_sync
+            }
+            _ => {
+                self.client_wait(Duration::MAX)?;
+                return Ok(());
+            }
+        };
+
+        if wait_sync(self.egl_display, self.egl_sync, 0) != egl::TRUE as EGLint {
+            return Err(Error::Failed);
+        }
+        Ok(())
+    }
+
+    // Waits on the CPU for at most `timeout` for the fence to be signaled. Returns false if it
+    // timed out.
+    pub(crate) unsafe fn client_wait(&self, timeout: Duration) -> Result<bool, Error> {
+        if self.egl_sync == EGL_NO_SYNC_KHR {
+            return Ok(true);
+        }
+
+        let client_wait_sync = EGL_EXTENSION_FUNCTIONS
+            .ClientWaitSyncKHR
+            .ok_or(Error::RequiredExtensionUnavailable)?;
+        // Timeouts too long to represent mean "forever".
+        let timeout = EGLTimeKHR::try_from(timeout.as_nanos()).unwrap_or(EGL_FOREVER_KHR);
+        match client_wait_sync(self.egl_display, self.egl_sync, 0, timeout) as EGLenum {
+            EGL_CONDITION_SATISFIED_KHR => Ok(true),
+            EGL_TIMEOUT_EXPIRED_KHR => Ok(false),
+            _ => Err(Error::Failed),
+        }
+    }
+
+    pub(crate) unsafe fn destroy(&mut self) {
+        if self.egl_sync != EGL_NO_SYNC_KHR {
+            if let Some(destroy_sync) = EGL_EXTENSION_FUNCTIONS.DestroySyncKHR {
+                destroy_sync(self.egl_display, self.egl_sync);
+            }
+            self.egl_sync = EGL_NO_SYNC_KHR;
+        }
+        self.destroyed = true;
+    }
+}
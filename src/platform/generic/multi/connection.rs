@@ -4,7 +4,7 @@
 
 use super::device::{Adapter, Device, NativeDevice};
 use super::surface::NativeWidget;
-use crate::connection::Connection as ConnectionInterface;
+use crate::connection::{BackendKind, Connection as ConnectionInterface};
 use crate::device::Device as DeviceInterface;
 use crate::Error;
 use crate::GLApi;
@@ -64,14 +64,51 @@ where
     Alt::Connection: ConnectionInterface<Device = Alt>,
 {
     /// Connects to the default display.
-    #[inline]
+    ///
+    /// This tries the default backend first, and falls back to the alternate backend if that
+    /// fails. If the `SURFMAN_BACKEND` environment variable is set, the backends it lists are
+    /// tried in order instead, as with `new_with_preference()`.
     pub fn new() -> Result<Connection<Def, Alt>, Error> {
+        if let Some(preference) = BackendKind::preference_from_env() {
+            return Connection::new_with_preference(&preference);
+        }
+
         match <Def::Connection>::new() {
             Ok(connection) => Ok(Connection::Default(connection)),
             Err(_) => <Alt::Connection>::new().map(Connection::Alternate),
         }
     }
 
+    /// Connects to the display with the first of the given backends that is available, in order.
+    ///
+    /// If none of the backends could be connected to, this returns the error from the last one
+    /// that was tried, or `UnsupportedOnThisPlatform` if neither the default nor the alternate
+    /// backend is among them.
+    pub fn new_with_preference(preference: &[BackendKind]) -> Result<Connection<Def, Alt>, Error> {
+        let mut result = Err(Error::UnsupportedOnThisPlatform);
+        for &backend_kind in preference {
+            match <Def::Connection>::new_with_preference(&[backend_kind]) {
+                Ok(connection) => return Ok(Connection::Default(connection)),
+                Err(Error::UnsupportedOnThisPlatform) => {}
+                Err(err) => result = Err(err),
+            }
+            match <Alt::Connection>::new_with_preference(&[backend_kind]) {
+                Ok(connection) => return Ok(Connection::Alternate(connection)),
+                Err(Error::UnsupportedOnThisPlatform) => {}
+                Err(err) => result = Err(err),
+            }
+        }
+        result
+    }
+
+    /// Returns which backend this connection uses.
+    pub fn backend_kind(&self) -> BackendKind {
+        match *self {
+            Connection::Default(ref connection) => connection.backend_kind(),
+            Connection::Alternate(ref connection) => connection.backend_kind(),
+        }
+    }
+
     /// Returns the native connection corresponding to this connection.
     pub fn native_connection(&self) -> NativeConnection<Def, Alt> {
         match *self {
@@ -272,6 +309,16 @@ where
         Connection::new()
     }
 
+    #[inline]
+    fn new_with_preference(preference: &[BackendKind]) -> Result<Connection<Def, Alt>, Error> {
+        Connection::new_with_preference(preference)
+    }
+
+    #[inline]
+    fn backend_kind(&self) -> BackendKind {
+        Connection::backend_kind(self)
+    }
+
     #[inline]
     fn native_connection(&self) -> NativeConnection<Def, Alt> {
         Connection::native_connection(self)
@@ -5,9 +5,12 @@
 use super::device::Device;
 use super::surface::Surface;
 use crate::device::Device as DeviceInterface;
-use crate::{ContextAttributes, ContextID, Error, SurfaceInfo};
+use crate::SurfaceInfo;
+use crate::{ContextAttributes, ContextCapabilities, ContextConfigInfo, ContextID};
+use crate::{ContextResetStatus, ContextVersionRange, Error, GLApi};
 
 use std::os::raw::c_void;
+use std::time::Duration;
 
 /// Represents an OpenGL rendering context.
 ///
@@ -63,6 +66,20 @@ where
     Alternate(Alt::NativeContext),
 }
 
+/// A fence in the command stream of a context, which can be waited on by other contexts.
+///
+/// Fences must be explicitly destroyed with `destroy_fence()`.
+pub enum Fence<Def, Alt>
+where
+    Def: DeviceInterface,
+    Alt: DeviceInterface,
+{
+    /// The default fence type.
+    Default(Def::Fence),
+    /// The alternate fence type.
+    Alternate(Alt::Fence),
+}
+
 impl<Def, Alt> Device<Def, Alt>
 where
     Def: DeviceInterface,
@@ -85,6 +102,60 @@ where
         }
     }
 
+    /// Returns a context descriptor for every framebuffer configuration that satisfies the given
+    /// attributes, in the order that the driver prefers them.
+    ///
+    /// The first candidate is the one that `create_context_descriptor()` picks.
+    pub fn enumerate_context_descriptors(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<Vec<ContextDescriptor<Def, Alt>>, Error> {
+        match *self {
+            Device::Default(ref device) => {
+                device
+                    .enumerate_context_descriptors(attributes)
+                    .map(|descriptors| {
+                        descriptors
+                            .into_iter()
+                            .map(ContextDescriptor::Default)
+                            .collect()
+                    })
+            }
+            Device::Alternate(ref device) => {
+                device
+                    .enumerate_context_descriptors(attributes)
+                    .map(|descriptors| {
+                        descriptors
+                            .into_iter()
+                            .map(ContextDescriptor::Alternate)
+                            .collect()
+                    })
+            }
+        }
+    }
+
+    /// Creates a context descriptor with the given attributes, using the framebuffer
+    /// configuration that the given function scores highest.
+    ///
+    /// The function returns `None` to reject a configuration.
+    pub fn choose_context_descriptor<F>(
+        &self,
+        attributes: &ContextAttributes,
+        score: F,
+    ) -> Result<ContextDescriptor<Def, Alt>, Error>
+    where
+        F: FnMut(&ContextConfigInfo) -> Option<u32>,
+    {
+        match *self {
+            Device::Default(ref device) => device
+                .choose_context_descriptor(attributes, score)
+                .map(ContextDescriptor::Default),
+            Device::Alternate(ref device) => device
+                .choose_context_descriptor(attributes, score)
+                .map(ContextDescriptor::Alternate),
+        }
+    }
+
     /// Creates a new OpenGL context.
     ///
     /// The context initially has no surface attached. Until a surface is bound to it, rendering
@@ -123,6 +194,44 @@ where
         }
     }
 
+    /// Creates a new OpenGL context with the first version that the driver supports, trying each
+    /// range in turn from its highest version to its lowest.
+    ///
+    /// Returns the context along with the attributes that it was created with. If no version
+    /// works, this returns the error from the last attempt.
+    pub fn create_context_with_fallbacks(
+        &mut self,
+        ranges: &[ContextVersionRange],
+        share_with: Option<&Context<Def, Alt>>,
+    ) -> Result<(Context<Def, Alt>, ContextAttributes), Error> {
+        match *self {
+            Device::Default(ref mut device) => {
+                let shared = match share_with {
+                    Some(Context::Default(other)) => Some(other),
+                    Some(_) => {
+                        return Err(Error::IncompatibleSharedContext);
+                    }
+                    None => None,
+                };
+                device
+                    .create_context_with_fallbacks(ranges, shared)
+                    .map(|(context, attributes)| (Context::Default(context), attributes))
+            }
+            Device::Alternate(ref mut device) => {
+                let shared = match share_with {
+                    Some(Context::Alternate(other)) => Some(other),
+                    Some(_) => {
+                        return Err(Error::IncompatibleSharedContext);
+                    }
+                    None => None,
+                };
+                device
+                    .create_context_with_fallbacks(ranges, shared)
+                    .map(|(context, attributes)| (Context::Alternate(context), attributes))
+            }
+        }
+    }
+
     /// Wraps an existing native context in a `Context` object.
     pub unsafe fn create_context_from_native_context(
         &self,
@@ -185,6 +294,17 @@ where
         }
     }
 
+    /// Returns the OpenGL API flavor (OpenGL or OpenGL ES) that the given context uses.
+    pub fn context_gl_api(&self, context: &Context<Def, Alt>) -> GLApi {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => device.context_gl_api(context),
+            (Device::Alternate(device), Context::Alternate(context)) => {
+                device.context_gl_api(context)
+            }
+            _ => panic!("Incompatible context!"),
+        }
+    }
+
     /// Makes the context the current OpenGL context for this thread.
     ///
     /// After calling this function, it is valid to use OpenGL rendering commands.
@@ -280,6 +400,22 @@ where
         }
     }
 
+    /// Returns the framebuffer configuration that the context descriptor selected.
+    pub fn context_descriptor_config(
+        &self,
+        context_descriptor: &ContextDescriptor<Def, Alt>,
+    ) -> Result<ContextConfigInfo, Error> {
+        match (self, context_descriptor) {
+            (Device::Default(device), ContextDescriptor::Default(context_descriptor)) => {
+                device.context_descriptor_config(context_descriptor)
+            }
+            (Device::Alternate(device), ContextDescriptor::Alternate(context_descriptor)) => {
+                device.context_descriptor_config(context_descriptor)
+            }
+            _ => Err(Error::IncompatibleContextDescriptor),
+        }
+    }
+
     /// Fetches the address of an OpenGL function associated with this context.
     ///
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -332,4 +468,98 @@ where
             _ => Err(Error::IncompatibleContext),
         }
     }
+
+    /// Returns whether the context has been lost because of a GPU reset, and if so, whether it
+    /// was responsible for the reset.
+    ///
+    /// Only contexts created with the `ROBUST` flag are guaranteed to be told about resets.
+    pub fn context_reset_status(
+        &self,
+        context: &Context<Def, Alt>,
+    ) -> Result<ContextResetStatus, Error> {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => {
+                device.context_reset_status(context)
+            }
+            (Device::Alternate(device), Context::Alternate(context)) => {
+                device.context_reset_status(context)
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Returns the version, extensions, and limits of the given context.
+    pub fn context_capabilities(
+        &self,
+        context: &Context<Def, Alt>,
+    ) -> Result<ContextCapabilities, Error> {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => {
+                device.context_capabilities(context)
+            }
+            (Device::Alternate(device), Context::Alternate(context)) => {
+                device.context_capabilities(context)
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Inserts a fence into the command stream of the given context, and flushes the context.
+    pub fn create_fence(&self, context: &Context<Def, Alt>) -> Result<Fence<Def, Alt>, Error> {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => {
+                device.create_fence(context).map(Fence::Default)
+            }
+            (Device::Alternate(device), Context::Alternate(context)) => {
+                device.create_fence(context).map(Fence::Alternate)
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Makes the given context wait for a fence to be signaled before executing any further
+    /// commands.
+    pub fn wait_fence(
+        &self,
+        context: &Context<Def, Alt>,
+        fence: &Fence<Def, Alt>,
+    ) -> Result<(), Error> {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => match *fence {
+                Fence::Default(ref fence) => device.wait_fence(context, fence),
+                _ => Err(Error::IncompatibleFence),
+            },
+            (Device::Alternate(device), Context::Alternate(context)) => match *fence {
+                Fence::Alternate(ref fence) => device.wait_fence(context, fence),
+                _ => Err(Error::IncompatibleFence),
+            },
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Blocks the calling thread until a fence is signaled or the timeout elapses.
+    pub fn client_wait_fence(
+        &self,
+        fence: &Fence<Def, Alt>,
+        timeout: Duration,
+    ) -> Result<bool, Error> {
+        match (self, fence) {
+            (Device::Default(device), Fence::Default(fence)) => {
+                device.client_wait_fence(fence, timeout)
+            }
+            (Device::Alternate(device), Fence::Alternate(fence)) => {
+                device.client_wait_fence(fence, timeout)
+            }
+            _ => Err(Error::IncompatibleFence),
+        }
+    }
+
+    /// Destroys a fence.
+    pub fn destroy_fence(&self, fence: &mut Fence<Def, Alt>) -> Result<(), Error> {
+        match (self, fence) {
+            (Device::Default(device), Fence::Default(fence)) => device.destroy_fence(fence),
+            (Device::Alternate(device), Fence::Alternate(fence)) => device.destroy_fence(fence),
+            _ => Err(Error::IncompatibleFence),
+        }
+    }
 }
@@ -3,16 +3,21 @@
 //! A device abstraction that allows the choice of backends dynamically.
 
 use super::connection::Connection;
-use super::context::{Context, ContextDescriptor, NativeContext};
+use super::context::{Context, ContextDescriptor, Fence, NativeContext};
 use super::surface::{NativeWidget, Surface, SurfaceTexture};
 use crate::connection::Connection as ConnectionInterface;
-use crate::context::ContextAttributes;
+use crate::context::{ContextAttributes, ContextVersionRange};
 use crate::device::Device as DeviceInterface;
 use crate::gl::types::{GLenum, GLuint};
-use crate::{ContextID, Error, GLApi, SurfaceAccess, SurfaceInfo, SurfaceType};
-use euclid::default::Size2D;
+#[cfg(free_unix)]
+use crate::platform::unix::dmabuf::{DmaBufDescriptor, DmaBufDevice};
+use crate::{AdapterInfo, ContextCapabilities, ContextConfigInfo, ContextID, ContextResetStatus};
+use crate::{Error, GLApi, LiveResource};
+use crate::{PixelFormat, PresentMode, SurfaceAccess, SurfaceInfo, SurfaceType};
+use euclid::default::{Rect, Size2D};
 
 use std::os::raw::c_void;
+use std::time::Duration;
 
 /// Represents a hardware display adapter that can be used for rendering (including the CPU).
 ///
@@ -107,6 +112,25 @@ where
             Device::Alternate(ref device) => device.gl_api(),
         }
     }
+
+    /// Returns information about the adapter that this device renders with.
+    ///
+    /// This creates and destroys a temporary context in order to query the OpenGL strings.
+    pub fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        match *self {
+            Device::Default(ref mut device) => device.adapter_info(),
+            Device::Alternate(ref mut device) => device.adapter_info(),
+        }
+    }
+
+    /// Returns the contexts, surfaces, and surface textures that this device has created and
+    /// that have not yet been destroyed.
+    pub fn live_resources(&self) -> Vec<LiveResource> {
+        match *self {
+            Device::Default(ref device) => device.live_resources(),
+            Device::Alternate(ref device) => device.live_resources(),
+        }
+    }
 }
 
 impl<Def, Alt> DeviceInterface for Device<Def, Alt>
@@ -122,6 +146,7 @@ where
     type NativeContext = NativeContext<Def, Alt>;
     type Surface = Surface<Def, Alt>;
     type SurfaceTexture = SurfaceTexture<Def, Alt>;
+    type Fence = Fence<Def, Alt>;
 
     // device.rs
 
@@ -145,6 +170,16 @@ where
         Device::gl_api(self)
     }
 
+    #[inline]
+    fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        Device::adapter_info(self)
+    }
+
+    #[inline]
+    fn live_resources(&self) -> Vec<LiveResource> {
+        Device::live_resources(self)
+    }
+
     // context.rs
 
     #[inline]
@@ -155,6 +190,26 @@ where
         Device::create_context_descriptor(self, attributes)
     }
 
+    #[inline]
+    fn enumerate_context_descriptors(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<Vec<Self::ContextDescriptor>, Error> {
+        Device::enumerate_context_descriptors(self, attributes)
+    }
+
+    #[inline]
+    fn choose_context_descriptor<F>(
+        &self,
+        attributes: &ContextAttributes,
+        score: F,
+    ) -> Result<Self::ContextDescriptor, Error>
+    where
+        F: FnMut(&ContextConfigInfo) -> Option<u32>,
+    {
+        Device::choose_context_descriptor(self, attributes, score)
+    }
+
     #[inline]
     fn create_context(
         &mut self,
@@ -164,6 +219,15 @@ where
         Device::create_context(self, descriptor, share_with)
     }
 
+    #[inline]
+    fn create_context_with_fallbacks(
+        &mut self,
+        ranges: &[ContextVersionRange],
+        share_with: Option<&Context<Def, Alt>>,
+    ) -> Result<(Context<Def, Alt>, ContextAttributes), Error> {
+        Device::create_context_with_fallbacks(self, ranges, share_with)
+    }
+
     #[inline]
     unsafe fn create_context_from_native_context(
         &self,
@@ -187,6 +251,11 @@ where
         Device::context_descriptor(self, context)
     }
 
+    #[inline]
+    fn context_gl_api(&self, context: &Context<Def, Alt>) -> GLApi {
+        Device::context_gl_api(self, context)
+    }
+
     #[inline]
     fn make_context_current(&self, context: &Context<Def, Alt>) -> Result<(), Error> {
         Device::make_context_current(self, context)
@@ -205,6 +274,14 @@ where
         Device::context_descriptor_attributes(self, context_descriptor)
     }
 
+    #[inline]
+    fn context_descriptor_config(
+        &self,
+        context_descriptor: &ContextDescriptor<Def, Alt>,
+    ) -> Result<ContextConfigInfo, Error> {
+        Device::context_descriptor_config(self, context_descriptor)
+    }
+
     #[inline]
     fn get_proc_address(&self, context: &Context<Def, Alt>, symbol_name: &str) -> *const c_void {
         Device::get_proc_address(self, context, symbol_name)
@@ -240,6 +317,46 @@ where
         Device::context_surface_info(self, context)
     }
 
+    #[inline]
+    fn context_reset_status(
+        &self,
+        context: &Context<Def, Alt>,
+    ) -> Result<ContextResetStatus, Error> {
+        Device::context_reset_status(self, context)
+    }
+
+    #[inline]
+    fn context_capabilities(
+        &self,
+        context: &Context<Def, Alt>,
+    ) -> Result<ContextCapabilities, Error> {
+        Device::context_capabilities(self, context)
+    }
+
+    #[inline]
+    fn create_fence(&self, context: &Context<Def, Alt>) -> Result<Fence<Def, Alt>, Error> {
+        Device::create_fence(self, context)
+    }
+
+    #[inline]
+    fn wait_fence(
+        &self,
+        context: &Context<Def, Alt>,
+        fence: &Fence<Def, Alt>,
+    ) -> Result<(), Error> {
+        Device::wait_fence(self, context, fence)
+    }
+
+    #[inline]
+    fn client_wait_fence(&self, fence: &Fence<Def, Alt>, timeout: Duration) -> Result<bool, Error> {
+        Device::client_wait_fence(self, fence, timeout)
+    }
+
+    #[inline]
+    fn destroy_fence(&self, fence: &mut Fence<Def, Alt>) -> Result<(), Error> {
+        Device::destroy_fence(self, fence)
+    }
+
     // surface.rs
 
     #[inline]
@@ -293,6 +410,35 @@ where
         Device::present_surface(self, context, surface)
     }
 
+    #[inline]
+    fn present_surface_with_damage(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &mut Surface<Def, Alt>,
+        damage: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        Device::present_surface_with_damage(self, context, surface, damage)
+    }
+
+    #[inline]
+    fn surface_buffer_age(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &Surface<Def, Alt>,
+    ) -> Result<u32, Error> {
+        Device::surface_buffer_age(self, context, surface)
+    }
+
+    #[inline]
+    fn set_present_mode(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &Surface<Def, Alt>,
+        mode: PresentMode,
+    ) -> Result<(), Error> {
+        Device::set_present_mode(self, context, surface, mode)
+    }
+
     #[inline]
     fn resize_surface(
         &self,
@@ -308,8 +454,52 @@ where
         Device::surface_info(self, surface)
     }
 
+    #[inline]
+    fn read_surface_pixels(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &Surface<Def, Alt>,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        Device::read_surface_pixels(self, context, surface, rect, format)
+    }
+
     #[inline]
     fn surface_texture_object(&self, surface_texture: &SurfaceTexture<Def, Alt>) -> GLuint {
         Device::surface_texture_object(self, surface_texture)
     }
 }
+
+#[cfg(free_unix)]
+impl<Def, Alt> DmaBufDevice for Device<Def, Alt>
+where
+    Def: DmaBufDevice,
+    Alt: DmaBufDevice,
+    Def::Connection: ConnectionInterface<Device = Def>,
+    Alt::Connection: ConnectionInterface<Device = Alt>,
+{
+    #[inline]
+    fn export_surface_dmabuf(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &Surface<Def, Alt>,
+    ) -> Result<DmaBufDescriptor, Error> {
+        Device::export_surface_dmabuf(self, context, surface)
+    }
+
+    #[inline]
+    fn create_surface_from_dmabuf(
+        &mut self,
+        context: &Context<Def, Alt>,
+        descriptor: DmaBufDescriptor,
+        access: SurfaceAccess,
+    ) -> Result<Surface<Def, Alt>, Error> {
+        Device::create_surface_from_dmabuf(self, context, descriptor, access)
+    }
+
+    #[inline]
+    fn adapter_identity(&self) -> String {
+        Device::adapter_identity(self)
+    }
+}
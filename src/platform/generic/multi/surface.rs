@@ -7,8 +7,10 @@ use super::device::Device;
 use crate::connection::Connection as ConnectionInterface;
 use crate::device::Device as DeviceInterface;
 use crate::gl::types::{GLenum, GLuint};
-use crate::{Error, SurfaceAccess, SurfaceInfo, SurfaceType};
-use euclid::default::Size2D;
+#[cfg(free_unix)]
+use crate::platform::unix::dmabuf::{DmaBufDescriptor, DmaBufDevice};
+use crate::{Error, PixelFormat, PresentMode, SurfaceAccess, SurfaceInfo, SurfaceType};
+use euclid::default::{Rect, Size2D};
 
 use std::fmt::{self, Debug, Formatter};
 
@@ -109,7 +111,15 @@ where
         match (&mut *self, context) {
             (&mut Device::Default(ref mut device), Context::Default(context)) => {
                 let surface_type = match surface_type {
-                    SurfaceType::Generic { size } => SurfaceType::Generic { size },
+                    SurfaceType::Generic {
+                        size,
+                        samples,
+                        format,
+                    } => SurfaceType::Generic {
+                        size,
+                        samples,
+                        format,
+                    },
                     SurfaceType::Widget {
                         native_widget: NativeWidget::Default(native_widget),
                     } => SurfaceType::Widget { native_widget },
@@ -123,7 +133,15 @@ where
             }
             (&mut Device::Alternate(ref mut device), Context::Alternate(context)) => {
                 let surface_type = match surface_type {
-                    SurfaceType::Generic { size } => SurfaceType::Generic { size },
+                    SurfaceType::Generic {
+                        size,
+                        samples,
+                        format,
+                    } => SurfaceType::Generic {
+                        size,
+                        samples,
+                        format,
+                    },
                     SurfaceType::Widget {
                         native_widget: NativeWidget::Alternate(native_widget),
                     } => SurfaceType::Widget { native_widget },
@@ -273,6 +291,70 @@ where
         }
     }
 
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// changed since the last present.
+    pub fn present_surface_with_damage(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &mut Surface<Def, Alt>,
+        damage: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => match *surface {
+                Surface::Default(ref mut surface) => {
+                    device.present_surface_with_damage(context, surface, damage)
+                }
+                _ => Err(Error::IncompatibleSurface),
+            },
+            (Device::Alternate(device), Context::Alternate(context)) => match *surface {
+                Surface::Alternate(ref mut surface) => {
+                    device.present_surface_with_damage(context, surface, damage)
+                }
+                _ => Err(Error::IncompatibleSurface),
+            },
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Returns the age of the back buffer of a widget surface, or 0 if its contents are undefined.
+    pub fn surface_buffer_age(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &Surface<Def, Alt>,
+    ) -> Result<u32, Error> {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => match *surface {
+                Surface::Default(ref surface) => device.surface_buffer_age(context, surface),
+                _ => Err(Error::IncompatibleSurface),
+            },
+            (Device::Alternate(device), Context::Alternate(context)) => match *surface {
+                Surface::Alternate(ref surface) => device.surface_buffer_age(context, surface),
+                _ => Err(Error::IncompatibleSurface),
+            },
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Sets how `present_surface()` synchronizes with the display for the given widget surface.
+    pub fn set_present_mode(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &Surface<Def, Alt>,
+        mode: PresentMode,
+    ) -> Result<(), Error> {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => match *surface {
+                Surface::Default(ref surface) => device.set_present_mode(context, surface, mode),
+                _ => Err(Error::IncompatibleSurface),
+            },
+            (Device::Alternate(device), Context::Alternate(context)) => match *surface {
+                Surface::Alternate(ref surface) => device.set_present_mode(context, surface, mode),
+                _ => Err(Error::IncompatibleSurface),
+            },
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
     /// Resizes a widget surface.
     pub fn resize_surface(
         &self,
@@ -324,6 +406,31 @@ where
         }
     }
 
+    /// Reads back the pixels in the given rectangle of a surface, with row 0 at the top.
+    pub fn read_surface_pixels(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &Surface<Def, Alt>,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => match *surface {
+                Surface::Default(ref surface) => {
+                    device.read_surface_pixels(context, surface, rect, format)
+                }
+                _ => Err(Error::IncompatibleSurface),
+            },
+            (Device::Alternate(device), Context::Alternate(context)) => match *surface {
+                Surface::Alternate(ref surface) => {
+                    device.read_surface_pixels(context, surface, rect, format)
+                }
+                _ => Err(Error::IncompatibleSurface),
+            },
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
     /// Returns the OpenGL texture object containing the contents of this surface.
     ///
     /// It is only legal to read from, not write to, this texture object.
@@ -339,3 +446,59 @@ where
         }
     }
 }
+
+#[cfg(free_unix)]
+impl<Def, Alt> Device<Def, Alt>
+where
+    Def: DmaBufDevice,
+    Alt: DmaBufDevice,
+{
+    /// Exports a generic surface as a set of DMA-BUF file descriptors.
+    ///
+    /// The surface must not be bound to a context.
+    pub fn export_surface_dmabuf(
+        &self,
+        context: &Context<Def, Alt>,
+        surface: &Surface<Def, Alt>,
+    ) -> Result<DmaBufDescriptor, Error> {
+        match (self, context) {
+            (Device::Default(device), Context::Default(context)) => match *surface {
+                Surface::Default(ref surface) => device.export_surface_dmabuf(context, surface),
+                _ => Err(Error::IncompatibleSurface),
+            },
+            (Device::Alternate(device), Context::Alternate(context)) => match *surface {
+                Surface::Alternate(ref surface) => device.export_surface_dmabuf(context, surface),
+                _ => Err(Error::IncompatibleSurface),
+            },
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Creates a generic surface that renders into existing DMA-BUFs.
+    pub fn create_surface_from_dmabuf(
+        &mut self,
+        context: &Context<Def, Alt>,
+        descriptor: DmaBufDescriptor,
+        access: SurfaceAccess,
+    ) -> Result<Surface<Def, Alt>, Error> {
+        match (&mut *self, context) {
+            (&mut Device::Default(ref mut device), Context::Default(context)) => device
+                .create_surface_from_dmabuf(context, descriptor, access)
+                .map(Surface::Default),
+            (&mut Device::Alternate(ref mut device), Context::Alternate(context)) => device
+                .create_surface_from_dmabuf(context, descriptor, access)
+                .map(Surface::Alternate),
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Returns a string identifying the driver and GPU behind this device.
+    ///
+    /// Surfaces can only be shared between devices with the same identity.
+    pub fn adapter_identity(&self) -> String {
+        match *self {
+            Device::Default(ref device) => device.adapter_identity(),
+            Device::Alternate(ref device) => device.adapter_identity(),
+        }
+    }
+}
@@ -6,6 +6,7 @@
 //! global window server connection.
 
 use super::device::{Adapter, Device};
+use crate::connection::BackendKind;
 use crate::platform::macos::system::connection::Connection as SystemConnection;
 use crate::platform::macos::system::device::NativeDevice;
 use crate::platform::macos::system::surface::NativeWidget;
@@ -24,11 +25,35 @@ pub struct Connection(pub SystemConnection);
 
 impl Connection {
     /// Connects to the default display.
+    ///
+    /// Returns `ConnectionFailed` if the `SURFMAN_BACKEND` environment variable names other
+    /// backends but not this one.
     #[inline]
     pub fn new() -> Result<Connection, Error> {
+        BackendKind::CGL.check_preference_from_env()?;
+        Connection::connect()
+    }
+
+    fn connect() -> Result<Connection, Error> {
         SystemConnection::new().map(Connection)
     }
 
+    /// Connects to the default display if `BackendKind::CGL` is among the given backends, and
+    /// returns `UnsupportedOnThisPlatform` otherwise.
+    #[inline]
+    pub fn new_with_preference(preference: &[BackendKind]) -> Result<Connection, Error> {
+        if !preference.contains(&BackendKind::CGL) {
+            return Err(Error::UnsupportedOnThisPlatform);
+        }
+        Connection::connect()
+    }
+
+    /// Returns which backend this connection uses, which is always `BackendKind::CGL`.
+    #[inline]
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::CGL
+    }
+
     /// An alias for `Connection::new()`, present for consistency with other backends.
     #[inline]
     pub unsafe fn from_native_connection(
@@ -80,7 +105,9 @@ impl Connection {
     /// Device handles are local to a single thread.
     #[inline]
     pub fn create_device(&self, adapter: &Adapter) -> Result<Device, Error> {
-        self.0.create_device(&adapter.0).map(Device)
+        self.0
+            .create_device(&adapter.0)
+            .map(Device::from_system_device)
     }
 
     /// An alias for `connection.create_device()` with the default adapter.
@@ -91,7 +118,7 @@ impl Connection {
     ) -> Result<Device, Error> {
         self.0
             .create_device_from_native_device(native_device)
-            .map(Device)
+            .map(Device::from_system_device)
     }
 
     /// Opens the display connection corresponding to the given `RawDisplayHandle`.
@@ -6,10 +6,14 @@ use super::device::Device;
 use super::error::ToWindowingApiError;
 use super::ffi::{CGLReleaseContext, CGLRetainContext};
 use super::surface::Surface;
-use crate::context::{ContextID, CREATE_CONTEXT_MUTEX};
+use crate::context::CREATE_CONTEXT_MUTEX;
+use crate::context::{create_context_with_fallbacks, ContextID, ContextResetStatus};
 use crate::gl_utils;
+use crate::resources;
 use crate::surface::Framebuffer;
-use crate::{ContextAttributeFlags, ContextAttributes, Error, GLVersion, Gl, SurfaceInfo};
+use crate::trace;
+use crate::{ContextAttributeFlags, ContextAttributes, ContextCapabilities, Error, GLVersion};
+use crate::{ContextConfigInfo, ContextVersionRange, GLApi, Gl, SurfaceInfo};
 
 use cgl::{kCGLPFAAllowOfflineRenderers, kCGLPFAAlphaSize, kCGLPFADepthSize};
 use cgl::{kCGLPFAOpenGLProfile, kCGLPFAStencilSize};
@@ -28,6 +32,7 @@ use std::os::raw::c_void;
 use std::ptr;
 use std::str::FromStr;
 use std::thread;
+use std::time::Duration;
 
 // No CGL error occurred.
 #[allow(non_upper_case_globals)]
@@ -92,7 +97,13 @@ impl Drop for Context {
     #[inline]
     fn drop(&mut self) {
         if !self.cgl_context.is_null() && !thread::panicking() {
-            panic!("Contexts must be destroyed explicitly with `destroy_context`!")
+            panic!(
+                "{}",
+                resources::leaked_context_message(
+                    "Contexts must be destroyed explicitly with `destroy_context`!",
+                    self.id,
+                )
+            )
         }
     }
 }
@@ -127,6 +138,14 @@ impl Clone for ContextDescriptor {
 
 unsafe impl Send for ContextDescriptor {}
 
+/// A fence in the command stream of a context.
+///
+/// This backend has no shareable fence objects, so fences are signaled as soon as they are
+/// created.
+pub struct Fence {
+    _private: (),
+}
+
 impl Device {
     /// Creates a context descriptor with the given attributes.
     ///
@@ -135,6 +154,13 @@ impl Device {
         &self,
         attributes: &ContextAttributes,
     ) -> Result<ContextDescriptor, Error> {
+        if attributes.flags.contains(ContextAttributeFlags::GLES) {
+            return Err(Error::UnsupportedGLType);
+        }
+        if attributes.flags.contains(ContextAttributeFlags::ROBUST) {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
         if attributes
             .flags
             .contains(ContextAttributeFlags::COMPATIBILITY_PROFILE)
@@ -197,7 +223,7 @@ impl Device {
             );
             if err != kCGLNoError {
                 return Err(Error::PixelFormatSelectionFailed(
-                    err.to_windowing_api_error(),
+                    err.to_native_error("CGLChoosePixelFormat"),
                 ));
             }
             if cgl_pixel_format_count == 0 {
@@ -208,10 +234,51 @@ impl Device {
         }
     }
 
+    /// Returns the context descriptors that satisfy the given attributes.
+    ///
+    /// CGL picks the pixel format itself, so there is at most one candidate: the one that
+    /// `create_context_descriptor()` returns.
+    #[inline]
+    pub fn enumerate_context_descriptors(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<Vec<ContextDescriptor>, Error> {
+        Ok(vec![self.create_context_descriptor(attributes)?])
+    }
+
+    /// Creates a context descriptor with the given attributes, using the pixel format that the
+    /// given function scores highest.
+    ///
+    /// Pixel formats can't be inspected on CGL, so this always returns
+    /// `UnsupportedOnThisPlatform`.
+    #[inline]
+    pub fn choose_context_descriptor<F>(
+        &self,
+        _: &ContextAttributes,
+        _: F,
+    ) -> Result<ContextDescriptor, Error>
+    where
+        F: FnMut(&ContextConfigInfo) -> Option<u32>,
+    {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
     /// Creates a new OpenGL context.
     ///
     /// The context initially has no surface attached. Until a surface is bound to it, rendering
     /// commands will fail or have no effect.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                share_with = ?share_with.map(|context| self.context_id(context).0),
+                context_id = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_context(
         &mut self,
         descriptor: &ContextDescriptor,
@@ -231,7 +298,9 @@ impl Device {
                 &mut cgl_context,
             );
             if err != kCGLNoError {
-                return Err(Error::ContextCreationFailed(err.to_windowing_api_error()));
+                return Err(Error::ContextCreationFailed(
+                    err.to_native_error("CGLCreateContext"),
+                ));
             }
             debug_assert_ne!(cgl_context, ptr::null_mut());
 
@@ -241,11 +310,27 @@ impl Device {
                 id: *next_context_id,
                 framebuffer: Framebuffer::None,
             };
+            trace::record_context(context.id);
+            self.1.track_context(context.id);
             next_context_id.0 += 1;
             Ok(context)
         }
     }
 
+    /// Creates a new OpenGL context with the first version that the driver supports, trying each
+    /// range in turn from its highest version to its lowest.
+    ///
+    /// Returns the context along with the attributes that it was created with. If no version
+    /// works, this returns the error from the last attempt.
+    #[inline]
+    pub fn create_context_with_fallbacks(
+        &mut self,
+        ranges: &[ContextVersionRange],
+        share_with: Option<&Context>,
+    ) -> Result<(Context, ContextAttributes), Error> {
+        create_context_with_fallbacks(self, ranges, share_with)
+    }
+
     /// Wraps a `CGLContext` in a `surfman` context and returns it.
     ///
     /// This function takes ownership of the native context and does not adjust its reference
@@ -260,6 +345,7 @@ impl Device {
             id: *next_context_id,
             framebuffer: Framebuffer::None,
         };
+        self.1.track_context(context.id);
         next_context_id.0 += 1;
         mem::forget(native_context);
         Ok(context)
@@ -268,6 +354,15 @@ impl Device {
     /// Destroys a context.
     ///
     /// The context must have been created on this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_context(&self, context: &mut Context) -> Result<(), Error> {
         if context.cgl_context.is_null() {
             return Ok(());
@@ -285,6 +380,7 @@ impl Device {
             context.cgl_context = ptr::null_mut();
         }
 
+        self.1.untrack_context(context.id);
         Ok(())
     }
 
@@ -298,14 +394,31 @@ impl Device {
         }
     }
 
+    /// Returns the OpenGL API flavor (OpenGL or OpenGL ES) that the given context uses.
+    #[inline]
+    pub fn context_gl_api(&self, _: &Context) -> GLApi {
+        self.gl_api()
+    }
+
     /// Makes the context the current OpenGL context for this thread.
     ///
     /// After calling this function, it is valid to use OpenGL rendering commands.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn make_context_current(&self, context: &Context) -> Result<(), Error> {
         unsafe {
             let err = CGLSetCurrentContext(context.cgl_context);
             if err != kCGLNoError {
-                return Err(Error::MakeCurrentFailed(err.to_windowing_api_error()));
+                return Err(Error::MakeCurrentFailed(
+                    err.to_native_error("CGLSetCurrentContext"),
+                ));
             }
             Ok(())
         }
@@ -315,11 +428,17 @@ impl Device {
     ///
     /// After calling this function, OpenGL rendering commands will fail until a new context is
     /// made current.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, err(Debug, level = "debug"))
+    )]
     pub fn make_no_context_current(&self) -> Result<(), Error> {
         unsafe {
             let err = CGLSetCurrentContext(ptr::null_mut());
             if err != kCGLNoError {
-                return Err(Error::MakeCurrentFailed(err.to_windowing_api_error()));
+                return Err(Error::MakeCurrentFailed(
+                    err.to_native_error("CGLSetCurrentContext"),
+                ));
             }
             Ok(())
         }
@@ -344,6 +463,17 @@ impl Device {
     /// `unbind_surface_from_context`.
     ///
     /// If an error is returned, the surface is returned alongside it.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&new_surface).id.0
+            )
+        )
+    )]
     pub fn bind_surface_to_context(
         &self,
         context: &mut Context,
@@ -367,6 +497,15 @@ impl Device {
     ///
     /// Any pending OpenGL commands targeting this surface will be automatically flushed, so the
     /// surface is safe to read from immediately when this function returns.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn unbind_surface_from_context(
         &self,
         context: &mut Context,
@@ -445,6 +584,18 @@ impl Device {
         }
     }
 
+    /// Returns the framebuffer configuration that the context descriptor selected.
+    ///
+    /// CGL pixel formats don't report their channels separately, so this always returns
+    /// `UnsupportedOnThisPlatform`.
+    #[inline]
+    pub fn context_descriptor_config(
+        &self,
+        _: &ContextDescriptor,
+    ) -> Result<ContextConfigInfo, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
     /// Fetches the address of an OpenGL function associated with this context.
     ///
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -468,6 +619,54 @@ impl Device {
         }
     }
 
+    /// Returns whether the context has been lost because of a GPU reset.
+    ///
+    /// CGL has no robustness support, so this always returns an `UnsupportedOnThisPlatform`
+    /// error.
+    #[inline]
+    pub fn context_reset_status(&self, _: &Context) -> Result<ContextResetStatus, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Returns the version, extensions, and limits of the given context.
+    pub fn context_capabilities(&self, context: &Context) -> Result<ContextCapabilities, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(ContextCapabilities::current)
+    }
+
+    /// Waits for all commands previously issued to the given context to finish, and returns a
+    /// fence that is already signaled.
+    ///
+    /// This backend has no shareable fence objects, so creating a fence waits for the context's
+    /// commands to finish.
+    pub fn create_fence(&self, context: &Context) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| unsafe { gl.Finish() });
+        Ok(Fence { _private: () })
+    }
+
+    /// Makes the given context wait for a fence to be signaled.
+    ///
+    /// Fences on this backend are signaled on creation, so this does nothing.
+    #[inline]
+    pub fn wait_fence(&self, _: &Context, _: &Fence) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Blocks the calling thread until a fence is signaled or the timeout elapses.
+    ///
+    /// Fences on this backend are signaled on creation, so this always returns true.
+    #[inline]
+    pub fn client_wait_fence(&self, _: &Fence, _: Duration) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    /// Destroys a fence.
+    #[inline]
+    pub fn destroy_fence(&self, _: &mut Fence) -> Result<(), Error> {
+        Ok(())
+    }
+
     /// Returns a unique ID representing a context.
     ///
     /// This ID is unique to all currently-allocated contexts. If you destroy a context and create
@@ -508,7 +707,7 @@ impl Drop for CurrentContextGuard {
 }
 
 impl CurrentContextGuard {
-    fn new() -> CurrentContextGuard {
+    pub(crate) fn new() -> CurrentContextGuard {
         unsafe {
             CurrentContextGuard {
                 old_cgl_context: CGLGetCurrentContext(),
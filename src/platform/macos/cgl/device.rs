@@ -3,8 +3,14 @@
 //! A handle to the device. (This is a no-op, because handles are implicit in Apple's Core OpenGL.)
 
 use super::connection::Connection;
+use super::context::CurrentContextGuard;
+use super::context::GL_FUNCTIONS;
 use crate::platform::macos::system::device::{Adapter as SystemAdapter, Device as SystemDevice};
-use crate::GLApi;
+use crate::resources::ResourceTracker;
+use crate::LiveResource;
+use crate::{AdapterInfo, ContextAttributeFlags, ContextAttributes, Error, GLApi, GLVersion};
+
+use std::sync::Arc;
 
 pub use crate::platform::macos::system::device::NativeDevice;
 
@@ -18,9 +24,14 @@ pub struct Adapter(pub(crate) SystemAdapter);
 ///
 /// Devices contain most of the relevant surface management methods.
 #[derive(Clone)]
-pub struct Device(pub(crate) SystemDevice);
+pub struct Device(pub(crate) SystemDevice, pub(crate) Arc<ResourceTracker>);
 
 impl Device {
+    #[inline]
+    pub(crate) fn from_system_device(system_device: SystemDevice) -> Device {
+        Device(system_device, Arc::new(ResourceTracker::new()))
+    }
+
     /// Returns the native device corresponding to this device.
     #[inline]
     pub fn native_device(&self) -> NativeDevice {
@@ -44,4 +55,33 @@ impl Device {
     pub fn gl_api(&self) -> GLApi {
         GLApi::GL
     }
+
+    /// Returns information about the adapter that this device renders with.
+    ///
+    /// This creates and destroys a temporary context in order to query the OpenGL strings.
+    pub fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        let context_descriptor = self.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(2, 0),
+            flags: ContextAttributeFlags::empty(),
+        })?;
+
+        // Destroying the temporary context leaves no context current, so restore the caller's
+        // context afterward.
+        let _guard = CurrentContextGuard::new();
+        let mut context = self.create_context(&context_descriptor, None)?;
+        let adapter_info = self
+            .make_context_current(&context)
+            .map(|()| GL_FUNCTIONS.with(|gl| AdapterInfo::current(gl, None, false)));
+        self.destroy_context(&mut context)?;
+        adapter_info
+    }
+
+    /// Returns the contexts, surfaces, and surface textures that this device has created and
+    /// that have not yet been destroyed.
+    ///
+    /// Resources are only tracked in debug builds; in release builds this is always empty.
+    #[inline]
+    pub fn live_resources(&self) -> Vec<LiveResource> {
+        self.1.live_resources()
+    }
 }
@@ -2,11 +2,12 @@
 //
 //! Translation of errors from the CGL API to `surfman` errors.
 
-use crate::WindowingApiError;
+use crate::{NativeError, WindowingApiError};
 use cgl::CGLError;
 
 pub(crate) trait ToWindowingApiError {
     fn to_windowing_api_error(self) -> WindowingApiError;
+    fn to_native_error(self, call: &'static str) -> NativeError;
 }
 
 impl ToWindowingApiError for CGLError {
@@ -33,4 +34,8 @@ impl ToWindowingApiError for CGLError {
             _ => WindowingApiError::Failed,
         }
     }
+
+    fn to_native_error(self, call: &'static str) -> NativeError {
+        NativeError::new(self.to_windowing_api_error(), call).with_code(self)
+    }
 }
@@ -9,10 +9,14 @@ use crate::gl::types::{GLenum, GLint, GLuint};
 use crate::gl_utils;
 use crate::platform::macos::system::surface::Surface as SystemSurface;
 use crate::renderbuffers::Renderbuffers;
-use crate::{gl, Error, SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType, WindowingApiError};
+use crate::trace;
+use crate::{
+    gl, Error, NativeError, PixelFormat, PresentMode, SurfaceAccess, SurfaceFormat, SurfaceID,
+    SurfaceInfo, SurfaceType, WindowingApiError,
+};
 
 use core_foundation::base::TCFType;
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
 use io_surface::{self, IOSurface};
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
@@ -79,6 +83,20 @@ impl Device {
     ///
     /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
     /// up in a `SurfaceTexture` for reading by other contexts.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = tracing::field::Empty,
+                width = tracing::field::Empty,
+                height = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_surface(
         &mut self,
         context: &Context,
@@ -128,16 +146,23 @@ impl Device {
                     }
                     let _ = self.0.destroy_surface(&mut system_surface);
                     // TODO: convert the GL error into a surfman error?
-                    return Err(Error::SurfaceCreationFailed(WindowingApiError::Failed));
+                    return Err(Error::SurfaceCreationFailed(NativeError::new(
+                        WindowingApiError::Failed,
+                        "glCheckFramebufferStatus",
+                    )));
                 }
 
-                Ok(Surface {
+                let surface = Surface {
                     system_surface,
                     context_id: context.id,
                     framebuffer_object,
                     texture_object,
                     renderbuffers,
-                })
+                };
+                let surface_info = self.surface_info(&surface);
+                trace::record_surface(&surface_info);
+                self.1.track_surface(&surface_info);
+                Ok(surface)
             }
         })
     }
@@ -152,6 +177,17 @@ impl Device {
     /// in another context.
     ///
     /// Calling this method on a widget surface returns a `WidgetAttached` error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
     pub fn create_surface_texture(
         &self,
         context: &mut Context,
@@ -167,6 +203,8 @@ impl Device {
             &surface.system_surface.io_surface,
             &surface.system_surface.size,
         );
+        self.1
+            .track_surface_texture(context.id, &self.surface_info(&surface));
         Ok(SurfaceTexture {
             surface,
             texture_object,
@@ -219,11 +257,24 @@ impl Device {
     ///
     /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
     /// the `drop` method.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_surface(
         &self,
         context: &mut Context,
         surface: &mut Surface,
     ) -> Result<(), Error> {
+        let surface_id = self.surface_info(surface).id;
         GL_FUNCTIONS.with(|gl| {
             if context.id != surface.context_id {
                 return Err(Error::IncompatibleSurface);
@@ -238,7 +289,9 @@ impl Device {
                 surface.texture_object = 0;
             }
 
-            self.0.destroy_surface(&mut surface.system_surface)
+            self.0.destroy_surface(&mut surface.system_surface)?;
+            self.1.untrack_surface(surface_id);
+            Ok(())
         })
     }
 
@@ -260,6 +313,8 @@ impl Device {
                 surface_texture.texture_object = 0;
             }
 
+            self.1
+                .untrack_surface_texture(self.surface_info(&surface_texture.surface).id);
             Ok(surface_texture.surface)
         })
     }
@@ -287,8 +342,21 @@ impl Device {
     ///
     /// The supplied context must match the context the surface was created with, or an
     /// `IncompatibleSurface` error is returned.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(surface_id = self.surface_info(surface).id.0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn present_surface(&self, _: &Context, surface: &mut Surface) -> Result<(), Error> {
+        // Presenting swaps the front and back `IOSurface`s, which changes the surface ID.
+        let surface_id = self.surface_info(surface).id;
         self.0.present_surface(&mut surface.system_surface)?;
+        self.1
+            .update_surface(surface_id, &self.surface_info(surface));
 
         GL_FUNCTIONS.with(|gl| {
             unsafe {
@@ -305,6 +373,62 @@ impl Device {
         })
     }
 
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// changed since the last present.
+    ///
+    /// This backend doesn't support partial presentation, so the whole surface is presented.
+    pub fn present_surface_with_damage(
+        &self,
+        context: &Context,
+        surface: &mut Surface,
+        _: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        self.present_surface(context, surface)
+    }
+
+    /// Reads back the pixels in the given rectangle of a surface.
+    ///
+    /// The rectangle has its origin at the top left of the surface, and the returned rows are
+    /// ordered from top to bottom. The surface must not be bound to a context.
+    pub fn read_surface_pixels(
+        &self,
+        context: &Context,
+        surface: &Surface,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            gl_utils::read_framebuffer_pixels(
+                gl,
+                surface.framebuffer_object,
+                surface.system_surface.size,
+                rect,
+                format,
+            )
+        })
+    }
+
+    /// Returns the age of the back buffer of a widget surface.
+    ///
+    /// This backend can't query the buffer age, so this always returns 0, meaning that the
+    /// contents of the back buffer are undefined.
+    pub fn surface_buffer_age(&self, _: &Context, _: &Surface) -> Result<u32, Error> {
+        Ok(0)
+    }
+
+    /// Sets how `present_surface()` synchronizes with the display for the given widget surface.
+    ///
+    /// This is currently unsupported on this backend, so it always returns
+    /// `UnsupportedOnThisPlatform`.
+    pub fn set_present_mode(&self, _: &Context, _: &Surface, _: PresentMode) -> Result<(), Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
     /// Resizes a widget surface.
     pub fn resize_surface(
         &self,
@@ -319,7 +443,10 @@ impl Device {
         let _guard = self.temporarily_make_context_current(context);
         let _guard = self.temporarily_bind_framebuffer(surface.framebuffer_object);
 
+        let surface_id = self.surface_info(surface).id;
         self.0.resize_surface(&mut surface.system_surface, size)?;
+        self.1
+            .update_surface(surface_id, &self.surface_info(surface));
 
         let context_descriptor = self.context_descriptor(context);
         let context_attributes = self.context_descriptor_attributes(&context_descriptor);
@@ -384,6 +511,7 @@ impl Device {
             id: system_surface_info.id,
             context_id: surface.context_id,
             framebuffer_object: surface.framebuffer_object,
+            format: SurfaceFormat::RGBA8,
         }
     }
 
@@ -6,7 +6,8 @@ use super::device::Device;
 use super::ffi::{kCVPixelFormatType_32BGRA, kIOMapDefaultCache, IOSurfaceLock, IOSurfaceUnlock};
 use super::ffi::{kCVReturnSuccess, kIOMapWriteCombineCache};
 use super::ffi::{IOSurfaceGetAllocSize, IOSurfaceGetBaseAddress, IOSurfaceGetBytesPerRow};
-use crate::{Error, SurfaceAccess, SurfaceID, SurfaceType, SystemSurfaceInfo};
+use crate::resources;
+use crate::{Error, SurfaceAccess, SurfaceFormat, SurfaceID, SurfaceType, SystemSurfaceInfo};
 
 use cocoa::appkit::{NSScreen, NSView as NSViewMethods, NSWindow};
 use cocoa::base::{id, YES};
@@ -65,7 +66,13 @@ impl Debug for Surface {
 impl Drop for Surface {
     fn drop(&mut self) {
         if !self.destroyed && !thread::panicking() {
-            panic!("Should have destroyed the surface first with `destroy_surface()`!")
+            panic!(
+                "{}",
+                resources::leaked_surface_message(
+                    "Should have destroyed the surface first with `destroy_surface()`!",
+                    self.id(),
+                )
+            )
         }
     }
 }
@@ -116,7 +123,14 @@ impl Device {
     ) -> Result<Surface, Error> {
         unsafe {
             let size = match surface_type {
-                SurfaceType::Generic { size } => size,
+                // Multisampled and non-RGBA8 generic surfaces are only supported on the Linux EGL
+                // backends.
+                SurfaceType::Generic {
+                    samples, format, ..
+                } if samples > 1 || format != SurfaceFormat::RGBA8 => {
+                    return Err(Error::UnsupportedOnThisPlatform)
+                }
+                SurfaceType::Generic { size, .. } => size,
                 SurfaceType::Widget { ref native_widget } => {
                     let window: id = msg_send![native_widget.view.0, window];
                     let bounds = window.convertRectToBacking(native_widget.view.0.bounds());
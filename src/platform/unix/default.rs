@@ -23,6 +23,7 @@ pub mod connection {
 pub mod context {
     use crate::platform::generic::multi::context::Context as MultiContext;
     use crate::platform::generic::multi::context::ContextDescriptor as MultiContextDescriptor;
+    use crate::platform::generic::multi::context::Fence as MultiFence;
     use crate::platform::generic::multi::context::NativeContext as MultiNativeContext;
     use crate::platform::generic::multi::device::Device as MultiDevice;
     use crate::platform::unix::generic::device::Device as SWDevice;
@@ -56,6 +57,11 @@ pub mod context {
 
     /// Either a Wayland or an X11 native context
     pub type NativeContext = MultiNativeContext<HWDevice, SWDevice>;
+
+    /// A fence in the command stream of a context, which can be waited on by other contexts.
+    ///
+    /// Fences must be explicitly destroyed with `destroy_fence()`.
+    pub type Fence = MultiFence<HWDevice, SWDevice>;
 }
 
 /// Thread-local handles to devices.
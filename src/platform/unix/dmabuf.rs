@@ -0,0 +1,102 @@
+// surfman/surfman/src/platform/unix/dmabuf.rs
+//
+//! Descriptions of surfaces shared as Linux DMA-BUFs.
+
+use crate::device::Device as DeviceInterface;
+use crate::{Error, SurfaceAccess, SurfaceFormat};
+
+use euclid::default::Size2D;
+use std::os::unix::io::OwnedFd;
+
+// DRM fourcc codes, from `drm_fourcc.h`. The names give the order of the channels within a
+// little-endian word, so `ABGR8888` is RGBA in memory.
+const DRM_FORMAT_ARGB8888: u32 = fourcc(b"AR24");
+const DRM_FORMAT_XRGB8888: u32 = fourcc(b"XR24");
+const DRM_FORMAT_ABGR8888: u32 = fourcc(b"AB24");
+const DRM_FORMAT_XBGR8888: u32 = fourcc(b"XB24");
+const DRM_FORMAT_ABGR2101010: u32 = fourcc(b"AB30");
+const DRM_FORMAT_XBGR2101010: u32 = fourcc(b"XB30");
+const DRM_FORMAT_ABGR16161616F: u32 = fourcc(b"AB4H");
+const DRM_FORMAT_XBGR16161616F: u32 = fourcc(b"XB4H");
+
+/// The DRM format modifier that indicates that the modifier of a buffer is unknown, and must be
+/// inferred by the driver.
+pub const DRM_FORMAT_MOD_INVALID: u64 = 0x00ff_ffff_ffff_ffff;
+
+/// A surface exported as a set of DMA-BUF file descriptors, one per plane.
+///
+/// This can be handed to any API that accepts DMA-BUFs, such as video encoders, the Wayland
+/// `linux-dmabuf` protocol, or another process. The file descriptors are closed when the
+/// descriptor is dropped.
+#[derive(Debug)]
+pub struct DmaBufDescriptor {
+    /// The size of the buffer, in pixels.
+    pub size: Size2D<i32>,
+    /// The DRM fourcc code of the pixel format, as defined in `drm_fourcc.h`.
+    pub fourcc: u32,
+    /// The DRM format modifier describing the memory layout of the buffer.
+    ///
+    /// This is `DRM_FORMAT_MOD_INVALID` if the driver didn't report one.
+    pub modifier: u64,
+    /// The planes of the buffer.
+    pub planes: Vec<DmaBufPlane>,
+}
+
+/// A single plane of a DMA-BUF.
+#[derive(Debug)]
+pub struct DmaBufPlane {
+    /// The DMA-BUF file descriptor containing the plane.
+    ///
+    /// Several planes may refer to the same buffer through different file descriptors.
+    pub fd: OwnedFd,
+    /// The offset of the plane within the buffer, in bytes.
+    pub offset: u32,
+    /// The number of bytes per row of the plane.
+    pub stride: u32,
+}
+
+/// Devices that can share generic surfaces as DMA-BUFs.
+///
+/// This is implemented by all the Linux backends, and lets code that is generic over devices,
+/// such as the `ipc` module, export and import surfaces.
+pub trait DmaBufDevice: DeviceInterface {
+    /// Exports a generic surface as a set of DMA-BUF file descriptors.
+    ///
+    /// The surface must not be bound to a context.
+    fn export_surface_dmabuf(
+        &self,
+        context: &Self::Context,
+        surface: &Self::Surface,
+    ) -> Result<DmaBufDescriptor, Error>;
+
+    /// Creates a generic surface that renders into existing DMA-BUFs.
+    fn create_surface_from_dmabuf(
+        &mut self,
+        context: &Self::Context,
+        descriptor: DmaBufDescriptor,
+        access: SurfaceAccess,
+    ) -> Result<Self::Surface, Error>;
+
+    /// Returns a string identifying the driver and GPU behind this device.
+    ///
+    /// Surfaces can only be shared between devices with the same identity.
+    fn adapter_identity(&self) -> String;
+}
+
+impl DmaBufDescriptor {
+    // Returns the surface format that a buffer with this layout is sampled and rendered as, or
+    // `None` if it can't be used as the color buffer of a surface.
+    pub(crate) fn surface_format(&self) -> Option<SurfaceFormat> {
+        match self.fourcc {
+            DRM_FORMAT_ARGB8888 | DRM_FORMAT_XRGB8888 | DRM_FORMAT_ABGR8888
+            | DRM_FORMAT_XBGR8888 => Some(SurfaceFormat::RGBA8),
+            DRM_FORMAT_ABGR2101010 | DRM_FORMAT_XBGR2101010 => Some(SurfaceFormat::RGB10_A2),
+            DRM_FORMAT_ABGR16161616F | DRM_FORMAT_XBGR16161616F => Some(SurfaceFormat::RGBA16F),
+            _ => None,
+        }
+    }
+}
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
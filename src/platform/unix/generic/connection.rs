@@ -4,6 +4,7 @@
 
 use super::device::{Adapter, Device, NativeDevice};
 use super::surface::NativeWidget;
+use crate::connection::BackendKind;
 use crate::egl;
 use crate::egl::types::{EGLAttrib, EGLDisplay};
 use crate::info::GLApi;
@@ -36,8 +37,16 @@ unsafe impl Sync for NativeConnectionWrapper {}
 
 impl Connection {
     /// Opens a surfaceless Mesa display.
+    ///
+    /// Returns `ConnectionFailed` if the `SURFMAN_BACKEND` environment variable names other
+    /// backends but not this one.
     #[inline]
     pub fn new() -> Result<Connection, Error> {
+        BackendKind::Surfaceless.check_preference_from_env()?;
+        Connection::connect()
+    }
+
+    fn connect() -> Result<Connection, Error> {
         unsafe {
             EGL_FUNCTIONS.with(|egl| {
                 let egl_display_attributes = [egl::NONE as EGLAttrib];
@@ -65,6 +74,22 @@ impl Connection {
         }
     }
 
+    /// Connects to the default display if `BackendKind::Surfaceless` is among the given backends, and
+    /// returns `UnsupportedOnThisPlatform` otherwise.
+    #[inline]
+    pub fn new_with_preference(preference: &[BackendKind]) -> Result<Connection, Error> {
+        if !preference.contains(&BackendKind::Surfaceless) {
+            return Err(Error::UnsupportedOnThisPlatform);
+        }
+        Connection::connect()
+    }
+
+    /// Returns which backend this connection uses, which is always `BackendKind::Surfaceless`.
+    #[inline]
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::Surfaceless
+    }
+
     /// An alias for `Connection::new()`, present for consistency with other backends.
     #[inline]
     pub unsafe fn from_native_connection(
@@ -82,6 +107,10 @@ impl Connection {
     }
 
     /// Returns the OpenGL API flavor that this connection supports (OpenGL or OpenGL ES).
+    ///
+    /// This is the API used by default. OpenGL ES contexts can be requested with the
+    /// `ContextAttributeFlags::GLES` flag, and `context_descriptor_attributes()` reports which API
+    /// a context actually uses.
     #[inline]
     pub fn gl_api(&self) -> GLApi {
         GLApi::GL
@@ -97,24 +126,63 @@ impl Connection {
 
     /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
     ///
-    /// On the OSMesa backend, this returns a software adapter.
+    /// On systems with several GPUs, this prefers one that the firmware didn't boot with.
     #[inline]
     pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::hardware())
+        self.find_hardware_adapter(|adapter| !adapter.is_boot_vga())
     }
 
     /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
     ///
-    /// On the OSMesa backend, this returns a software adapter.
+    /// On systems with several GPUs, this prefers the one that the firmware booted with.
     #[inline]
     pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::low_power())
+        self.find_hardware_adapter(Adapter::is_boot_vga)
     }
 
-    /// Returns the "best" adapter on this system, preferring software adapters.
+    /// Returns an adapter that renders in software.
+    ///
+    /// This returns `NoAdapterFound` if no EGL device renders in software, or if devices can't be
+    /// enumerated.
     #[inline]
     pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::software())
+        self.enumerate_adapters_if_supported()?
+            .into_iter()
+            .find(Adapter::is_software)
+            .ok_or(Error::NoAdapterFound)
+    }
+
+    /// Returns all the adapters on this system.
+    ///
+    /// This requires the `EGL_EXT_device_enumeration` and `EGL_EXT_device_query` extensions;
+    /// `RequiredExtensionUnavailable` is returned if they aren't supported.
+    pub fn enumerate_adapters(&self) -> Result<Vec<Adapter>, Error> {
+        unsafe { Adapter::enumerate() }
+    }
+
+    // Picks the first hardware adapter matching `preferred`, then any hardware adapter. If there
+    // are none, or devices can't be enumerated, falls back to the device behind this connection.
+    fn find_hardware_adapter<F>(&self, preferred: F) -> Result<Adapter, Error>
+    where
+        F: Fn(&Adapter) -> bool,
+    {
+        let adapters: Vec<Adapter> = self
+            .enumerate_adapters_if_supported()?
+            .into_iter()
+            .filter(|adapter| !adapter.is_software())
+            .collect();
+        let adapter = adapters
+            .iter()
+            .find(|adapter| preferred(adapter))
+            .or_else(|| adapters.first());
+        Ok(adapter.cloned().unwrap_or_else(Adapter::display_default))
+    }
+
+    fn enumerate_adapters_if_supported(&self) -> Result<Vec<Adapter>, Error> {
+        match self.enumerate_adapters() {
+            Err(Error::RequiredExtensionUnavailable) => Ok(vec![]),
+            result => result,
+        }
     }
 
     /// Opens the hardware device corresponding to the given adapter.
@@ -125,13 +193,14 @@ impl Connection {
         Device::new(self, adapter)
     }
 
-    /// An alias for `connection.create_device()` with the default adapter.
+    /// Opens the hardware device corresponding to the adapter wrapped in the given native
+    /// device.
     #[inline]
     pub unsafe fn create_device_from_native_device(
         &self,
-        _: NativeDevice,
+        native_device: NativeDevice,
     ) -> Result<Device, Error> {
-        Device::new(self, &self.create_adapter()?)
+        Device::new(self, &native_device.adapter)
     }
 
     /// Opens the display connection corresponding to the given `RawDisplayHandle`.
@@ -3,63 +3,218 @@
 //! A wrapper around surfaceless Mesa `EGLDisplay`s.
 
 use super::connection::{Connection, NativeConnectionWrapper};
-use crate::{Error, GLApi};
+use super::context::GL_FUNCTIONS;
+use crate::egl;
+use crate::egl::types::{EGLAttrib, EGLDeviceEXT, EGLDisplay, EGLenum, EGLint};
+use crate::platform::generic::egl::context::CurrentContextGuard;
+use crate::platform::generic::egl::device::{self, EGL_FUNCTIONS};
+use crate::platform::generic::egl::ffi::{EGL_DRIVER_NAME_EXT, EGL_DRM_DEVICE_FILE_EXT};
+use crate::platform::generic::egl::ffi::{EGL_DRM_RENDER_NODE_FILE_EXT, EGL_EXTENSION_FUNCTIONS};
+use crate::platform::generic::egl::ffi::{EGL_NO_DEVICE_EXT, EGL_PLATFORM_DEVICE_EXT};
+use crate::resources::ResourceTracker;
+use crate::LiveResource;
+use crate::{AdapterInfo, ContextAttributeFlags, ContextAttributes, Error, GLApi, GLVersion};
 
-use std::env;
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::ptr;
 use std::sync::Arc;
 
-static MESA_SOFTWARE_RENDERING_ENV_VAR: &'static str = "LIBGL_ALWAYS_SOFTWARE";
-static MESA_DRI_PRIME_ENV_VAR: &'static str = "DRI_PRIME";
-
 /// Represents a hardware display adapter that can be used for rendering (including the CPU).
 ///
+/// On the surfaceless backend, adapters correspond to the EGL devices reported by
+/// `EGL_EXT_device_enumeration`, and opening a device renders on exactly that GPU. On X11 and
+/// Wayland, rendering always happens on the GPU that the display server connection uses, and
+/// creating a device with any other adapter fails with `IncompatibleAdapter`.
+///
 /// Adapters can be sent between threads. To render with an adapter, open a thread-local `Device`.
 #[derive(Clone, Debug)]
-pub enum Adapter {
-    #[doc(hidden)]
-    Hardware,
-    #[doc(hidden)]
-    HardwarePrime,
-    #[doc(hidden)]
-    Software,
+pub struct Adapter {
+    pub(crate) egl_device: Option<EGLDevice>,
+    drm_node_path: Option<PathBuf>,
+    driver_name: Option<String>,
+    software: bool,
+    boot_vga: bool,
 }
 
-impl Adapter {
-    #[inline]
-    pub(crate) fn hardware() -> Adapter {
-        Adapter::HardwarePrime
-    }
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct EGLDevice(pub(crate) EGLDeviceEXT);
+
+// EGL devices are process-global handles that are never destroyed.
+unsafe impl Send for EGLDevice {}
+unsafe impl Sync for EGLDevice {}
 
+impl Adapter {
+    /// The adapter that the display connection itself renders with.
     #[inline]
-    pub(crate) fn low_power() -> Adapter {
-        Adapter::Hardware
+    pub(crate) fn display_default() -> Adapter {
+        Adapter {
+            egl_device: None,
+            drm_node_path: None,
+            driver_name: None,
+            software: false,
+            boot_vga: false,
+        }
     }
 
+    /// An adapter that requires software rendering, but leaves it to the display connection to
+    /// decide which software renderer.
     #[inline]
     pub(crate) fn software() -> Adapter {
-        Adapter::Software
+        Adapter {
+            software: true,
+            ..Adapter::display_default()
+        }
     }
 
-    pub(crate) fn set_environment_variables(&self) {
-        match *self {
-            Adapter::Hardware | Adapter::HardwarePrime => {
-                env::remove_var(MESA_SOFTWARE_RENDERING_ENV_VAR);
-            }
-            Adapter::Software => {
-                env::set_var(MESA_SOFTWARE_RENDERING_ENV_VAR, "1");
+    /// Returns all the EGL devices on the system.
+    ///
+    /// Returns `RequiredExtensionUnavailable` if the EGL implementation doesn't support
+    /// `EGL_EXT_device_enumeration` and `EGL_EXT_device_query`.
+    pub(crate) unsafe fn enumerate() -> Result<Vec<Adapter>, Error> {
+        let query_devices = match (
+            EGL_EXTENSION_FUNCTIONS.QueryDevicesEXT,
+            EGL_EXTENSION_FUNCTIONS.QueryDeviceStringEXT,
+        ) {
+            (Some(query_devices), Some(_))
+                if device::display_has_extension(egl::NO_DISPLAY, "EGL_EXT_device_enumeration") =>
+            {
+                query_devices
             }
+            _ => return Err(Error::RequiredExtensionUnavailable),
+        };
+
+        let mut device_count = 0;
+        if query_devices(0, ptr::null_mut(), &mut device_count) == egl::FALSE {
+            return Err(Error::DeviceOpenFailed);
         }
+        let mut egl_devices = vec![EGL_NO_DEVICE_EXT; device_count as usize];
+        if query_devices(device_count, egl_devices.as_mut_ptr(), &mut device_count) == egl::FALSE {
+            return Err(Error::DeviceOpenFailed);
+        }
+        egl_devices.truncate(device_count as usize);
+
+        Ok(egl_devices
+            .into_iter()
+            .map(|egl_device| Adapter::from_egl_device(egl_device))
+            .collect())
+    }
 
-        match *self {
-            Adapter::Software => {}
-            Adapter::Hardware => {
-                env::remove_var(MESA_DRI_PRIME_ENV_VAR);
+    // Describes the given EGL device, via `EGL_EXT_device_query` and sysfs.
+    pub(crate) unsafe fn from_egl_device(egl_device: EGLDeviceEXT) -> Adapter {
+        let query_string = |name: EGLenum| {
+            let query_device_string = EGL_EXTENSION_FUNCTIONS.QueryDeviceStringEXT?;
+            let string = query_device_string(egl_device, name as EGLint);
+            if string.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(string).to_string_lossy().into_owned())
             }
-            Adapter::HardwarePrime => {
-                env::set_var(MESA_DRI_PRIME_ENV_VAR, "1");
+        };
+
+        let extensions = query_string(egl::EXTENSIONS).unwrap_or_default();
+        let has_extension = |name| extensions.split_whitespace().any(|ext| ext == name);
+
+        let drm_node_path = if has_extension("EGL_EXT_device_drm_render_node") {
+            query_string(EGL_DRM_RENDER_NODE_FILE_EXT)
+        } else {
+            None
+        }
+        .or_else(|| {
+            if has_extension("EGL_EXT_device_drm") {
+                query_string(EGL_DRM_DEVICE_FILE_EXT)
+            } else {
+                None
             }
+        })
+        .map(PathBuf::from);
+
+        // The kernel knows the driver name of any DRM node, so prefer that to the EGL-level name,
+        // which only a few implementations expose.
+        let sysfs_device_path = drm_node_path.as_ref().and_then(|path| {
+            Some(
+                Path::new("/sys/class/drm")
+                    .join(path.file_name()?)
+                    .join("device"),
+            )
+        });
+        let driver_name = sysfs_device_path
+            .as_ref()
+            .and_then(|path| fs::read_link(path.join("driver")).ok())
+            .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()))
+            .or_else(|| {
+                if has_extension("EGL_EXT_device_persistent_id") {
+                    query_string(EGL_DRIVER_NAME_EXT)
+                } else {
+                    None
+                }
+            });
+        let boot_vga = sysfs_device_path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path.join("boot_vga")).ok())
+            .map_or(false, |boot_vga| boot_vga.trim() == "1");
+
+        Adapter {
+            egl_device: Some(EGLDevice(egl_device)),
+            drm_node_path,
+            driver_name,
+            software: has_extension("EGL_MESA_device_software"),
+            boot_vga,
+        }
+    }
+
+    /// Checks that a device rendering with the given display, which chose its own GPU, actually
+    /// renders with this adapter.
+    ///
+    /// Adapters that don't name an EGL device and don't require software rendering accept any
+    /// display. Otherwise, this returns `IncompatibleAdapter` if the display uses a different
+    /// device or doesn't report one.
+    pub(crate) unsafe fn check_display(&self, egl_display: EGLDisplay) -> Result<(), Error> {
+        let compatible = match (self.egl_device, device::display_device(egl_display)) {
+            (None, _) if !self.software => true,
+            (Some(EGLDevice(egl_device)), Some(display_device)) => egl_device == display_device,
+            (None, Some(display_device)) => Adapter::from_egl_device(display_device).software,
+            (_, None) => false,
+        };
+        if compatible {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleAdapter)
         }
     }
+
+    /// Returns the path to the DRM node of this adapter, if it has one.
+    ///
+    /// This is the render node (e.g. `/dev/dri/renderD128`) if the EGL implementation reports
+    /// it, and the primary node (e.g. `/dev/dri/card0`) otherwise. Software adapters have no
+    /// DRM node.
+    #[inline]
+    pub fn drm_node_path(&self) -> Option<&Path> {
+        self.drm_node_path.as_deref()
+    }
+
+    /// Returns the name of the kernel driver behind this adapter (e.g. `amdgpu` or `i915`), if
+    /// known.
+    #[inline]
+    pub fn driver_name(&self) -> Option<&str> {
+        self.driver_name.as_deref()
+    }
+
+    /// Returns true if this adapter renders on the CPU.
+    #[inline]
+    pub fn is_software(&self) -> bool {
+        self.software
+    }
+
+    /// Returns true if this is the GPU that the firmware initialized at boot.
+    ///
+    /// On multi-GPU systems this is usually the integrated, low-power GPU.
+    #[inline]
+    pub(crate) fn is_boot_vga(&self) -> bool {
+        self.boot_vga
+    }
 }
 
 /// A thread-local handle to a device.
@@ -68,6 +223,7 @@ impl Adapter {
 pub struct Device {
     pub(crate) native_connection: Arc<NativeConnectionWrapper>,
     pub(crate) adapter: Adapter,
+    pub(crate) resources: ResourceTracker,
 }
 
 /// Wraps an adapter.
@@ -82,9 +238,22 @@ pub struct NativeDevice {
 impl Device {
     #[inline]
     pub(crate) fn new(connection: &Connection, adapter: &Adapter) -> Result<Device, Error> {
+        let native_connection = match adapter.egl_device {
+            None => unsafe {
+                adapter.check_display(connection.native_connection.egl_display)?;
+                connection.native_connection.clone()
+            },
+            Some(EGLDevice(egl_device)) => unsafe {
+                Arc::new(NativeConnectionWrapper {
+                    egl_display: open_device_display(egl_device)?,
+                })
+            },
+        };
+
         Ok(Device {
-            native_connection: connection.native_connection.clone(),
+            native_connection,
             adapter: (*adapter).clone(),
+            resources: ResourceTracker::new(),
         })
     }
 
@@ -114,8 +283,77 @@ impl Device {
     }
 
     /// Returns the OpenGL API flavor that this device supports (OpenGL or OpenGL ES).
+    ///
+    /// This is the API used by default. OpenGL ES contexts can be requested with the
+    /// `ContextAttributeFlags::GLES` flag, and `context_gl_api()` reports which API a context
+    /// actually uses.
     #[inline]
     pub fn gl_api(&self) -> GLApi {
         GLApi::GL
     }
+
+    /// Returns information about the adapter that this device renders with.
+    ///
+    /// This creates and destroys a temporary context in order to query the OpenGL strings.
+    pub fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        let context_descriptor = self.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(2, 0),
+            flags: ContextAttributeFlags::empty(),
+        })?;
+
+        // Destroying the temporary context leaves no context current, so restore the caller's
+        // context afterward.
+        let _guard = CurrentContextGuard::new();
+        let mut context = self.create_context(&context_descriptor, None)?;
+        let adapter_info = self.make_context_current(&context).map(|()| {
+            let egl_display = self.native_connection.egl_display;
+            let driver_name = unsafe { device::display_driver_name(egl_display) };
+            let display_extensions = unsafe { device::display_extensions(egl_display) };
+            let software = self.adapter.is_software();
+            GL_FUNCTIONS.with(|gl| AdapterInfo {
+                display_extensions,
+                ..AdapterInfo::current(gl, driver_name, software)
+            })
+        });
+        self.destroy_context(&mut context)?;
+        adapter_info
+    }
+
+    /// Returns the contexts, surfaces, and surface textures that this device has created and
+    /// that have not yet been destroyed.
+    ///
+    /// Resources are only tracked in debug builds; in release builds this is always empty.
+    #[inline]
+    pub fn live_resources(&self) -> Vec<LiveResource> {
+        self.resources.live_resources()
+    }
+
+    // Surfaces can only be shared between devices that share an EGL display, which is identified
+    // by its vendor and DRM node.
+    #[inline]
+    pub(crate) fn adapter_identity(&self) -> String {
+        unsafe { device::display_identity(self.native_connection.egl_display) }
+    }
+}
+
+unsafe fn open_device_display(egl_device: EGLDeviceEXT) -> Result<EGLDisplay, Error> {
+    EGL_FUNCTIONS.with(|egl| {
+        let egl_display_attributes = [egl::NONE as EGLAttrib];
+        let egl_display = egl.GetPlatformDisplay(
+            EGL_PLATFORM_DEVICE_EXT,
+            egl_device as *mut c_void,
+            egl_display_attributes.as_ptr(),
+        );
+        if egl_display == egl::NO_DISPLAY {
+            return Err(Error::DeviceOpenFailed);
+        }
+
+        let (mut egl_major_version, mut egl_minor_version) = (0, 0);
+        let ok = egl.Initialize(egl_display, &mut egl_major_version, &mut egl_minor_version);
+        if ok == egl::FALSE {
+            return Err(Error::DeviceOpenFailed);
+        }
+
+        Ok(egl_display)
+    })
 }
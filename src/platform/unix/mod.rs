@@ -2,16 +2,24 @@
 //
 //! Backends specific to Unix-like systems, particularly Linux.
 
-// The default when x11 is enabled, and wayland default is not explicitly selected.
-#[cfg(all(x11_platform, not(wayland_default)))]
+// The default when x11 is enabled, and wayland or OSMesa default is not explicitly selected.
+#[cfg(all(x11_platform, not(any(wayland_default, osmesa_default))))]
 pub mod default;
 
-#[cfg(wayland_default)]
+#[cfg(all(wayland_default, not(osmesa_default)))]
 pub use wayland as default;
 
+#[cfg(osmesa_default)]
+pub use osmesa as default;
+
+pub mod dmabuf;
+
 #[cfg(free_unix)]
 pub mod generic;
 
+#[cfg(osmesa_platform)]
+pub mod osmesa;
+
 #[cfg(wayland_platform)]
 pub mod wayland;
 #[cfg(x11_platform)]
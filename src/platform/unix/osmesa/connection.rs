@@ -0,0 +1,175 @@
+// surfman/surfman/src/platform/unix/osmesa/connection.rs
+//
+//! A no-op connection for OSMesa, which has no display server.
+
+use super::device::{Adapter, Device, NativeDevice};
+use super::ffi::OSMESA_LIBRARY;
+use super::surface::NativeWidget;
+use crate::connection::BackendKind;
+use crate::info::GLApi;
+use crate::Error;
+
+use euclid::default::Size2D;
+
+use std::os::raw::c_void;
+
+/// A no-op connection.
+///
+/// OSMesa renders into buffers in main memory, so there is no display server to connect to.
+#[derive(Clone)]
+pub struct Connection;
+
+/// A no-op native connection.
+#[derive(Clone)]
+pub struct NativeConnection;
+
+impl Connection {
+    /// Loads the OSMesa library.
+    ///
+    /// Returns `NoGLLibraryFound` if `libOSMesa` couldn't be loaded.
+    ///
+    /// Returns `ConnectionFailed` if the `SURFMAN_BACKEND` environment variable names other
+    /// backends but not this one.
+    #[inline]
+    pub fn new() -> Result<Connection, Error> {
+        BackendKind::OSMesa.check_preference_from_env()?;
+        Connection::connect()
+    }
+
+    fn connect() -> Result<Connection, Error> {
+        if OSMESA_LIBRARY.is_none() {
+            return Err(Error::NoGLLibraryFound);
+        }
+        Ok(Connection)
+    }
+
+    /// Connects to the default display if `BackendKind::OSMesa` is among the given backends, and
+    /// returns `UnsupportedOnThisPlatform` otherwise.
+    #[inline]
+    pub fn new_with_preference(preference: &[BackendKind]) -> Result<Connection, Error> {
+        if !preference.contains(&BackendKind::OSMesa) {
+            return Err(Error::UnsupportedOnThisPlatform);
+        }
+        Connection::connect()
+    }
+
+    /// Returns which backend this connection uses, which is always `BackendKind::OSMesa`.
+    #[inline]
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::OSMesa
+    }
+
+    /// An alias for `Connection::new()`, present for consistency with other backends.
+    #[inline]
+    pub unsafe fn from_native_connection(_: NativeConnection) -> Result<Connection, Error> {
+        Connection::new()
+    }
+
+    /// Returns the underlying native connection.
+    #[inline]
+    pub fn native_connection(&self) -> NativeConnection {
+        NativeConnection
+    }
+
+    /// Returns the OpenGL API flavor that this connection supports (OpenGL or OpenGL ES).
+    #[inline]
+    pub fn gl_api(&self) -> GLApi {
+        GLApi::GL
+    }
+
+    /// Returns the "best" adapter on this system.
+    ///
+    /// OSMesa always renders on the CPU, so this is the same as the software adapter.
+    #[inline]
+    pub fn create_adapter(&self) -> Result<Adapter, Error> {
+        self.create_software_adapter()
+    }
+
+    /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
+    ///
+    /// OSMesa always renders on the CPU, so this is the same as the software adapter.
+    #[inline]
+    pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
+        self.create_software_adapter()
+    }
+
+    /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
+    ///
+    /// OSMesa always renders on the CPU, so this is the same as the software adapter.
+    #[inline]
+    pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
+        self.create_software_adapter()
+    }
+
+    /// Returns the CPU adapter.
+    #[inline]
+    pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
+        Ok(Adapter::software())
+    }
+
+    /// Returns all the adapters on this system, which for OSMesa is just the CPU.
+    #[inline]
+    pub fn enumerate_adapters(&self) -> Result<Vec<Adapter>, Error> {
+        Ok(vec![Adapter::software()])
+    }
+
+    /// Opens the device corresponding to the given adapter.
+    ///
+    /// Device handles are local to a single thread.
+    #[inline]
+    pub fn create_device(&self, adapter: &Adapter) -> Result<Device, Error> {
+        Device::new(adapter)
+    }
+
+    /// Opens the device corresponding to the adapter wrapped in the given native device.
+    #[inline]
+    pub unsafe fn create_device_from_native_device(
+        &self,
+        native_device: NativeDevice,
+    ) -> Result<Device, Error> {
+        Device::new(&native_device.adapter)
+    }
+
+    /// Opens the display connection corresponding to the given `RawDisplayHandle`.
+    #[cfg(feature = "sm-raw-window-handle-05")]
+    pub fn from_raw_display_handle(_: rwh_05::RawDisplayHandle) -> Result<Connection, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+
+    /// Opens the display connection corresponding to the given `DisplayHandle`.
+    #[cfg(feature = "sm-raw-window-handle-06")]
+    pub fn from_display_handle(_: rwh_06::DisplayHandle) -> Result<Connection, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+
+    /// Create a native widget from a raw pointer
+    pub unsafe fn create_native_widget_from_ptr(
+        &self,
+        _raw: *mut c_void,
+        _size: Size2D<i32>,
+    ) -> NativeWidget {
+        NativeWidget
+    }
+
+    /// Create a native widget type from the given `RawWindowHandle`.
+    #[cfg(feature = "sm-raw-window-handle-05")]
+    #[inline]
+    pub fn create_native_widget_from_raw_window_handle(
+        &self,
+        _: rwh_05::RawWindowHandle,
+        _size: Size2D<i32>,
+    ) -> Result<NativeWidget, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+
+    /// Create a native widget type from the given `WindowHandle`.
+    #[cfg(feature = "sm-raw-window-handle-06")]
+    #[inline]
+    pub fn create_native_widget_from_window_handle(
+        &self,
+        _: rwh_06::WindowHandle,
+        _size: Size2D<i32>,
+    ) -> Result<NativeWidget, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+}
@@ -0,0 +1,811 @@
+// surfman/surfman/src/platform/unix/osmesa/context.rs
+//
+//! OpenGL rendering contexts on OSMesa.
+
+use super::device::Device;
+use super::ffi::{osmesa, OSMESA_LIBRARY};
+use super::surface::Surface;
+use crate::context::{self, ContextID, ContextResetStatus, CREATE_CONTEXT_MUTEX};
+use crate::gl;
+use crate::gl::types::GLenum;
+use crate::resources;
+use crate::surface::Framebuffer;
+use crate::trace;
+use crate::{ConfigCaveat, ConfigSurfaceTypes, ContextConfigInfo, Gl, SurfaceInfo};
+use crate::{ContextAttributeFlags, ContextAttributes, ContextCapabilities, Error, GLVersion};
+use crate::{ContextVersionRange, GLApi, NativeError, WindowingApiError};
+
+use euclid::default::Size2D;
+use osmesa_sys::{OSMesaContext, OSMESA_ACCUM_BITS, OSMESA_COMPAT_PROFILE};
+use osmesa_sys::{OSMESA_CONTEXT_MAJOR_VERSION, OSMESA_CONTEXT_MINOR_VERSION};
+use osmesa_sys::{OSMESA_CORE_PROFILE, OSMESA_DEPTH_BITS, OSMESA_FORMAT, OSMESA_PROFILE};
+use osmesa_sys::{OSMESA_RGBA, OSMESA_STENCIL_BITS, OSMESA_TYPE};
+use std::cell::UnsafeCell;
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::OwnedFd;
+use std::ptr;
+use std::thread;
+use std::time::Duration;
+
+thread_local! {
+    #[doc(hidden)]
+    pub static GL_FUNCTIONS: Gl = Gl::load_with(get_proc_address);
+}
+
+/// Represents an OpenGL rendering context.
+///
+/// A context allows you to issue rendering commands to a surface. When initially created, a
+/// context has no attached surface, so rendering commands will fail or be ignored. Typically, you
+/// attach a surface to the context before rendering.
+///
+/// Contexts take ownership of the surfaces attached to them. In order to mutate a surface in any
+/// way other than rendering to it (e.g. presenting it to a window, which causes a buffer swap), it
+/// must first be detached from its context. Each surface is associated with a single context upon
+/// creation and may not be rendered to from any other context. However, you can wrap a surface in
+/// a surface texture, which allows the surface to be read from another context.
+///
+/// OpenGL objects may not be shared across contexts directly, but surface textures effectively
+/// allow for sharing of texture data. Contexts are local to a single thread and device.
+///
+/// A context must be explicitly destroyed with `destroy_context()`, or a panic will occur.
+pub struct Context {
+    pub(crate) osmesa_context: OSMesaContext,
+    pub(crate) id: ContextID,
+    framebuffer: Framebuffer<Surface, ColorBuffer>,
+    descriptor: ContextDescriptor,
+    context_is_owned: bool,
+    // OSMesa contexts can only be made current along with a buffer to render into, so contexts
+    // without a surface render into this single pixel instead.
+    placeholder_pixel: Box<UnsafeCell<u32>>,
+}
+
+/// Wraps an OSMesa context, along with the buffer that it renders into.
+pub struct NativeContext {
+    /// The OSMesa context.
+    pub osmesa_context: OSMesaContext,
+    /// The buffer that the context renders into.
+    pub color_buffer: *mut c_void,
+    /// The OpenGL type of the components of the pixels in the color buffer (e.g.
+    /// `GL_UNSIGNED_BYTE`).
+    pub color_buffer_type: GLenum,
+    /// The size of the color buffer, in pixels.
+    pub color_buffer_size: Size2D<i32>,
+}
+
+/// Information needed to create a context. Some APIs call this a "config" or a "pixel format".
+///
+/// These are local to a device.
+#[derive(Clone)]
+pub struct ContextDescriptor {
+    attributes: ContextAttributes,
+}
+
+/// A fence in the command stream of a context.
+///
+/// OSMesa has no fence objects, so fences are signaled as soon as they are created.
+pub struct Fence {
+    _private: (),
+}
+
+// A block of memory that an OSMesa context renders into.
+#[derive(Clone, Copy)]
+pub(crate) struct ColorBuffer {
+    pub(crate) data: *mut c_void,
+    pub(crate) gl_type: GLenum,
+    pub(crate) size: Size2D<i32>,
+}
+
+#[must_use]
+pub(crate) struct CurrentContextGuard {
+    old_context: Option<(OSMesaContext, ColorBuffer)>,
+}
+
+impl Drop for Context {
+    #[inline]
+    fn drop(&mut self) {
+        if !self.osmesa_context.is_null() && !thread::panicking() {
+            panic!(
+                "{}",
+                resources::leaked_context_message(
+                    "Contexts must be destroyed explicitly with `destroy_context`!",
+                    self.id,
+                )
+            )
+        }
+    }
+}
+
+impl Drop for CurrentContextGuard {
+    fn drop(&mut self) {
+        unsafe {
+            match self.old_context {
+                Some((osmesa_context, color_buffer)) => {
+                    make_current(osmesa_context, color_buffer).ok();
+                }
+                None => {
+                    make_no_context_current().ok();
+                }
+            }
+        }
+    }
+}
+
+impl Device {
+    /// Creates a context descriptor with the given attributes.
+    ///
+    /// Context descriptors are local to this device.
+    pub fn create_context_descriptor(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<ContextDescriptor, Error> {
+        let flags = attributes.flags;
+        if flags.contains(ContextAttributeFlags::GLES) {
+            return Err(Error::UnsupportedGLType);
+        }
+        if flags.contains(ContextAttributeFlags::ROBUST) {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
+        // OSMesa can't create debug contexts, and its buffers always have an alpha channel.
+        let mut flags = flags - ContextAttributeFlags::DEBUG;
+        flags.insert(ContextAttributeFlags::ALPHA);
+
+        Ok(ContextDescriptor {
+            attributes: ContextAttributes {
+                version: attributes.version,
+                flags,
+            },
+        })
+    }
+
+    /// Returns the context descriptors that satisfy the given attributes.
+    ///
+    /// OSMesa has no notion of configs, so there is at most one candidate: the one that
+    /// `create_context_descriptor()` returns.
+    #[inline]
+    pub fn enumerate_context_descriptors(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<Vec<ContextDescriptor>, Error> {
+        Ok(vec![self.create_context_descriptor(attributes)?])
+    }
+
+    /// Creates a context descriptor with the given attributes, if the given function accepts the
+    /// configuration that OSMesa would use.
+    ///
+    /// If the function returns `None`, this returns `NoPixelFormatFound`.
+    #[inline]
+    pub fn choose_context_descriptor<F>(
+        &self,
+        attributes: &ContextAttributes,
+        score: F,
+    ) -> Result<ContextDescriptor, Error>
+    where
+        F: FnMut(&ContextConfigInfo) -> Option<u32>,
+    {
+        context::choose_context_descriptor(self, attributes, score)
+    }
+
+    /// Creates a new OpenGL context.
+    ///
+    /// The context initially has no surface attached. Until a surface is bound to it, rendering
+    /// commands will fail or have no effect.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                share_with = ?share_with.map(|context| self.context_id(context).0),
+                context_id = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
+    pub fn create_context(
+        &mut self,
+        descriptor: &ContextDescriptor,
+        share_with: Option<&Context>,
+    ) -> Result<Context, Error> {
+        let mut next_context_id = CREATE_CONTEXT_MUTEX.lock().unwrap();
+
+        let ContextAttributes { version, flags } = descriptor.attributes;
+        let depth_bits = if flags.contains(ContextAttributeFlags::DEPTH) {
+            24
+        } else {
+            0
+        };
+        let stencil_bits = if flags.contains(ContextAttributeFlags::STENCIL) {
+            8
+        } else {
+            0
+        };
+
+        // Versions before 3.1 have no core profile.
+        let profile = if flags.contains(ContextAttributeFlags::COMPATIBILITY_PROFILE)
+            || version.major < 3
+            || version.major == 3 && version.minor == 0
+        {
+            OSMESA_COMPAT_PROFILE
+        } else {
+            OSMESA_CORE_PROFILE
+        };
+
+        let osmesa_context_attributes = [
+            OSMESA_FORMAT,
+            OSMESA_RGBA as c_int,
+            OSMESA_DEPTH_BITS,
+            depth_bits,
+            OSMESA_STENCIL_BITS,
+            stencil_bits,
+            OSMESA_ACCUM_BITS,
+            0,
+            OSMESA_PROFILE,
+            profile,
+            OSMESA_CONTEXT_MAJOR_VERSION,
+            version.major as c_int,
+            OSMESA_CONTEXT_MINOR_VERSION,
+            version.minor as c_int,
+            0,
+        ];
+
+        unsafe {
+            let osmesa_context = (osmesa().OSMesaCreateContextAttribs)(
+                osmesa_context_attributes.as_ptr(),
+                share_with.map_or(ptr::null_mut(), |context| context.osmesa_context),
+            );
+            // OSMesa doesn't report why context creation failed, but the usual reason is that the
+            // requested version is unsupported.
+            if osmesa_context.is_null() {
+                return Err(Error::ContextCreationFailed(NativeError::new(
+                    WindowingApiError::BadMatch,
+                    "OSMesaCreateContextAttribs",
+                )));
+            }
+
+            let context = Context {
+                osmesa_context,
+                id: *next_context_id,
+                framebuffer: Framebuffer::None,
+                descriptor: descriptor.clone(),
+                context_is_owned: true,
+                placeholder_pixel: Box::new(UnsafeCell::new(0)),
+            };
+            trace::record_context(context.id);
+            self.resources.track_context(context.id);
+            next_context_id.0 += 1;
+            Ok(context)
+        }
+    }
+
+    /// Creates a new OpenGL context with the first version that the driver supports, trying each
+    /// range in turn from its highest version to its lowest.
+    ///
+    /// Returns the context along with the attributes that it was created with. If no version
+    /// works, this returns the error from the last attempt.
+    #[inline]
+    pub fn create_context_with_fallbacks(
+        &mut self,
+        ranges: &[ContextVersionRange],
+        share_with: Option<&Context>,
+    ) -> Result<(Context, ContextAttributes), Error> {
+        context::create_context_with_fallbacks(self, ranges, share_with)
+    }
+
+    /// Wraps an OSMesa context in a `surfman` context and returns it.
+    ///
+    /// The context keeps rendering into the buffer that the native context was bound to. It is
+    /// not destroyed when the returned context is, so it is the caller's responsibility to keep
+    /// the OSMesa context and its buffer alive as long as the returned `Context` object is.
+    pub unsafe fn create_context_from_native_context(
+        &self,
+        native_context: NativeContext,
+    ) -> Result<Context, Error> {
+        let color_buffer = ColorBuffer {
+            data: native_context.color_buffer,
+            gl_type: native_context.color_buffer_type,
+            size: native_context.color_buffer_size,
+        };
+
+        // Find out what kind of context this is.
+        let descriptor = {
+            let _guard = CurrentContextGuard::new();
+            make_current(native_context.osmesa_context, color_buffer)?;
+            GL_FUNCTIONS.with(|gl| {
+                // Every context supports at least OpenGL 2.0.
+                let version = GLVersion::current(gl).unwrap_or(GLVersion::new(2, 0));
+                let mut flags = ContextAttributeFlags::ALPHA;
+                flags.set(
+                    ContextAttributeFlags::COMPATIBILITY_PROFILE,
+                    context::current_context_uses_compatibility_profile(gl),
+                );
+                flags.set(
+                    ContextAttributeFlags::DEPTH,
+                    current_context_has_depth_buffer(),
+                );
+                ContextDescriptor {
+                    attributes: ContextAttributes { version, flags },
+                }
+            })
+        };
+
+        let mut next_context_id = CREATE_CONTEXT_MUTEX.lock().unwrap();
+        let context = Context {
+            osmesa_context: native_context.osmesa_context,
+            id: *next_context_id,
+            framebuffer: Framebuffer::External(color_buffer),
+            descriptor,
+            context_is_owned: false,
+            placeholder_pixel: Box::new(UnsafeCell::new(0)),
+        };
+        self.resources.track_context(context.id);
+        next_context_id.0 += 1;
+        Ok(context)
+    }
+
+    /// Destroys a context.
+    ///
+    /// The context must have been created on this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
+    pub fn destroy_context(&self, context: &mut Context) -> Result<(), Error> {
+        if context.osmesa_context.is_null() {
+            return Ok(());
+        }
+
+        if let Framebuffer::Surface(mut surface) =
+            mem::replace(&mut context.framebuffer, Framebuffer::None)
+        {
+            self.destroy_surface(context, &mut surface)?;
+        }
+
+        unsafe {
+            make_no_context_current()?;
+            if context.context_is_owned {
+                (osmesa().OSMesaDestroyContext)(context.osmesa_context);
+            }
+            context.osmesa_context = ptr::null_mut();
+        }
+
+        self.resources.untrack_context(context.id);
+        Ok(())
+    }
+
+    /// Given a context, returns its underlying OSMesa context and the buffer it renders into.
+    pub fn native_context(&self, context: &Context) -> NativeContext {
+        let color_buffer = context.color_buffer();
+        NativeContext {
+            osmesa_context: context.osmesa_context,
+            color_buffer: color_buffer.data,
+            color_buffer_type: color_buffer.gl_type,
+            color_buffer_size: color_buffer.size,
+        }
+    }
+
+    /// Returns the descriptor that this context was created with.
+    #[inline]
+    pub fn context_descriptor(&self, context: &Context) -> ContextDescriptor {
+        context.descriptor.clone()
+    }
+
+    /// Returns the OpenGL API flavor (OpenGL or OpenGL ES) that the given context uses.
+    #[inline]
+    pub fn context_gl_api(&self, _: &Context) -> GLApi {
+        self.gl_api()
+    }
+
+    /// Makes the context the current OpenGL context for this thread.
+    ///
+    /// After calling this function, it is valid to use OpenGL rendering commands.
+    #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
+    pub fn make_context_current(&self, context: &Context) -> Result<(), Error> {
+        unsafe { make_current(context.osmesa_context, context.color_buffer()) }
+    }
+
+    /// Removes the current OpenGL context from this thread.
+    ///
+    /// After calling this function, OpenGL rendering commands will fail until a new context is
+    /// made current.
+    #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, err(Debug, level = "debug"))
+    )]
+    pub fn make_no_context_current(&self) -> Result<(), Error> {
+        unsafe { make_no_context_current() }
+    }
+
+    #[inline]
+    pub(crate) fn temporarily_make_context_current(
+        &self,
+        context: &Context,
+    ) -> Result<CurrentContextGuard, Error> {
+        let guard = CurrentContextGuard::new();
+        self.make_context_current(context)?;
+        Ok(guard)
+    }
+
+    /// Returns the attributes that the context descriptor was created with.
+    #[inline]
+    pub fn context_descriptor_attributes(
+        &self,
+        context_descriptor: &ContextDescriptor,
+    ) -> ContextAttributes {
+        context_descriptor.attributes
+    }
+
+    /// Returns the buffer configuration that the context descriptor selects.
+    ///
+    /// OSMesa always renders into RGBA8 buffers, with 24-bit depth and 8-bit stencil buffers if
+    /// requested.
+    pub fn context_descriptor_config(
+        &self,
+        context_descriptor: &ContextDescriptor,
+    ) -> Result<ContextConfigInfo, Error> {
+        let flags = context_descriptor.attributes.flags;
+        Ok(ContextConfigInfo {
+            native_id: None,
+            red_bits: 8,
+            green_bits: 8,
+            blue_bits: 8,
+            alpha_bits: 8,
+            depth_bits: if flags.contains(ContextAttributeFlags::DEPTH) {
+                24
+            } else {
+                0
+            },
+            stencil_bits: if flags.contains(ContextAttributeFlags::STENCIL) {
+                8
+            } else {
+                0
+            },
+            samples: 0,
+            caveat: ConfigCaveat::None,
+            // OSMesa only renders into buffers in main memory.
+            surface_types: ConfigSurfaceTypes::empty(),
+        })
+    }
+
+    /// Fetches the address of an OpenGL function associated with this context.
+    ///
+    /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
+    /// with any other context.
+    ///
+    /// This method is typically used with a function like `gl::load_with()` from the `gl` crate to
+    /// load OpenGL function pointers.
+    #[inline]
+    pub fn get_proc_address(&self, _: &Context, symbol_name: &str) -> *const c_void {
+        get_proc_address(symbol_name)
+    }
+
+    /// Attaches a surface to a context for rendering.
+    ///
+    /// This function takes ownership of the surface. The surface must have been created with this
+    /// context, or an `IncompatibleSurface` error is returned.
+    ///
+    /// If this function is called with a surface already bound, a `SurfaceAlreadyBound` error is
+    /// returned. To avoid this error, first unbind the existing surface with
+    /// `unbind_surface_from_context`.
+    ///
+    /// If an error is returned, the surface is returned alongside it.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&new_surface).id.0
+            )
+        )
+    )]
+    pub fn bind_surface_to_context(
+        &self,
+        context: &mut Context,
+        new_surface: Surface,
+    ) -> Result<(), (Error, Surface)> {
+        match context.framebuffer {
+            Framebuffer::External(_) => return Err((Error::ExternalRenderTarget, new_surface)),
+            Framebuffer::Surface(_) => return Err((Error::SurfaceAlreadyBound, new_surface)),
+            Framebuffer::None => {}
+        }
+
+        if new_surface.context_id != context.id {
+            return Err((Error::IncompatibleSurface, new_surface));
+        }
+
+        // OSMesa renders into the buffer that the context was last made current with, so switch
+        // buffers right away if the context is current.
+        let context_is_current = context.is_current();
+        context.framebuffer = Framebuffer::Surface(new_surface);
+        if context_is_current {
+            if let Err(err) = self.make_context_current(context) {
+                match mem::replace(&mut context.framebuffer, Framebuffer::None) {
+                    Framebuffer::Surface(surface) => return Err((err, surface)),
+                    _ => unreachable!(),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes and returns any attached surface from this context.
+    ///
+    /// Any pending OpenGL commands targeting this surface will be automatically flushed, so the
+    /// surface is safe to read from immediately when this function returns.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
+    pub fn unbind_surface_from_context(
+        &self,
+        context: &mut Context,
+    ) -> Result<Option<Surface>, Error> {
+        match context.framebuffer {
+            Framebuffer::External(_) => return Err(Error::ExternalRenderTarget),
+            Framebuffer::None => return Ok(None),
+            Framebuffer::Surface(_) => {}
+        }
+
+        // OSMesa may not write rendering out to the buffer until the context is finished.
+        {
+            let _guard = self.temporarily_make_context_current(context)?;
+            GL_FUNCTIONS.with(|gl| unsafe { gl.Finish() });
+        }
+
+        let context_is_current = context.is_current();
+        let surface = match mem::replace(&mut context.framebuffer, Framebuffer::None) {
+            Framebuffer::Surface(surface) => surface,
+            _ => unreachable!(),
+        };
+
+        // Stop the context from rendering into the surface.
+        if context_is_current {
+            if let Err(err) = self.make_context_current(context) {
+                context.framebuffer = Framebuffer::Surface(surface);
+                return Err(err);
+            }
+        }
+        Ok(Some(surface))
+    }
+
+    /// Returns a unique ID representing a context.
+    ///
+    /// This ID is unique to all currently-allocated contexts. If you destroy a context and create
+    /// a new one, the new context might have the same ID as the destroyed one.
+    #[inline]
+    pub fn context_id(&self, context: &Context) -> ContextID {
+        context.id
+    }
+
+    /// Returns various information about the surface attached to a context.
+    ///
+    /// This includes, most notably, the OpenGL framebuffer object needed to render to the surface.
+    pub fn context_surface_info(&self, context: &Context) -> Result<Option<SurfaceInfo>, Error> {
+        match context.framebuffer {
+            Framebuffer::None => Ok(None),
+            Framebuffer::External(_) => Err(Error::ExternalRenderTarget),
+            Framebuffer::Surface(ref surface) => Ok(Some(self.surface_info(surface))),
+        }
+    }
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    ///
+    /// OSMesa has no robustness support, so this always returns an `UnsupportedOnThisPlatform`
+    /// error.
+    #[inline]
+    pub fn context_reset_status(&self, _: &Context) -> Result<ContextResetStatus, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Returns the version, extensions, and limits of the given context.
+    pub fn context_capabilities(&self, context: &Context) -> Result<ContextCapabilities, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(ContextCapabilities::current)
+    }
+
+    /// Waits for all commands previously issued to the given context to finish, and returns a
+    /// fence that is already signaled.
+    ///
+    /// OSMesa has no fence objects, so creating a fence waits for the context's commands to
+    /// finish.
+    pub fn create_fence(&self, context: &Context) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| unsafe { gl.Finish() });
+        Ok(Fence { _private: () })
+    }
+
+    /// Makes the given context wait for a fence to be signaled.
+    ///
+    /// Fences on this backend are signaled on creation, so this does nothing.
+    #[inline]
+    pub fn wait_fence(&self, _: &Context, _: &Fence) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Blocks the calling thread until a fence is signaled or the timeout elapses.
+    ///
+    /// Fences on this backend are signaled on creation, so this always returns true.
+    #[inline]
+    pub fn client_wait_fence(&self, _: &Fence, _: Duration) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    /// Destroys a fence.
+    #[inline]
+    pub fn destroy_fence(&self, _: &mut Fence) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Exports a fence as a Linux sync file descriptor.
+    ///
+    /// OSMesa has no native fences, so this always returns `RequiredExtensionUnavailable`.
+    #[inline]
+    pub fn export_fence_fd(&self, _: &Fence) -> Result<OwnedFd, Error> {
+        Err(Error::RequiredExtensionUnavailable)
+    }
+
+    /// Imports a Linux sync file descriptor as a fence.
+    ///
+    /// OSMesa has no native fences, so this always returns `RequiredExtensionUnavailable`.
+    #[inline]
+    pub fn import_fence_fd(&self, _: &Context, _: OwnedFd) -> Result<Fence, Error> {
+        Err(Error::RequiredExtensionUnavailable)
+    }
+}
+
+impl NativeContext {
+    /// Returns the current OSMesa context and the buffer that it renders into, or a
+    /// `NoCurrentContext` error if there is no current context.
+    pub fn current() -> Result<NativeContext, Error> {
+        if OSMESA_LIBRARY.is_none() {
+            return Err(Error::NoCurrentContext);
+        }
+
+        match unsafe { current_context() } {
+            None => Err(Error::NoCurrentContext),
+            Some((osmesa_context, color_buffer)) => Ok(NativeContext {
+                osmesa_context,
+                color_buffer: color_buffer.data,
+                color_buffer_type: color_buffer.gl_type,
+                color_buffer_size: color_buffer.size,
+            }),
+        }
+    }
+}
+
+impl Context {
+    // Returns the buffer that this context renders into when it's made current.
+    fn color_buffer(&self) -> ColorBuffer {
+        match self.framebuffer {
+            Framebuffer::Surface(ref surface) => surface.color_buffer(),
+            Framebuffer::External(color_buffer) => color_buffer,
+            Framebuffer::None => ColorBuffer {
+                data: self.placeholder_pixel.get() as *mut c_void,
+                gl_type: gl::UNSIGNED_BYTE,
+                size: Size2D::new(1, 1),
+            },
+        }
+    }
+
+    fn is_current(&self) -> bool {
+        unsafe { (osmesa().OSMesaGetCurrentContext)() == self.osmesa_context }
+    }
+}
+
+impl CurrentContextGuard {
+    pub(crate) fn new() -> CurrentContextGuard {
+        CurrentContextGuard {
+            old_context: unsafe { current_context() },
+        }
+    }
+}
+
+// Returns the current context and the buffer that it renders into.
+unsafe fn current_context() -> Option<(OSMesaContext, ColorBuffer)> {
+    let osmesa = osmesa();
+    let osmesa_context = (osmesa.OSMesaGetCurrentContext)();
+    if osmesa_context.is_null() {
+        return None;
+    }
+
+    let (mut width, mut height, mut format, mut gl_type) = (0, 0, 0, 0);
+    let mut data = ptr::null_mut();
+    (osmesa.OSMesaGetColorBuffer)(
+        osmesa_context,
+        &mut width,
+        &mut height,
+        &mut format,
+        &mut data,
+    );
+    (osmesa.OSMesaGetIntegerv)(OSMESA_TYPE, &mut gl_type);
+    Some((
+        osmesa_context,
+        ColorBuffer {
+            data,
+            gl_type: gl_type as GLenum,
+            size: Size2D::new(width, height),
+        },
+    ))
+}
+
+unsafe fn make_current(
+    osmesa_context: OSMesaContext,
+    color_buffer: ColorBuffer,
+) -> Result<(), Error> {
+    let ok = (osmesa().OSMesaMakeCurrent)(
+        osmesa_context,
+        color_buffer.data,
+        color_buffer.gl_type,
+        color_buffer.size.width,
+        color_buffer.size.height,
+    );
+    if ok == gl::FALSE {
+        return Err(Error::MakeCurrentFailed(NativeError::new(
+            WindowingApiError::Failed,
+            "OSMesaMakeCurrent",
+        )));
+    }
+    Ok(())
+}
+
+unsafe fn make_no_context_current() -> Result<(), Error> {
+    let ok = (osmesa().OSMesaMakeCurrent)(ptr::null_mut(), ptr::null_mut(), 0, 0, 0);
+    if ok == gl::FALSE {
+        return Err(Error::MakeCurrentFailed(NativeError::new(
+            WindowingApiError::Failed,
+            "OSMesaMakeCurrent",
+        )));
+    }
+    Ok(())
+}
+
+unsafe fn current_context_has_depth_buffer() -> bool {
+    let (mut width, mut height, mut bytes_per_value) = (0, 0, 0);
+    let mut data = ptr::null_mut();
+    let osmesa = osmesa();
+    (osmesa.OSMesaGetDepthBuffer)(
+        (osmesa.OSMesaGetCurrentContext)(),
+        &mut width,
+        &mut height,
+        &mut bytes_per_value,
+        &mut data,
+    ) != gl::FALSE
+}
+
+fn get_proc_address(symbol_name: &str) -> *const c_void {
+    let osmesa = match OSMESA_LIBRARY.as_deref() {
+        Some(osmesa) => osmesa,
+        None => return ptr::null(),
+    };
+    unsafe {
+        let symbol_name = CString::new(symbol_name).unwrap();
+        match (osmesa.OSMesaGetProcAddress)(symbol_name.as_ptr()) {
+            Some(function) => function as *const c_void,
+            None => ptr::null(),
+        }
+    }
+}
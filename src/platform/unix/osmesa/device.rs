@@ -0,0 +1,134 @@
+// surfman/surfman/src/platform/unix/osmesa/device.rs
+//
+//! A thread-local handle to OSMesa.
+
+use super::connection::Connection;
+use super::context::{CurrentContextGuard, GL_FUNCTIONS};
+use crate::resources::ResourceTracker;
+use crate::LiveResource;
+use crate::{AdapterInfo, ContextAttributeFlags, ContextAttributes, Error, GLApi, GLVersion};
+
+use std::path::Path;
+
+/// Represents the CPU, which is the only adapter that OSMesa can render with.
+///
+/// Adapters can be sent between threads. To render with an adapter, open a thread-local `Device`.
+#[derive(Clone, Debug)]
+pub struct Adapter {
+    _private: (),
+}
+
+impl Adapter {
+    #[inline]
+    pub(crate) fn software() -> Adapter {
+        Adapter { _private: () }
+    }
+
+    /// Returns the path to the DRM node of this adapter, which is always `None`, since OSMesa
+    /// doesn't use the GPU.
+    #[inline]
+    pub fn drm_node_path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Returns the name of the kernel driver behind this adapter, which is always `None`, since
+    /// OSMesa doesn't use the GPU.
+    #[inline]
+    pub fn driver_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns true if this adapter renders on the CPU, which is always the case for OSMesa.
+    #[inline]
+    pub fn is_software(&self) -> bool {
+        true
+    }
+}
+
+/// A thread-local handle to a device.
+///
+/// Devices contain most of the relevant surface management methods.
+pub struct Device {
+    adapter: Adapter,
+    pub(crate) resources: ResourceTracker,
+}
+
+/// Wraps an adapter.
+///
+/// On OSMesa, devices and adapters are essentially identical types.
+#[derive(Clone)]
+pub struct NativeDevice {
+    /// The adapter corresponding to this device.
+    pub adapter: Adapter,
+}
+
+impl Device {
+    #[inline]
+    pub(crate) fn new(adapter: &Adapter) -> Result<Device, Error> {
+        Ok(Device {
+            adapter: (*adapter).clone(),
+            resources: ResourceTracker::new(),
+        })
+    }
+
+    /// Returns the native device corresponding to this device.
+    #[inline]
+    pub fn native_device(&self) -> NativeDevice {
+        NativeDevice {
+            adapter: self.adapter(),
+        }
+    }
+
+    /// Returns the display server connection that this device was created with.
+    #[inline]
+    pub fn connection(&self) -> Connection {
+        Connection
+    }
+
+    /// Returns the adapter that this device was created with.
+    #[inline]
+    pub fn adapter(&self) -> Adapter {
+        self.adapter.clone()
+    }
+
+    /// Returns the OpenGL API flavor that this device supports (OpenGL or OpenGL ES).
+    #[inline]
+    pub fn gl_api(&self) -> GLApi {
+        GLApi::GL
+    }
+
+    /// Returns information about the adapter that this device renders with.
+    ///
+    /// This creates and destroys a temporary context in order to query the OpenGL strings.
+    pub fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        let context_descriptor = self.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(2, 0),
+            flags: ContextAttributeFlags::empty(),
+        })?;
+
+        // Destroying the temporary context leaves no context current, so restore the caller's
+        // context afterward.
+        let _guard = CurrentContextGuard::new();
+        let mut context = self.create_context(&context_descriptor, None)?;
+        let adapter_info = self
+            .make_context_current(&context)
+            .map(|()| GL_FUNCTIONS.with(|gl| AdapterInfo::current(gl, None, true)));
+        self.destroy_context(&mut context)?;
+        adapter_info
+    }
+
+    /// Returns the contexts, surfaces, and surface textures that this device has created and
+    /// that have not yet been destroyed.
+    ///
+    /// Resources are only tracked in debug builds; in release builds this is always empty.
+    #[inline]
+    pub fn live_resources(&self) -> Vec<LiveResource> {
+        self.resources.live_resources()
+    }
+
+    // OSMesa buffers live in main memory and can't be shared with other devices.
+    #[inline]
+    pub(crate) fn adapter_identity(&self) -> String {
+        "OSMesa".to_owned()
+    }
+}
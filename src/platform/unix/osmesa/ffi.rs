@@ -0,0 +1,42 @@
+// surfman/surfman/src/platform/unix/osmesa/ffi.rs
+//
+//! Dynamic loading of the OSMesa library.
+
+use osmesa_sys::OsMesa;
+
+use std::ops::Deref;
+use std::path::Path;
+
+lazy_static! {
+    pub(crate) static ref OSMESA_LIBRARY: Option<OSMesaLibrary> = {
+        // Distributions usually only ship the unversioned name in the development package.
+        ["libOSMesa.so.8", "libOSMesa.so.6", "libOSMesa.so"]
+            .iter()
+            .find_map(|soname| OsMesa::open(Path::new(soname)).ok())
+            .map(OSMesaLibrary)
+    };
+}
+
+pub(crate) struct OSMesaLibrary(OsMesa);
+
+// The library is only ever read from after it's loaded.
+unsafe impl Send for OSMesaLibrary {}
+unsafe impl Sync for OSMesaLibrary {}
+
+impl Deref for OSMesaLibrary {
+    type Target = OsMesa;
+    #[inline]
+    fn deref(&self) -> &OsMesa {
+        &self.0
+    }
+}
+
+/// Returns the entry points of the OSMesa library.
+///
+/// Connections can only be created once the library has been loaded, so this never panics when
+/// called on behalf of a connection or something created from it.
+pub(crate) fn osmesa() -> &'static OsMesa {
+    OSMESA_LIBRARY
+        .as_deref()
+        .expect("The OSMesa library must be loaded before using it")
+}
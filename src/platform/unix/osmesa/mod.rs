@@ -0,0 +1,21 @@
+// surfman/surfman/src/platform/unix/osmesa/mod.rs
+//
+//! Bindings to OSMesa, Mesa's off-screen software renderer.
+//!
+//! This backend renders on the CPU into buffers in main memory, without EGL, a display server, or
+//! a GPU. It's useful for headless rendering on machines without any of those, such as CI
+//! servers. The OSMesa library is loaded at runtime, so creating a connection fails with
+//! `NoGLLibraryFound` if it isn't installed.
+
+pub mod connection;
+pub mod context;
+pub mod device;
+mod ffi;
+pub mod surface;
+
+#[path = "../../../implementation/mod.rs"]
+mod implementation;
+
+#[cfg(test)]
+#[path = "../../../tests.rs"]
+mod tests;
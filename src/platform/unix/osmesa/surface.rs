@@ -0,0 +1,605 @@
+// surfman/surfman/src/platform/unix/osmesa/surface.rs
+//
+//! Surfaces in main memory on OSMesa.
+
+use super::context::{ColorBuffer, Context, GL_FUNCTIONS};
+use super::device::Device;
+use crate::context::ContextID;
+use crate::gl;
+use crate::gl::types::{GLenum, GLint, GLuint};
+use crate::platform::unix::dmabuf::DmaBufDescriptor;
+use crate::resources;
+use crate::trace;
+use crate::{Error, PixelFormat, PresentMode, SurfaceAccess, SurfaceFormat, SurfaceID};
+use crate::{NativeError, SurfaceInfo, SurfaceType, WindowingApiError};
+
+use euclid::default::{Rect, Size2D};
+use std::cell::UnsafeCell;
+use std::fmt::{self, Debug, Formatter};
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::slice;
+use std::thread;
+
+const SURFACE_GL_TEXTURE_TARGET: GLenum = gl::TEXTURE_2D;
+
+/// Represents a buffer of pixels in main memory that OSMesa can render to.
+///
+/// Surfaces on this backend are always generic surfaces; there are no windows to display them in.
+/// They can be read from any context by wrapping them in a `SurfaceTexture`, which uploads their
+/// contents to a texture.
+///
+/// Surfaces are specific to a given context and cannot be rendered to from any context other than
+/// the one they were created with.
+///
+/// Surfaces must be destroyed with the `destroy_surface()` method, or a panic will occur.
+pub struct Surface {
+    // The pixels, in RGBA order, with rows stored bottom to top, as in OpenGL. `None` once the
+    // surface has been destroyed.
+    pixels: Option<Box<[UnsafeCell<u32>]>>,
+    pub(crate) size: Size2D<i32>,
+    pub(crate) context_id: ContextID,
+    access: SurfaceAccess,
+}
+
+/// Represents an OpenGL texture that wraps a surface.
+///
+/// The texture holds a copy of the surface's contents as of the time the surface texture was
+/// created. It is undefined behavior to write to such a texture (e.g. by binding it to a
+/// framebuffer and rendering to that framebuffer).
+///
+/// Surface textures are local to a context, but that context does not have to be the same context
+/// as that associated with the underlying surface. The texture must be destroyed with the
+/// `destroy_surface_texture()` method, or a panic will occur.
+pub struct SurfaceTexture {
+    pub(crate) surface: Surface,
+    pub(crate) texture_object: GLuint,
+    pub(crate) phantom: PhantomData<*const ()>,
+}
+
+/// A placeholder wrapper for a native widget.
+#[derive(Clone)]
+pub struct NativeWidget;
+
+unsafe impl Send for Surface {}
+
+impl Debug for Surface {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Surface({:x})", self.id().0)
+    }
+}
+
+impl Debug for SurfaceTexture {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "SurfaceTexture({:?})", self.surface)
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        if self.pixels.is_some() && !thread::panicking() {
+            panic!(
+                "{}",
+                resources::leaked_surface_message(
+                    "Should have destroyed the surface first with `destroy_surface()`!",
+                    self.id(),
+                )
+            )
+        }
+    }
+}
+
+impl Device {
+    /// Creates a generic surface.
+    ///
+    /// OSMesa has no windows, so widget surfaces return `UnsupportedOnThisPlatform`. Only
+    /// single-sampled `RGBA8` surfaces are supported.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = tracing::field::Empty,
+                width = tracing::field::Empty,
+                height = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
+    pub fn create_surface(
+        &mut self,
+        context: &Context,
+        access: SurfaceAccess,
+        surface_type: SurfaceType<NativeWidget>,
+    ) -> Result<Surface, Error> {
+        let size = match surface_type {
+            SurfaceType::Generic {
+                size,
+                samples,
+                format,
+            } => {
+                if samples > 1 || format != SurfaceFormat::RGBA8 {
+                    return Err(Error::UnsupportedOnThisPlatform);
+                }
+                size
+            }
+            SurfaceType::Widget { .. } => return Err(Error::UnsupportedOnThisPlatform),
+        };
+
+        if size.width <= 0 || size.height <= 0 {
+            return Err(Error::SurfaceCreationFailed(NativeError::new(
+                WindowingApiError::BadValue,
+                "OSMesaMakeCurrent",
+            )));
+        }
+
+        let surface = Surface {
+            pixels: Some(allocate_pixels(size)),
+            size,
+            context_id: context.id,
+            access,
+        };
+        let surface_info = self.surface_info(&surface);
+        trace::record_surface(&surface_info);
+        self.resources.track_surface(&surface_info);
+        Ok(surface)
+    }
+
+    /// Creates a surface that renders into existing DMA-BUFs.
+    ///
+    /// OSMesa renders into main memory only, so this always returns an error.
+    pub fn create_surface_from_dmabuf(
+        &mut self,
+        _: &Context,
+        descriptor: DmaBufDescriptor,
+        _: SurfaceAccess,
+    ) -> Result<Surface, Error> {
+        if descriptor.surface_format().is_none() {
+            return Err(Error::SurfaceImportFailed(NativeError::new(
+                WindowingApiError::BadPixelFormat,
+                "OSMesaMakeCurrent",
+            )));
+        }
+        Err(Error::RequiredExtensionUnavailable)
+    }
+
+    /// Creates a surface texture from an existing generic surface for use with the given context.
+    ///
+    /// The surface texture is local to the supplied context and takes ownership of the surface.
+    /// Destroying the surface texture allows you to retrieve the surface again.
+    ///
+    /// *The supplied context does not have to be the same context that the surface is associated
+    /// with.* This allows you to render to a surface in one context and sample from that surface
+    /// in another context.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
+    pub fn create_surface_texture(
+        &self,
+        context: &mut Context,
+        surface: Surface,
+    ) -> Result<SurfaceTexture, (Error, Surface)> {
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, surface)),
+        };
+        self.resources
+            .track_surface_texture(context.id, &self.surface_info(&surface));
+
+        GL_FUNCTIONS.with(|gl| unsafe {
+            let mut old_texture_object = 0;
+            let mut old_unpack_buffer = 0;
+            gl.GetIntegerv(gl::TEXTURE_BINDING_2D, &mut old_texture_object);
+            gl.GetIntegerv(gl::PIXEL_UNPACK_BUFFER_BINDING, &mut old_unpack_buffer);
+            gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+
+            let mut texture_object = 0;
+            gl.GenTextures(1, &mut texture_object);
+            gl.BindTexture(gl::TEXTURE_2D, texture_object);
+            gl.TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as GLint,
+                surface.size.width,
+                surface.size.height,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                surface.color_buffer().data,
+            );
+            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl.TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl.TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+
+            gl.BindTexture(gl::TEXTURE_2D, old_texture_object as GLuint);
+            gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, old_unpack_buffer as GLuint);
+
+            Ok(SurfaceTexture {
+                surface,
+                texture_object,
+                phantom: PhantomData,
+            })
+        })
+    }
+
+    /// Destroys a surface.
+    ///
+    /// The supplied context must be the context the surface is associated with, or this returns
+    /// an `IncompatibleSurface` error.
+    ///
+    /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
+    /// the `drop` method.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
+    pub fn destroy_surface(
+        &self,
+        context: &mut Context,
+        surface: &mut Surface,
+    ) -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        self.resources
+            .untrack_surface(self.surface_info(surface).id);
+        surface.pixels = None;
+        Ok(())
+    }
+
+    /// Destroys a surface texture and returns the underlying surface.
+    ///
+    /// The supplied context must be the same context the surface texture was created with, or an
+    /// `IncompatibleSurfaceTexture` error is returned.
+    ///
+    /// All surface textures must be explicitly destroyed with this function, or a panic will
+    /// occur.
+    pub fn destroy_surface_texture(
+        &self,
+        context: &mut Context,
+        surface_texture: SurfaceTexture,
+    ) -> Result<Surface, (Error, SurfaceTexture)> {
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, surface_texture)),
+        };
+
+        GL_FUNCTIONS.with(|gl| unsafe {
+            gl.DeleteTextures(1, &surface_texture.texture_object);
+        });
+        self.resources
+            .untrack_surface_texture(self.surface_info(&surface_texture.surface).id);
+        Ok(surface_texture.surface)
+    }
+
+    /// Displays the contents of a widget surface on screen.
+    ///
+    /// OSMesa has no widget surfaces, so this always returns `NoWidgetAttached`.
+    #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, err(Debug, level = "debug"))
+    )]
+    pub fn present_surface(&self, _: &Context, _: &mut Surface) -> Result<(), Error> {
+        Err(Error::NoWidgetAttached)
+    }
+
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// changed since the last present.
+    ///
+    /// OSMesa has no widget surfaces, so this always returns `NoWidgetAttached`.
+    #[inline]
+    pub fn present_surface_with_damage(
+        &self,
+        _: &Context,
+        _: &mut Surface,
+        _: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        Err(Error::NoWidgetAttached)
+    }
+
+    /// Reads back the pixels in the given rectangle of a surface.
+    ///
+    /// The rectangle has its origin at the top left of the surface, and the returned rows are
+    /// ordered from top to bottom. The surface must not be bound to a context.
+    pub fn read_surface_pixels(
+        &self,
+        context: &Context,
+        surface: &Surface,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        if surface.context_id != context.id {
+            return Err(Error::IncompatibleSurface);
+        }
+        if rect.size.width < 0
+            || rect.size.height < 0
+            || !Rect::from_size(surface.size).contains_rect(&rect)
+        {
+            return Err(Error::Failed);
+        }
+
+        // Empty rectangles pass the check above wherever they are, so don't index with them.
+        if rect.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let source = surface.bytes();
+        let source_stride = surface.stride();
+        let row_length = rect.size.width as usize * format.bytes_per_pixel();
+        let mut pixels = Vec::with_capacity(row_length * rect.size.height as usize);
+
+        // The surface stores the bottom row first.
+        for y in rect.min_y()..rect.max_y() {
+            let row_start = (surface.size.height - y - 1) as usize * source_stride;
+            let row = &source[row_start..(row_start + source_stride)];
+            let row = &row[(rect.min_x() as usize * 4)..(rect.max_x() as usize * 4)];
+            for pixel in row.chunks_exact(4) {
+                match format {
+                    PixelFormat::RGBA8 => pixels.extend_from_slice(pixel),
+                    PixelFormat::BGRA8 => {
+                        pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]])
+                    }
+                    PixelFormat::RGBA32F => {
+                        for &component in pixel {
+                            pixels.extend_from_slice(&(component as f32 / 255.0).to_ne_bytes());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    /// Returns the age of the back buffer of a widget surface.
+    ///
+    /// OSMesa has no widget surfaces, so this always returns `NoWidgetAttached`.
+    #[inline]
+    pub fn surface_buffer_age(&self, _: &Context, _: &Surface) -> Result<u32, Error> {
+        Err(Error::NoWidgetAttached)
+    }
+
+    /// Sets how `present_surface()` synchronizes with the display for the given widget surface.
+    ///
+    /// This backend has no widget surfaces, so this always returns `UnsupportedOnThisPlatform`.
+    pub fn set_present_mode(&self, _: &Context, _: &Surface, _: PresentMode) -> Result<(), Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Resizes a surface.
+    ///
+    /// The contents of the surface are cleared. The surface must not be bound to a context.
+    pub fn resize_surface(
+        &self,
+        _context: &Context,
+        surface: &mut Surface,
+        size: Size2D<i32>,
+    ) -> Result<(), Error> {
+        let surface_id = self.surface_info(surface).id;
+        if size.width <= 0 || size.height <= 0 {
+            return Err(Error::Failed);
+        }
+
+        surface.pixels = Some(allocate_pixels(size));
+        surface.size = size;
+        self.resources
+            .update_surface(surface_id, &self.surface_info(surface));
+        Ok(())
+    }
+
+    /// Returns a pointer to the underlying surface data for reading or writing by the CPU.
+    ///
+    /// Only surfaces created with `GPUCPU` or `GPUCPUWriteCombined` access can be locked; other
+    /// surfaces return `SurfaceDataInaccessible`. The surface must not be bound to a context.
+    pub fn lock_surface_data<'s>(
+        &self,
+        surface: &'s mut Surface,
+    ) -> Result<SurfaceDataGuard<'s>, Error> {
+        if surface.access == SurfaceAccess::GPUOnly {
+            return Err(Error::SurfaceDataInaccessible);
+        }
+        Ok(SurfaceDataGuard { surface })
+    }
+
+    /// Reads back the pixels of a surface.
+    ///
+    /// Surfaces on this backend already live in main memory, so the pixels are copied right away
+    /// and the returned `Readback` is always ready. The supplied context must match the context
+    /// the surface was created with, or an `IncompatibleSurface` error is returned.
+    pub fn begin_surface_readback(
+        &self,
+        context: &Context,
+        surface: &Surface,
+    ) -> Result<Readback, Error> {
+        if surface.context_id != context.id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        Ok(Readback {
+            data: surface.bytes().to_vec(),
+            size: surface.size,
+            stride: surface.stride(),
+        })
+    }
+
+    /// Exports a generic surface as a set of DMA-BUF file descriptors.
+    ///
+    /// OSMesa surfaces live in main memory rather than in DMA-BUFs, so this always returns an
+    /// error.
+    pub fn export_surface_dmabuf(
+        &self,
+        context: &Context,
+        surface: &Surface,
+    ) -> Result<DmaBufDescriptor, Error> {
+        if surface.context_id != context.id {
+            return Err(Error::IncompatibleSurface);
+        }
+        Err(Error::RequiredExtensionUnavailable)
+    }
+
+    /// Returns the OpenGL texture target needed to read from this surface texture.
+    ///
+    /// This will be `GL_TEXTURE_2D` or `GL_TEXTURE_RECTANGLE`, depending on platform.
+    #[inline]
+    pub fn surface_gl_texture_target(&self) -> GLenum {
+        SURFACE_GL_TEXTURE_TARGET
+    }
+
+    /// Returns various information about the surface, including the framebuffer object needed to
+    /// render to this surface.
+    ///
+    /// Before rendering to a surface attached to a context, you must call `glBindFramebuffer()`
+    /// on the framebuffer object returned by this function. This framebuffer object may or not be
+    /// 0, the default framebuffer, depending on platform.
+    #[inline]
+    pub fn surface_info(&self, surface: &Surface) -> SurfaceInfo {
+        SurfaceInfo {
+            size: surface.size,
+            id: surface.id(),
+            context_id: surface.context_id,
+            framebuffer_object: 0,
+            format: SurfaceFormat::RGBA8,
+        }
+    }
+
+    /// Returns the OpenGL texture object containing the contents of this surface.
+    ///
+    /// It is only legal to read from, not write to, this texture object.
+    #[inline]
+    pub fn surface_texture_object(&self, surface_texture: &SurfaceTexture) -> GLuint {
+        surface_texture.texture_object
+    }
+}
+
+impl Surface {
+    fn id(&self) -> SurfaceID {
+        SurfaceID(
+            self.pixels
+                .as_ref()
+                .map_or(0, |pixels| pixels.as_ptr() as usize),
+        )
+    }
+
+    #[inline]
+    fn stride(&self) -> usize {
+        self.size.width as usize * 4
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self.pixels {
+            None => &[],
+            Some(ref pixels) => unsafe {
+                slice::from_raw_parts(pixels.as_ptr() as *const u8, pixels.len() * 4)
+            },
+        }
+    }
+
+    pub(crate) fn color_buffer(&self) -> ColorBuffer {
+        ColorBuffer {
+            data: self.pixels.as_ref().map_or(std::ptr::null_mut(), |pixels| {
+                pixels.as_ptr() as *mut c_void
+            }),
+            gl_type: gl::UNSIGNED_BYTE,
+            size: self.size,
+        }
+    }
+}
+
+fn allocate_pixels(size: Size2D<i32>) -> Box<[UnsafeCell<u32>]> {
+    (0..(size.width as usize * size.height as usize))
+        .map(|_| UnsafeCell::new(0))
+        .collect()
+}
+
+/// Represents the CPU view of the pixel data of this surface.
+pub struct SurfaceDataGuard<'a> {
+    surface: &'a mut Surface,
+}
+
+impl<'a> SurfaceDataGuard<'a> {
+    /// Returns the number of bytes per row of the surface.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.surface.stride()
+    }
+
+    /// Returns a mutable slice of the pixel data in this surface, in RGBA order.
+    ///
+    /// Rows are stored bottom to top, as in OpenGL.
+    #[inline]
+    pub fn data(&mut self) -> &mut [u8] {
+        match self.surface.pixels {
+            None => &mut [],
+            Some(ref mut pixels) => unsafe {
+                slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u8, pixels.len() * 4)
+            },
+        }
+    }
+}
+
+/// A readback of the pixels of a surface, started with `begin_surface_readback()`.
+///
+/// The pixels are in RGBA order, with rows stored bottom to top, as in OpenGL. On this backend,
+/// the pixels are available as soon as the readback is created.
+pub struct Readback {
+    data: Vec<u8>,
+    size: Size2D<i32>,
+    stride: usize,
+}
+
+impl Readback {
+    /// Returns the size of the surface that was read back, in pixels.
+    #[inline]
+    pub fn size(&self) -> Size2D<i32> {
+        self.size
+    }
+
+    /// Returns the number of bytes per row of the pixel data.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the pixel data, which is always available on this backend.
+    ///
+    /// This never blocks.
+    #[inline]
+    pub fn try_map(&mut self) -> Result<Option<&[u8]>, Error> {
+        Ok(Some(&self.data))
+    }
+
+    /// Returns the pixel data, which is always available on this backend.
+    #[inline]
+    pub fn wait(&mut self) -> Result<&[u8], Error> {
+        Ok(&self.data)
+    }
+}
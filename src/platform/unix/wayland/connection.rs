@@ -4,6 +4,7 @@
 
 use super::device::{Adapter, Device, NativeDevice};
 use super::surface::NativeWidget;
+use crate::connection::BackendKind;
 use crate::egl;
 use crate::egl::types::{EGLAttrib, EGLDisplay};
 use crate::info::GLApi;
@@ -35,14 +36,38 @@ unsafe impl Send for Connection {}
 
 impl Connection {
     /// Connects to the default Wayland server.
+    ///
+    /// Returns `ConnectionFailed` if the `SURFMAN_BACKEND` environment variable names other
+    /// backends but not this one.
     #[inline]
     pub fn new() -> Result<Connection, Error> {
+        BackendKind::Wayland.check_preference_from_env()?;
+        Connection::connect()
+    }
+
+    fn connect() -> Result<Connection, Error> {
         unsafe {
             let wayland_display = (WAYLAND_CLIENT_HANDLE.wl_display_connect)(ptr::null());
             Connection::from_wayland_display(wayland_display, true)
         }
     }
 
+    /// Connects to the default display if `BackendKind::Wayland` is among the given backends, and
+    /// returns `UnsupportedOnThisPlatform` otherwise.
+    #[inline]
+    pub fn new_with_preference(preference: &[BackendKind]) -> Result<Connection, Error> {
+        if !preference.contains(&BackendKind::Wayland) {
+            return Err(Error::UnsupportedOnThisPlatform);
+        }
+        Connection::connect()
+    }
+
+    /// Returns which backend this connection uses, which is always `BackendKind::Wayland`.
+    #[inline]
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::Wayland
+    }
+
     /// Wraps an existing EGL display in a `Connection`.
     ///
     /// The display is not retained, as there is no way to do this in the EGL API. Therefore, it is
@@ -61,6 +86,10 @@ impl Connection {
     }
 
     /// Returns the OpenGL API flavor that this connection supports (OpenGL or OpenGL ES).
+    ///
+    /// This is the API used by default. OpenGL ES contexts can be requested with the
+    /// `ContextAttributeFlags::GLES` flag, and `context_descriptor_attributes()` reports which API
+    /// a context actually uses.
     #[inline]
     pub fn gl_api(&self) -> GLApi {
         GLApi::GL
@@ -77,19 +106,45 @@ impl Connection {
     /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
     #[inline]
     pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::hardware())
+        Ok(Adapter::display_default())
     }
 
     /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
+    ///
+    /// On Wayland, this is the same as `create_hardware_adapter()`, since rendering always happens
+    /// on the GPU that the display server connection uses.
     #[inline]
     pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::low_power())
+        Ok(Adapter::display_default())
     }
 
-    /// Returns the "best" adapter on this system, preferring software adapters.
+    /// Returns an adapter that renders in software.
+    ///
+    /// On Wayland, rendering always happens on the GPU that the display server connection uses, so
+    /// this returns `NoAdapterFound` unless that is a software renderer (for example, because
+    /// `LIBGL_ALWAYS_SOFTWARE` was set before the connection was opened).
     #[inline]
     pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::software())
+        let adapter = Adapter::software();
+        match unsafe { adapter.check_display(self.native_connection.egl_display) } {
+            Ok(()) => Ok(adapter),
+            Err(_) => Err(Error::NoAdapterFound),
+        }
+    }
+
+    /// Returns the adapters on this system that devices on this connection can render with.
+    ///
+    /// On Wayland, rendering always happens on the GPU that the display server connection uses, so
+    /// this is at most that one adapter. Other adapters are rejected by `create_device()` with
+    /// `IncompatibleAdapter`.
+    pub fn enumerate_adapters(&self) -> Result<Vec<Adapter>, Error> {
+        let egl_display = self.native_connection.egl_display;
+        unsafe {
+            Ok(Adapter::enumerate()?
+                .into_iter()
+                .filter(|adapter| adapter.check_display(egl_display).is_ok())
+                .collect())
+        }
     }
 
     /// Opens the hardware device corresponding to the given adapter.
@@ -3,7 +3,12 @@
 //! A wrapper around Wayland `EGLDisplay`s.
 
 use super::connection::{Connection, NativeConnectionWrapper};
-use crate::{Error, GLApi};
+use super::context::GL_FUNCTIONS;
+use crate::platform::generic::egl::context::CurrentContextGuard;
+use crate::platform::generic::egl::device;
+use crate::resources::ResourceTracker;
+use crate::LiveResource;
+use crate::{AdapterInfo, ContextAttributeFlags, ContextAttributes, Error, GLApi, GLVersion};
 
 use std::sync::Arc;
 
@@ -15,6 +20,7 @@ pub use crate::platform::unix::generic::device::Adapter;
 pub struct Device {
     pub(crate) native_connection: Arc<NativeConnectionWrapper>,
     pub(crate) adapter: Adapter,
+    pub(crate) resources: ResourceTracker,
 }
 
 /// Wraps an adapter.
@@ -29,9 +35,11 @@ pub struct NativeDevice {
 impl Device {
     #[inline]
     pub(crate) fn new(connection: &Connection, adapter: &Adapter) -> Result<Device, Error> {
+        unsafe { adapter.check_display(connection.native_connection.egl_display)? };
         Ok(Device {
             native_connection: connection.native_connection.clone(),
             adapter: (*adapter).clone(),
+            resources: ResourceTracker::new(),
         })
     }
 
@@ -61,8 +69,55 @@ impl Device {
     }
 
     /// Returns the OpenGL API flavor that this device supports (OpenGL or OpenGL ES).
+    ///
+    /// This is the API used by default. OpenGL ES contexts can be requested with the
+    /// `ContextAttributeFlags::GLES` flag, and `context_gl_api()` reports which API a context
+    /// actually uses.
     #[inline]
     pub fn gl_api(&self) -> GLApi {
         GLApi::GL
     }
+
+    /// Returns information about the adapter that this device renders with.
+    ///
+    /// This creates and destroys a temporary context in order to query the OpenGL strings.
+    pub fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        let context_descriptor = self.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(2, 0),
+            flags: ContextAttributeFlags::empty(),
+        })?;
+
+        // Destroying the temporary context leaves no context current, so restore the caller's
+        // context afterward.
+        let _guard = CurrentContextGuard::new();
+        let mut context = self.create_context(&context_descriptor, None)?;
+        let adapter_info = self.make_context_current(&context).map(|()| {
+            let egl_display = self.native_connection.egl_display;
+            let driver_name = unsafe { device::display_driver_name(egl_display) };
+            let display_extensions = unsafe { device::display_extensions(egl_display) };
+            let software = self.adapter.is_software();
+            GL_FUNCTIONS.with(|gl| AdapterInfo {
+                display_extensions,
+                ..AdapterInfo::current(gl, driver_name, software)
+            })
+        });
+        self.destroy_context(&mut context)?;
+        adapter_info
+    }
+
+    /// Returns the contexts, surfaces, and surface textures that this device has created and
+    /// that have not yet been destroyed.
+    ///
+    /// Resources are only tracked in debug builds; in release builds this is always empty.
+    #[inline]
+    pub fn live_resources(&self) -> Vec<LiveResource> {
+        self.resources.live_resources()
+    }
+
+    // Surfaces can only be shared between devices that share an EGL display, which is identified
+    // by its vendor and DRM node.
+    #[inline]
+    pub(crate) fn adapter_identity(&self) -> String {
+        unsafe { device::display_identity(self.native_connection.egl_display) }
+    }
 }
@@ -4,21 +4,24 @@
 
 use super::device::{Device, NativeDevice};
 use super::surface::NativeWidget;
+use crate::connection::BackendKind;
 use crate::egl;
 use crate::egl::types::{EGLAttrib, EGLDisplay};
 use crate::error::Error;
 use crate::info::GLApi;
 use crate::platform::generic::egl::device::EGL_FUNCTIONS;
-use crate::platform::generic::egl::ffi::EGL_PLATFORM_X11_KHR;
+use crate::platform::generic::egl::ffi::EGL_PLATFORM_XCB_SCREEN_EXT;
+use crate::platform::generic::egl::ffi::{EGL_PLATFORM_X11_KHR, EGL_PLATFORM_XCB_EXT};
 use crate::platform::unix::generic::device::Adapter;
 
 use euclid::default::Size2D;
 
 use std::marker::PhantomData;
-use std::os::raw::c_void;
+use std::os::raw::{c_int, c_void};
 use std::ptr;
 use std::sync::Arc;
-use x11::xlib::{Display, XCloseDisplay, XInitThreads, XLockDisplay, XOpenDisplay, XUnlockDisplay};
+use x11::xlib::XUnlockDisplay;
+use x11::xlib::{Display, Window, XCloseDisplay, XInitThreads, XLockDisplay, XOpenDisplay};
 
 lazy_static! {
     static ref X_THREADS_INIT: () = {
@@ -38,6 +41,7 @@ unsafe impl Send for Connection {}
 
 pub(crate) struct NativeConnectionWrapper {
     pub(crate) egl_display: EGLDisplay,
+    // Null if the connection was opened from an XCB connection.
     x11_display: *mut Display,
     x11_display_is_owned: bool,
 }
@@ -51,7 +55,10 @@ pub struct NativeConnection {
     ///
     /// It is assumed that this EGL display is already initialized, via `eglInitialize()`.
     pub egl_display: EGLDisplay,
-    /// The corresponding Xlib Display. This must be present; do not pass NULL.
+    /// The corresponding Xlib Display.
+    ///
+    /// This is null if the connection was opened from an XCB connection, in which case the EGL
+    /// display is on `EGL_PLATFORM_XCB_EXT` rather than `EGL_PLATFORM_X11_KHR`.
     pub x11_display: *mut Display,
 }
 
@@ -69,8 +76,16 @@ impl Drop for NativeConnectionWrapper {
 
 impl Connection {
     /// Connects to the default display.
+    ///
+    /// Returns `ConnectionFailed` if the `SURFMAN_BACKEND` environment variable names other
+    /// backends but not this one.
     #[inline]
     pub fn new() -> Result<Connection, Error> {
+        BackendKind::X11.check_preference_from_env()?;
+        Connection::connect()
+    }
+
+    fn connect() -> Result<Connection, Error> {
         unsafe {
             *X_THREADS_INIT;
 
@@ -91,6 +106,22 @@ impl Connection {
         }
     }
 
+    /// Connects to the default display if `BackendKind::X11` is among the given backends, and
+    /// returns `UnsupportedOnThisPlatform` otherwise.
+    #[inline]
+    pub fn new_with_preference(preference: &[BackendKind]) -> Result<Connection, Error> {
+        if !preference.contains(&BackendKind::X11) {
+            return Err(Error::UnsupportedOnThisPlatform);
+        }
+        Connection::connect()
+    }
+
+    /// Returns which backend this connection uses, which is always `BackendKind::X11`.
+    #[inline]
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::X11
+    }
+
     /// Wraps an existing X11 `Display` in a `Connection`.
     ///
     /// Important: Before calling this function, X11 must have be initialized in a thread-safe
@@ -100,6 +131,9 @@ impl Connection {
     /// The display is not retained, as there is no way to do that in the X11 API. Therefore, it is
     /// the caller's responsibility to ensure that the display connection is not closed before this
     /// `Connection` object is disposed of.
+    ///
+    /// If `x11_display` is null, the EGL display must have been created from an XCB connection on
+    /// `EGL_PLATFORM_XCB_EXT`.
     #[inline]
     pub unsafe fn from_native_connection(
         native_connection: NativeConnection,
@@ -126,6 +160,20 @@ impl Connection {
         }
     }
 
+    // Opens an EGL display on the given XCB connection, via `EGL_EXT_platform_xcb`.
+    fn from_xcb_connection(
+        xcb_connection: *mut c_void,
+        screen: c_int,
+    ) -> Result<Connection, Error> {
+        unsafe {
+            let egl_display = create_xcb_egl_display(xcb_connection, screen)?;
+            Connection::from_native_connection(NativeConnection {
+                egl_display,
+                x11_display: ptr::null_mut(),
+            })
+        }
+    }
+
     /// Returns the underlying native connection.
     #[inline]
     pub fn native_connection(&self) -> NativeConnection {
@@ -136,6 +184,10 @@ impl Connection {
     }
 
     /// Returns the OpenGL API flavor that this connection supports (OpenGL or OpenGL ES).
+    ///
+    /// This is the API used by default. OpenGL ES contexts can be requested with the
+    /// `ContextAttributeFlags::GLES` flag, and `context_descriptor_attributes()` reports which API
+    /// a context actually uses.
     #[inline]
     pub fn gl_api(&self) -> GLApi {
         GLApi::GL
@@ -152,19 +204,45 @@ impl Connection {
     /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
     #[inline]
     pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::hardware())
+        Ok(Adapter::display_default())
     }
 
     /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
+    ///
+    /// On X11, this is the same as `create_hardware_adapter()`, since rendering always happens
+    /// on the GPU that the display server connection uses.
     #[inline]
     pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::low_power())
+        Ok(Adapter::display_default())
     }
 
-    /// Returns the "best" adapter on this system, preferring software adapters.
+    /// Returns an adapter that renders in software.
+    ///
+    /// On X11, rendering always happens on the GPU that the display server connection uses, so
+    /// this returns `NoAdapterFound` unless that is a software renderer (for example, because
+    /// `LIBGL_ALWAYS_SOFTWARE` was set before the connection was opened).
     #[inline]
     pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::software())
+        let adapter = Adapter::software();
+        match unsafe { adapter.check_display(self.native_connection.egl_display) } {
+            Ok(()) => Ok(adapter),
+            Err(_) => Err(Error::NoAdapterFound),
+        }
+    }
+
+    /// Returns the adapters on this system that devices on this connection can render with.
+    ///
+    /// On X11, rendering always happens on the GPU that the display server connection uses, so
+    /// this is at most that one adapter. Other adapters are rejected by `create_device()` with
+    /// `IncompatibleAdapter`.
+    pub fn enumerate_adapters(&self) -> Result<Vec<Adapter>, Error> {
+        let egl_display = self.native_connection.egl_display;
+        unsafe {
+            Ok(Adapter::enumerate()?
+                .into_iter()
+                .filter(|adapter| adapter.check_display(egl_display).is_ok())
+                .collect())
+        }
     }
 
     /// Opens the hardware device corresponding to the given adapter.
@@ -188,38 +266,48 @@ impl Connection {
     }
 
     /// Opens the display connection corresponding to the given `RawDisplayHandle`.
+    ///
+    /// XCB connections require `EGL_EXT_platform_xcb`; if it's missing, this returns
+    /// `RequiredExtensionUnavailable`.
     #[cfg(feature = "sm-raw-window-handle-05")]
     pub fn from_raw_display_handle(
         raw_handle: rwh_05::RawDisplayHandle,
     ) -> Result<Connection, Error> {
         use rwh_05::RawDisplayHandle::Xcb;
         use rwh_05::RawDisplayHandle::Xlib;
-        use rwh_05::XlibDisplayHandle;
-        let display = match raw_handle {
-            Xlib(XlibDisplayHandle { display, .. }) => display as *mut Display,
-            Xcb(_) => return Err(Error::Unimplemented),
-            _ => return Err(Error::IncompatibleRawDisplayHandle),
-        };
-
-        Connection::from_x11_display(display, false)
+        use rwh_05::{XcbDisplayHandle, XlibDisplayHandle};
+        match raw_handle {
+            Xlib(XlibDisplayHandle { display, .. }) if !display.is_null() => {
+                Connection::from_x11_display(display as *mut Display, false)
+            }
+            Xcb(XcbDisplayHandle {
+                connection, screen, ..
+            }) if !connection.is_null() => Connection::from_xcb_connection(connection, screen),
+            _ => Err(Error::IncompatibleRawDisplayHandle),
+        }
     }
 
     /// Opens the display connection corresponding to the given `DisplayHandle`.
+    ///
+    /// XCB connections require `EGL_EXT_platform_xcb`; if it's missing, this returns
+    /// `RequiredExtensionUnavailable`.
     #[cfg(feature = "sm-raw-window-handle-06")]
     pub fn from_display_handle(handle: rwh_06::DisplayHandle) -> Result<Connection, Error> {
         use rwh_06::RawDisplayHandle::Xcb;
         use rwh_06::RawDisplayHandle::Xlib;
-        use rwh_06::XlibDisplayHandle;
-        let display = match handle.as_raw() {
+        use rwh_06::{XcbDisplayHandle, XlibDisplayHandle};
+        match handle.as_raw() {
             Xlib(XlibDisplayHandle {
                 display: Some(display),
                 ..
-            }) => display.as_ptr() as *mut Display,
-            Xcb(_) => return Err(Error::Unimplemented),
-            _ => return Err(Error::IncompatibleRawDisplayHandle),
-        };
-
-        Connection::from_x11_display(display, false)
+            }) => Connection::from_x11_display(display.as_ptr() as *mut Display, false),
+            Xcb(XcbDisplayHandle {
+                connection: Some(connection),
+                screen,
+                ..
+            }) => Connection::from_xcb_connection(connection.as_ptr(), screen),
+            _ => Err(Error::IncompatibleRawDisplayHandle),
+        }
     }
 
     /// Create a native widget from a raw pointer
@@ -240,12 +328,17 @@ impl Connection {
         raw_handle: rwh_05::RawWindowHandle,
         _size: Size2D<i32>,
     ) -> Result<NativeWidget, Error> {
-        use rwh_05::RawWindowHandle::Xlib;
+        use rwh_05::RawWindowHandle::{Xcb, Xlib};
 
+        // XCB and Xlib share window IDs, so either kind of window can be used with either kind of
+        // connection.
         match raw_handle {
             Xlib(handle) => Ok(NativeWidget {
                 window: handle.window,
             }),
+            Xcb(handle) => Ok(NativeWidget {
+                window: handle.window as Window,
+            }),
             _ => Err(Error::IncompatibleNativeWidget),
         }
     }
@@ -257,27 +350,37 @@ impl Connection {
         handle: rwh_06::WindowHandle,
         _size: Size2D<i32>,
     ) -> Result<NativeWidget, Error> {
-        use rwh_06::RawWindowHandle::Xlib;
+        use rwh_06::RawWindowHandle::{Xcb, Xlib};
 
+        // XCB and Xlib share window IDs, so either kind of window can be used with either kind of
+        // connection.
         match handle.as_raw() {
             Xlib(handle) => Ok(NativeWidget {
                 window: handle.window,
             }),
+            Xcb(handle) => Ok(NativeWidget {
+                window: handle.window.get() as Window,
+            }),
             _ => Err(Error::IncompatibleNativeWidget),
         }
     }
 }
 
 impl NativeConnectionWrapper {
+    // Returns `None` if the connection was opened from an XCB connection, since there's no Xlib
+    // display to lock.
     #[inline]
-    pub(crate) fn lock_display(&self) -> DisplayGuard {
+    pub(crate) fn lock_display(&self) -> Option<DisplayGuard> {
+        if self.x11_display.is_null() {
+            return None;
+        }
         unsafe {
             let display = self.x11_display;
             XLockDisplay(display);
-            DisplayGuard {
+            Some(DisplayGuard {
                 display,
                 phantom: PhantomData,
-            }
+            })
         }
     }
 }
@@ -318,3 +421,33 @@ unsafe fn create_egl_display(display: *mut Display) -> EGLDisplay {
         egl_display
     })
 }
+
+unsafe fn create_xcb_egl_display(
+    xcb_connection: *mut c_void,
+    screen: c_int,
+) -> Result<EGLDisplay, Error> {
+    EGL_FUNCTIONS.with(|egl| {
+        let display_attributes = [
+            EGL_PLATFORM_XCB_SCREEN_EXT as EGLAttrib,
+            screen as EGLAttrib,
+            egl::NONE as EGLAttrib,
+        ];
+        let egl_display = egl.GetPlatformDisplay(
+            EGL_PLATFORM_XCB_EXT,
+            xcb_connection,
+            display_attributes.as_ptr(),
+        );
+        // This fails with `EGL_BAD_PARAMETER` if `EGL_EXT_platform_xcb` isn't supported.
+        if egl_display == egl::NO_DISPLAY {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
+        let (mut egl_major_version, mut egl_minor_version) = (0, 0);
+        let ok = egl.Initialize(egl_display, &mut egl_major_version, &mut egl_minor_version);
+        if ok == egl::FALSE {
+            return Err(Error::ConnectionFailed);
+        }
+
+        Ok(egl_display)
+    })
+}
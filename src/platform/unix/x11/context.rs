@@ -4,13 +4,19 @@
 
 use super::device::Device;
 use super::surface::Surface;
-use crate::context::ContextID;
+use crate::context::{choose_context_descriptor, create_context_with_fallbacks};
+use crate::context::{current_context_reset_status, ContextID, ContextResetStatus};
 use crate::egl;
 use crate::egl::types::EGLint;
 use crate::platform::generic::egl::context::{self, CurrentContextGuard, EGLBackedContext};
-use crate::{ContextAttributes, Error, Gl, SurfaceInfo};
+use crate::platform::generic::egl::sync::EGLFence;
+use crate::trace;
+use crate::{ContextAttributes, ContextCapabilities, ContextConfigInfo, ContextVersionRange};
+use crate::{Error, GLApi, Gl, SurfaceInfo};
 
 use std::os::raw::c_void;
+use std::os::unix::io::OwnedFd;
+use std::time::Duration;
 
 pub use crate::platform::generic::egl::context::{ContextDescriptor, NativeContext};
 
@@ -37,6 +43,12 @@ thread_local! {
 /// A context must be explicitly destroyed with `destroy_context()`, or a panic will occur.
 pub struct Context(pub(crate) EGLBackedContext);
 
+/// A fence in the command stream of a context, which can be waited on by other contexts.
+///
+/// Fences can be sent between threads. They must be explicitly destroyed with `destroy_fence()`,
+/// or a panic will occur.
+pub struct Fence(pub(crate) EGLFence);
+
 impl Device {
     /// Creates a context descriptor with the given attributes.
     ///
@@ -46,42 +58,104 @@ impl Device {
         &self,
         attributes: &ContextAttributes,
     ) -> Result<ContextDescriptor, Error> {
-        // Set environment variables as appropriate.
-        self.adapter.set_environment_variables();
+        self.enumerate_context_descriptors(attributes)?
+            .into_iter()
+            .next()
+            .ok_or(Error::NoPixelFormatFound)
+    }
 
+    /// Returns a context descriptor for every EGL config that satisfies the given attributes, in
+    /// the order that `eglChooseConfig` sorts them.
+    ///
+    /// The first candidate is the one that `create_context_descriptor()` picks.
+    pub fn enumerate_context_descriptors(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<Vec<ContextDescriptor>, Error> {
+        let gl_api = context::requested_gl_api(attributes);
         unsafe {
-            ContextDescriptor::new(
+            ContextDescriptor::enumerate(
                 self.native_connection.egl_display,
                 attributes,
                 &[
                     egl::SURFACE_TYPE as EGLint,
                     egl::WINDOW_BIT as EGLint,
                     egl::RENDERABLE_TYPE as EGLint,
-                    egl::OPENGL_BIT as EGLint,
+                    context::renderable_type_bit(gl_api, attributes.version),
                 ],
+                gl_api,
             )
         }
     }
 
+    /// Creates a context descriptor with the given attributes, using the EGL config that the
+    /// given function scores highest.
+    ///
+    /// The function returns `None` to reject a config. Ties go to the config that
+    /// `eglChooseConfig` sorts first. If every config is rejected, this returns
+    /// `NoPixelFormatFound`.
+    #[inline]
+    pub fn choose_context_descriptor<F>(
+        &self,
+        attributes: &ContextAttributes,
+        score: F,
+    ) -> Result<ContextDescriptor, Error>
+    where
+        F: FnMut(&ContextConfigInfo) -> Option<u32>,
+    {
+        choose_context_descriptor(self, attributes, score)
+    }
+
     /// Creates a new OpenGL context.
     ///
     /// The context initially has no surface attached. Until a surface is bound to it, rendering
     /// commands will fail or have no effect.
     #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                share_with = ?share_with.map(|context| self.context_id(context).0),
+                context_id = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_context(
         &mut self,
         descriptor: &ContextDescriptor,
         share_with: Option<&Context>,
     ) -> Result<Context, Error> {
-        unsafe {
-            EGLBackedContext::new(
-                self.native_connection.egl_display,
-                descriptor,
-                share_with.map(|ctx| &ctx.0),
-                self.gl_api(),
-            )
-            .map(Context)
-        }
+        GL_FUNCTIONS
+            .with(|gl| unsafe {
+                EGLBackedContext::new(
+                    gl,
+                    self.native_connection.egl_display,
+                    descriptor,
+                    share_with.map(|ctx| &ctx.0),
+                )
+                .map(Context)
+            })
+            .inspect(|context| {
+                trace::record_context(context.0.id);
+                self.resources.track_context(context.0.id);
+            })
+    }
+
+    /// Creates a new OpenGL context with the first version that the driver supports, trying each
+    /// range in turn from its highest version to its lowest.
+    ///
+    /// Returns the context along with the attributes that it was created with. If no version
+    /// works, this returns the error from the last attempt.
+    #[inline]
+    pub fn create_context_with_fallbacks(
+        &mut self,
+        ranges: &[ContextVersionRange],
+        share_with: Option<&Context>,
+    ) -> Result<(Context, ContextAttributes), Error> {
+        create_context_with_fallbacks(self, ranges, share_with)
     }
 
     /// Wraps an `EGLContext` in a native context and returns it.
@@ -94,14 +168,23 @@ impl Device {
         &self,
         native_context: NativeContext,
     ) -> Result<Context, Error> {
-        Ok(Context(EGLBackedContext::from_native_context(
-            native_context,
-        )))
+        let context = Context(EGLBackedContext::from_native_context(native_context));
+        self.resources.track_context(context.0.id);
+        Ok(context)
     }
 
     /// Destroys a context.
     ///
     /// The context must have been created on this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_context(&self, context: &mut Context) -> Result<(), Error> {
         if let Ok(Some(mut surface)) = self.unbind_surface_from_context(context) {
             self.destroy_surface(context, &mut surface)?;
@@ -109,8 +192,9 @@ impl Device {
 
         unsafe {
             context.0.destroy(self.native_connection.egl_display);
-            Ok(())
         }
+        self.resources.untrack_context(context.0.id);
+        Ok(())
     }
 
     /// Given a context, returns its underlying EGL context and attached surfaces.
@@ -131,10 +215,27 @@ impl Device {
         })
     }
 
+    /// Returns the OpenGL API flavor (OpenGL or OpenGL ES) that the given context uses.
+    #[inline]
+    pub fn context_gl_api(&self, context: &Context) -> GLApi {
+        unsafe {
+            context::context_gl_api(self.native_connection.egl_display, context.0.egl_context)
+        }
+    }
+
     /// Makes the context the current OpenGL context for this thread.
     ///
     /// After calling this function, it is valid to use OpenGL rendering commands.
     #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn make_context_current(&self, context: &Context) -> Result<(), Error> {
         unsafe { context.0.make_current(self.native_connection.egl_display) }
     }
@@ -144,6 +245,10 @@ impl Device {
     /// After calling this function, OpenGL rendering commands will fail until a new context is
     /// made current.
     #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, err(Debug, level = "debug"))
+    )]
     pub fn make_no_context_current(&self) -> Result<(), Error> {
         unsafe { context::make_no_context_current(self.native_connection.egl_display) }
     }
@@ -167,6 +272,14 @@ impl Device {
         unsafe { context_descriptor.attributes(self.native_connection.egl_display) }
     }
 
+    /// Returns the EGL config that the context descriptor selected.
+    pub fn context_descriptor_config(
+        &self,
+        context_descriptor: &ContextDescriptor,
+    ) -> Result<ContextConfigInfo, Error> {
+        unsafe { Ok(context_descriptor.config_info(self.native_connection.egl_display)) }
+    }
+
     /// Fetches the address of an OpenGL function associated with this context.
     ///
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -190,6 +303,17 @@ impl Device {
     ///
     /// If an error is returned, the surface is returned alongside it.
     #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
     pub fn bind_surface_to_context(
         &self,
         context: &mut Context,
@@ -207,6 +331,15 @@ impl Device {
     ///
     /// Any pending OpenGL commands targeting this surface will be automatically flushed, so the
     /// surface is safe to read from immediately when this function returns.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn unbind_surface_from_context(
         &self,
         context: &mut Context,
@@ -240,4 +373,85 @@ impl Device {
     pub fn context_surface_info(&self, context: &Context) -> Result<Option<SurfaceInfo>, Error> {
         context.0.surface_info()
     }
+
+    /// Returns whether the context has been lost because of a GPU reset, and if so, whether it
+    /// was responsible for the reset.
+    ///
+    /// Only contexts created with the `ROBUST` flag are guaranteed to be told about resets.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        Ok(GL_FUNCTIONS.with(current_context_reset_status))
+    }
+
+    /// Returns the version, extensions, and limits of the given context.
+    pub fn context_capabilities(&self, context: &Context) -> Result<ContextCapabilities, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(ContextCapabilities::current)
+    }
+
+    /// Inserts a fence into the command stream of the given context, and flushes the context.
+    ///
+    /// This uses `EGL_KHR_fence_sync`. If the display supports `EGL_ANDROID_native_fence_sync`,
+    /// the fence is backed by a sync file and can be exported with `export_fence_fd()`.
+    pub fn create_fence(&self, context: &Context) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        let egl_display = self.native_connection.egl_display;
+        GL_FUNCTIONS.with(|gl| unsafe { EGLFence::new(gl, egl_display).map(Fence) })
+    }
+
+    /// Makes the given context wait for a fence to be signaled before executing any further
+    /// commands.
+    ///
+    /// This waits on the GPU via `EGL_KHR_wait_sync` if available, and blocks otherwise.
+    pub fn wait_fence(&self, context: &Context, fence: &Fence) -> Result<(), Error> {
+        if fence.0.egl_display != self.native_connection.egl_display {
+            return Err(Error::IncompatibleFence);
+        }
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe { fence.0.wait() }
+    }
+
+    /// Blocks the calling thread until a fence is signaled or the timeout elapses.
+    ///
+    /// Returns true if the fence was signaled and false if the wait timed out.
+    pub fn client_wait_fence(&self, fence: &Fence, timeout: Duration) -> Result<bool, Error> {
+        if fence.0.egl_display != self.native_connection.egl_display {
+            return Err(Error::IncompatibleFence);
+        }
+        unsafe { fence.0.client_wait(timeout) }
+    }
+
+    /// Destroys a fence.
+    ///
+    /// Fences must be explicitly destroyed with this function, or a panic will occur.
+    pub fn destroy_fence(&self, fence: &mut Fence) -> Result<(), Error> {
+        if fence.0.egl_display != self.native_connection.egl_display {
+            return Err(Error::IncompatibleFence);
+        }
+        unsafe {
+            fence.0.destroy();
+        }
+        Ok(())
+    }
+
+    /// Exports a fence as a Linux sync file descriptor, via `EGL_ANDROID_native_fence_sync`.
+    ///
+    /// Each call returns a new file descriptor. If the display doesn't support native fences,
+    /// this returns `RequiredExtensionUnavailable`.
+    pub fn export_fence_fd(&self, fence: &Fence) -> Result<OwnedFd, Error> {
+        if fence.0.egl_display != self.native_connection.egl_display {
+            return Err(Error::IncompatibleFence);
+        }
+        unsafe { fence.0.export_fd() }
+    }
+
+    /// Imports a Linux sync file descriptor as a fence in the command stream of the given
+    /// context, via `EGL_ANDROID_native_fence_sync`.
+    ///
+    /// The fence takes ownership of the file descriptor. If the display doesn't support native
+    /// fences, this returns `RequiredExtensionUnavailable`.
+    pub fn import_fence_fd(&self, context: &Context, fd: OwnedFd) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe { EGLFence::from_fd(self.native_connection.egl_display, fd).map(Fence) }
+    }
 }
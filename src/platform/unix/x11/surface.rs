@@ -5,16 +5,20 @@
 use super::context::{Context, GL_FUNCTIONS};
 use super::device::Device;
 use crate::egl;
-use crate::egl::types::EGLint;
+use crate::egl::types::{EGLConfig, EGLDisplay, EGLint};
 use crate::gl;
 use crate::gl::types::{GLenum, GLuint};
 use crate::platform::generic::egl::context;
-use crate::platform::generic::egl::surface::{EGLBackedSurface, EGLSurfaceTexture};
-use crate::{Error, SurfaceAccess, SurfaceInfo, SurfaceType};
+use crate::platform::generic::egl::device::EGL_FUNCTIONS;
+use crate::platform::generic::egl::surface::{EGLBackedSurface, EGLReadback, EGLSurfaceTexture};
+use crate::platform::unix::dmabuf::DmaBufDescriptor;
+use crate::trace;
+use crate::SurfaceType;
+use crate::{Error, PixelFormat, PresentMode, SurfaceAccess, SurfaceFormat, SurfaceInfo};
 
-use euclid::default::Size2D;
-use std::marker::PhantomData;
+use euclid::default::{Rect, Size2D};
 use std::os::raw::c_void;
+use std::slice;
 use x11::xlib::{Window, XGetGeometry};
 
 // FIXME(pcwalton): Is this right, or should it be `TEXTURE_EXTERNAL_OES`?
@@ -64,37 +68,66 @@ impl Device {
     ///
     /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
     /// up in a `SurfaceTexture` for reading by other contexts.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = tracing::field::Empty,
+                width = tracing::field::Empty,
+                height = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_surface(
         &mut self,
         context: &Context,
-        _: SurfaceAccess,
+        access: SurfaceAccess,
         surface_type: SurfaceType<NativeWidget>,
     ) -> Result<Surface, Error> {
-        match surface_type {
-            SurfaceType::Generic { size } => self.create_generic_surface(context, &size),
+        let surface = match surface_type {
+            SurfaceType::Generic {
+                size,
+                samples,
+                format,
+            } => self.create_generic_surface(context, &size, access, samples, format),
             SurfaceType::Widget { native_widget } => unsafe {
                 self.create_window_surface(context, native_widget.window)
             },
-        }
+        }?;
+        let surface_info = self.surface_info(&surface);
+        trace::record_surface(&surface_info);
+        self.resources.track_surface(&surface_info);
+        Ok(surface)
     }
 
     fn create_generic_surface(
         &mut self,
         context: &Context,
         size: &Size2D<i32>,
+        access: SurfaceAccess,
+        samples: u8,
+        format: SurfaceFormat,
     ) -> Result<Surface, Error> {
         let _guard = self.temporarily_make_context_current(context)?;
         let context_descriptor = self.context_descriptor(context);
         let context_attributes = self.context_descriptor_attributes(&context_descriptor);
         GL_FUNCTIONS.with(|gl| {
-            Ok(Surface(EGLBackedSurface::new_generic(
+            EGLBackedSurface::new_generic(
                 gl,
                 self.native_connection.egl_display,
                 context.0.egl_context,
                 context.0.id,
                 &context_attributes,
                 size,
-            )))
+                access,
+                samples,
+                format,
+            )
+            .map(Surface)
         })
     }
 
@@ -111,7 +144,12 @@ impl Device {
         let egl_config =
             context::egl_config_from_id(self.native_connection.egl_display, egl_config_id);
 
-        let display_guard = self.native_connection.lock_display();
+        // XCB connections have no Xlib display to query the window geometry with, so ask EGL for
+        // the size once the surface exists instead.
+        let display_guard = match self.native_connection.lock_display() {
+            Some(display_guard) => display_guard,
+            None => return Ok(self.create_xcb_window_surface(context, egl_config, x11_window)),
+        };
         let (mut root_window, mut x, mut y, mut width, mut height) = (0, 0, 0, 0, 0);
         let (mut border_width, mut depth) = (0, 0);
         XGetGeometry(
@@ -136,6 +174,71 @@ impl Device {
         )))
     }
 
+    unsafe fn create_xcb_window_surface(
+        &self,
+        context: &Context,
+        egl_config: EGLConfig,
+        x11_window: Window,
+    ) -> Surface {
+        let egl_display = self.native_connection.egl_display;
+
+        // `EGL_EXT_platform_xcb` takes a pointer to an `xcb_window_t`, which is 32 bits wide.
+        let mut xcb_window = x11_window as u32;
+        let mut surface = EGLBackedSurface::new_window(
+            egl_display,
+            egl_config,
+            &mut xcb_window as *mut u32 as *mut c_void,
+            context.0.id,
+            &Size2D::zero(),
+        );
+
+        let egl_surface = surface.egl_surfaces().draw;
+        let (mut width, mut height) = (0, 0);
+        EGL_FUNCTIONS.with(|egl| {
+            egl.QuerySurface(egl_display, egl_surface, egl::WIDTH as EGLint, &mut width);
+            egl.QuerySurface(egl_display, egl_surface, egl::HEIGHT as EGLint, &mut height);
+        });
+        surface.size = Size2D::new(width, height);
+        Surface(surface)
+    }
+
+    /// Creates a generic surface that renders into existing DMA-BUFs, via
+    /// `EGL_EXT_image_dma_buf_import`.
+    ///
+    /// The surface can be rendered to with the given context, or wrapped in a `SurfaceTexture`
+    /// for sampling, just like surfaces created with `create_surface()`. The file descriptors in
+    /// the descriptor are closed once the buffers have been imported; the surface keeps the
+    /// buffers themselves alive until it is destroyed.
+    ///
+    /// Only RGB formats can be imported; other formats, such as YUV formats, return a
+    /// `SurfaceImportFailed` error. If the display doesn't support the extension, or the
+    /// descriptor has a format modifier and `EGL_EXT_image_dma_buf_import_modifiers` is missing,
+    /// this returns `RequiredExtensionUnavailable`.
+    pub fn create_surface_from_dmabuf(
+        &mut self,
+        context: &Context,
+        descriptor: DmaBufDescriptor,
+        access: SurfaceAccess,
+    ) -> Result<Surface, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        let context_descriptor = self.context_descriptor(context);
+        let context_attributes = self.context_descriptor_attributes(&context_descriptor);
+        let surface = GL_FUNCTIONS.with(|gl| {
+            EGLBackedSurface::new_from_dmabuf(
+                gl,
+                self.native_connection.egl_display,
+                context.0.egl_context,
+                context.0.id,
+                &context_attributes,
+                descriptor,
+                access,
+            )
+            .map(Surface)
+        })?;
+        self.resources.track_surface(&self.surface_info(&surface));
+        Ok(surface)
+    }
+
     /// Creates a surface texture from an existing generic surface for use with the given context.
     ///
     /// The surface texture is local to the supplied context and takes ownership of the surface.
@@ -146,6 +249,17 @@ impl Device {
     /// in another context.
     ///
     /// Calling this method on a widget surface returns a `WidgetAttached` error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
     pub fn create_surface_texture(
         &self,
         context: &mut Context,
@@ -156,8 +270,13 @@ impl Device {
             Err(err) => return Err((err, surface)),
         };
 
+        let surface_info = self.surface_info(&surface);
         GL_FUNCTIONS.with(|gl| match surface.0.to_surface_texture(gl) {
-            Ok(surface_texture) => Ok(SurfaceTexture(surface_texture)),
+            Ok(surface_texture) => {
+                self.resources
+                    .track_surface_texture(context.0.id, &surface_info);
+                Ok(SurfaceTexture(surface_texture))
+            }
             Err((err, surface)) => Err((err, Surface(surface))),
         })
     }
@@ -169,14 +288,28 @@ impl Device {
     ///
     /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
     /// the `drop` method.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_surface(
         &self,
         context: &mut Context,
         surface: &mut Surface,
     ) -> Result<(), Error> {
+        let surface_id = self.surface_info(surface).id;
         GL_FUNCTIONS.with(|gl| {
             let egl_display = self.native_connection.egl_display;
             surface.0.destroy(gl, egl_display, context.0.id)?;
+            self.resources.untrack_surface(surface_id);
             Ok(())
         })
     }
@@ -194,7 +327,12 @@ impl Device {
         surface_texture: SurfaceTexture,
     ) -> Result<Surface, (Error, SurfaceTexture)> {
         match self.temporarily_make_context_current(context) {
-            Ok(_guard) => GL_FUNCTIONS.with(|gl| Ok(Surface(surface_texture.0.destroy(gl)))),
+            Ok(_guard) => GL_FUNCTIONS.with(|gl| {
+                let surface = Surface(surface_texture.0.destroy(gl));
+                self.resources
+                    .untrack_surface_texture(self.surface_info(&surface).id);
+                Ok(surface)
+            }),
             Err(err) => Err((err, surface_texture)),
         }
     }
@@ -206,10 +344,111 @@ impl Device {
     ///
     /// The supplied context must match the context the surface was created with, or an
     /// `IncompatibleSurface` error is returned.
+    ///
+    /// Generic surfaces always return `NoWidgetAttached`, but multisampled ones are resolved into
+    /// their texture first.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn present_surface(&self, context: &Context, surface: &mut Surface) -> Result<(), Error> {
+        GL_FUNCTIONS.with(|gl| {
+            surface.0.present(
+                gl,
+                self.native_connection.egl_display,
+                context.0.egl_context,
+            )
+        })
+    }
+
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// changed since the last present.
+    ///
+    /// The rectangles are in framebuffer coordinates, with the origin at the bottom left. This
+    /// uses `EGL_KHR_swap_buffers_with_damage` or `EGL_EXT_swap_buffers_with_damage` if available,
+    /// and presents the whole surface otherwise.
+    pub fn present_surface_with_damage(
+        &self,
+        context: &Context,
+        surface: &mut Surface,
+        damage: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        GL_FUNCTIONS.with(|gl| {
+            surface.0.present_with_damage(
+                gl,
+                self.native_connection.egl_display,
+                context.0.egl_context,
+                damage,
+            )
+        })
+    }
+
+    /// Reads back the pixels in the given rectangle of a surface.
+    ///
+    /// The rectangle has its origin at the top left of the surface, and the returned rows are
+    /// ordered from top to bottom. The surface must not be bound to a context.
+    pub fn read_surface_pixels(
+        &self,
+        context: &Context,
+        surface: &Surface,
+        rect: Rect<i32>,
+        format: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        if surface.0.context_id != context.0.id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        GL_FUNCTIONS.with(|gl| {
+            surface.0.read_pixels(
+                gl,
+                self.native_connection.egl_display,
+                context.0.egl_context,
+                rect,
+                format,
+            )
+        })
+    }
+
+    /// Returns the age of the back buffer of a widget surface, via `EGL_EXT_buffer_age`.
+    ///
+    /// A value of 0 means the contents of the back buffer are undefined. This is always the case
+    /// if `EGL_EXT_buffer_age` isn't supported.
+    pub fn surface_buffer_age(&self, context: &Context, surface: &Surface) -> Result<u32, Error> {
         surface
             .0
-            .present(self.native_connection.egl_display, context.0.egl_context)
+            .buffer_age(self.native_connection.egl_display, context.0.egl_context)
+    }
+
+    /// Sets how `present_surface()` synchronizes with the display for the given widget surface.
+    ///
+    /// X11 supports `Fifo` and `Immediate`. `Mailbox` returns `UnsupportedOnThisPlatform`.
+    pub fn set_present_mode(
+        &self,
+        context: &Context,
+        surface: &Surface,
+        mode: PresentMode,
+    ) -> Result<(), Error> {
+        if surface.0.context_id != context.0.id {
+            return Err(Error::IncompatibleSurface);
+        }
+        let interval = match mode {
+            PresentMode::Fifo => 1,
+            PresentMode::Immediate => 0,
+            PresentMode::Mailbox => return Err(Error::UnsupportedOnThisPlatform),
+        };
+        surface.0.set_swap_interval(
+            self.native_connection.egl_display,
+            context.0.egl_context,
+            interval,
+        )
     }
 
     /// Resizes a widget surface.
@@ -219,14 +458,88 @@ impl Device {
         surface: &mut Surface,
         size: Size2D<i32>,
     ) -> Result<(), Error> {
+        let surface_id = self.surface_info(surface).id;
         surface.0.size = size;
+        self.resources
+            .update_surface(surface_id, &self.surface_info(surface));
         Ok(())
     }
 
     /// Returns a pointer to the underlying surface data for reading or writing by the CPU.
-    #[inline]
-    pub fn lock_surface_data<'s>(&self, _: &'s mut Surface) -> Result<SurfaceDataGuard<'s>, Error> {
-        Err(Error::Unimplemented)
+    ///
+    /// Only generic surfaces created with `GPUCPU` or `GPUCPUWriteCombined` access can be locked;
+    /// other surfaces return `SurfaceDataInaccessible`. The surface data is read back from the GPU
+    /// when the surface is locked, and changes are uploaded to the GPU when the returned guard is
+    /// dropped.
+    pub fn lock_surface_data<'s>(
+        &self,
+        surface: &'s mut Surface,
+    ) -> Result<SurfaceDataGuard<'s>, Error> {
+        let egl_display = self.native_connection.egl_display;
+        let (ptr, len, stride) = GL_FUNCTIONS.with(|gl| surface.0.lock_data(gl, egl_display))?;
+        Ok(SurfaceDataGuard {
+            surface,
+            egl_display,
+            stride,
+            ptr,
+            len,
+        })
+    }
+
+    /// Starts reading back the pixels of a surface asynchronously, without stalling the CPU.
+    ///
+    /// The pixels are read into a pixel buffer object, and become available from the returned
+    /// `Readback` once the GPU has finished the read. The surface must not be bound to a context,
+    /// but it may be rendered to again or destroyed as soon as this returns.
+    ///
+    /// This requires OpenGL 3.0 or OpenGL ES 3.0; older contexts return `UnsupportedGLVersion`.
+    /// The supplied context must match the context the surface was created with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn begin_surface_readback(
+        &self,
+        context: &Context,
+        surface: &Surface,
+    ) -> Result<Readback, Error> {
+        if surface.0.context_id != context.0.id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let egl_display = self.native_connection.egl_display;
+        let readback =
+            GL_FUNCTIONS.with(|gl| surface.0.begin_readback(gl, egl_display, &context.0))?;
+        Ok(Readback {
+            readback,
+            egl_display,
+        })
+    }
+
+    /// Exports a generic surface as a set of DMA-BUF file descriptors, via
+    /// `EGL_MESA_image_dma_buf_export`.
+    ///
+    /// The surface must not be bound to a context. The context is flushed, but the GPU may still
+    /// be rendering to the surface when this returns; create a fence with `create_fence()` first
+    /// and share it with the consumer of the buffer if it needs to wait for that rendering.
+    ///
+    /// Widget surfaces can't be exported, and return `WidgetAttached`. If the display doesn't
+    /// support the extension, this returns `RequiredExtensionUnavailable`. The supplied context
+    /// must match the context the surface was created with, or an `IncompatibleSurface` error is
+    /// returned.
+    pub fn export_surface_dmabuf(
+        &self,
+        context: &Context,
+        surface: &Surface,
+    ) -> Result<DmaBufDescriptor, Error> {
+        if surface.0.context_id != context.0.id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        GL_FUNCTIONS.with(|gl| {
+            surface.0.export_dmabuf(
+                gl,
+                self.native_connection.egl_display,
+                context.0.egl_context,
+            )
+        })
     }
 
     /// Returns the OpenGL texture target needed to read from this surface texture.
@@ -258,5 +571,81 @@ impl Device {
 
 /// Represents the CPU view of the pixel data of this surface.
 pub struct SurfaceDataGuard<'a> {
-    phantom: PhantomData<&'a ()>,
+    surface: &'a mut Surface,
+    egl_display: EGLDisplay,
+    stride: usize,
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl<'a> SurfaceDataGuard<'a> {
+    /// Returns the number of bytes per row of the surface.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns a mutable slice of the pixel data in this surface, in the surface's format.
+    ///
+    /// Rows are stored bottom to top, as in OpenGL.
+    #[inline]
+    pub fn data(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<'a> Drop for SurfaceDataGuard<'a> {
+    fn drop(&mut self) {
+        GL_FUNCTIONS.with(|gl| self.surface.0.unlock_data(gl, self.egl_display))
+    }
+}
+
+/// An asynchronous readback of the pixels of a surface, started with `begin_surface_readback()`.
+///
+/// The pixels are in the surface's format, with rows stored bottom to top, as in OpenGL. The
+/// readback belongs to the context that started it. If that context is destroyed first, mapping
+/// the readback fails and dropping it does nothing, since its pixel buffer went with the context.
+pub struct Readback {
+    readback: EGLReadback,
+    egl_display: EGLDisplay,
+}
+
+impl Readback {
+    /// Returns the size of the surface that was read back, in pixels.
+    #[inline]
+    pub fn size(&self) -> Size2D<i32> {
+        self.readback.size()
+    }
+
+    /// Returns the number of bytes per row of the pixel data.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.readback.stride()
+    }
+
+    /// Returns the pixel data if the GPU has finished reading it back, or `None` otherwise.
+    ///
+    /// This never blocks.
+    pub fn try_map(&mut self) -> Result<Option<&[u8]>, Error> {
+        let egl_display = self.egl_display;
+        let mapped = GL_FUNCTIONS.with(|gl| self.readback.map(gl, egl_display, 0))?;
+        Ok(if mapped {
+            Some(self.readback.data())
+        } else {
+            None
+        })
+    }
+
+    /// Blocks until the GPU has finished reading back the pixel data, and returns it.
+    pub fn wait(&mut self) -> Result<&[u8], Error> {
+        let egl_display = self.egl_display;
+        GL_FUNCTIONS.with(|gl| self.readback.map(gl, egl_display, gl::TIMEOUT_IGNORED))?;
+        Ok(self.readback.data())
+    }
+}
+
+impl Drop for Readback {
+    fn drop(&mut self) {
+        GL_FUNCTIONS.with(|gl| self.readback.destroy(gl, self.egl_display))
+    }
 }
@@ -9,6 +9,7 @@
 
 use super::device::{Adapter, Device, NativeDevice, VendorPreference};
 use super::surface::NativeWidget;
+use crate::connection::BackendKind;
 use crate::egl::types::{EGLDisplay, EGLNativeWindowType};
 use crate::Error;
 use crate::GLApi;
@@ -42,11 +43,35 @@ pub struct NativeConnection;
 
 impl Connection {
     /// Connects to the default display.
+    ///
+    /// Returns `ConnectionFailed` if the `SURFMAN_BACKEND` environment variable names other
+    /// backends but not this one.
     #[inline]
     pub fn new() -> Result<Connection, Error> {
+        BackendKind::ANGLE.check_preference_from_env()?;
+        Connection::connect()
+    }
+
+    fn connect() -> Result<Connection, Error> {
         Ok(Connection)
     }
 
+    /// Connects to the default display if `BackendKind::ANGLE` is among the given backends, and
+    /// returns `UnsupportedOnThisPlatform` otherwise.
+    #[inline]
+    pub fn new_with_preference(preference: &[BackendKind]) -> Result<Connection, Error> {
+        if !preference.contains(&BackendKind::ANGLE) {
+            return Err(Error::UnsupportedOnThisPlatform);
+        }
+        Connection::connect()
+    }
+
+    /// Returns which backend this connection uses, which is always `BackendKind::ANGLE`.
+    #[inline]
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::ANGLE
+    }
+
     /// An alias for `Connection::new()`, present for consistency with other backends.
     #[inline]
     pub unsafe fn from_native_connection(_: NativeConnection) -> Result<Connection, Error> {
@@ -4,19 +4,26 @@
 
 use super::device::Device;
 use super::surface::{Surface, Synchronization, Win32Objects};
-use crate::context::{ContextID, CREATE_CONTEXT_MUTEX};
+use crate::context::CREATE_CONTEXT_MUTEX;
+use crate::context::{choose_context_descriptor, create_context_with_fallbacks};
+use crate::context::{current_context_reset_status, ContextID, ContextResetStatus};
 use crate::egl;
 use crate::egl::types::{EGLConfig, EGLContext, EGLint};
 use crate::platform::generic::egl::context::{self, CurrentContextGuard};
 use crate::platform::generic::egl::device::EGL_FUNCTIONS;
 use crate::platform::generic::egl::error::ToWindowingApiError;
 use crate::platform::generic::egl::surface::ExternalEGLSurfaces;
+use crate::platform::generic::egl::sync::EGLFence;
+use crate::resources;
 use crate::surface::Framebuffer;
-use crate::{ContextAttributes, Error, Gl, SurfaceInfo};
+use crate::trace;
+use crate::{ContextAttributes, ContextCapabilities, ContextConfigInfo, ContextVersionRange};
+use crate::{Error, GLApi, Gl, SurfaceInfo};
 
 use std::mem;
 use std::os::raw::c_void;
 use std::thread;
+use std::time::Duration;
 use winapi::shared::winerror::S_OK;
 use winapi::um::winbase::INFINITE;
 
@@ -50,11 +57,23 @@ pub struct Context {
     context_is_owned: bool,
 }
 
+/// A fence in the command stream of a context, which can be waited on by other contexts.
+///
+/// Fences can be sent between threads. They must be explicitly destroyed with `destroy_fence()`,
+/// or a panic will occur.
+pub struct Fence(pub(crate) EGLFence);
+
 impl Drop for Context {
     #[inline]
     fn drop(&mut self) {
         if self.egl_context != egl::NO_CONTEXT && !thread::panicking() {
-            panic!("Contexts must be destroyed explicitly with `destroy_context`!")
+            panic!(
+                "{}",
+                resources::leaked_context_message(
+                    "Contexts must be destroyed explicitly with `destroy_context`!",
+                    self.id,
+                )
+            )
         }
     }
 }
@@ -68,8 +87,22 @@ impl Device {
         &self,
         attributes: &ContextAttributes,
     ) -> Result<ContextDescriptor, Error> {
+        self.enumerate_context_descriptors(attributes)?
+            .into_iter()
+            .next()
+            .ok_or(Error::NoPixelFormatFound)
+    }
+
+    /// Returns a context descriptor for every EGL config that satisfies the given attributes, in
+    /// the order that `eglChooseConfig` sorts them.
+    ///
+    /// The first candidate is the one that `create_context_descriptor()` picks.
+    pub fn enumerate_context_descriptors(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<Vec<ContextDescriptor>, Error> {
         unsafe {
-            ContextDescriptor::new(
+            ContextDescriptor::enumerate(
                 self.egl_display,
                 attributes,
                 &[
@@ -80,14 +113,45 @@ impl Device {
                     egl::RENDERABLE_TYPE as EGLint,
                     egl::OPENGL_ES2_BIT as EGLint,
                 ],
+                self.gl_api(),
             )
         }
     }
 
+    /// Creates a context descriptor with the given attributes, using the EGL config that the
+    /// given function scores highest.
+    ///
+    /// The function returns `None` to reject a config. Ties go to the config that
+    /// `eglChooseConfig` sorts first. If every config is rejected, this returns
+    /// `NoPixelFormatFound`.
+    #[inline]
+    pub fn choose_context_descriptor<F>(
+        &self,
+        attributes: &ContextAttributes,
+        score: F,
+    ) -> Result<ContextDescriptor, Error>
+    where
+        F: FnMut(&ContextConfigInfo) -> Option<u32>,
+    {
+        choose_context_descriptor(self, attributes, score)
+    }
+
     /// Creates a new OpenGL context.
     ///
     /// The context initially has no surface attached. Until a surface is bound to it, rendering
     /// commands will fail or have no effect.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                share_with = ?share_with.map(|context| self.context_id(context).0),
+                context_id = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_context(
         &mut self,
         descriptor: &ContextDescriptor,
@@ -99,20 +163,50 @@ impl Device {
                 self.egl_display,
                 descriptor,
                 share_with.map_or(egl::NO_CONTEXT, |ctx| ctx.egl_context),
-                self.gl_api(),
             )?;
 
+            if descriptor.debug {
+                let result = GL_FUNCTIONS.with(|gl| {
+                    context::install_debug_message_callback(
+                        gl,
+                        self.egl_display,
+                        egl_context,
+                        *next_context_id,
+                    )
+                });
+                if let Err(err) = result {
+                    EGL_FUNCTIONS.with(|egl| egl.DestroyContext(self.egl_display, egl_context));
+                    return Err(err);
+                }
+            }
+
             let context = Context {
                 egl_context,
                 id: *next_context_id,
                 framebuffer: Framebuffer::None,
                 context_is_owned: true,
             };
+            trace::record_context(context.id);
+            self.resources.track_context(context.id);
             next_context_id.0 += 1;
             Ok(context)
         }
     }
 
+    /// Creates a new OpenGL context with the first version that the driver supports, trying each
+    /// range in turn from its highest version to its lowest.
+    ///
+    /// Returns the context along with the attributes that it was created with. If no version
+    /// works, this returns the error from the last attempt.
+    #[inline]
+    pub fn create_context_with_fallbacks(
+        &mut self,
+        ranges: &[ContextVersionRange],
+        share_with: Option<&Context>,
+    ) -> Result<(Context, ContextAttributes), Error> {
+        create_context_with_fallbacks(self, ranges, share_with)
+    }
+
     /// Wraps a native `EGLContext` in a context object.
     ///
     /// The underlying `EGLContext` is not retained, as there is no way to do this in the EGL API.
@@ -134,6 +228,7 @@ impl Device {
             }),
             context_is_owned: false,
         };
+        self.resources.track_context(context.id);
         next_context_id.0 += 1;
 
         Ok(context)
@@ -142,6 +237,15 @@ impl Device {
     /// Destroys a context.
     ///
     /// The context must have been created on this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_context(&self, context: &mut Context) -> Result<(), Error> {
         if context.egl_context == egl::NO_CONTEXT {
             return Ok(());
@@ -168,6 +272,7 @@ impl Device {
             context.egl_context = egl::NO_CONTEXT;
         });
 
+        self.resources.untrack_context(context.id);
         Ok(())
     }
 
@@ -180,9 +285,24 @@ impl Device {
         }
     }
 
+    /// Returns the OpenGL API flavor (OpenGL or OpenGL ES) that the given context uses.
+    #[inline]
+    pub fn context_gl_api(&self, context: &Context) -> GLApi {
+        unsafe { context::context_gl_api(self.egl_display, context.egl_context) }
+    }
+
     /// Makes the context the current OpenGL context for this thread.
     ///
     /// After calling this function, it is valid to use OpenGL rendering commands.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn make_context_current(&self, context: &Context) -> Result<(), Error> {
         unsafe {
             let (egl_draw_surface, egl_read_surface) = match context.framebuffer {
@@ -199,7 +319,7 @@ impl Device {
                     context.egl_context,
                 );
                 if result == egl::FALSE {
-                    let err = egl.GetError().to_windowing_api_error();
+                    let err = egl.GetError().to_native_error("eglMakeCurrent");
                     return Err(Error::MakeCurrentFailed(err));
                 }
                 Ok(())
@@ -211,6 +331,10 @@ impl Device {
     ///
     /// After calling this function, OpenGL rendering commands will fail until a new context is
     /// made current.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, err(Debug, level = "debug"))
+    )]
     pub fn make_no_context_current(&self) -> Result<(), Error> {
         unsafe { context::make_no_context_current(self.egl_display) }
     }
@@ -237,6 +361,14 @@ impl Device {
         unsafe { context_descriptor.attributes(self.egl_display) }
     }
 
+    /// Returns the EGL config that the context descriptor selected.
+    pub fn context_descriptor_config(
+        &self,
+        context_descriptor: &ContextDescriptor,
+    ) -> Result<ContextConfigInfo, Error> {
+        unsafe { Ok(context_descriptor.config_info(self.egl_display)) }
+    }
+
     /// Fetches the address of an OpenGL function associated with this context.
     ///
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -267,6 +399,17 @@ impl Device {
     /// `unbind_surface_from_context`.
     ///
     /// If an error is returned, the surface is returned alongside it.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
     pub fn bind_surface_to_context(
         &self,
         context: &mut Context,
@@ -319,6 +462,15 @@ impl Device {
     ///
     /// Any pending OpenGL commands targeting this surface will be automatically flushed, so the
     /// surface is safe to read from immediately when this function returns.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn unbind_surface_from_context(
         &self,
         context: &mut Context,
@@ -368,6 +520,65 @@ impl Device {
         }
     }
 
+    /// Returns whether the context has been lost because of a GPU reset, and if so, whether it
+    /// was responsible for the reset.
+    ///
+    /// Only contexts created with the `ROBUST` flag are guaranteed to be told about resets.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        Ok(GL_FUNCTIONS.with(current_context_reset_status))
+    }
+
+    /// Returns the version, extensions, and limits of the given context.
+    pub fn context_capabilities(&self, context: &Context) -> Result<ContextCapabilities, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(ContextCapabilities::current)
+    }
+
+    /// Inserts a fence into the command stream of the given context, and flushes the context.
+    ///
+    /// This uses `EGL_KHR_fence_sync`, and falls back to waiting for the context's commands to
+    /// finish if that extension is unavailable.
+    pub fn create_fence(&self, context: &Context) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| unsafe { EGLFence::new(gl, self.egl_display).map(Fence) })
+    }
+
+    /// Makes the given context wait for a fence to be signaled before executing any further
+    /// commands.
+    ///
+    /// This waits on the GPU via `EGL_KHR_wait_sync` if available, and blocks otherwise.
+    pub fn wait_fence(&self, context: &Context, fence: &Fence) -> Result<(), Error> {
+        if fence.0.egl_display != self.egl_display {
+            return Err(Error::IncompatibleFence);
+        }
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe { fence.0.wait() }
+    }
+
+    /// Blocks the calling thread until a fence is signaled or the timeout elapses.
+    ///
+    /// Returns true if the fence was signaled and false if the wait timed out.
+    pub fn client_wait_fence(&self, fence: &Fence, timeout: Duration) -> Result<bool, Error> {
+        if fence.0.egl_display != self.egl_display {
+            return Err(Error::IncompatibleFence);
+        }
+        unsafe { fence.0.client_wait(timeout) }
+    }
+
+    /// Destroys a fence.
+    ///
+    /// Fences must be explicitly destroyed with this function, or a panic will occur.
+    pub fn destroy_fence(&self, fence: &mut Fence) -> Result<(), Error> {
+        if fence.0.egl_display != self.egl_display {
+            return Err(Error::IncompatibleFence);
+        }
+        unsafe {
+            fence.0.destroy();
+        }
+        Ok(())
+    }
+
     /// Given a context, returns its underlying EGL context and attached surfaces.
     pub fn native_context(&self, context: &Context) -> NativeContext {
         let (egl_draw_surface, egl_read_surface) = match context.framebuffer {
@@ -3,13 +3,17 @@
 //! A thread-local handle to the device.
 
 use super::connection::Connection;
+use super::context::GL_FUNCTIONS;
 use crate::egl;
 use crate::egl::types::{EGLAttrib, EGLDeviceEXT, EGLDisplay, EGLint};
-use crate::platform::generic::egl::device::EGL_FUNCTIONS;
+use crate::platform::generic::egl::context::CurrentContextGuard;
+use crate::platform::generic::egl::device::{self, EGL_FUNCTIONS};
 use crate::platform::generic::egl::ffi::EGL_DEVICE_EXT;
 use crate::platform::generic::egl::ffi::{EGL_D3D11_DEVICE_ANGLE, EGL_EXTENSION_FUNCTIONS};
 use crate::platform::generic::egl::ffi::{EGL_NO_DEVICE_EXT, EGL_PLATFORM_DEVICE_EXT};
-use crate::{Error, GLApi};
+use crate::resources::ResourceTracker;
+use crate::LiveResource;
+use crate::{AdapterInfo, ContextAttributeFlags, ContextAttributes, Error, GLApi, GLVersion};
 
 use std::cell::{RefCell, RefMut};
 use std::mem;
@@ -19,7 +23,8 @@ use winapi::shared::dxgi::{self, IDXGIAdapter, IDXGIDevice, IDXGIFactory1};
 use winapi::shared::minwindef::UINT;
 use winapi::shared::winerror::{self, S_OK};
 use winapi::um::d3d11::{D3D11CreateDevice, ID3D11Device, D3D11_SDK_VERSION};
-use winapi::um::d3dcommon::{D3D_DRIVER_TYPE, D3D_DRIVER_TYPE_UNKNOWN, D3D_FEATURE_LEVEL_9_3};
+use winapi::um::d3dcommon::D3D_FEATURE_LEVEL_9_3;
+use winapi::um::d3dcommon::{D3D_DRIVER_TYPE, D3D_DRIVER_TYPE_UNKNOWN, D3D_DRIVER_TYPE_WARP};
 use winapi::Interface;
 use wio::com::ComPtr;
 
@@ -46,6 +51,7 @@ pub struct Device {
     pub(crate) d3d11_device: ComPtr<ID3D11Device>,
     pub(crate) d3d_driver_type: D3D_DRIVER_TYPE,
     pub(crate) display_is_owned: bool,
+    pub(crate) resources: ResourceTracker,
 }
 
 pub(crate) enum VendorPreference {
@@ -215,6 +221,7 @@ impl Device {
                     d3d11_device,
                     d3d_driver_type,
                     display_is_owned: true,
+                    resources: ResourceTracker::new(),
                 })
             })
         }
@@ -228,6 +235,7 @@ impl Device {
                 d3d11_device: ComPtr::from_raw(native_device.d3d11_device),
                 d3d_driver_type: native_device.d3d_driver_type,
                 display_is_owned: false,
+                resources: ResourceTracker::new(),
             })
         }
     }
@@ -267,6 +275,7 @@ impl Device {
                 d3d11_device: ComPtr::from_raw(d3d11_device),
                 d3d_driver_type: D3D_DRIVER_TYPE_UNKNOWN,
                 display_is_owned: false,
+                resources: ResourceTracker::new(),
             })
         }
     }
@@ -317,6 +326,41 @@ impl Device {
     pub fn gl_api(&self) -> GLApi {
         GLApi::GLES
     }
+
+    /// Returns information about the adapter that this device renders with.
+    ///
+    /// This creates and destroys a temporary context in order to query the OpenGL strings.
+    pub fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        let context_descriptor = self.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(2, 0),
+            flags: ContextAttributeFlags::empty(),
+        })?;
+
+        // Destroying the temporary context leaves no context current, so restore the caller's
+        // context afterward.
+        let _guard = CurrentContextGuard::new();
+        let mut context = self.create_context(&context_descriptor, None)?;
+        let adapter_info = self.make_context_current(&context).map(|()| {
+            let driver_name = unsafe { device::display_driver_name(self.egl_display) };
+            let display_extensions = unsafe { device::display_extensions(self.egl_display) };
+            let software = self.d3d_driver_type == D3D_DRIVER_TYPE_WARP;
+            GL_FUNCTIONS.with(|gl| AdapterInfo {
+                display_extensions,
+                ..AdapterInfo::current(gl, driver_name, software)
+            })
+        });
+        self.destroy_context(&mut context)?;
+        adapter_info
+    }
+
+    /// Returns the contexts, surfaces, and surface textures that this device has created and
+    /// that have not yet been destroyed.
+    ///
+    /// Resources are only tracked in debug builds; in release builds this is always empty.
+    #[inline]
+    pub fn live_resources(&self) -> Vec<LiveResource> {
+        self.resources.live_resources()
+    }
 }
 
 impl Drop for Device {
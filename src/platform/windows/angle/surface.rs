@@ -16,9 +16,14 @@ use crate::platform::generic::egl::ffi::EGL_D3D_TEXTURE_2D_SHARE_HANDLE_ANGLE;
 use crate::platform::generic::egl::ffi::EGL_D3D_TEXTURE_ANGLE;
 use crate::platform::generic::egl::ffi::EGL_DXGI_KEYED_MUTEX_ANGLE;
 use crate::platform::generic::egl::ffi::EGL_EXTENSION_FUNCTIONS;
-use crate::{Error, SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType};
-
-use euclid::default::Size2D;
+use crate::resources;
+use crate::trace;
+use crate::{
+    Error, PixelFormat, PresentMode, SurfaceAccess, SurfaceFormat, SurfaceID, SurfaceInfo,
+    SurfaceType,
+};
+
+use euclid::default::{Rect, Size2D};
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
 use std::os::raw::c_void;
@@ -86,7 +91,13 @@ impl Debug for Surface {
 impl Drop for Surface {
     fn drop(&mut self) {
         if self.egl_surface != egl::NO_SURFACE && !thread::panicking() {
-            panic!("Should have destroyed the surface first with `destroy_surface()`!")
+            panic!(
+                "{}",
+                resources::leaked_surface_message(
+                    "Should have destroyed the surface first with `destroy_surface()`!",
+                    self.id(),
+                )
+            )
         }
     }
 }
@@ -127,18 +138,45 @@ impl Device {
     ///
     /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
     /// up in a `SurfaceTexture` for reading by other contexts.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = tracing::field::Empty,
+                width = tracing::field::Empty,
+                height = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_surface(
         &mut self,
         context: &Context,
         _: SurfaceAccess,
         surface_type: SurfaceType<NativeWidget>,
     ) -> Result<Surface, Error> {
-        match surface_type {
-            SurfaceType::Generic { ref size } => self.create_pbuffer_surface(context, size, None),
+        let surface = match surface_type {
+            // Multisampled and non-RGBA8 generic surfaces are only supported on the Linux EGL
+            // backends.
+            SurfaceType::Generic {
+                samples, format, ..
+            } if samples > 1 || format != SurfaceFormat::RGBA8 => {
+                Err(Error::UnsupportedOnThisPlatform)
+            }
+            SurfaceType::Generic { ref size, .. } => {
+                self.create_pbuffer_surface(context, size, None)
+            }
             SurfaceType::Widget { ref native_widget } => {
                 self.create_window_surface(context, native_widget)
             }
-        }
+        }?;
+        let surface_info = self.surface_info(&surface);
+        trace::record_surface(&surface_info);
+        self.resources.track_surface(&surface_info);
+        Ok(surface)
     }
 
     #[allow(non_snake_case)]
@@ -243,7 +281,9 @@ impl Device {
         size: &Size2D<i32>,
         texture: ComPtr<d3d11::ID3D11Texture2D>,
     ) -> Result<Surface, Error> {
-        self.create_pbuffer_surface(context, size, Some(texture))
+        let surface = self.create_pbuffer_surface(context, size, Some(texture))?;
+        self.resources.track_surface(&self.surface_info(&surface));
+        Ok(surface)
     }
 
     fn create_window_surface(
@@ -304,6 +344,17 @@ impl Device {
     ///
     /// Calling this method on a widget surface returns a `WidgetAttached` error.
     #[allow(non_snake_case)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
     pub fn create_surface_texture(
         &self,
         context: &mut Context,
@@ -341,8 +392,10 @@ impl Device {
                     pbuffer_attributes.as_ptr(),
                 );
                 if local_egl_surface == egl::NO_SURFACE {
-                    let windowing_api_error = egl.GetError().to_windowing_api_error();
-                    return Err((Error::SurfaceImportFailed(windowing_api_error), surface));
+                    let err = egl
+                        .GetError()
+                        .to_native_error("eglCreatePbufferFromClientBuffer");
+                    return Err((Error::SurfaceImportFailed(err), surface));
                 }
 
                 let mut local_keyed_mutex: *mut IDXGIKeyedMutex = ptr::null_mut();
@@ -382,6 +435,7 @@ impl Device {
         local_egl_surface: EGLSurface,
         local_keyed_mutex: Option<ComPtr<IDXGIKeyedMutex>>,
     ) -> Result<SurfaceTexture, (Error, Surface)> {
+        let surface_info = self.surface_info(&surface);
         EGL_FUNCTIONS.with(|egl| {
             unsafe {
                 let _guard = self.temporarily_make_context_current(context);
@@ -399,11 +453,8 @@ impl Device {
                         egl::BACK_BUFFER as GLint,
                     ) == egl::FALSE
                     {
-                        let windowing_api_error = egl.GetError().to_windowing_api_error();
-                        return Err((
-                            Error::SurfaceTextureCreationFailed(windowing_api_error),
-                            surface,
-                        ));
+                        let err = egl.GetError().to_native_error("eglBindTexImage");
+                        return Err((Error::SurfaceTextureCreationFailed(err), surface));
                     }
 
                     // Initialize the texture, for convenience.
@@ -423,6 +474,8 @@ impl Device {
                     gl.BindTexture(gl::TEXTURE_2D, 0);
                     debug_assert_eq!(gl.GetError(), gl::NO_ERROR);
 
+                    self.resources
+                        .track_surface_texture(context.id, &surface_info);
                     Ok(SurfaceTexture {
                         surface,
                         local_egl_surface,
@@ -445,6 +498,7 @@ impl Device {
         texture: ComPtr<d3d11::ID3D11Texture2D>,
     ) -> Result<SurfaceTexture, Error> {
         let surface = self.create_pbuffer_surface(context, size, Some(texture))?;
+        self.resources.track_surface(&self.surface_info(&surface));
         let local_egl_surface = surface.egl_surface;
         self.create_surface_texture_from_local_surface(context, surface, local_egl_surface, None)
             .map_err(|(err, mut surface)| {
@@ -460,6 +514,18 @@ impl Device {
     ///
     /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
     /// the `drop` method.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_surface(
         &self,
         context: &mut Context,
@@ -468,6 +534,7 @@ impl Device {
         if context.id != surface.context_id {
             return Err(Error::IncompatibleSurface);
         }
+        let surface_id = self.surface_info(surface).id;
 
         EGL_FUNCTIONS.with(|egl| {
             unsafe {
@@ -487,6 +554,7 @@ impl Device {
                     texture.take();
                 }
             }
+            self.resources.untrack_surface(surface_id);
             Ok(())
         })
     }
@@ -517,6 +585,8 @@ impl Device {
             })
         }
 
+        self.resources
+            .untrack_surface_texture(self.surface_info(&surface_texture.surface).id);
         Ok(surface_texture.surface)
     }
 
@@ -544,6 +614,15 @@ impl Device {
     ///
     /// The supplied context must match the context the surface was created with, or an
     /// `IncompatibleSurface` error is returned.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(surface_id = self.surface_info(surface).id.0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn present_surface(&self, _: &Context, surface: &mut Surface) -> Result<(), Error> {
         match surface.win32_objects {
             Win32Objects::Window { .. } => {}
@@ -557,6 +636,48 @@ impl Device {
         })
     }
 
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// changed since the last present.
+    ///
+    /// This backend doesn't support partial presentation, so the whole surface is presented.
+    pub fn present_surface_with_damage(
+        &self,
+        context: &Context,
+        surface: &mut Surface,
+        _: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        self.present_surface(context, surface)
+    }
+
+    /// Reads back the pixels in the given rectangle of a surface.
+    ///
+    /// This is currently unimplemented on this backend, so it always returns `Unimplemented`.
+    pub fn read_surface_pixels(
+        &self,
+        _: &Context,
+        _: &Surface,
+        _: Rect<i32>,
+        _: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Returns the age of the back buffer of a widget surface.
+    ///
+    /// This backend can't query the buffer age, so this always returns 0, meaning that the
+    /// contents of the back buffer are undefined.
+    pub fn surface_buffer_age(&self, _: &Context, _: &Surface) -> Result<u32, Error> {
+        Ok(0)
+    }
+
+    /// Sets how `present_surface()` synchronizes with the display for the given widget surface.
+    ///
+    /// This is currently unsupported on this backend, so it always returns
+    /// `UnsupportedOnThisPlatform`.
+    pub fn set_present_mode(&self, _: &Context, _: &Surface, _: PresentMode) -> Result<(), Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
     /// Resizes a widget surface.
     pub fn resize_surface(
         &self,
@@ -564,7 +685,10 @@ impl Device {
         surface: &mut Surface,
         size: Size2D<i32>,
     ) -> Result<(), Error> {
+        let surface_id = self.surface_info(surface).id;
         surface.size = size;
+        self.resources
+            .update_surface(surface_id, &self.surface_info(surface));
         Ok(())
     }
 
@@ -581,6 +705,7 @@ impl Device {
             id: surface.id(),
             context_id: surface.context_id,
             framebuffer_object: 0,
+            format: SurfaceFormat::RGBA8,
         }
     }
 
@@ -4,20 +4,25 @@
 
 use super::device::{DCGuard, Device, HiddenWindow};
 use super::surface::{Surface, Win32Objects};
-use crate::context::{self, CREATE_CONTEXT_MUTEX};
+use crate::context::{self, ContextResetStatus, CREATE_CONTEXT_MUTEX};
 use crate::surface::Framebuffer;
-use crate::{ContextAttributeFlags, ContextAttributes, ContextID, Error, GLVersion};
+use crate::{ConfigCaveat, ConfigSurfaceTypes, ContextCapabilities, ContextConfigInfo};
+use crate::{ContextAttributeFlags, ContextAttributes, ContextID, ContextVersionRange};
+use crate::{Error, GLApi, GLVersion, NativeError};
 use crate::{SurfaceInfo, WindowingApiError};
 
 use crate::gl;
 use crate::gl::types::{GLenum, GLint, GLuint};
+use crate::trace;
 use crate::Gl;
 use std::borrow::Cow;
 use std::ffi::{CStr, CString};
+use std::io;
 use std::mem;
 use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 use std::thread;
+use std::time::Duration;
 use winapi::shared::minwindef::{BOOL, FALSE, FLOAT, HMODULE, LPARAM, LPVOID, LRESULT, UINT};
 use winapi::shared::minwindef::{WORD, WPARAM};
 use winapi::shared::ntdef::{HANDLE, LPCSTR};
@@ -31,23 +36,36 @@ use winapi::um::winuser::{self, COLOR_BACKGROUND, CREATESTRUCTA, CS_OWNDC, WM_CR
 use winapi::um::winuser::{WS_OVERLAPPEDWINDOW, WS_VISIBLE};
 
 const WGL_DRAW_TO_WINDOW_ARB: GLenum = 0x2001;
+const WGL_DRAW_TO_BITMAP_ARB: GLenum = 0x2002;
 const WGL_ACCELERATION_ARB: GLenum = 0x2003;
 const WGL_SUPPORT_OPENGL_ARB: GLenum = 0x2010;
 const WGL_DOUBLE_BUFFER_ARB: GLenum = 0x2011;
 const WGL_PIXEL_TYPE_ARB: GLenum = 0x2013;
 const WGL_COLOR_BITS_ARB: GLenum = 0x2014;
+const WGL_RED_BITS_ARB: GLenum = 0x2015;
+const WGL_GREEN_BITS_ARB: GLenum = 0x2017;
+const WGL_BLUE_BITS_ARB: GLenum = 0x2019;
 const WGL_ALPHA_BITS_ARB: GLenum = 0x201b;
 const WGL_DEPTH_BITS_ARB: GLenum = 0x2022;
 const WGL_STENCIL_BITS_ARB: GLenum = 0x2023;
 const WGL_FULL_ACCELERATION_ARB: GLenum = 0x2027;
 const WGL_TYPE_RGBA_ARB: GLenum = 0x202b;
+const WGL_DRAW_TO_PBUFFER_ARB: GLenum = 0x202d;
 const WGL_CONTEXT_MAJOR_VERSION_ARB: GLenum = 0x2091;
 const WGL_CONTEXT_MINOR_VERSION_ARB: GLenum = 0x2092;
+const WGL_CONTEXT_FLAGS_ARB: GLenum = 0x2094;
+const WGL_LOSE_CONTEXT_ON_RESET_ARB: GLenum = 0x8252;
+const WGL_CONTEXT_RESET_NOTIFICATION_STRATEGY_ARB: GLenum = 0x8256;
 const WGL_CONTEXT_PROFILE_MASK_ARB: GLenum = 0x9126;
 
+const WGL_CONTEXT_DEBUG_BIT_ARB: GLenum = 0x00000001;
+const WGL_CONTEXT_ROBUST_ACCESS_BIT_ARB: GLenum = 0x00000004;
+
 const WGL_CONTEXT_CORE_PROFILE_BIT_ARB: GLenum = 0x00000001;
 const WGL_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB: GLenum = 0x00000002;
 
+const MAX_PIXEL_FORMAT_CANDIDATES: usize = 64;
+
 #[allow(non_snake_case)]
 #[derive(Default)]
 pub(crate) struct WGLExtensionFunctions {
@@ -107,6 +125,8 @@ pub struct ContextDescriptor {
     pixel_format: c_int,
     gl_version: GLVersion,
     compatibility_profile: bool,
+    debug: bool,
+    robust: bool,
 }
 
 /// Represents an OpenGL rendering context.
@@ -158,16 +178,43 @@ lazy_static! {
         thread::spawn(extension_loader_thread).join().unwrap();
 }
 
+/// A fence in the command stream of a context.
+///
+/// This backend has no shareable fence objects, so fences are signaled as soon as they are
+/// created.
+pub struct Fence {
+    _private: (),
+}
+
 impl Device {
     /// Creates a context descriptor with the given attributes.
     ///
     /// Context descriptors are local to this device.
-    #[allow(non_snake_case)]
     pub fn create_context_descriptor(
         &self,
         attributes: &ContextAttributes,
     ) -> Result<ContextDescriptor, Error> {
+        self.enumerate_context_descriptors(attributes)?
+            .into_iter()
+            .next()
+            .ok_or(Error::NoPixelFormatFound)
+    }
+
+    /// Returns a context descriptor for every pixel format that satisfies the given attributes,
+    /// in the order that `wglChoosePixelFormatARB` sorts them.
+    ///
+    /// The first candidate is the one that `create_context_descriptor()` picks. At most 64
+    /// candidates are returned.
+    #[allow(non_snake_case)]
+    pub fn enumerate_context_descriptors(
+        &self,
+        attributes: &ContextAttributes,
+    ) -> Result<Vec<ContextDescriptor>, Error> {
         let flags = attributes.flags;
+        if flags.contains(ContextAttributeFlags::GLES) {
+            return Err(Error::UnsupportedGLType);
+        }
+
         let alpha_bits = if flags.contains(ContextAttributeFlags::ALPHA) {
             8
         } else {
@@ -184,6 +231,8 @@ impl Device {
             0
         };
         let compatibility_profile = flags.contains(ContextAttributeFlags::COMPATIBILITY_PROFILE);
+        let debug = flags.contains(ContextAttributeFlags::DEBUG);
+        let robust = flags.contains(ContextAttributeFlags::ROBUST);
 
         let attrib_i_list = [
             WGL_DRAW_TO_WINDOW_ARB as c_int,
@@ -214,35 +263,72 @@ impl Device {
 
         let hidden_window_dc = self.hidden_window.get_dc();
         unsafe {
-            let (mut pixel_format, mut pixel_format_count) = (0, 0);
+            let mut pixel_formats = [0; MAX_PIXEL_FORMAT_CANDIDATES];
+            let mut pixel_format_count = 0;
             let ok = wglChoosePixelFormatARB(
                 hidden_window_dc.dc,
                 attrib_i_list.as_ptr(),
                 ptr::null(),
-                1,
-                &mut pixel_format,
+                pixel_formats.len() as UINT,
+                pixel_formats.as_mut_ptr(),
                 &mut pixel_format_count,
             );
             if ok == FALSE {
-                return Err(Error::PixelFormatSelectionFailed(WindowingApiError::Failed));
-            }
-            if pixel_format_count == 0 {
-                return Err(Error::NoPixelFormatFound);
+                return Err(Error::PixelFormatSelectionFailed(last_os_error(
+                    "wglChoosePixelFormatARB",
+                )));
             }
 
-            Ok(ContextDescriptor {
-                pixel_format,
-                gl_version: attributes.version,
-                compatibility_profile,
-            })
+            // The count is that of all matching pixel formats, which may be more than fit.
+            let pixel_format_count = (pixel_format_count as usize).min(pixel_formats.len());
+            Ok(pixel_formats[..pixel_format_count]
+                .iter()
+                .map(|&pixel_format| ContextDescriptor {
+                    pixel_format,
+                    gl_version: attributes.version,
+                    compatibility_profile,
+                    debug,
+                    robust,
+                })
+                .collect())
         }
     }
 
+    /// Creates a context descriptor with the given attributes, using the pixel format that the
+    /// given function scores highest.
+    ///
+    /// The function returns `None` to reject a pixel format. Ties go to the pixel format that
+    /// `wglChoosePixelFormatARB` sorts first. If every pixel format is rejected, this returns
+    /// `NoPixelFormatFound`.
+    #[inline]
+    pub fn choose_context_descriptor<F>(
+        &self,
+        attributes: &ContextAttributes,
+        score: F,
+    ) -> Result<ContextDescriptor, Error>
+    where
+        F: FnMut(&ContextConfigInfo) -> Option<u32>,
+    {
+        context::choose_context_descriptor(self, attributes, score)
+    }
+
     /// Creates a new OpenGL context.
     ///
     /// The context initially has no surface attached. Until a surface is bound to it, rendering
     /// commands will fail or have no effect.
     #[allow(non_snake_case)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                share_with = ?share_with.map(|context| self.context_id(context).0),
+                context_id = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_context(
         &mut self,
         descriptor: &ContextDescriptor,
@@ -272,22 +358,39 @@ impl Device {
                 } else {
                     WGL_CONTEXT_CORE_PROFILE_BIT_ARB
                 };
-                let wgl_attributes = [
+                let mut context_flags = 0;
+                if descriptor.debug {
+                    context_flags |= WGL_CONTEXT_DEBUG_BIT_ARB;
+                }
+                if descriptor.robust {
+                    context_flags |= WGL_CONTEXT_ROBUST_ACCESS_BIT_ARB;
+                }
+                let mut wgl_attributes = vec![
                     WGL_CONTEXT_MAJOR_VERSION_ARB as c_int,
                     descriptor.gl_version.major as c_int,
                     WGL_CONTEXT_MINOR_VERSION_ARB as c_int,
                     descriptor.gl_version.minor as c_int,
                     WGL_CONTEXT_PROFILE_MASK_ARB as c_int,
                     profile_mask as c_int,
-                    0,
+                    WGL_CONTEXT_FLAGS_ARB as c_int,
+                    context_flags as c_int,
                 ];
+                if descriptor.robust {
+                    wgl_attributes.extend_from_slice(&[
+                        WGL_CONTEXT_RESET_NOTIFICATION_STRATEGY_ARB as c_int,
+                        WGL_LOSE_CONTEXT_ON_RESET_ARB as c_int,
+                    ]);
+                }
+                wgl_attributes.push(0);
                 glrc = wglCreateContextAttribsARB(
                     dc,
                     share_with.map_or(ptr::null_mut(), |ctx| ctx.glrc),
                     wgl_attributes.as_ptr(),
                 );
                 if glrc.is_null() {
-                    return Err(Error::ContextCreationFailed(WindowingApiError::Failed));
+                    return Err(Error::ContextCreationFailed(last_os_error(
+                        "wglCreateContextAttribsARB",
+                    )));
                 }
 
                 // Temporarily make the context current.
@@ -297,6 +400,10 @@ impl Device {
 
                 // Load the GL functions.
                 gl = Gl::load_with(get_proc_address);
+
+                if descriptor.debug {
+                    context::install_debug_message_callback(&gl, *next_context_id);
+                }
             }
 
             // Create the initial context.
@@ -308,11 +415,27 @@ impl Device {
                 framebuffer: Framebuffer::None,
                 status: ContextStatus::Owned,
             };
+            trace::record_context(context.id);
+            self.resources.track_context(context.id);
             next_context_id.0 += 1;
             Ok(context)
         }
     }
 
+    /// Creates a new OpenGL context with the first version that the driver supports, trying each
+    /// range in turn from its highest version to its lowest.
+    ///
+    /// Returns the context along with the attributes that it was created with. If no version
+    /// works, this returns the error from the last attempt.
+    #[inline]
+    pub fn create_context_with_fallbacks(
+        &mut self,
+        ranges: &[ContextVersionRange],
+        share_with: Option<&Context>,
+    ) -> Result<(Context, ContextAttributes), Error> {
+        context::create_context_with_fallbacks(self, ranges, share_with)
+    }
+
     /// Wraps an `HGLRC` in a `surfman` context and returns it.
     ///
     /// The `HGLRC` is not retained, as there is no way to do this in the Win32 API. Therefore, it
@@ -343,6 +466,7 @@ impl Device {
             framebuffer: Framebuffer::External(()),
             status: ContextStatus::Referenced,
         };
+        self.resources.track_context(context.id);
         next_context_id.0 += 1;
         Ok(context)
     }
@@ -350,6 +474,15 @@ impl Device {
     /// Destroys a context.
     ///
     /// The context must have been created on this device.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_context(&self, context: &mut Context) -> Result<(), Error> {
         if context.status == ContextStatus::Destroyed {
             return Ok(());
@@ -371,6 +504,7 @@ impl Device {
 
         context.glrc = ptr::null_mut();
         context.status = ContextStatus::Destroyed;
+        self.resources.untrack_context(context.id);
         Ok(())
     }
 
@@ -382,18 +516,29 @@ impl Device {
 
             let _guard = self.temporarily_make_context_current(context);
 
-            let gl_version = GLVersion::current(&context.gl);
+            // Every context supports at least OpenGL 2.0.
+            let gl_version = GLVersion::current(&context.gl).unwrap_or(GLVersion::new(2, 0));
             let compatibility_profile =
                 context::current_context_uses_compatibility_profile(&context.gl);
+            let debug = context::current_context_is_debug(&context.gl);
+            let robust = context::current_context_is_robust(&context.gl);
 
             ContextDescriptor {
                 pixel_format,
                 gl_version,
                 compatibility_profile,
+                debug,
+                robust,
             }
         }
     }
 
+    /// Returns the OpenGL API flavor (OpenGL or OpenGL ES) that the given context uses.
+    #[inline]
+    pub fn context_gl_api(&self, _: &Context) -> GLApi {
+        self.gl_api()
+    }
+
     /// Returns the attributes that the context descriptor was created with.
     #[allow(non_snake_case)]
     pub fn context_descriptor_attributes(
@@ -446,11 +591,85 @@ impl Device {
             if stencil_bits > 0 {
                 attributes.flags.insert(ContextAttributeFlags::STENCIL);
             }
+            if context_descriptor.debug {
+                attributes.flags.insert(ContextAttributeFlags::DEBUG);
+            }
+            if context_descriptor.robust {
+                attributes.flags.insert(ContextAttributeFlags::ROBUST);
+            }
 
             attributes
         }
     }
 
+    /// Returns the pixel format that the context descriptor selected.
+    #[allow(non_snake_case)]
+    pub fn context_descriptor_config(
+        &self,
+        context_descriptor: &ContextDescriptor,
+    ) -> Result<ContextConfigInfo, Error> {
+        let wglGetPixelFormatAttribivARB = WGL_EXTENSION_FUNCTIONS
+            .pixel_format_functions
+            .as_ref()
+            .ok_or(Error::RequiredExtensionUnavailable)?
+            .GetPixelFormatAttribivARB;
+
+        let dc_guard = self.hidden_window.get_dc();
+
+        unsafe {
+            let attrib_name_i_list = [
+                WGL_RED_BITS_ARB as c_int,
+                WGL_GREEN_BITS_ARB as c_int,
+                WGL_BLUE_BITS_ARB as c_int,
+                WGL_ALPHA_BITS_ARB as c_int,
+                WGL_DEPTH_BITS_ARB as c_int,
+                WGL_STENCIL_BITS_ARB as c_int,
+                WGL_ACCELERATION_ARB as c_int,
+                WGL_DRAW_TO_WINDOW_ARB as c_int,
+                WGL_DRAW_TO_PBUFFER_ARB as c_int,
+                WGL_DRAW_TO_BITMAP_ARB as c_int,
+            ];
+            let mut attrib_value_i_list = [0; 10];
+            let ok = wglGetPixelFormatAttribivARB(
+                dc_guard.dc,
+                context_descriptor.pixel_format,
+                0,
+                attrib_name_i_list.len() as UINT,
+                attrib_name_i_list.as_ptr(),
+                attrib_value_i_list.as_mut_ptr(),
+            );
+            if ok == FALSE {
+                return Err(Error::PixelFormatSelectionFailed(last_os_error(
+                    "wglGetPixelFormatAttribivARB",
+                )));
+            }
+
+            let caveat = if attrib_value_i_list[6] == WGL_FULL_ACCELERATION_ARB as c_int {
+                ConfigCaveat::None
+            } else {
+                ConfigCaveat::Slow
+            };
+            let mut surface_types = ConfigSurfaceTypes::empty();
+            surface_types.set(ConfigSurfaceTypes::WINDOW, attrib_value_i_list[7] != 0);
+            surface_types.set(ConfigSurfaceTypes::PBUFFER, attrib_value_i_list[8] != 0);
+            surface_types.set(ConfigSurfaceTypes::PIXMAP, attrib_value_i_list[9] != 0);
+
+            // Surfaces render to framebuffer objects, so the pixel format is never multisampled.
+            Ok(ContextConfigInfo {
+                native_id: Some(context_descriptor.pixel_format),
+                red_bits: attrib_value_i_list[0],
+                green_bits: attrib_value_i_list[1],
+                blue_bits: attrib_value_i_list[2],
+                alpha_bits: attrib_value_i_list[3],
+                depth_bits: attrib_value_i_list[4],
+                stencil_bits: attrib_value_i_list[5],
+                samples: 0,
+                caveat,
+                surface_types,
+            })
+        }
+    }
+
     pub(crate) fn temporarily_bind_framebuffer<'a>(
         &self,
         context: &'a Context,
@@ -475,6 +694,15 @@ impl Device {
     /// Makes the context the current OpenGL context for this thread.
     ///
     /// After calling this function, it is valid to use OpenGL rendering commands.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn make_context_current(&self, context: &Context) -> Result<(), Error> {
         unsafe {
             let dc_guard = self.get_context_dc(context);
@@ -482,7 +710,7 @@ impl Device {
             if ok != FALSE {
                 Ok(())
             } else {
-                Err(Error::MakeCurrentFailed(WindowingApiError::Failed))
+                Err(Error::MakeCurrentFailed(last_os_error("wglMakeCurrent")))
             }
         }
     }
@@ -492,13 +720,17 @@ impl Device {
     /// After calling this function, OpenGL rendering commands will fail until a new context is
     /// made current.
     #[inline]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, err(Debug, level = "debug"))
+    )]
     pub fn make_no_context_current(&self) -> Result<(), Error> {
         unsafe {
             let ok = wglMakeCurrent(ptr::null_mut(), ptr::null_mut());
             if ok != FALSE {
                 Ok(())
             } else {
-                Err(Error::MakeCurrentFailed(WindowingApiError::Failed))
+                Err(Error::MakeCurrentFailed(last_os_error("wglMakeCurrent")))
             }
         }
     }
@@ -530,6 +762,17 @@ impl Device {
     /// `unbind_surface_from_context`.
     ///
     /// If an error is returned, the surface is returned alongside it.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
     pub fn bind_surface_to_context(
         &self,
         context: &mut Context,
@@ -562,6 +805,15 @@ impl Device {
     ///
     /// Any pending OpenGL commands targeting this surface will be automatically flushed, so the
     /// surface is safe to read from immediately when this function returns.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(context_id = self.context_id(context).0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn unbind_surface_from_context(
         &self,
         context: &mut Context,
@@ -613,6 +865,56 @@ impl Device {
         }
     }
 
+    /// Returns whether the context has been lost because of a GPU reset, and if so, whether it
+    /// was responsible for the reset.
+    ///
+    /// Only contexts created with the `ROBUST` flag are guaranteed to be told about resets.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        Ok(context::current_context_reset_status(&context.gl))
+    }
+
+    /// Returns the version, extensions, and limits of the given context.
+    pub fn context_capabilities(&self, context: &Context) -> Result<ContextCapabilities, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        ContextCapabilities::current(&context.gl)
+    }
+
+    /// Waits for all commands previously issued to the given context to finish, and returns a
+    /// fence that is already signaled.
+    ///
+    /// This backend has no shareable fence objects, so creating a fence waits for the context's
+    /// commands to finish.
+    pub fn create_fence(&self, context: &Context) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe {
+            context.gl.Finish();
+        }
+        Ok(Fence { _private: () })
+    }
+
+    /// Makes the given context wait for a fence to be signaled.
+    ///
+    /// Fences on this backend are signaled on creation, so this does nothing.
+    #[inline]
+    pub fn wait_fence(&self, _: &Context, _: &Fence) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Blocks the calling thread until a fence is signaled or the timeout elapses.
+    ///
+    /// Fences on this backend are signaled on creation, so this always returns true.
+    #[inline]
+    pub fn client_wait_fence(&self, _: &Fence, _: Duration) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    /// Destroys a fence.
+    #[inline]
+    pub fn destroy_fence(&self, _: &mut Fence) -> Result<(), Error> {
+        Ok(())
+    }
+
     /// Given a context, returns its underlying `HGLRC`.
     #[inline]
     pub fn native_context(&self, context: &Context) -> NativeContext {
@@ -858,7 +1160,7 @@ impl Drop for CurrentContextGuard {
 
 impl CurrentContextGuard {
     #[inline]
-    fn new() -> CurrentContextGuard {
+    pub(crate) fn new() -> CurrentContextGuard {
         unsafe {
             CurrentContextGuard {
                 old_dc: wglGetCurrentDC(),
@@ -883,6 +1185,15 @@ fn get_proc_address(symbol_name: &str) -> *const c_void {
     }
 }
 
+// Captures `GetLastError()` for a failed Win32 or WGL call.
+pub(crate) fn last_os_error(call: &'static str) -> NativeError {
+    let err = NativeError::new(WindowingApiError::Failed, call);
+    match io::Error::last_os_error().raw_os_error() {
+        Some(code) => err.with_code(code),
+        None => err,
+    }
+}
+
 pub(crate) fn set_dc_pixel_format(dc: HDC, pixel_format: c_int) {
     unsafe {
         let mut pixel_format_descriptor = mem::zeroed();
@@ -3,8 +3,11 @@
 //! An implementation of the GPU device for Windows using the WGL API.
 
 use super::connection::Connection;
+use super::context::CurrentContextGuard;
 use super::context::WGL_EXTENSION_FUNCTIONS;
-use crate::{Error, GLApi};
+use crate::resources::ResourceTracker;
+use crate::LiveResource;
+use crate::{AdapterInfo, ContextAttributeFlags, ContextAttributes, Error, GLApi, GLVersion};
 
 use std::marker::PhantomData;
 use std::mem;
@@ -56,6 +59,7 @@ pub struct Device {
     pub(crate) d3d11_device_context: ComPtr<ID3D11DeviceContext>,
     pub(crate) gl_dx_interop_device: HANDLE,
     pub(crate) hidden_window: HiddenWindow,
+    pub(crate) resources: ResourceTracker,
 }
 
 /// Wraps a Direct3D 11 device and its associated GL/DX interop device.
@@ -166,6 +170,7 @@ impl Device {
                 d3d11_device_context,
                 gl_dx_interop_device,
                 hidden_window,
+                resources: ResourceTracker::new(),
             })
         }
     }
@@ -201,6 +206,7 @@ impl Device {
                 d3d11_device_context,
                 gl_dx_interop_device,
                 hidden_window,
+                resources: ResourceTracker::new(),
             })
         }
     }
@@ -237,6 +243,35 @@ impl Device {
     pub fn gl_api(&self) -> GLApi {
         GLApi::GL
     }
+
+    /// Returns information about the adapter that this device renders with.
+    ///
+    /// This creates and destroys a temporary context in order to query the OpenGL strings.
+    pub fn adapter_info(&mut self) -> Result<AdapterInfo, Error> {
+        let context_descriptor = self.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(2, 0),
+            flags: ContextAttributeFlags::empty(),
+        })?;
+
+        // Destroying the temporary context leaves no context current, so restore the caller's
+        // context afterward.
+        let _guard = CurrentContextGuard::new();
+        let mut context = self.create_context(&context_descriptor, None)?;
+        let adapter_info = self
+            .make_context_current(&context)
+            .map(|()| AdapterInfo::current(&context.gl, None, false));
+        self.destroy_context(&mut context)?;
+        adapter_info
+    }
+
+    /// Returns the contexts, surfaces, and surface textures that this device has created and
+    /// that have not yet been destroyed.
+    ///
+    /// Resources are only tracked in debug builds; in release builds this is always empty.
+    #[inline]
+    pub fn live_resources(&self) -> Vec<LiveResource> {
+        self.resources.live_resources()
+    }
 }
 
 impl Adapter {
@@ -4,14 +4,19 @@
 
 use super::context::{self, Context, WGL_EXTENSION_FUNCTIONS};
 use super::device::Device;
-use crate::error::WindowingApiError;
+use crate::error::{NativeError, WindowingApiError};
 use crate::renderbuffers::Renderbuffers;
-use crate::{ContextID, Error, SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType};
+use crate::resources;
+use crate::{
+    ContextID, Error, PixelFormat, PresentMode, SurfaceAccess, SurfaceFormat, SurfaceID,
+    SurfaceInfo, SurfaceType,
+};
 
 use crate::gl;
 use crate::gl::types::{GLenum, GLint, GLuint};
 use crate::gl_utils;
-use euclid::default::Size2D;
+use crate::trace;
+use euclid::default::{Rect, Size2D};
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
 use std::mem;
@@ -105,7 +110,13 @@ impl Debug for Surface {
 impl Drop for Surface {
     fn drop(&mut self) {
         if !self.destroyed && !thread::panicking() {
-            panic!("Should have destroyed the surface first with `destroy_surface()`!")
+            panic!(
+                "{}",
+                resources::leaked_surface_message(
+                    "Should have destroyed the surface first with `destroy_surface()`!",
+                    self.id(),
+                )
+            )
         }
     }
 }
@@ -129,18 +140,43 @@ impl Device {
     ///
     /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
     /// up in a `SurfaceTexture` for reading by other contexts.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = tracing::field::Empty,
+                width = tracing::field::Empty,
+                height = tracing::field::Empty
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn create_surface(
         &mut self,
         context: &Context,
         _: SurfaceAccess,
         surface_type: SurfaceType<NativeWidget>,
     ) -> Result<Surface, Error> {
-        match surface_type {
-            SurfaceType::Generic { size } => self.create_generic_surface(context, &size),
+        let surface = match surface_type {
+            // Multisampled and non-RGBA8 generic surfaces are only supported on the Linux EGL
+            // backends.
+            SurfaceType::Generic {
+                samples, format, ..
+            } if samples > 1 || format != SurfaceFormat::RGBA8 => {
+                Err(Error::UnsupportedOnThisPlatform)
+            }
+            SurfaceType::Generic { size, .. } => self.create_generic_surface(context, &size),
             SurfaceType::Widget { native_widget } => {
                 self.create_widget_surface(context, native_widget)
             }
-        }
+        }?;
+        let surface_info = self.surface_info(&surface);
+        trace::record_surface(&surface_info);
+        self.resources.track_surface(&surface_info);
+        Ok(surface)
     }
 
     fn create_generic_surface(
@@ -179,7 +215,8 @@ impl Device {
                 &mut d3d11_texture,
             );
             if !winerror::SUCCEEDED(result) {
-                return Err(Error::SurfaceCreationFailed(WindowingApiError::Failed));
+                let err = NativeError::new(WindowingApiError::Failed, "CreateTexture2D");
+                return Err(Error::SurfaceCreationFailed(err.with_code(result)));
             }
             assert!(!d3d11_texture.is_null());
             let d3d11_texture = ComPtr::from_raw(d3d11_texture);
@@ -222,12 +259,12 @@ impl Device {
             );
             // Per the spec, and unlike other HANDLEs, null indicates an error.
             if gl_dx_interop_object.is_null() {
-                let msg = std::io::Error::last_os_error(); // Equivalent to GetLastError().
+                let err = context::last_os_error("wglDXRegisterObjectNV");
                 error!(
-                    "Unable to share surface between OpenGL and DirectX. OS error '{}'.",
-                    msg
+                    "Unable to share surface between OpenGL and DirectX: {}.",
+                    err
                 );
-                return Err(Error::SurfaceCreationFailed(WindowingApiError::Failed));
+                return Err(Error::SurfaceCreationFailed(err));
             }
 
             // Build our FBO.
@@ -311,6 +348,18 @@ impl Device {
     ///
     /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
     /// the `drop` method.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(surface).id.0
+            ),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn destroy_surface(
         &self,
         context: &mut Context,
@@ -358,6 +407,8 @@ impl Device {
             surface.destroyed = true;
         }
 
+        self.resources
+            .untrack_surface(self.surface_info(surface).id);
         Ok(())
     }
 
@@ -371,6 +422,17 @@ impl Device {
     /// in another context.
     ///
     /// Calling this method on a widget surface returns a `WidgetAttached` error.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                context_id = self.context_id(context).0,
+                surface_id = self.surface_info(&surface).id.0
+            )
+        )
+    )]
     pub fn create_surface_texture(
         &self,
         context: &mut Context,
@@ -402,10 +464,8 @@ impl Device {
                 &mut local_d3d11_texture,
             );
             if !winerror::SUCCEEDED(result) || local_d3d11_texture.is_null() {
-                return Err((
-                    Error::SurfaceImportFailed(WindowingApiError::Failed),
-                    surface,
-                ));
+                let err = NativeError::new(WindowingApiError::Failed, "OpenSharedResource");
+                return Err((Error::SurfaceImportFailed(err.with_code(result)), surface));
             }
             let local_d3d11_texture = ComPtr::from_raw(local_d3d11_texture as *mut ID3D11Texture2D);
 
@@ -458,6 +518,8 @@ impl Device {
             );
 
             // Finish up.
+            self.resources
+                .track_surface_texture(context.id, &self.surface_info(&surface));
             Ok(SurfaceTexture {
                 surface,
                 local_d3d11_texture,
@@ -512,6 +574,8 @@ impl Device {
             surface_texture.gl_texture = 0;
         }
 
+        self.resources
+            .untrack_surface_texture(self.surface_info(&surface_texture.surface).id);
         Ok(surface_texture.surface)
     }
 
@@ -587,6 +651,15 @@ impl Device {
     ///
     /// The supplied context must match the context the surface was created with, or an
     /// `IncompatibleSurface` error is returned.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(surface_id = self.surface_info(surface).id.0),
+            err(Debug, level = "debug")
+        )
+    )]
     pub fn present_surface(&self, _: &Context, surface: &mut Surface) -> Result<(), Error> {
         let window_handle = match surface.win32_objects {
             Win32Objects::Widget { window_handle } => window_handle,
@@ -602,6 +675,48 @@ impl Device {
         }
     }
 
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// changed since the last present.
+    ///
+    /// This backend doesn't support partial presentation, so the whole surface is presented.
+    pub fn present_surface_with_damage(
+        &self,
+        context: &Context,
+        surface: &mut Surface,
+        _: &[Rect<i32>],
+    ) -> Result<(), Error> {
+        self.present_surface(context, surface)
+    }
+
+    /// Reads back the pixels in the given rectangle of a surface.
+    ///
+    /// This is currently unimplemented on this backend, so it always returns `Unimplemented`.
+    pub fn read_surface_pixels(
+        &self,
+        _: &Context,
+        _: &Surface,
+        _: Rect<i32>,
+        _: PixelFormat,
+    ) -> Result<Vec<u8>, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Returns the age of the back buffer of a widget surface.
+    ///
+    /// This backend can't query the buffer age, so this always returns 0, meaning that the
+    /// contents of the back buffer are undefined.
+    pub fn surface_buffer_age(&self, _: &Context, _: &Surface) -> Result<u32, Error> {
+        Ok(0)
+    }
+
+    /// Sets how `present_surface()` synchronizes with the display for the given widget surface.
+    ///
+    /// This is currently unsupported on this backend, so it always returns
+    /// `UnsupportedOnThisPlatform`.
+    pub fn set_present_mode(&self, _: &Context, _: &Surface, _: PresentMode) -> Result<(), Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
     /// Resizes a widget surface.
     pub fn resize_surface(
         &self,
@@ -609,7 +724,10 @@ impl Device {
         surface: &mut Surface,
         size: Size2D<i32>,
     ) -> Result<(), Error> {
+        let surface_id = self.surface_info(surface).id;
         surface.size = size;
+        self.resources
+            .update_surface(surface_id, &self.surface_info(surface));
         Ok(())
     }
 
@@ -629,6 +747,7 @@ impl Device {
                 Win32Objects::Texture { gl_framebuffer, .. } => gl_framebuffer,
                 Win32Objects::Widget { .. } => 0,
             },
+            format: SurfaceFormat::RGBA8,
         }
     }
 
@@ -4,7 +4,8 @@
 
 use crate::context::{ContextAttributeFlags, ContextAttributes};
 use crate::gl;
-use crate::gl::types::GLuint;
+use crate::gl::types::{GLenum, GLsizei, GLuint};
+use crate::gl_utils;
 use crate::Gl;
 use std::thread;
 
@@ -15,6 +16,14 @@ pub(crate) enum Renderbuffers {
     CombinedDepthStencil(GLuint),
 }
 
+// A multisampled color buffer, along with the framebuffer object that renders to it. Its contents
+// are resolved into a single-sampled framebuffer with `resolve()`.
+#[allow(dead_code)]
+pub(crate) struct MultisampleFramebuffer {
+    pub(crate) framebuffer_object: GLuint,
+    color_renderbuffer: GLuint,
+}
+
 impl Drop for Renderbuffers {
     fn drop(&mut self) {
         match *self {
@@ -32,11 +41,31 @@ impl Drop for Renderbuffers {
     }
 }
 
+impl Drop for MultisampleFramebuffer {
+    fn drop(&mut self) {
+        if self.framebuffer_object != 0 && !thread::panicking() {
+            panic!("Should have destroyed the multisample framebuffer with `destroy()`!")
+        }
+    }
+}
+
 impl Renderbuffers {
+    #[allow(dead_code)]
     pub(crate) fn new(
         gl: &Gl,
         size: &Size2D<i32>,
         attributes: &ContextAttributes,
+    ) -> Renderbuffers {
+        Renderbuffers::with_samples(gl, size, attributes, 0)
+    }
+
+    // Like `new()`, but allocates multisampled storage if `samples` is greater than 1, for use
+    // with a `MultisampleFramebuffer`.
+    pub(crate) fn with_samples(
+        gl: &Gl,
+        size: &Size2D<i32>,
+        attributes: &ContextAttributes,
+        samples: GLsizei,
     ) -> Renderbuffers {
         unsafe {
             if attributes
@@ -46,12 +75,7 @@ impl Renderbuffers {
                 let mut renderbuffer = 0;
                 gl.GenRenderbuffers(1, &mut renderbuffer);
                 gl.BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
-                gl.RenderbufferStorage(
-                    gl::RENDERBUFFER,
-                    gl::DEPTH24_STENCIL8,
-                    size.width,
-                    size.height,
-                );
+                renderbuffer_storage(gl, gl::DEPTH24_STENCIL8, size, samples);
                 gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
                 return Renderbuffers::CombinedDepthStencil(renderbuffer);
             }
@@ -60,22 +84,12 @@ impl Renderbuffers {
             if attributes.flags.contains(ContextAttributeFlags::DEPTH) {
                 gl.GenRenderbuffers(1, &mut depth_renderbuffer);
                 gl.BindRenderbuffer(gl::RENDERBUFFER, depth_renderbuffer);
-                gl.RenderbufferStorage(
-                    gl::RENDERBUFFER,
-                    gl::DEPTH_COMPONENT24,
-                    size.width,
-                    size.height,
-                );
+                renderbuffer_storage(gl, gl::DEPTH_COMPONENT24, size, samples);
             }
             if attributes.flags.contains(ContextAttributeFlags::STENCIL) {
                 gl.GenRenderbuffers(1, &mut stencil_renderbuffer);
                 gl.BindRenderbuffer(gl::RENDERBUFFER, stencil_renderbuffer);
-                gl.RenderbufferStorage(
-                    gl::RENDERBUFFER,
-                    gl::STENCIL_INDEX8,
-                    size.width,
-                    size.height,
-                );
+                renderbuffer_storage(gl, gl::STENCIL_INDEX8, size, samples);
             }
             gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
 
@@ -152,3 +166,115 @@ impl Renderbuffers {
         }
     }
 }
+
+#[allow(dead_code)]
+impl MultisampleFramebuffer {
+    // Clamps the given sample count to `GL_MAX_SAMPLES`. Depth and stencil buffers attached
+    // alongside the color buffer must be created with the same clamped count.
+    pub(crate) fn clamp_samples(gl: &Gl, samples: GLsizei) -> GLsizei {
+        unsafe {
+            let mut max_samples = 0;
+            gl.GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+            samples.min(max_samples)
+        }
+    }
+
+    // Creates a multisampled color buffer with the given sized internal format and a framebuffer
+    // object that renders to it, and leaves that framebuffer object bound.
+    //
+    // The sample count must already have been clamped with `clamp_samples()`.
+    pub(crate) fn new(
+        gl: &Gl,
+        size: &Size2D<i32>,
+        samples: GLsizei,
+        internal_format: GLenum,
+    ) -> MultisampleFramebuffer {
+        unsafe {
+            let mut color_renderbuffer = 0;
+            gl.GenRenderbuffers(1, &mut color_renderbuffer);
+            gl.BindRenderbuffer(gl::RENDERBUFFER, color_renderbuffer);
+            renderbuffer_storage(gl, internal_format, size, samples);
+            gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            let mut framebuffer_object = 0;
+            gl.GenFramebuffers(1, &mut framebuffer_object);
+            gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+            gl.FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                color_renderbuffer,
+            );
+
+            MultisampleFramebuffer {
+                framebuffer_object,
+                color_renderbuffer,
+            }
+        }
+    }
+
+    // Resolves the samples into the color attachment of the given framebuffer object. The
+    // framebuffer bindings and scissor test of the current context are preserved.
+    pub(crate) fn resolve(&self, gl: &Gl, size: &Size2D<i32>, framebuffer_object: GLuint) {
+        unsafe {
+            let (mut old_read_framebuffer, mut old_draw_framebuffer) = (0, 0);
+            gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut old_read_framebuffer);
+            gl.GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut old_draw_framebuffer);
+            let scissor_test_enabled = gl.IsEnabled(gl::SCISSOR_TEST) != gl::FALSE;
+
+            // The scissor test applies to blits, so turn it off for the resolve.
+            if scissor_test_enabled {
+                gl.Disable(gl::SCISSOR_TEST);
+            }
+            gl.BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer_object);
+            gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, framebuffer_object);
+            gl.BlitFramebuffer(
+                0,
+                0,
+                size.width,
+                size.height,
+                0,
+                0,
+                size.width,
+                size.height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+
+            gl.BindFramebuffer(gl::READ_FRAMEBUFFER, old_read_framebuffer as GLuint);
+            gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, old_draw_framebuffer as GLuint);
+            if scissor_test_enabled {
+                gl.Enable(gl::SCISSOR_TEST);
+            }
+        }
+    }
+
+    pub(crate) fn destroy(&mut self, gl: &Gl) {
+        unsafe {
+            gl_utils::destroy_framebuffer(gl, self.framebuffer_object);
+            self.framebuffer_object = 0;
+
+            gl.DeleteRenderbuffers(1, &self.color_renderbuffer);
+            self.color_renderbuffer = 0;
+        }
+    }
+}
+
+unsafe fn renderbuffer_storage(
+    gl: &Gl,
+    internal_format: GLenum,
+    size: &Size2D<i32>,
+    samples: GLsizei,
+) {
+    if samples > 1 {
+        gl.RenderbufferStorageMultisample(
+            gl::RENDERBUFFER,
+            samples,
+            internal_format,
+            size.width,
+            size.height,
+        );
+    } else {
+        gl.RenderbufferStorage(gl::RENDERBUFFER, internal_format, size.width, size.height);
+    }
+}
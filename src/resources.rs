@@ -0,0 +1,263 @@
+// surfman/surfman/src/resources.rs
+//
+//! Bookkeeping for the contexts, surfaces, and surface textures that a device has allocated.
+//!
+//! In debug builds, each device records every resource it hands out along with the backtrace of
+//! the call that created it. Leaked resources are reported in the log when the device is dropped,
+//! and contexts and surfaces that are dropped without being destroyed say where they were created
+//! in their panic message. In release builds the tracker does nothing.
+
+use crate::{ContextID, SurfaceID, SurfaceInfo};
+
+use euclid::default::Size2D;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, Weak};
+
+lazy_static! {
+    // The resource lists of all trackers, so that a resource being dropped without having been
+    // destroyed can find its own record without a reference to its device.
+    static ref TRACKERS: Mutex<Vec<Weak<Mutex<Vec<LiveResource>>>>> = Mutex::new(vec![]);
+}
+
+/// The type of a resource that a device is keeping track of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// A context, created with `create_context()`.
+    Context,
+    /// A surface, created with `create_surface()` or one of its variants.
+    Surface,
+    /// A surface texture, created with `create_surface_texture()`.
+    SurfaceTexture,
+}
+
+/// A context, surface, or surface texture that has been created and not yet destroyed.
+///
+/// Returned by `Device::live_resources()`.
+#[derive(Clone, Debug)]
+pub struct LiveResource {
+    /// The type of the resource.
+    pub kind: ResourceKind,
+    /// The context this resource belongs to.
+    ///
+    /// For a context, this is its own ID. For a surface, this is the context that created it. For
+    /// a surface texture, this is the context that the texture was created in.
+    pub context_id: ContextID,
+    /// The ID of the surface, for surfaces and surface textures.
+    pub surface_id: Option<SurfaceID>,
+    /// The size of the surface, for surfaces and surface textures.
+    pub size: Option<Size2D<i32>>,
+    /// The stack at the point the resource was created.
+    ///
+    /// This is captured with `Backtrace::capture()`, so it is only populated if the
+    /// `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` environment variable is set.
+    pub backtrace: Arc<Backtrace>,
+}
+
+pub(crate) struct ResourceTracker {
+    resources: Arc<Mutex<Vec<LiveResource>>>,
+}
+
+impl ResourceTracker {
+    #[inline]
+    pub(crate) fn new() -> ResourceTracker {
+        let resources = Arc::new(Mutex::new(vec![]));
+        if cfg!(debug_assertions) {
+            let mut trackers = TRACKERS.lock().unwrap();
+            trackers.retain(|tracker| tracker.strong_count() > 0);
+            trackers.push(Arc::downgrade(&resources));
+        }
+        ResourceTracker { resources }
+    }
+
+    pub(crate) fn track_context(&self, context_id: ContextID) {
+        self.track(ResourceKind::Context, context_id, None, None)
+    }
+
+    pub(crate) fn track_surface(&self, surface_info: &SurfaceInfo) {
+        self.track(
+            ResourceKind::Surface,
+            surface_info.context_id,
+            Some(surface_info.id),
+            Some(surface_info.size),
+        )
+    }
+
+    pub(crate) fn track_surface_texture(&self, context_id: ContextID, surface_info: &SurfaceInfo) {
+        self.track(
+            ResourceKind::SurfaceTexture,
+            context_id,
+            Some(surface_info.id),
+            Some(surface_info.size),
+        )
+    }
+
+    pub(crate) fn untrack_context(&self, context_id: ContextID) {
+        self.untrack(|resource| {
+            resource.kind == ResourceKind::Context && resource.context_id == context_id
+        })
+    }
+
+    pub(crate) fn untrack_surface(&self, surface_id: SurfaceID) {
+        self.untrack(|resource| {
+            resource.kind == ResourceKind::Surface && resource.surface_id == Some(surface_id)
+        })
+    }
+
+    pub(crate) fn untrack_surface_texture(&self, surface_id: SurfaceID) {
+        self.untrack(|resource| {
+            resource.kind == ResourceKind::SurfaceTexture && resource.surface_id == Some(surface_id)
+        })
+    }
+
+    /// Updates a surface's entry after it has been resized or presented, either of which can give
+    /// it a new ID on some backends.
+    pub(crate) fn update_surface(&self, old_surface_id: SurfaceID, surface_info: &SurfaceInfo) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let mut resources = self.resources.lock().unwrap();
+        let resource = resources.iter_mut().rev().find(|resource| {
+            resource.kind == ResourceKind::Surface && resource.surface_id == Some(old_surface_id)
+        });
+        if let Some(resource) = resource {
+            resource.surface_id = Some(surface_info.id);
+            resource.size = Some(surface_info.size);
+        }
+    }
+
+    pub(crate) fn live_resources(&self) -> Vec<LiveResource> {
+        self.resources.lock().unwrap().clone()
+    }
+
+    fn track(
+        &self,
+        kind: ResourceKind,
+        context_id: ContextID,
+        surface_id: Option<SurfaceID>,
+        size: Option<Size2D<i32>>,
+    ) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        self.resources.lock().unwrap().push(LiveResource {
+            kind,
+            context_id,
+            surface_id,
+            size,
+            backtrace: Arc::new(Backtrace::capture()),
+        })
+    }
+
+    fn untrack<F>(&self, predicate: F)
+    where
+        F: Fn(&LiveResource) -> bool,
+    {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        // Remove the most recent match, in case a native handle has been reused as an ID.
+        let mut resources = self.resources.lock().unwrap();
+        if let Some(index) = resources.iter().rposition(predicate) {
+            resources.remove(index);
+        }
+    }
+}
+
+impl Drop for ResourceTracker {
+    fn drop(&mut self) {
+        let resources = match self.resources.lock() {
+            Ok(resources) => resources,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if resources.is_empty() {
+            return;
+        }
+
+        let mut report = format!(
+            "surfman: device dropped with {} live resource(s):",
+            resources.len()
+        );
+        let mut any_backtraces = false;
+        for resource in resources.iter() {
+            let _ = write!(report, "\n  {:?}", resource.kind);
+            if let Some(surface_id) = resource.surface_id {
+                let _ = write!(report, " {:x}", surface_id.0);
+            }
+            if let Some(size) = resource.size {
+                let _ = write!(report, " ({}x{})", size.width, size.height);
+            }
+            let _ = write!(report, " for context {}", resource.context_id.0);
+            if resource.backtrace.status() == BacktraceStatus::Captured {
+                any_backtraces = true;
+                let _ = write!(report, ", created at:\n{}", resource.backtrace);
+            }
+        }
+        if !any_backtraces {
+            report.push_str("\nSet `RUST_BACKTRACE=1` to see where these were created.");
+        }
+        warn!("{}", report);
+    }
+}
+
+/// Builds the panic message for a context that was dropped without being destroyed.
+// WGL contexts don't panic when they're leaked, so this is unused on Windows without ANGLE.
+#[allow(dead_code)]
+pub(crate) fn leaked_context_message(message: &str, context_id: ContextID) -> String {
+    leaked_resource_message(message, |resource| {
+        resource.kind == ResourceKind::Context && resource.context_id == context_id
+    })
+    .unwrap_or_else(|| format!("{} (context {})", message, context_id.0))
+}
+
+/// Builds the panic message for a surface that was dropped without being destroyed.
+pub(crate) fn leaked_surface_message(message: &str, surface_id: SurfaceID) -> String {
+    leaked_resource_message(message, |resource| {
+        resource.kind == ResourceKind::Surface && resource.surface_id == Some(surface_id)
+    })
+    .unwrap_or_else(|| format!("{} (surface {:x})", message, surface_id.0))
+}
+
+// In debug builds, appends where the leaked resource was created, or a hint about how to find out.
+// Returns `None` if the resource isn't tracked, e.g. because its device has already been dropped.
+fn leaked_resource_message<F>(message: &str, predicate: F) -> Option<String>
+where
+    F: Fn(&LiveResource) -> bool,
+{
+    if !cfg!(debug_assertions) {
+        return Some(message.to_owned());
+    }
+    let trackers = match TRACKERS.lock() {
+        Ok(trackers) => trackers,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let resource = trackers
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find_map(|resources| {
+            let resources = match resources.lock() {
+                Ok(resources) => resources,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            resources
+                .iter()
+                .rev()
+                .find(|resource| predicate(resource))
+                .cloned()
+        })?;
+
+    let mut report = format!("{} ({:?}", message, resource.kind);
+    if let Some(surface_id) = resource.surface_id {
+        let _ = write!(report, " {:x}", surface_id.0);
+    }
+    let _ = write!(report, " for context {}", resource.context_id.0);
+    if resource.backtrace.status() == BacktraceStatus::Captured {
+        let _ = write!(report, ", created at:\n{})", resource.backtrace);
+    } else {
+        report.push_str(
+            "; set `RUST_BACKTRACE=1` to see where it was created, or call \
+             `Device::live_resources()` to list what is still alive)",
+        );
+    }
+    Some(report)
+}
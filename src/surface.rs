@@ -4,10 +4,14 @@
 
 use crate::context::ContextID;
 
-use crate::gl::types::GLuint;
+use crate::gl;
+use crate::gl::types::{GLenum, GLint, GLuint};
 use euclid::default::Size2D;
 use std::fmt::{self, Display, Formatter};
 
+// `GL_BGRA` on desktop OpenGL, and `GL_BGRA_EXT` from `EXT_read_format_bgra` on OpenGL ES.
+const GL_BGRA: GLenum = 0x80e1;
+
 /// Various data about the surface.
 pub struct SystemSurfaceInfo {
     /// The surface's size, in device pixels.
@@ -28,6 +32,10 @@ pub struct SurfaceInfo {
     ///
     /// This is only valid when the surface is actually attached to a context.
     pub framebuffer_object: GLuint,
+    /// The color format of the surface.
+    ///
+    /// Surface textures created from this surface sample this same format.
+    pub format: SurfaceFormat,
 }
 
 // The default framebuffer for a context.
@@ -92,6 +100,56 @@ pub enum SurfaceAccess {
     GPUCPUWriteCombined,
 }
 
+/// The color format of a generic surface.
+///
+/// Formats other than `RGBA8` require OpenGL 3.0 or OpenGL ES 3.0, and some drivers can't render
+/// to all of them. Surface creation fails with a `SurfaceCreationFailed` error in that case.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum SurfaceFormat {
+    /// 8 bits per channel, linearly encoded. This is the default.
+    #[default]
+    RGBA8,
+    /// 16-bit floating point per channel, for high dynamic range rendering.
+    RGBA16F,
+    /// 10 bits per color channel and 2 bits of alpha.
+    RGB10_A2,
+    /// 8 bits per channel, with the color channels sRGB-encoded.
+    ///
+    /// Sampling from the surface decodes to linear values. On desktop OpenGL, writes are only
+    /// encoded to sRGB while `GL_FRAMEBUFFER_SRGB` is enabled.
+    SRGB8_ALPHA8,
+}
+
+/// The layout of pixel data read back from a surface.
+///
+/// On OpenGL ES, `RGBA8` can always be read from 8-bit surfaces, but other combinations of pixel
+/// format and surface format may not be supported by the driver.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelFormat {
+    /// 8-bit unsigned normalized red, green, blue, and alpha, in that order.
+    RGBA8,
+    /// 8-bit unsigned normalized blue, green, red, and alpha, in that order.
+    ///
+    /// On OpenGL ES, this requires the `EXT_read_format_bgra` extension.
+    BGRA8,
+    /// 32-bit floating point red, green, blue, and alpha, in that order, in native byte order.
+    RGBA32F,
+}
+
+/// How presenting a widget surface synchronizes with the display.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PresentMode {
+    /// Presentation waits for the vertical blank, so frames are never torn or dropped. This is
+    /// vsync.
+    Fifo,
+    /// Presentation happens immediately, without waiting for the vertical blank. Frames may tear.
+    Immediate,
+    /// Presentation doesn't block, and the most recently presented frame is shown at the next
+    /// vertical blank. Frames never tear, but may be dropped.
+    Mailbox,
+}
+
 /// Information specific to the type of surface: generic or widget.
 #[derive(Clone)]
 pub enum SurfaceType<NativeWidget> {
@@ -103,6 +161,21 @@ pub enum SurfaceType<NativeWidget> {
         ///
         /// For HiDPI screens, this is a physical size, not a logical size.
         size: Size2D<i32>,
+        /// The number of samples per pixel. Values of 0 and 1 both create a single-sampled
+        /// surface.
+        ///
+        /// Multisampled surfaces are rendered to through a separate multisampled framebuffer,
+        /// which is resolved into the surface automatically when the surface is unbound from its
+        /// context and when it's presented. The sample count is clamped to `GL_MAX_SAMPLES`.
+        ///
+        /// Backends that don't support multisampled surfaces return an
+        /// `UnsupportedOnThisPlatform` error for values greater than 1.
+        samples: u8,
+        /// The color format of the surface.
+        ///
+        /// Backends that only support 8-bit RGBA surfaces return an `UnsupportedOnThisPlatform`
+        /// error for other formats.
+        format: SurfaceFormat,
     },
     /// A surface displayed inside a native widget (window or view). The size of a widget surface
     /// is automatically determined based on the size of the widget. (For example, if the widget is
@@ -116,6 +189,67 @@ pub enum SurfaceType<NativeWidget> {
     },
 }
 
+impl SurfaceFormat {
+    // Returns the internal format, format, and type to pass to `glTexImage2D()` in order to
+    // allocate a texture of this format.
+    #[allow(dead_code)]
+    pub(crate) fn gl_texture_formats(self) -> (GLint, GLenum, GLenum) {
+        match self {
+            // Unsized, for compatibility with OpenGL ES 2.0.
+            SurfaceFormat::RGBA8 => (gl::RGBA as GLint, gl::RGBA, gl::UNSIGNED_BYTE),
+            SurfaceFormat::RGBA16F => (gl::RGBA16F as GLint, gl::RGBA, gl::HALF_FLOAT),
+            SurfaceFormat::RGB10_A2 => (
+                gl::RGB10_A2 as GLint,
+                gl::RGBA,
+                gl::UNSIGNED_INT_2_10_10_10_REV,
+            ),
+            SurfaceFormat::SRGB8_ALPHA8 => (gl::SRGB8_ALPHA8 as GLint, gl::RGBA, gl::UNSIGNED_BYTE),
+        }
+    }
+
+    // Returns the number of bytes that each pixel takes up in CPU-accessible surface data.
+    #[allow(dead_code)]
+    pub(crate) fn bytes_per_pixel(self) -> usize {
+        match self {
+            SurfaceFormat::RGBA16F => 8,
+            SurfaceFormat::RGBA8 | SurfaceFormat::RGB10_A2 | SurfaceFormat::SRGB8_ALPHA8 => 4,
+        }
+    }
+
+    // Returns the sized internal format to use for a renderbuffer of this format.
+    #[allow(dead_code)]
+    pub(crate) fn gl_renderbuffer_format(self) -> GLenum {
+        match self {
+            SurfaceFormat::RGBA8 => gl::RGBA8,
+            SurfaceFormat::RGBA16F => gl::RGBA16F,
+            SurfaceFormat::RGB10_A2 => gl::RGB10_A2,
+            SurfaceFormat::SRGB8_ALPHA8 => gl::SRGB8_ALPHA8,
+        }
+    }
+}
+
+impl PixelFormat {
+    // Returns the format and type to pass to `glReadPixels()` in order to read pixels in this
+    // format.
+    #[allow(dead_code)]
+    pub(crate) fn gl_read_formats(self) -> (GLenum, GLenum) {
+        match self {
+            PixelFormat::RGBA8 => (gl::RGBA, gl::UNSIGNED_BYTE),
+            PixelFormat::BGRA8 => (GL_BGRA, gl::UNSIGNED_BYTE),
+            PixelFormat::RGBA32F => (gl::RGBA, gl::FLOAT),
+        }
+    }
+
+    // Returns the number of bytes that each pixel takes up.
+    #[allow(dead_code)]
+    pub(crate) fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::RGBA8 | PixelFormat::BGRA8 => 4,
+            PixelFormat::RGBA32F => 16,
+        }
+    }
+}
+
 impl SurfaceAccess {
     #[allow(dead_code)]
     #[inline]
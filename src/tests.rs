@@ -9,19 +9,41 @@
 #![allow(missing_docs)]
 
 use super::connection::Connection;
-use super::context::{Context, ContextDescriptor, NativeContext};
+use super::context::{Context, ContextDescriptor, Fence, NativeContext};
 use super::device::{Adapter, Device};
 use super::surface::Surface;
+use crate::connection::BACKEND_ENV_VAR;
 use crate::gl;
 use crate::gl::types::{GLenum, GLuint};
-use crate::{ContextAttributeFlags, ContextAttributes, Error, GLApi, GLVersion, Gl, SurfaceAccess};
-use crate::{SurfaceType, WindowingApiError};
-
-use euclid::default::Size2D;
+use crate::ContextVersionRange;
+use crate::{
+    BackendKind, ContextAttributeFlags, ContextAttributes, ContextResetStatus, Error, GLApi,
+    GLVersion,
+};
+use crate::{ConfigCaveat, Gl, PixelFormat, PresentMode, SurfaceAccess, SurfaceFormat};
+use crate::{ResourceKind, SurfaceType, WindowingApiError};
+
+use euclid::default::{Point2D, Rect, Size2D};
 use serial_test::serial;
-use std::os::raw::c_void;
+use std::env;
+use std::ffi::CStr;
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+use std::mem;
+#[cfg(all(
+    target_os = "linux",
+    not(target_env = "ohos"),
+    feature = "sm-x11",
+    feature = "sm-raw-window-handle-06"
+))]
+use std::os::raw::c_int;
+use std::os::raw::{c_char, c_void};
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::mpsc;
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+use std::sync::{Mutex, Once};
 use std::thread;
+use std::time::Duration;
 
 static GL_VERSIONS: [GLVersion; 6] = [
     GLVersion { major: 2, minor: 0 },
@@ -45,7 +67,87 @@ pub fn test_adapter_creation() {
     let connection = Connection::new().unwrap();
     connection.create_hardware_adapter().unwrap();
     connection.create_low_power_adapter().unwrap();
-    connection.create_software_adapter().unwrap();
+    match connection.create_software_adapter() {
+        Ok(_) | Err(Error::NoAdapterFound) => {}
+        Err(err) => panic!("Unexpected error: {:?}", err),
+    }
+}
+
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_software_adapter() {
+    let connection = Connection::new().unwrap();
+    let adapter = match connection.create_software_adapter() {
+        Ok(adapter) => adapter,
+        Err(Error::NoAdapterFound) => return,
+        Err(err) => panic!("Unexpected error: {:?}", err),
+    };
+    assert!(adapter.is_software());
+
+    // Check the renderer string rather than `AdapterInfo::is_software`, which trusts the adapter.
+    let mut device = connection.create_device(&adapter).unwrap();
+    let info = device.adapter_info().unwrap();
+    assert!(
+        crate::info::renderer_is_software(&info.renderer),
+        "Software adapter rendered with {:?}",
+        info.renderer
+    );
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_connection_backend_preference() {
+    let connection = Connection::new().unwrap();
+    let backend_kind = connection.backend_kind();
+    assert_eq!(
+        BackendKind::from_name(backend_kind.name()),
+        Some(backend_kind)
+    );
+
+    // Force each other backend through `SURFMAN_BACKEND`, then this one after a backend that
+    // doesn't exist here. Restore the variable before checking anything, so a failure here can't
+    // leak into other tests.
+    let saved_env_var = env::var_os(BACKEND_ENV_VAR);
+    let mut results = vec![];
+    for &other_kind in BackendKind::ALL
+        .iter()
+        .filter(|&&kind| kind != backend_kind)
+    {
+        env::set_var(BACKEND_ENV_VAR, other_kind.name());
+        results.push((other_kind, Connection::new().map(|c| c.backend_kind())));
+    }
+    let other_kind = *BackendKind::ALL
+        .iter()
+        .find(|&&kind| kind != backend_kind)
+        .unwrap();
+    env::set_var(
+        BACKEND_ENV_VAR,
+        format!("{},{}", other_kind.name(), backend_kind.name()),
+    );
+    let fallback_result = Connection::new().map(|c| c.backend_kind());
+    match saved_env_var {
+        Some(saved_env_var) => env::set_var(BACKEND_ENV_VAR, saved_env_var),
+        None => env::remove_var(BACKEND_ENV_VAR),
+    }
+
+    for (other_kind, result) in results {
+        match result {
+            Ok(kind) => assert_eq!(kind, other_kind),
+            Err(Error::ConnectionFailed) => {}
+            Err(err) => panic!("Unexpected error: {:?}", err),
+        }
+    }
+    assert_eq!(fallback_result.unwrap(), backend_kind);
+
+    let connection = Connection::new_with_preference(&[backend_kind]).unwrap();
+    assert_eq!(connection.backend_kind(), backend_kind);
+
+    match Connection::new_with_preference(&[]) {
+        Err(Error::UnsupportedOnThisPlatform) => {}
+        Ok(_) => panic!("Connected without any backend to connect with!"),
+        Err(err) => panic!("Unexpected error: {:?}", err),
+    }
 }
 
 #[cfg_attr(not(feature = "sm-test"), test)]
@@ -111,7 +213,10 @@ pub fn test_context_creation() {
             let attributes = ContextAttributes { version, flags };
             let descriptor = match device.create_context_descriptor(&attributes) {
                 Ok(descriptor) => descriptor,
-                Err(Error::UnsupportedGLProfile) | Err(Error::UnsupportedGLVersion) => {
+                Err(Error::UnsupportedGLProfile)
+                | Err(Error::UnsupportedGLVersion)
+                | Err(Error::UnsupportedGLType)
+                | Err(Error::RequiredExtensionUnavailable) => {
                     // Nothing we can do about this. Go on to the next one.
                     continue;
                 }
@@ -124,7 +229,9 @@ pub fn test_context_creation() {
                     let actual_descriptor = device.context_descriptor(&context);
                     let actual_attributes =
                         device.context_descriptor_attributes(&actual_descriptor);
-                    if !actual_attributes.flags.contains(attributes.flags) {
+                    // `DEBUG` is only a hint, so it isn't guaranteed to round-trip.
+                    let expected_flags = attributes.flags - ContextAttributeFlags::DEBUG;
+                    if !actual_attributes.flags.contains(expected_flags) {
                         device.destroy_context(&mut context).unwrap();
                         panic!(
                             "Expected at least attribute flags {:?} for {:?} {:?} but got \
@@ -145,12 +252,21 @@ pub fn test_context_creation() {
 
                     device.destroy_context(&mut context).unwrap();
                 }
-                Err(Error::ContextCreationFailed(WindowingApiError::BadPixelFormat))
-                | Err(Error::ContextCreationFailed(WindowingApiError::BadConfig))
-                | Err(Error::ContextCreationFailed(WindowingApiError::BadMatch)) => {
+                Err(Error::ContextCreationFailed(ref err))
+                    if matches!(
+                        err.kind(),
+                        WindowingApiError::BadPixelFormat
+                            | WindowingApiError::BadConfig
+                            | WindowingApiError::BadMatch
+                    ) =>
+                {
                     // This is OK, as it just means the GL implementation didn't support the
                     // requested GL version.
                 }
+                Err(Error::UnsupportedGLProfile) => {
+                    // This is OK, as it just means the GL implementation didn't support the
+                    // requested GL version or profile.
+                }
                 Err(error) => {
                     panic!(
                         "Failed to create context ({:?}/{:?}): {:?}",
@@ -162,6 +278,256 @@ pub fn test_context_creation() {
     }
 }
 
+// Tests that context creation falls back through the given version ranges in order.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_context_version_fallbacks() {
+    let range = ContextVersionRange::new(
+        GLVersion::new(4, 6),
+        GLVersion::new(3, 2),
+        ContextAttributeFlags::empty(),
+    );
+    let versions: Vec<_> = range
+        .candidates(GLApi::GL)
+        .into_iter()
+        .map(|attributes| attributes.version)
+        .collect();
+    assert_eq!(versions.first(), Some(&GLVersion::new(4, 6)));
+    assert_eq!(versions.last(), Some(&GLVersion::new(3, 2)));
+    assert_eq!(versions.len(), 9);
+    let exact = ContextVersionRange::exact(&ContextAttributes {
+        version: GLVersion::new(1, 1),
+        flags: ContextAttributeFlags::empty(),
+    });
+    assert_eq!(exact.candidates(GLApi::GL).len(), 1);
+
+    let connection = Connection::new().unwrap();
+    let adapter = connection
+        .create_low_power_adapter()
+        .expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    match device.create_context_with_fallbacks(&[], None) {
+        Err(Error::UnsupportedGLVersion) => {}
+        _ => panic!("Creating a context without any versions to try should fail!"),
+    }
+
+    let ranges = match device.gl_api() {
+        GLApi::GL => [
+            range,
+            ContextVersionRange::new(
+                GLVersion::new(3, 0),
+                GLVersion::new(3, 0),
+                ContextAttributeFlags::COMPATIBILITY_PROFILE,
+            ),
+        ],
+        GLApi::GLES => [
+            ContextVersionRange::new(
+                GLVersion::new(3, 2),
+                GLVersion::new(3, 0),
+                ContextAttributeFlags::empty(),
+            ),
+            ContextVersionRange::new(
+                GLVersion::new(2, 0),
+                GLVersion::new(2, 0),
+                ContextAttributeFlags::empty(),
+            ),
+        ],
+    };
+    let (mut context, attributes) = device.create_context_with_fallbacks(&ranges, None).unwrap();
+    assert!(ranges.iter().any(|range| {
+        range.flags == attributes.flags
+            && attributes.version >= range.min_version
+            && attributes.version <= range.max_version
+    }));
+    let actual_attributes =
+        device.context_descriptor_attributes(&device.context_descriptor(&context));
+    assert!(actual_attributes.version >= attributes.version);
+    assert!(actual_attributes.flags.contains(attributes.flags));
+    device.destroy_context(&mut context).unwrap();
+}
+
+// Tests that OpenGL ES contexts can be requested explicitly on backends that support both APIs.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_gles_context_creation() {
+    let connection = Connection::new().unwrap();
+    let adapter = connection
+        .create_low_power_adapter()
+        .expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    let context_descriptor = match device.create_context_descriptor(&ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::GLES,
+    }) {
+        Ok(context_descriptor) => context_descriptor,
+        Err(Error::UnsupportedGLType) | Err(Error::NoPixelFormatFound) => {
+            // This backend can't create OpenGL ES contexts.
+            return;
+        }
+        Err(err) => panic!("Context descriptor creation failed: {:?}", err),
+    };
+
+    let mut context = device.create_context(&context_descriptor, None).unwrap();
+    let actual_descriptor = device.context_descriptor(&context);
+    let actual_attributes = device.context_descriptor_attributes(&actual_descriptor);
+    assert!(actual_attributes
+        .flags
+        .contains(ContextAttributeFlags::GLES));
+    assert_eq!(device.context_gl_api(&context), GLApi::GLES);
+
+    let surface = make_surface(&mut device, &context);
+    device
+        .bind_surface_to_context(&mut context, surface)
+        .unwrap();
+    device.make_context_current(&context).unwrap();
+    let gl = Gl::load_with(|symbol| device.get_proc_address(&context, symbol));
+
+    unsafe {
+        let version_string = CStr::from_ptr(gl.GetString(gl::VERSION) as *const c_char);
+        assert!(version_string.to_string_lossy().starts_with("OpenGL ES"));
+
+        bind_context_fbo(&gl, &device, &context);
+        clear(&gl, &[0, 255, 0, 255]);
+        assert_eq!(get_pixel_from_bottom_row(&gl), [0, 255, 0, 255]);
+    }
+
+    device.destroy_context(&mut context).unwrap();
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_debug_context_creation() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let context_descriptor = env
+        .device
+        .create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(3, 0),
+            flags: ContextAttributeFlags::DEBUG,
+        })
+        .unwrap();
+
+    // Installing the debug message callback must leave the previously current context current.
+    clear(&env.gl, &[0, 0, 255, 255]);
+    let mut context = env
+        .device
+        .create_context(&context_descriptor, None)
+        .unwrap();
+    assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 0, 255, 255]);
+
+    let context_attributes = env
+        .device
+        .context_descriptor_attributes(&env.device.context_descriptor(&context));
+    assert!(context_attributes
+        .flags
+        .contains(ContextAttributeFlags::DEBUG));
+
+    let surface = make_surface(&mut env.device, &context);
+    env.device
+        .bind_surface_to_context(&mut context, surface)
+        .unwrap();
+    env.device.make_context_current(&context).unwrap();
+    let gl = Gl::load_with(|symbol| env.device.get_proc_address(&context, symbol));
+    bind_context_fbo(&gl, &env.device, &context);
+    clear(&gl, &[0, 255, 0, 255]);
+    assert_eq!(get_pixel_from_bottom_row(&gl), [0, 255, 0, 255]);
+
+    // GL errors must show up in the log. The OpenGL ES bindings don't include `KHR_debug`, so
+    // nothing is forwarded there.
+    #[cfg(not(any(target_os = "android", target_env = "ohos")))]
+    unsafe {
+        let mut context_flags = 0;
+        gl.GetIntegerv(gl::CONTEXT_FLAGS, &mut context_flags);
+        assert_ne!(context_flags & gl::CONTEXT_FLAG_DEBUG_BIT as i32, 0);
+
+        CapturingLogger::install();
+        CAPTURING_LOGGER.take();
+        gl.Enable(0xffff);
+        assert_eq!(gl.GetError(), gl::INVALID_ENUM);
+
+        let prefix = format!(
+            "GL debug message from context {:?}",
+            env.device.context_id(&context)
+        );
+        let messages = CAPTURING_LOGGER.take();
+        assert!(
+            messages.iter().any(|message| message.starts_with(&prefix)),
+            "Expected a GL debug message in {:?}",
+            messages
+        );
+    }
+
+    // Restore the previously current context, and make sure its contents are intact.
+    env.device.destroy_context(&mut context).unwrap();
+    env.device.make_context_current(&env.context).unwrap();
+    bind_context_fbo(&env.gl, &env.device, &env.context);
+    assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 0, 255, 255]);
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_robust_context_reset_status() {
+    let connection = Connection::new().unwrap();
+    let adapter = connection
+        .create_low_power_adapter()
+        .expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    let context_descriptor = match device.create_context_descriptor(&ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::ROBUST,
+    }) {
+        Ok(context_descriptor) => context_descriptor,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // This backend can't create robust contexts.
+            return;
+        }
+        Err(err) => panic!("Context descriptor creation failed: {:?}", err),
+    };
+
+    let mut context = device.create_context(&context_descriptor, None).unwrap();
+    let actual_descriptor = device.context_descriptor(&context);
+    let actual_attributes = device.context_descriptor_attributes(&actual_descriptor);
+    assert!(actual_attributes
+        .flags
+        .contains(ContextAttributeFlags::ROBUST));
+
+    assert_eq!(
+        device.context_reset_status(&context).unwrap(),
+        ContextResetStatus::NoReset
+    );
+
+    device.destroy_context(&mut context).unwrap();
+}
+
 // Tests that newly-created contexts are not immediately made current (issue #7).
 #[cfg_attr(not(feature = "sm-test"), test)]
 #[serial]
@@ -312,6 +678,8 @@ pub fn test_generic_surface_creation() {
                 access,
                 SurfaceType::Generic {
                     size: Size2D::new(640, 480),
+                    samples: 0,
+                    format: SurfaceFormat::RGBA8,
                 },
             )
             .unwrap();
@@ -604,12 +972,13 @@ pub fn test_cross_thread_surface_texture_blit_framebuffer() {
 
         clear(&gl, &[0, 255, 0, 255]);
         assert_eq!(get_pixel_from_bottom_row(&gl), [0, 255, 0, 255]);
+        let fence = device.create_fence(&context).unwrap();
 
         let surface = device
             .unbind_surface_from_context(&mut context)
             .unwrap()
             .unwrap();
-        to_main_sender.send(surface).unwrap();
+        to_main_sender.send((surface, fence)).unwrap();
 
         let mut surface = to_worker_receiver.recv().unwrap();
         device.destroy_surface(&mut context, &mut surface).unwrap();
@@ -620,12 +989,14 @@ pub fn test_cross_thread_surface_texture_blit_framebuffer() {
         clear(&env.gl, &[255, 0, 0, 255]);
         assert_eq!(get_pixel_from_bottom_row(&env.gl), [255, 0, 0, 255]);
 
-        let green_surface = to_main_receiver.recv().unwrap();
+        let (green_surface, mut fence): (Surface, Fence) = to_main_receiver.recv().unwrap();
         let green_surface_texture = env
             .device
             .create_surface_texture(&mut env.context, green_surface)
             .unwrap();
 
+        env.device.wait_fence(&env.context, &fence).unwrap();
+        env.device.destroy_fence(&mut fence).unwrap();
         env.device.make_context_current(&env.context).unwrap();
         assert_eq!(get_pixel_from_bottom_row(&env.gl), [255, 0, 0, 255]);
 
@@ -732,106 +1103,1303 @@ pub fn test_surface_texture_right_side_up() {
     }
 }
 
-#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+// Tests that rendering to a multisampled surface is resolved into its surface texture.
 #[cfg_attr(not(feature = "sm-test"), test)]
 #[serial]
-pub fn test_depth_and_stencil() {
-    let connection = Connection::new().unwrap();
-    let adapter = connection
-        .create_low_power_adapter()
-        .expect("Failed to create adapter!");
-    let mut device = match connection.create_device(&adapter) {
-        Ok(device) => device,
-        Err(Error::RequiredExtensionUnavailable) => {
-            // Can't run these tests on this hardware.
-            return;
-        }
-        Err(err) => panic!("Failed to create device: {:?}", err),
+pub fn test_multisampled_surface_resolve() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
     };
 
-    // Can't readback from the depth or stencil buffer on OpenGL ES, so just disable this test.
-    if device.gl_api() == GLApi::GLES {
-        return;
-    }
-
-    // Check depth.
-
-    let depth_context_descriptor = device
-        .create_context_descriptor(&ContextAttributes {
-            version: GLVersion::new(3, 0),
-            flags: ContextAttributeFlags::DEPTH,
-        })
-        .unwrap();
-
-    let mut depth_context = device
-        .create_context(&depth_context_descriptor, None)
-        .unwrap();
-    let depth_surface = make_surface(&mut device, &depth_context);
-    device
-        .bind_surface_to_context(&mut depth_context, depth_surface)
-        .unwrap();
-    device.make_context_current(&depth_context).unwrap();
+    unsafe {
+        let multisampled_surface = match env.device.create_surface(
+            &env.context,
+            SurfaceAccess::GPUOnly,
+            SurfaceType::Generic {
+                size: Size2D::new(640, 480),
+                samples: 4,
+                format: SurfaceFormat::RGBA8,
+            },
+        ) {
+            Ok(surface) => surface,
+            Err(Error::UnsupportedOnThisPlatform) => {
+                // This backend doesn't support multisampled surfaces.
+                env.device.destroy_context(&mut env.context).unwrap();
+                return;
+            }
+            Err(err) => panic!("Failed to create multisampled surface: {:?}", err),
+        };
 
-    let gl = Gl::load_with(|symbol| device.get_proc_address(&depth_context, symbol));
+        let mut main_surface = env
+            .device
+            .unbind_surface_from_context(&mut env.context)
+            .unwrap()
+            .unwrap();
+        env.device
+            .bind_surface_to_context(&mut env.context, multisampled_surface)
+            .unwrap();
 
-    unsafe {
-        let framebuffer_object = device
-            .context_surface_info(&depth_context)
+        let multisampled_framebuffer_object = env
+            .device
+            .context_surface_info(&env.context)
             .unwrap()
             .unwrap()
             .framebuffer_object;
-        gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
-        gl.Viewport(0, 0, 640, 480);
+        env.gl
+            .BindFramebuffer(gl::FRAMEBUFFER, multisampled_framebuffer_object);
+        clear(&env.gl, &[0, 255, 0, 255]);
+        check_gl(&env.gl);
 
-        gl.ClearDepth(0.5);
-        gl.Clear(gl::DEPTH_BUFFER_BIT);
+        // Unbinding the surface resolves it.
+        let multisampled_surface = env
+            .device
+            .unbind_surface_from_context(&mut env.context)
+            .unwrap()
+            .unwrap();
+        let multisampled_surface_texture = env
+            .device
+            .create_surface_texture(&mut env.context, multisampled_surface)
+            .unwrap();
 
-        let mut depth_value: f32 = -1.0;
-        gl.ReadPixels(
-            0,
-            0,
-            1,
-            1,
-            gl::DEPTH_COMPONENT,
-            gl::FLOAT,
-            (&mut depth_value) as *mut f32 as *mut c_void,
-        );
-        assert!(
-            approx_eq(depth_value, 0.5),
-            "actual depth value was {}, but expected 0.5",
-            depth_value
+        let framebuffer_object = make_fbo(
+            &env.gl,
+            env.device.surface_gl_texture_target(),
+            env.device
+                .surface_texture_object(&multisampled_surface_texture),
         );
-    }
-
-    device.destroy_context(&mut depth_context).unwrap();
-
-    // Check stencil.
-
-    let stencil_context_descriptor = device
-        .create_context_descriptor(&ContextAttributes {
-            version: GLVersion::new(3, 0),
-            flags: ContextAttributeFlags::STENCIL,
-        })
-        .unwrap();
+        assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 255, 0, 255]);
 
-    let mut stencil_context = device
-        .create_context(&stencil_context_descriptor, None)
-        .unwrap();
-    let stencil_surface = make_surface(&mut device, &stencil_context);
-    device
-        .bind_surface_to_context(&mut stencil_context, stencil_surface)
-        .unwrap();
-    device.make_context_current(&stencil_context).unwrap();
+        // Clean up.
+        env.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        check_gl(&env.gl);
+        env.gl.DeleteFramebuffers(1, &framebuffer_object);
 
-    let gl = Gl::load_with(|symbol| device.get_proc_address(&stencil_context, symbol));
+        let mut multisampled_surface = env
+            .device
+            .destroy_surface_texture(&mut env.context, multisampled_surface_texture)
+            .unwrap();
+        env.device
+            .destroy_surface(&mut env.context, &mut multisampled_surface)
+            .unwrap();
 
-    unsafe {
-        let framebuffer_object = device
-            .context_surface_info(&stencil_context)
-            .unwrap()
-            .unwrap()
-            .framebuffer_object;
-        gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        // Sample counts above `GL_MAX_SAMPLES` are clamped, and the depth and stencil buffers must
+        // be clamped to match the color buffer, or the framebuffer is incomplete.
+        let mut max_samples = 0;
+        env.gl.GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+        let context_descriptor = env
+            .device
+            .create_context_descriptor(&ContextAttributes {
+                version: GLVersion::new(3, 0),
+                flags: ContextAttributeFlags::DEPTH | ContextAttributeFlags::STENCIL,
+            })
+            .unwrap();
+        let mut context = env
+            .device
+            .create_context(&context_descriptor, None)
+            .unwrap();
+        let multisampled_surface = env
+            .device
+            .create_surface(
+                &context,
+                SurfaceAccess::GPUOnly,
+                SurfaceType::Generic {
+                    size: Size2D::new(640, 480),
+                    samples: (max_samples + 1).min(u8::MAX as i32) as u8,
+                    format: SurfaceFormat::RGBA8,
+                },
+            )
+            .unwrap();
+        env.device
+            .bind_surface_to_context(&mut context, multisampled_surface)
+            .unwrap();
+        env.device.make_context_current(&context).unwrap();
+        let gl = Gl::load_with(|symbol| env.device.get_proc_address(&context, symbol));
+        bind_context_fbo(&gl, &env.device, &context);
+        assert_eq!(
+            gl.CheckFramebufferStatus(gl::FRAMEBUFFER),
+            gl::FRAMEBUFFER_COMPLETE
+        );
+        clear(&gl, &[0, 0, 255, 255]);
+        check_gl(&gl);
+
+        let multisampled_surface = env
+            .device
+            .unbind_surface_from_context(&mut context)
+            .unwrap()
+            .unwrap();
+        let multisampled_surface_texture = env
+            .device
+            .create_surface_texture(&mut context, multisampled_surface)
+            .unwrap();
+        let framebuffer_object = make_fbo(
+            &gl,
+            env.device.surface_gl_texture_target(),
+            env.device
+                .surface_texture_object(&multisampled_surface_texture),
+        );
+        assert_eq!(get_pixel_from_bottom_row(&gl), [0, 0, 255, 255]);
+
+        gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl.DeleteFramebuffers(1, &framebuffer_object);
+        let mut multisampled_surface = env
+            .device
+            .destroy_surface_texture(&mut context, multisampled_surface_texture)
+            .unwrap();
+        env.device
+            .destroy_surface(&mut context, &mut multisampled_surface)
+            .unwrap();
+        env.device.destroy_context(&mut context).unwrap();
+
+        env.device.make_context_current(&env.context).unwrap();
+        env.device
+            .destroy_surface(&mut env.context, &mut main_surface)
+            .unwrap();
+        env.device.destroy_context(&mut env.context).unwrap();
+    }
+}
+
+// Tests that present modes can't be set on generic surfaces.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_generic_surface_present_mode() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let mut surface = make_surface(&mut env.device, &env.context);
+    for &mode in &[
+        PresentMode::Fifo,
+        PresentMode::Immediate,
+        PresentMode::Mailbox,
+    ] {
+        match env.device.set_present_mode(&env.context, &surface, mode) {
+            Err(Error::NoWidgetAttached) | Err(Error::UnsupportedOnThisPlatform) => {}
+            result => panic!(
+                "Setting {:?} on a generic surface returned {:?}",
+                mode, result
+            ),
+        }
+    }
+
+    env.device
+        .destroy_surface(&mut env.context, &mut surface)
+        .unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that damage-region presentation and buffer age queries behave like `present_surface()` on
+// generic surfaces.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_generic_surface_present_with_damage() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let mut surface = make_surface(&mut env.device, &env.context);
+    let damage = [Rect::new(Point2D::new(0, 0), Size2D::new(16, 16))];
+    match env
+        .device
+        .present_surface_with_damage(&env.context, &mut surface, &damage)
+    {
+        Err(Error::NoWidgetAttached) => {}
+        result => panic!("Presenting a generic surface returned {:?}", result),
+    }
+    match env.device.surface_buffer_age(&env.context, &surface) {
+        Err(Error::NoWidgetAttached) | Ok(0) => {}
+        result => panic!("Querying the buffer age returned {:?}", result),
+    }
+
+    env.device
+        .destroy_surface(&mut env.context, &mut surface)
+        .unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that reading back surface pixels returns the top row first.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_read_surface_pixels() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let framebuffer_object = env
+        .device
+        .context_surface_info(&env.context)
+        .unwrap()
+        .unwrap()
+        .framebuffer_object;
+    unsafe {
+        env.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        env.gl.Viewport(0, 0, 640, 480);
+    }
+    clear(&env.gl, &[255, 0, 0, 255]);
+    clear_bottom_row(&env.gl, &[0, 255, 0, 255]);
+    unsafe {
+        env.gl.Flush();
+    }
+
+    let mut surface = env
+        .device
+        .unbind_surface_from_context(&mut env.context)
+        .unwrap()
+        .unwrap();
+
+    let pixels = match env.device.read_surface_pixels(
+        &env.context,
+        &surface,
+        Rect::new(Point2D::zero(), Size2D::new(640, 480)),
+        PixelFormat::RGBA8,
+    ) {
+        Err(Error::Unimplemented) => {
+            env.device
+                .destroy_surface(&mut env.context, &mut surface)
+                .unwrap();
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        result => result.unwrap(),
+    };
+    assert_eq!(pixels.len(), 640 * 480 * 4);
+    assert_eq!(&pixels[0..4], &[255, 0, 0, 255]);
+    assert_eq!(
+        &pixels[(640 * 479 * 4)..(640 * 479 * 4 + 4)],
+        &[0, 255, 0, 255]
+    );
+
+    // The rectangle's origin is at the top left, so this is the bottom row.
+    let pixels = env
+        .device
+        .read_surface_pixels(
+            &env.context,
+            &surface,
+            Rect::new(Point2D::new(320, 479), Size2D::new(2, 1)),
+            PixelFormat::RGBA8,
+        )
+        .unwrap();
+    assert_eq!(pixels, [0, 255, 0, 255, 0, 255, 0, 255]);
+
+    match env.device.read_surface_pixels(
+        &env.context,
+        &surface,
+        Rect::new(Point2D::new(0, 479), Size2D::new(640, 2)),
+        PixelFormat::RGBA8,
+    ) {
+        Err(Error::Failed) => {}
+        result => panic!("Reading outside the surface returned {:?}", result),
+    }
+
+    // Empty rectangles read nothing, even if they stick out of the surface.
+    let pixels = env
+        .device
+        .read_surface_pixels(
+            &env.context,
+            &surface,
+            Rect::new(Point2D::new(0, 470), Size2D::new(0, 20)),
+            PixelFormat::RGBA8,
+        )
+        .unwrap();
+    assert!(pixels.is_empty());
+
+    env.device
+        .destroy_surface(&mut env.context, &mut surface)
+        .unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that fences created after rendering are signaled once that rendering finishes.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_fences() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    clear(&env.gl, &[0, 255, 0, 255]);
+    let mut fence = env.device.create_fence(&env.context).unwrap();
+    assert!(env
+        .device
+        .client_wait_fence(&fence, Duration::from_secs(10))
+        .unwrap());
+    // Waiting on a signaled fence again should return immediately.
+    assert!(env
+        .device
+        .client_wait_fence(&fence, Duration::from_secs(0))
+        .unwrap());
+    env.device.wait_fence(&env.context, &fence).unwrap();
+    env.device.destroy_fence(&mut fence).unwrap();
+
+    env.device.make_context_current(&env.context).unwrap();
+    assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 255, 0, 255]);
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that adapter information and context capabilities can be queried, and that querying the
+// adapter doesn't disturb the current context.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_adapter_info_and_context_capabilities() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    clear(&env.gl, &[0, 255, 0, 255]);
+    let adapter_info = env.device.adapter_info().unwrap();
+    assert!(!adapter_info.renderer.is_empty());
+    assert!(GLVersion::parse(&adapter_info.version).is_some());
+    for extension in &adapter_info.display_extensions {
+        assert!(extension.starts_with("EGL_"));
+    }
+    assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 255, 0, 255]);
+
+    let capabilities = env.device.context_capabilities(&env.context).unwrap();
+    assert_eq!(capabilities.api, env.device.context_gl_api(&env.context));
+    assert!(capabilities.version.major >= 3);
+    assert!(capabilities.shading_language_version.is_some());
+    assert!(capabilities.max_texture_size >= 1024);
+    assert!(capabilities.max_renderbuffer_size >= 1024);
+    assert!(capabilities.max_samples >= 1);
+    for extension in &capabilities.extensions {
+        assert!(extension.starts_with("GL_"));
+        assert!(capabilities.has_extension(extension));
+    }
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that the configuration a context descriptor selected has the buffers that were asked for.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_context_descriptor_config() {
+    let connection = Connection::new().unwrap();
+    let adapter = connection
+        .create_low_power_adapter()
+        .expect("Failed to create adapter!");
+    let device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    for flags in [
+        ContextAttributeFlags::empty(),
+        ContextAttributeFlags::ALPHA,
+        ContextAttributeFlags::DEPTH | ContextAttributeFlags::STENCIL,
+        ContextAttributeFlags::ALPHA
+            | ContextAttributeFlags::DEPTH
+            | ContextAttributeFlags::STENCIL,
+    ] {
+        let context_descriptor = device
+            .create_context_descriptor(&ContextAttributes {
+                version: GLVersion::new(3, 0),
+                flags,
+            })
+            .unwrap();
+        let config = match device.context_descriptor_config(&context_descriptor) {
+            Err(Error::UnsupportedOnThisPlatform) => return,
+            result => result.unwrap(),
+        };
+        assert!(config.red_bits >= 8);
+        assert!(config.green_bits >= 8);
+        assert!(config.blue_bits >= 8);
+        if flags.contains(ContextAttributeFlags::ALPHA) {
+            assert!(config.alpha_bits >= 8);
+        }
+        if flags.contains(ContextAttributeFlags::DEPTH) {
+            assert!(config.depth_bits >= 24);
+        }
+        if flags.contains(ContextAttributeFlags::STENCIL) {
+            assert!(config.stencil_bits >= 8);
+        }
+    }
+}
+
+// Tests that every candidate context descriptor satisfies the attributes, and that a scoring
+// function can pick among them.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_enumerate_context_descriptors() {
+    let connection = Connection::new().unwrap();
+    let adapter = connection
+        .create_low_power_adapter()
+        .expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    let attributes = ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::DEPTH,
+    };
+    let candidates = device.enumerate_context_descriptors(&attributes).unwrap();
+    assert!(!candidates.is_empty());
+
+    let first_config = match device.context_descriptor_config(&candidates[0]) {
+        Err(Error::UnsupportedOnThisPlatform) => return,
+        result => result.unwrap(),
+    };
+    let default_descriptor = device.create_context_descriptor(&attributes).unwrap();
+    assert_eq!(
+        device
+            .context_descriptor_config(&default_descriptor)
+            .unwrap(),
+        first_config
+    );
+    for candidate in &candidates {
+        let config = device.context_descriptor_config(candidate).unwrap();
+        assert!(config.depth_bits >= 24);
+    }
+
+    // Prefer the candidate with the most depth bits, and never pick slow configs.
+    let chosen_descriptor = device
+        .choose_context_descriptor(&attributes, |config| {
+            if config.caveat == ConfigCaveat::Slow {
+                None
+            } else {
+                Some(config.depth_bits as u32)
+            }
+        })
+        .unwrap();
+    let chosen_config = device
+        .context_descriptor_config(&chosen_descriptor)
+        .unwrap();
+    assert_ne!(chosen_config.caveat, ConfigCaveat::Slow);
+    for candidate in &candidates {
+        let config = device.context_descriptor_config(candidate).unwrap();
+        if config.caveat != ConfigCaveat::Slow {
+            assert!(config.depth_bits <= chosen_config.depth_bits);
+        }
+    }
+
+    match device.choose_context_descriptor(&attributes, |_| None) {
+        Err(Error::NoPixelFormatFound) => {}
+        _ => panic!("Rejecting every candidate should fail!"),
+    }
+
+    let mut context = device.create_context(&chosen_descriptor, None).unwrap();
+    device.destroy_context(&mut context).unwrap();
+}
+
+// Tests that asynchronous readbacks capture the surface as it was when they were started.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_surface_readback() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let framebuffer_object = env
+        .device
+        .context_surface_info(&env.context)
+        .unwrap()
+        .unwrap()
+        .framebuffer_object;
+    unsafe {
+        env.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        env.gl.Viewport(0, 0, 640, 480);
+    }
+    clear(&env.gl, &[255, 0, 0, 255]);
+    clear_bottom_row(&env.gl, &[0, 255, 0, 255]);
+
+    let surface = env
+        .device
+        .unbind_surface_from_context(&mut env.context)
+        .unwrap()
+        .unwrap();
+    let mut readback = match env.device.begin_surface_readback(&env.context, &surface) {
+        Err(Error::UnsupportedGLVersion) => {
+            // Pixel buffer objects and fences aren't available on this context.
+            let mut surface = surface;
+            env.device
+                .destroy_surface(&mut env.context, &mut surface)
+                .unwrap();
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        result => result.unwrap(),
+    };
+    assert_eq!(readback.size(), Size2D::new(640, 480));
+    assert_eq!(readback.stride(), 640 * 4);
+
+    // Rendering to the surface again mustn't affect the readback.
+    env.device
+        .bind_surface_to_context(&mut env.context, surface)
+        .unwrap();
+    unsafe {
+        env.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+    }
+    clear(&env.gl, &[0, 0, 255, 255]);
+
+    // Rows are stored bottom to top.
+    {
+        let data = readback.wait().unwrap();
+        assert_eq!(data.len(), 640 * 480 * 4);
+        assert_eq!(&data[0..4], &[0, 255, 0, 255]);
+        assert_eq!(
+            &data[(640 * 479 * 4)..(640 * 479 * 4 + 4)],
+            &[255, 0, 0, 255]
+        );
+    }
+    assert!(readback.try_map().unwrap().is_some());
+    drop(readback);
+
+    assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 0, 255, 255]);
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that a readback outliving its context doesn't touch whichever context is current when it
+// is dropped.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_surface_readback_after_context_destroyed() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let mut surface = env
+        .device
+        .unbind_surface_from_context(&mut env.context)
+        .unwrap()
+        .unwrap();
+    let readback = env.device.begin_surface_readback(&env.context, &surface);
+    env.device
+        .destroy_surface(&mut env.context, &mut surface)
+        .unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+    let mut readback = match readback {
+        Err(Error::UnsupportedGLVersion) => {
+            // Pixel buffer objects and fences aren't available on this context.
+            return;
+        }
+        result => result.unwrap(),
+    };
+    assert!(readback.wait().is_err());
+
+    // The new context most likely hands out the same buffer name as the readback's.
+    let mut context = env
+        .device
+        .create_context(&env.context_descriptor, None)
+        .unwrap();
+    let surface = make_surface(&mut env.device, &context);
+    env.device
+        .bind_surface_to_context(&mut context, surface)
+        .unwrap();
+    env.device.make_context_current(&context).unwrap();
+    let gl = Gl::load_with(|symbol| env.device.get_proc_address(&context, symbol));
+    let mut buffer = 0;
+    unsafe {
+        gl.GenBuffers(1, &mut buffer);
+        gl.BindBuffer(gl::PIXEL_PACK_BUFFER, buffer);
+        gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+    }
+
+    drop(readback);
+    unsafe {
+        assert_eq!(gl.IsBuffer(buffer), gl::TRUE);
+        gl.DeleteBuffers(1, &buffer);
+    }
+
+    env.device.destroy_context(&mut context).unwrap();
+}
+
+// Tests that the CPU can read and write the data of surfaces created with CPU access.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_lock_surface_data() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    unsafe {
+        let mut main_surface = env
+            .device
+            .unbind_surface_from_context(&mut env.context)
+            .unwrap()
+            .unwrap();
+
+        // GPU-only surfaces can't be locked.
+        let mut gpu_surface = make_surface(&mut env.device, &env.context);
+        let lock_error = env.device.lock_surface_data(&mut gpu_surface).err();
+        env.device
+            .destroy_surface(&mut env.context, &mut gpu_surface)
+            .unwrap();
+        match lock_error {
+            Some(Error::SurfaceDataInaccessible) => {}
+            Some(err) => panic!("Locking a GPU-only surface failed with {:?}", err),
+            None => panic!("Locking a GPU-only surface succeeded"),
+        }
+
+        let surface = env
+            .device
+            .create_surface(
+                &env.context,
+                SurfaceAccess::GPUCPU,
+                SurfaceType::Generic {
+                    size: Size2D::new(640, 480),
+                    samples: 0,
+                    format: SurfaceFormat::RGBA8,
+                },
+            )
+            .unwrap();
+        env.device
+            .bind_surface_to_context(&mut env.context, surface)
+            .unwrap();
+        let framebuffer_object = env
+            .device
+            .context_surface_info(&env.context)
+            .unwrap()
+            .unwrap()
+            .framebuffer_object;
+        env.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        clear(&env.gl, &[255, 0, 0, 255]);
+        let mut surface = env
+            .device
+            .unbind_surface_from_context(&mut env.context)
+            .unwrap()
+            .unwrap();
+
+        // Read what the GPU rendered, and overwrite it.
+        {
+            let mut data_guard = env.device.lock_surface_data(&mut surface).unwrap();
+            assert_eq!(data_guard.stride(), 640 * 4);
+            let data = data_guard.data();
+            assert_eq!(data.len(), 640 * 4 * 480);
+            assert_eq!(&data[0..4], &[255, 0, 0, 255]);
+            for pixel in data.chunks_mut(4) {
+                pixel.copy_from_slice(&[0, 0, 255, 255]);
+            }
+        }
+
+        // Make sure the GPU sees what the CPU wrote.
+        let surface_texture = env
+            .device
+            .create_surface_texture(&mut env.context, surface)
+            .unwrap();
+        let framebuffer_object = make_fbo(
+            &env.gl,
+            env.device.surface_gl_texture_target(),
+            env.device.surface_texture_object(&surface_texture),
+        );
+        assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 0, 255, 255]);
+
+        env.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        check_gl(&env.gl);
+        env.gl.DeleteFramebuffers(1, &framebuffer_object);
+
+        let mut surface = env
+            .device
+            .destroy_surface_texture(&mut env.context, surface_texture)
+            .unwrap();
+        env.device
+            .destroy_surface(&mut env.context, &mut surface)
+            .unwrap();
+        env.device
+            .destroy_surface(&mut env.context, &mut main_surface)
+            .unwrap();
+        env.device.destroy_context(&mut env.context).unwrap();
+    }
+}
+
+// Tests that fences can be exported as sync files and imported again, on drivers that support
+// native fences.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_fence_fd_export_import() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let mut fence = env.device.create_fence(&env.context).unwrap();
+    let fd = match env.device.export_fence_fd(&fence) {
+        Ok(fd) => fd,
+        Err(Error::RequiredExtensionUnavailable) => {
+            env.device.destroy_fence(&mut fence).unwrap();
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Exporting a fence failed with {:?}", err),
+    };
+
+    let mut imported_fence = env.device.import_fence_fd(&env.context, fd).unwrap();
+    env.device
+        .wait_fence(&env.context, &imported_fence)
+        .unwrap();
+    assert!(env
+        .device
+        .client_wait_fence(&imported_fence, Duration::from_secs(10))
+        .unwrap());
+
+    env.device.destroy_fence(&mut imported_fence).unwrap();
+    env.device.destroy_fence(&mut fence).unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that generic surfaces can be exported as DMA-BUFs, on drivers that support it.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_export_surface_dmabuf() {
+    // 'AB24', which is RGBA in memory order.
+    const DRM_FORMAT_ABGR8888: u32 = 0x3432_4241;
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let mut surface = make_surface(&mut env.device, &env.context);
+    let result = env.device.export_surface_dmabuf(&env.context, &surface);
+    env.device
+        .destroy_surface(&mut env.context, &mut surface)
+        .unwrap();
+
+    match result {
+        Ok(descriptor) => {
+            assert_eq!(descriptor.size, Size2D::new(640, 480));
+            assert_eq!(descriptor.fourcc, DRM_FORMAT_ABGR8888);
+            assert!(!descriptor.planes.is_empty());
+            assert!(descriptor.planes[0].stride >= 640 * 4);
+        }
+        Err(Error::RequiredExtensionUnavailable) => {}
+        Err(err) => panic!("Exporting a surface failed with {:?}", err),
+    }
+
+    // Widget surfaces and surfaces from other contexts can't be exported.
+    let main_surface = env
+        .device
+        .unbind_surface_from_context(&mut env.context)
+        .unwrap();
+    let mut other_context = env
+        .device
+        .create_context(&env.context_descriptor, None)
+        .unwrap();
+    let mut other_surface = make_surface(&mut env.device, &other_context);
+    match env
+        .device
+        .export_surface_dmabuf(&env.context, &other_surface)
+    {
+        Err(Error::IncompatibleSurface) => {}
+        result => panic!("Exporting a foreign surface returned {:?}", result),
+    }
+    env.device
+        .destroy_surface(&mut other_context, &mut other_surface)
+        .unwrap();
+    env.device.destroy_context(&mut other_context).unwrap();
+    if let Some(mut main_surface) = main_surface {
+        env.device
+            .destroy_surface(&mut env.context, &mut main_surface)
+            .unwrap();
+    }
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that surfaces can be created from DMA-BUFs, on drivers that support it.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_create_surface_from_dmabuf() {
+    use crate::{DmaBufDescriptor, DmaBufPlane};
+    use std::fs::File;
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    // YUV buffers can't back surfaces.
+    let nv12_descriptor = DmaBufDescriptor {
+        size: Size2D::new(640, 480),
+        fourcc: u32::from_le_bytes(*b"NV12"),
+        modifier: 0,
+        planes: vec![DmaBufPlane {
+            fd: File::open("/dev/null").unwrap().into(),
+            offset: 0,
+            stride: 640,
+        }],
+    };
+    match env.device.create_surface_from_dmabuf(
+        &env.context,
+        nv12_descriptor,
+        SurfaceAccess::GPUOnly,
+    ) {
+        Err(Error::SurfaceImportFailed(err)) => {
+            // The error should name the native call that rejected the buffer.
+            assert_eq!(err.kind(), WindowingApiError::BadPixelFormat);
+            assert_eq!(err.call(), "eglCreateImageKHR");
+            let error = Error::SurfaceImportFailed(err);
+            let source = std::error::Error::source(&error).unwrap();
+            assert_eq!(error.to_string(), "couldn't import the surface");
+            assert!(source.to_string().starts_with("eglCreateImageKHR failed"));
+        }
+        result => panic!("Importing an NV12 buffer returned {:?}", result),
+    }
+
+    // Render to a surface, and check that the pixels show up in a surface imported from it.
+    let framebuffer_object = env
+        .device
+        .context_surface_info(&env.context)
+        .unwrap()
+        .unwrap()
+        .framebuffer_object;
+    unsafe {
+        env.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        env.gl.Viewport(0, 0, 640, 480);
+    }
+    clear(&env.gl, &[255, 0, 0, 255]);
+    let mut surface = env
+        .device
+        .unbind_surface_from_context(&mut env.context)
+        .unwrap()
+        .unwrap();
+
+    let descriptor = match env.device.export_surface_dmabuf(&env.context, &surface) {
+        Ok(descriptor) => descriptor,
+        Err(Error::RequiredExtensionUnavailable) => {
+            env.device
+                .destroy_surface(&mut env.context, &mut surface)
+                .unwrap();
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Exporting a surface failed with {:?}", err),
+    };
+    let mut fence = env.device.create_fence(&env.context).unwrap();
+    assert!(env
+        .device
+        .client_wait_fence(&fence, Duration::from_secs(10))
+        .unwrap());
+    env.device.destroy_fence(&mut fence).unwrap();
+
+    let mut imported_surface = match env.device.create_surface_from_dmabuf(
+        &env.context,
+        descriptor,
+        SurfaceAccess::GPUOnly,
+    ) {
+        Ok(imported_surface) => imported_surface,
+        Err(Error::RequiredExtensionUnavailable) => {
+            env.device
+                .destroy_surface(&mut env.context, &mut surface)
+                .unwrap();
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Importing a surface failed with {:?}", err),
+    };
+    let pixels = env
+        .device
+        .read_surface_pixels(
+            &env.context,
+            &imported_surface,
+            Rect::new(Point2D::zero(), Size2D::new(1, 1)),
+            PixelFormat::RGBA8,
+        )
+        .unwrap();
+    assert_eq!(pixels, [255, 0, 0, 255]);
+
+    env.device
+        .destroy_surface(&mut env.context, &mut imported_surface)
+        .unwrap();
+    env.device
+        .destroy_surface(&mut env.context, &mut surface)
+        .unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that surfaces can be sent to another device over a Unix domain socket.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_ipc_surface_sharing() {
+    use crate::{ipc, DmaBufDescriptor, DmaBufPlane};
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    // Devices on the same adapter pass the handshake.
+    let (stream, other_stream) = UnixStream::pair().unwrap();
+    let other_connection = env.connection.clone();
+    let other_adapter = env.adapter.clone();
+    let other_context_descriptor = env.context_descriptor.clone();
+    let receiver = thread::spawn(move || {
+        let mut device = other_connection.create_device(&other_adapter).unwrap();
+        let mut context = device
+            .create_context(&other_context_descriptor, None)
+            .unwrap();
+        ipc::handshake(&other_stream, &device).unwrap();
+
+        let result =
+            ipc::receive_surface(&other_stream, &mut device, &context, SurfaceAccess::GPUOnly);
+        let pixels = match result {
+            Ok(mut surface) => {
+                let pixels = device
+                    .read_surface_pixels(
+                        &context,
+                        &surface,
+                        Rect::new(Point2D::zero(), Size2D::new(1, 1)),
+                        PixelFormat::RGBA8,
+                    )
+                    .unwrap();
+                device.destroy_surface(&mut context, &mut surface).unwrap();
+                Some(pixels)
+            }
+            Err(Error::IpcFailed(_)) => None,
+            Err(err) => panic!("Receiving a surface failed with {:?}", err),
+        };
+        device.destroy_context(&mut context).unwrap();
+        pixels
+    });
+    ipc::handshake(&stream, &env.device).unwrap();
+
+    let framebuffer_object = env
+        .device
+        .context_surface_info(&env.context)
+        .unwrap()
+        .unwrap()
+        .framebuffer_object;
+    unsafe {
+        env.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        env.gl.Viewport(0, 0, 640, 480);
+    }
+    clear(&env.gl, &[0, 0, 255, 255]);
+    let mut surface = env
+        .device
+        .unbind_surface_from_context(&mut env.context)
+        .unwrap()
+        .unwrap();
+    match ipc::send_surface(&stream, &env.device, &env.context, &surface) {
+        Ok(()) => {}
+        Err(Error::RequiredExtensionUnavailable) => {}
+        Err(err) => panic!("Sending a surface failed with {:?}", err),
+    }
+    drop(stream);
+    if let Some(pixels) = receiver.join().unwrap() {
+        assert_eq!(pixels, [0, 0, 255, 255]);
+    }
+    env.device
+        .destroy_surface(&mut env.context, &mut surface)
+        .unwrap();
+
+    // Buffer descriptions and file descriptors make it across intact.
+    let (stream, other_stream) = UnixStream::pair().unwrap();
+    let descriptor = DmaBufDescriptor {
+        size: Size2D::new(640, 480),
+        fourcc: u32::from_le_bytes(*b"NV12"),
+        modifier: 0x0100_0000_0000_0001,
+        planes: vec![
+            DmaBufPlane {
+                fd: File::open("/dev/null").unwrap().into(),
+                offset: 0,
+                stride: 640,
+            },
+            DmaBufPlane {
+                fd: File::open("/dev/null").unwrap().into(),
+                offset: 640 * 480,
+                stride: 640,
+            },
+        ],
+    };
+    ipc::send_dmabuf(&stream, &descriptor).unwrap();
+    let received = ipc::receive_dmabuf(&other_stream).unwrap();
+    assert_eq!(received.size, descriptor.size);
+    assert_eq!(received.fourcc, descriptor.fourcc);
+    assert_eq!(received.modifier, descriptor.modifier);
+    assert_eq!(received.planes.len(), 2);
+    for (received_plane, plane) in received.planes.iter().zip(descriptor.planes.iter()) {
+        assert_ne!(received_plane.fd.as_raw_fd(), plane.fd.as_raw_fd());
+        assert_eq!(received_plane.offset, plane.offset);
+        assert_eq!(received_plane.stride, plane.stride);
+    }
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that EGL devices are enumerated as adapters, and that Mesa's software device can be
+// opened and rendered with.
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_enumerate_adapters() {
+    let connection = Connection::new().unwrap();
+    let adapters = match connection.enumerate_adapters() {
+        Ok(adapters) => adapters,
+        Err(Error::RequiredExtensionUnavailable) => return,
+        Err(err) => panic!("Failed to enumerate adapters: {:?}", err),
+    };
+    for adapter in &adapters {
+        if let Some(drm_node_path) = adapter.drm_node_path() {
+            assert!(drm_node_path.starts_with("/dev/dri"));
+        }
+    }
+
+    let adapter = match adapters.iter().find(|adapter| adapter.is_software()) {
+        Some(adapter) => adapter,
+        None => return,
+    };
+    assert!(adapter.drm_node_path().is_none());
+
+    let mut device = connection.create_device(adapter).unwrap();
+    assert!(device.adapter().is_software());
+    let context_descriptor = device
+        .create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(3, 0),
+            flags: ContextAttributeFlags::empty(),
+        })
+        .unwrap();
+    let mut context = device.create_context(&context_descriptor, None).unwrap();
+    let surface = make_surface(&mut device, &context);
+    device
+        .bind_surface_to_context(&mut context, surface)
+        .unwrap();
+    device.make_context_current(&context).unwrap();
+
+    let gl = Gl::load_with(|symbol| device.get_proc_address(&context, symbol));
+    bind_context_fbo(&gl, &device, &context);
+    clear(&gl, &[255, 0, 0, 255]);
+    assert_eq!(get_pixel_from_bottom_row(&gl), [255, 0, 0, 255]);
+
+    device.destroy_context(&mut context).unwrap();
+}
+
+// Tests that generic surfaces can be created in each color format, and that surface textures
+// created from them sample that same format.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_surface_formats() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    unsafe {
+        let mut main_surface = env
+            .device
+            .unbind_surface_from_context(&mut env.context)
+            .unwrap()
+            .unwrap();
+
+        for &format in &[
+            SurfaceFormat::RGBA8,
+            SurfaceFormat::RGBA16F,
+            SurfaceFormat::RGB10_A2,
+            SurfaceFormat::SRGB8_ALPHA8,
+        ] {
+            let surface = match env.device.create_surface(
+                &env.context,
+                SurfaceAccess::GPUOnly,
+                SurfaceType::Generic {
+                    size: Size2D::new(640, 480),
+                    samples: 0,
+                    format,
+                },
+            ) {
+                Ok(surface) => surface,
+                Err(Error::UnsupportedOnThisPlatform) | Err(Error::SurfaceCreationFailed(_)) => {
+                    // This backend or driver doesn't support the format.
+                    continue;
+                }
+                Err(err) => panic!("Failed to create {:?} surface: {:?}", format, err),
+            };
+            assert_eq!(env.device.surface_info(&surface).format, format);
+
+            env.device
+                .bind_surface_to_context(&mut env.context, surface)
+                .unwrap();
+            let framebuffer_object = env
+                .device
+                .context_surface_info(&env.context)
+                .unwrap()
+                .unwrap()
+                .framebuffer_object;
+            env.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+            clear(&env.gl, &[0, 255, 0, 255]);
+            check_gl(&env.gl);
+
+            let surface = env
+                .device
+                .unbind_surface_from_context(&mut env.context)
+                .unwrap()
+                .unwrap();
+            let surface_texture = env
+                .device
+                .create_surface_texture(&mut env.context, surface)
+                .unwrap();
+
+            // Querying the internal format of a texture requires OpenGL ES 3.1.
+            #[cfg(not(any(target_os = "android", target_env = "ohos")))]
+            if format != SurfaceFormat::RGBA8 {
+                let texture_target = env.device.surface_gl_texture_target();
+                let mut internal_format = 0;
+                env.gl.BindTexture(
+                    texture_target,
+                    env.device.surface_texture_object(&surface_texture),
+                );
+                env.gl.GetTexLevelParameteriv(
+                    texture_target,
+                    0,
+                    gl::TEXTURE_INTERNAL_FORMAT,
+                    &mut internal_format,
+                );
+                env.gl.BindTexture(texture_target, 0);
+                assert_eq!(internal_format as GLenum, format.gl_renderbuffer_format());
+            }
+
+            let framebuffer_object = make_fbo(
+                &env.gl,
+                env.device.surface_gl_texture_target(),
+                env.device.surface_texture_object(&surface_texture),
+            );
+            assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 255, 0, 255]);
+
+            env.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+            check_gl(&env.gl);
+            env.gl.DeleteFramebuffers(1, &framebuffer_object);
+
+            let mut surface = env
+                .device
+                .destroy_surface_texture(&mut env.context, surface_texture)
+                .unwrap();
+            env.device
+                .destroy_surface(&mut env.context, &mut surface)
+                .unwrap();
+        }
+
+        env.device
+            .destroy_surface(&mut env.context, &mut main_surface)
+            .unwrap();
+        env.device.destroy_context(&mut env.context).unwrap();
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_depth_and_stencil() {
+    let connection = Connection::new().unwrap();
+    let adapter = connection
+        .create_low_power_adapter()
+        .expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    // Can't readback from the depth or stencil buffer on OpenGL ES, so just disable this test.
+    if device.gl_api() == GLApi::GLES {
+        return;
+    }
+
+    // Check depth.
+
+    let depth_context_descriptor = device
+        .create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(3, 0),
+            flags: ContextAttributeFlags::DEPTH,
+        })
+        .unwrap();
+
+    let mut depth_context = device
+        .create_context(&depth_context_descriptor, None)
+        .unwrap();
+    let depth_surface = make_surface(&mut device, &depth_context);
+    device
+        .bind_surface_to_context(&mut depth_context, depth_surface)
+        .unwrap();
+    device.make_context_current(&depth_context).unwrap();
+
+    let gl = Gl::load_with(|symbol| device.get_proc_address(&depth_context, symbol));
+
+    unsafe {
+        let framebuffer_object = device
+            .context_surface_info(&depth_context)
+            .unwrap()
+            .unwrap()
+            .framebuffer_object;
+        gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        gl.Viewport(0, 0, 640, 480);
+
+        gl.ClearDepth(0.5);
+        gl.Clear(gl::DEPTH_BUFFER_BIT);
+
+        let mut depth_value: f32 = -1.0;
+        gl.ReadPixels(
+            0,
+            0,
+            1,
+            1,
+            gl::DEPTH_COMPONENT,
+            gl::FLOAT,
+            (&mut depth_value) as *mut f32 as *mut c_void,
+        );
+        assert!(
+            approx_eq(depth_value, 0.5),
+            "actual depth value was {}, but expected 0.5",
+            depth_value
+        );
+    }
+
+    device.destroy_context(&mut depth_context).unwrap();
+
+    // Check stencil.
+
+    let stencil_context_descriptor = device
+        .create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(3, 0),
+            flags: ContextAttributeFlags::STENCIL,
+        })
+        .unwrap();
+
+    let mut stencil_context = device
+        .create_context(&stencil_context_descriptor, None)
+        .unwrap();
+    let stencil_surface = make_surface(&mut device, &stencil_context);
+    device
+        .bind_surface_to_context(&mut stencil_context, stencil_surface)
+        .unwrap();
+    device.make_context_current(&stencil_context).unwrap();
+
+    let gl = Gl::load_with(|symbol| device.get_proc_address(&stencil_context, symbol));
+
+    unsafe {
+        let framebuffer_object = device
+            .context_surface_info(&stencil_context)
+            .unwrap()
+            .unwrap()
+            .framebuffer_object;
+        gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
         gl.Viewport(0, 0, 640, 480);
 
         gl.ClearStencil(99);
@@ -882,6 +2450,205 @@ pub fn test_get_native_context() {
     env.device.destroy_context(&mut env.context).unwrap();
 }
 
+// Make sure that the device keeps track of the contexts, surfaces, and surface textures that are
+// still alive.
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_live_resources() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    // Resources are only tracked in debug builds.
+    if !cfg!(debug_assertions) {
+        env.device.destroy_context(&mut env.context).unwrap();
+        return;
+    }
+
+    let context_id = env.device.context_id(&env.context);
+    let bound_surface_info = env
+        .device
+        .context_surface_info(&env.context)
+        .unwrap()
+        .unwrap();
+    let live_resources = env.device.live_resources();
+    assert_eq!(live_resources.len(), 2);
+    assert!(live_resources.iter().any(|resource| {
+        resource.kind == ResourceKind::Context && resource.context_id == context_id
+    }));
+    assert!(live_resources.iter().any(|resource| {
+        resource.kind == ResourceKind::Surface && resource.surface_id == Some(bound_surface_info.id)
+    }));
+
+    let surface = make_surface(&mut env.device, &env.context);
+    let surface_id = env.device.surface_info(&surface).id;
+    let live_resources = env.device.live_resources();
+    let resource = live_resources
+        .iter()
+        .find(|resource| resource.surface_id == Some(surface_id))
+        .unwrap();
+    assert_eq!(resource.kind, ResourceKind::Surface);
+    assert_eq!(resource.context_id, context_id);
+    assert_eq!(resource.size, Some(Size2D::new(640, 480)));
+
+    let surface_texture = env
+        .device
+        .create_surface_texture(&mut env.context, surface)
+        .unwrap();
+    assert!(env.device.live_resources().iter().any(|resource| {
+        resource.kind == ResourceKind::SurfaceTexture && resource.surface_id == Some(surface_id)
+    }));
+
+    let mut surface = env
+        .device
+        .destroy_surface_texture(&mut env.context, surface_texture)
+        .unwrap();
+    assert!(!env
+        .device
+        .live_resources()
+        .iter()
+        .any(|resource| resource.kind == ResourceKind::SurfaceTexture));
+
+    env.device
+        .destroy_surface(&mut env.context, &mut surface)
+        .unwrap();
+    assert_eq!(env.device.live_resources().len(), 2);
+
+    // Destroying the context also destroys the surface bound to it.
+    env.device.destroy_context(&mut env.context).unwrap();
+    assert!(env.device.live_resources().is_empty());
+}
+
+// Tests that dropping a surface without destroying it panics with a message that says which
+// surface it was, and in debug builds, where it was created.
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_leaked_surface_panic_message() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let context_id = env.device.context_id(&env.context);
+    let surface = make_surface(&mut env.device, &env.context);
+    let surface_id = env.device.surface_info(&surface).id;
+    let panic = panic::catch_unwind(AssertUnwindSafe(|| drop(surface))).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("Should have destroyed the surface first"));
+    if cfg!(debug_assertions) {
+        let description = format!("Surface {:x} for context {}", surface_id.0, context_id.0);
+        assert!(message.contains(&description), "{}", message);
+    }
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Tests that a connection opened from an XCB display handle can present to a window. This needs an
+// X server (e.g. Xvfb), so it does nothing if `DISPLAY` doesn't point at one. Backends other than
+// X11 reject XCB handles, so it does nothing on those either.
+#[cfg(all(
+    target_os = "linux",
+    not(target_env = "ohos"),
+    feature = "sm-x11",
+    feature = "sm-raw-window-handle-06"
+))]
+#[cfg_attr(not(feature = "sm-test"), test)]
+#[serial]
+pub fn test_xcb_window_surface() {
+    use rwh_06::{DisplayHandle, RawDisplayHandle, WindowHandle, XcbDisplayHandle};
+    use rwh_06::{RawWindowHandle, XcbWindowHandle};
+    use std::num::NonZeroU32;
+    use std::ptr::{self, NonNull};
+    use x11::xlib;
+
+    unsafe {
+        let mut screen = 0;
+        let xcb_connection = xcb_connect(ptr::null(), &mut screen);
+        if xcb_connection_has_error(xcb_connection) != 0 {
+            xcb_disconnect(xcb_connection);
+            return;
+        }
+
+        // XCB and Xlib share window IDs, so create the window with Xlib.
+        let x11_display = xlib::XOpenDisplay(ptr::null());
+        assert!(!x11_display.is_null());
+        let window = xlib::XCreateSimpleWindow(
+            x11_display,
+            xlib::XRootWindow(x11_display, screen),
+            0,
+            0,
+            640,
+            480,
+            0,
+            0,
+            0,
+        );
+        xlib::XMapWindow(x11_display, window);
+        xlib::XSync(x11_display, xlib::False);
+
+        let display_handle = XcbDisplayHandle::new(NonNull::new(xcb_connection), screen);
+        let display_handle = DisplayHandle::borrow_raw(RawDisplayHandle::Xcb(display_handle));
+        match Connection::from_display_handle(display_handle) {
+            Ok(connection) => {
+                let window_handle = XcbWindowHandle::new(NonZeroU32::new(window as u32).unwrap());
+                let window_handle = WindowHandle::borrow_raw(RawWindowHandle::Xcb(window_handle));
+                present_to_window(&connection, window_handle);
+            }
+            Err(Error::IncompatibleRawDisplayHandle) | Err(Error::RequiredExtensionUnavailable) => {
+            }
+            Err(err) => panic!("Failed to open XCB connection: {:?}", err),
+        }
+
+        xlib::XDestroyWindow(x11_display, window);
+        xlib::XCloseDisplay(x11_display);
+        xcb_disconnect(xcb_connection);
+    }
+
+    fn present_to_window(connection: &Connection, window_handle: rwh_06::WindowHandle) {
+        let adapter = connection.create_low_power_adapter().unwrap();
+        let mut device = connection.create_device(&adapter).unwrap();
+        let context_descriptor = device
+            .create_context_descriptor(&ContextAttributes {
+                version: GLVersion::new(3, 0),
+                flags: ContextAttributeFlags::empty(),
+            })
+            .unwrap();
+        let mut context = device.create_context(&context_descriptor, None).unwrap();
+
+        let native_widget = connection
+            .create_native_widget_from_window_handle(window_handle, Size2D::new(640, 480))
+            .unwrap();
+        let surface = device
+            .create_surface(
+                &context,
+                SurfaceAccess::GPUOnly,
+                SurfaceType::Widget { native_widget },
+            )
+            .unwrap();
+        // The window size comes from EGL rather than Xlib on XCB connections.
+        assert_eq!(device.surface_info(&surface).size, Size2D::new(640, 480));
+        device
+            .bind_surface_to_context(&mut context, surface)
+            .unwrap();
+        device.make_context_current(&context).unwrap();
+
+        let gl = Gl::load_with(|symbol| device.get_proc_address(&context, symbol));
+        bind_context_fbo(&gl, &device, &context);
+        clear(&gl, &[255, 0, 0, 255]);
+        assert_eq!(get_pixel_from_bottom_row(&gl), [255, 0, 0, 255]);
+
+        let mut surface = device
+            .unbind_surface_from_context(&mut context)
+            .unwrap()
+            .unwrap();
+        device.present_surface(&context, &mut surface).unwrap();
+        device.destroy_surface(&mut context, &mut surface).unwrap();
+        device.destroy_context(&mut context).unwrap();
+    }
+}
+
 fn bind_context_fbo(gl: &Gl, device: &Device, context: &Context) {
     unsafe {
         gl.BindFramebuffer(gl::FRAMEBUFFER, context_fbo(device, context));
@@ -904,6 +2671,8 @@ fn make_surface(device: &mut Device, context: &Context) -> Surface {
             SurfaceAccess::GPUOnly,
             SurfaceType::Generic {
                 size: Size2D::new(640, 480),
+                samples: 0,
+                format: SurfaceFormat::RGBA8,
             },
         )
         .unwrap()
@@ -954,6 +2723,49 @@ fn make_fbo(gl: &Gl, texture_target: GLenum, texture: GLuint) -> GLuint {
     }
 }
 
+// Records everything that is logged, so that tests can check for messages.
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+struct CapturingLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+static CAPTURING_LOGGER: CapturingLogger = CapturingLogger {
+    messages: Mutex::new(Vec::new()),
+};
+
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+impl CapturingLogger {
+    // A process can only have one logger, so this installs it the first time it's called.
+    fn install() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            log::set_logger(&CAPTURING_LOGGER).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    fn take(&self) -> Vec<String> {
+        mem::take(&mut *self.messages.lock().unwrap())
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_env = "ohos")))]
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.messages
+            .lock()
+            .unwrap()
+            .push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
 struct BasicEnvironment {
     connection: Connection,
     adapter: Adapter,
@@ -1084,3 +2896,16 @@ fn check_gl(gl: &Gl) {
 fn approx_eq(a: f32, b: f32) -> bool {
     f32::abs(a - b) < 0.001
 }
+
+#[cfg(all(
+    target_os = "linux",
+    not(target_env = "ohos"),
+    feature = "sm-x11",
+    feature = "sm-raw-window-handle-06"
+))]
+#[link(name = "xcb")]
+extern "C" {
+    fn xcb_connect(display_name: *const c_char, screen: *mut c_int) -> *mut c_void;
+    fn xcb_connection_has_error(connection: *mut c_void) -> c_int;
+    fn xcb_disconnect(connection: *mut c_void);
+}
@@ -0,0 +1,31 @@
+// surfman/surfman/src/trace.rs
+//
+//! Optional instrumentation with the `tracing` crate.
+//!
+//! Backends attach spans to their device methods with
+//! `#[cfg_attr(feature = "tracing", tracing::instrument(...))]`. The functions here fill in span
+//! fields that are only known once an operation has finished, and do nothing when the `tracing`
+//! feature is disabled.
+
+use crate::{ContextID, SurfaceInfo};
+
+/// Records the ID of a newly-created context on the current span.
+#[inline]
+pub(crate) fn record_context(context_id: ContextID) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("context_id", context_id.0);
+    #[cfg(not(feature = "tracing"))]
+    let _ = context_id;
+}
+
+/// Records the ID and size of a newly-created surface on the current span.
+#[inline]
+pub(crate) fn record_surface(surface_info: &SurfaceInfo) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current()
+        .record("surface_id", surface_info.id.0)
+        .record("width", surface_info.size.width)
+        .record("height", surface_info.size.height);
+    #[cfg(not(feature = "tracing"))]
+    let _ = surface_info;
+}